@@ -1,3 +1,13 @@
 fn main() {
+    let build_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=SLIDES_BUILD_TIME={}", build_time);
+    // tauri_build registers its own rerun-if-changed paths, after which cargo stops rerunning
+    // this script for source changes and the build time above would go stale
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
     tauri_build::build()
 }
@@ -1,3 +1,5 @@
+mod model_cache;
 mod provider;
 
+pub use model_cache::*;
 pub use provider::*;
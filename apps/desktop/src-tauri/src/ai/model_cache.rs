@@ -0,0 +1,174 @@
+// Cache of provider model lists, so the settings screen works offline and doesn't hit
+// provider APIs on every open.
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::future::Future;
+
+use super::ModelInfo;
+use crate::db::Database;
+use crate::error::AppResult;
+
+// How long a cached model list is served without asking the provider again
+const DEFAULT_MODEL_CACHE_TTL_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelList {
+    pub provider: String,
+    pub models: Vec<ModelInfo>,
+    pub fetched_at: DateTime<Utc>,
+    /// The provider couldn't be reached and the list is older than the cache TTL
+    pub stale: bool,
+}
+
+/// Cache TTL, configurable through SLIDES_MODEL_CACHE_TTL_SECS.
+pub fn model_cache_ttl() -> Duration {
+    let secs = std::env::var("SLIDES_MODEL_CACHE_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_MODEL_CACHE_TTL_SECS);
+    Duration::seconds(secs)
+}
+
+/// Returns the provider's models from the cache while it is fresh, otherwise calls `fetch`
+/// and caches the result. If `fetch` fails, falls back to the cached list marked stale.
+/// Entries cached for a different base URL are ignored.
+pub async fn cached_models<F, Fut>(
+    db: &Database,
+    provider: &str,
+    base_url: Option<&str>,
+    ttl: Duration,
+    fetch: F,
+) -> AppResult<ModelList>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = AppResult<Vec<ModelInfo>>>,
+{
+    let cached = db
+        .get_cached_models(provider)
+        .await?
+        .filter(|entry| entry.base_url.as_deref() == base_url)
+        .and_then(|entry| {
+            // An unreadable entry is treated as a miss and overwritten
+            let models = serde_json::from_str::<Vec<ModelInfo>>(&entry.models).ok()?;
+            Some((models, entry.fetched_at))
+        });
+
+    if let Some((models, fetched_at)) = &cached {
+        if Utc::now() - *fetched_at < ttl {
+            return Ok(ModelList {
+                provider: provider.to_string(),
+                models: models.clone(),
+                fetched_at: *fetched_at,
+                stale: false,
+            });
+        }
+    }
+
+    match fetch().await {
+        Ok(models) => {
+            let fetched_at = db.store_cached_models(provider, base_url, &models).await?;
+            Ok(ModelList {
+                provider: provider.to_string(),
+                models,
+                fetched_at,
+                stale: false,
+            })
+        }
+        Err(e) => match cached {
+            Some((models, fetched_at)) => {
+                tracing::warn!("Listing {} models failed, serving cached list: {}", provider, e);
+                Ok(ModelList {
+                    provider: provider.to_string(),
+                    models,
+                    fetched_at,
+                    stale: true,
+                })
+            }
+            None => Err(e),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+
+    async fn test_db() -> Database {
+        let db = Database::new_with_url("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        db
+    }
+
+    fn models(ids: &[&str]) -> Vec<ModelInfo> {
+        ids.iter()
+            .map(|id| ModelInfo {
+                id: id.to_string(),
+                display_name: id.to_string(),
+                created_at: None,
+            })
+            .collect()
+    }
+
+    async fn offline() -> AppResult<Vec<ModelInfo>> {
+        Err(AppError::Internal("offline".to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_fresh_cache_hit_skips_fetch() {
+        let db = test_db().await;
+        let ttl = Duration::hours(1);
+
+        let first = cached_models(&db, "openai", None, ttl, || async { Ok(models(&["a"])) }).await.unwrap();
+        assert!(!first.stale);
+
+        let second = cached_models(&db, "openai", None, ttl, || async {
+            panic!("fresh cache should not fetch");
+        })
+        .await
+        .unwrap();
+        assert_eq!(second.models[0].id, "a");
+        assert!(!second.stale);
+    }
+
+    #[tokio::test]
+    async fn test_expired_cache_falls_back_when_fetch_fails() {
+        let db = test_db().await;
+
+        cached_models(&db, "openai", None, Duration::hours(1), || async { Ok(models(&["a", "b"])) })
+            .await
+            .unwrap();
+
+        let expired = Duration::zero();
+        let fallback = cached_models(&db, "openai", None, expired, offline).await.unwrap();
+        assert!(fallback.stale);
+        assert_eq!(fallback.models.len(), 2);
+
+        let refreshed = cached_models(&db, "openai", None, expired, || async { Ok(models(&["c"])) })
+            .await
+            .unwrap();
+        assert!(!refreshed.stale);
+        assert_eq!(refreshed.models[0].id, "c");
+
+        assert!(cached_models(&db, "anthropic", None, expired, offline).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_base_url_change_invalidates_cache() {
+        let db = test_db().await;
+        let ttl = Duration::hours(1);
+
+        cached_models(&db, "ollama", Some("http://a:11434"), ttl, || async { Ok(models(&["llama"])) })
+            .await
+            .unwrap();
+
+        // Neither served fresh nor used as a stale fallback for the new endpoint
+        assert!(cached_models(&db, "ollama", Some("http://b:11434"), ttl, offline).await.is_err());
+
+        let other = cached_models(&db, "ollama", Some("http://b:11434"), ttl, || async { Ok(models(&["qwen"])) })
+            .await
+            .unwrap();
+        assert_eq!(other.models[0].id, "qwen");
+    }
+}
@@ -1,8 +1,12 @@
 use async_trait::async_trait;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-use crate::error::{AppError, AppResult};
+use crate::error::{AppError, AppResult, ProviderErrorKind};
+use crate::trace;
 
 #[derive(Debug, Clone, Default)]
 pub struct GenerateOptions {
@@ -23,10 +27,133 @@ pub struct ModelInfo {
     pub created_at: Option<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationRole {
+    User,
+    Assistant,
+}
+
+impl ConversationRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConversationRole::User => "user",
+            ConversationRole::Assistant => "assistant",
+        }
+    }
+}
+
+/// One turn of a conversation with a provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMessage {
+    pub role: ConversationRole,
+    pub content: String,
+}
+
+impl ConversationMessage {
+    fn user(content: &str) -> Self {
+        Self { role: ConversationRole::User, content: content.to_string() }
+    }
+}
+
+// Generation can take minutes and streams stay open while tokens arrive, so it gets longer
+// than the shared client's default deadline
+const GENERATION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Headers forwarding the current trace id, so provider-side logs can be correlated.
+fn trace_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = trace::current().and_then(|id| HeaderValue::from_str(&id).ok()) {
+        headers.insert(trace::TRACE_HEADER, value);
+    }
+    headers
+}
+
+/// Lines of a streamed response body, without their line endings.
+fn response_lines(response: reqwest::Response) -> impl Stream<Item = AppResult<String>> {
+    lines(response.bytes_stream())
+}
+
+fn lines<S, B, E>(chunks: S) -> impl Stream<Item = AppResult<String>>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::fmt::Display,
+{
+    async_stream::try_stream! {
+        let mut chunks = chunks;
+        // Bytes, not text: a chunk may end in the middle of a UTF-8 sequence
+        let mut buffer: Vec<u8> = Vec::new();
+
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| AppError::Provider {
+                kind: ProviderErrorKind::Network,
+                message: format!("Stream interrupted: {}", e),
+            })?;
+            buffer.extend_from_slice(chunk.as_ref());
+
+            while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                yield String::from_utf8_lossy(&line).trim_end().to_string();
+            }
+        }
+
+        if !buffer.is_empty() {
+            yield String::from_utf8_lossy(&buffer).trim_end().to_string();
+        }
+    }
+}
+
+/// Payloads of the `data:` lines of a server-sent events response.
+fn sse_data(response: reqwest::Response) -> impl Stream<Item = AppResult<String>> {
+    response_lines(response).filter_map(|line| async move {
+        match line {
+            Ok(line) => line.strip_prefix("data:").map(|data| Ok(data.trim_start().to_string())),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// A request that got no response from the provider.
+fn request_failed(e: reqwest::Error) -> AppError {
+    let kind = if e.is_connect() || e.is_timeout() || e.is_request() {
+        ProviderErrorKind::Network
+    } else {
+        ProviderErrorKind::Provider
+    };
+    AppError::Provider { kind, message: format!("HTTP request failed: {}", e) }
+}
+
+/// An error status from a provider's API. 401 and 403 mean the API key was rejected.
+fn api_error(provider: &str, status: reqwest::StatusCode, body: &str) -> AppError {
+    let kind = match status {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => ProviderErrorKind::Auth,
+        _ => ProviderErrorKind::Provider,
+    };
+    AppError::Provider { kind, message: format!("{} API error ({}): {}", provider, status, body) }
+}
+
+fn parse_event<T: serde::de::DeserializeOwned>(data: &str) -> AppResult<T> {
+    serde_json::from_str(data).map_err(|e| AppError::Internal(format!("Failed to parse stream event: {}", e)))
+}
+
 #[async_trait]
 pub trait AIProvider: Send + Sync {
+    /// Model used when `GenerateOptions::model` is not set.
+    fn default_model(&self) -> &str;
     async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String>;
     async fn list_models(&self) -> AppResult<Vec<ModelInfo>>;
+
+    /// Replies to a conversation that starts and ends with a user message and alternates
+    /// roles in between. An image in `options` goes with the last message.
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String>;
+
+    /// Streams the generated text in chunks as the provider produces it. Providers without
+    /// streaming support yield the whole response as a single chunk.
+    async fn generate_stream(&self, prompt: &str, options: GenerateOptions) -> AppResult<BoxStream<'_, AppResult<String>>> {
+        let content = self.generate_content(prompt, options).await?;
+        Ok(stream::once(async { Ok(content) }).boxed())
+    }
 }
 
 // Anthropic Provider
@@ -38,13 +165,38 @@ pub struct AnthropicProvider {
 }
 
 impl AnthropicProvider {
-    pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
+    pub fn new(client: Client, api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
         Self {
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://api.anthropic.com".to_string()),
             default_model: model.unwrap_or_else(|| "claude-sonnet-4-20250514".to_string()),
-            client: Client::new(),
+            client,
+        }
+    }
+
+    async fn send_message(&self, messages: &[ConversationMessage], options: GenerateOptions, stream: bool) -> AppResult<reqwest::Response> {
+        let request = AnthropicRequest::new(messages, options, &self.default_model, stream);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(&request)
+            .headers(trace_headers())
+            .timeout(GENERATION_TIMEOUT)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(api_error("Anthropic", status, &body));
         }
+
+        Ok(response)
     }
 }
 
@@ -54,6 +206,46 @@ struct AnthropicRequest {
     max_tokens: u32,
     system: String,
     messages: Vec<AnthropicMessage>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+impl AnthropicRequest {
+    /// The conversation as Anthropic messages, with the image on the last one.
+    fn new(conversation: &[ConversationMessage], options: GenerateOptions, default_model: &str, stream: bool) -> Self {
+        let last = conversation.len().saturating_sub(1);
+        let messages = conversation
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut content = Vec::new();
+                if let (true, Some(image_data)) = (i == last, &options.image_base64) {
+                    content.push(AnthropicContent::Image {
+                        source: AnthropicImageSource {
+                            source_type: "base64".to_string(),
+                            media_type: options.image_mime_type.clone().unwrap_or_else(|| "image/png".to_string()),
+                            data: image_data.clone(),
+                        },
+                    });
+                }
+                content.push(AnthropicContent::Text { text: message.content.clone() });
+                AnthropicMessage {
+                    role: message.role.as_str().to_string(),
+                    content,
+                }
+            })
+            .collect();
+
+        Self {
+            model: options.model.unwrap_or_else(|| default_model.to_string()),
+            max_tokens: options.max_tokens.unwrap_or(2000),
+            system: options.system_prompt.unwrap_or_else(|| {
+                "You are a presentation assistant that generates markdown slides separated by ---.".to_string()
+            }),
+            messages,
+            stream,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -91,6 +283,20 @@ struct AnthropicResponseContent {
     text: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct AnthropicStreamEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    delta: Option<AnthropicStreamDelta>,
+    error: Option<serde_json::Value>,
+}
+
+// Only `text_delta` deltas carry `text`
+#[derive(Deserialize)]
+struct AnthropicStreamDelta {
+    text: Option<String>,
+}
+
 #[derive(Deserialize)]
 struct AnthropicModelsResponse {
     data: Vec<AnthropicModel>,
@@ -105,52 +311,16 @@ struct AnthropicModel {
 
 #[async_trait]
 impl AIProvider for AnthropicProvider {
-    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
-        let mut content = Vec::new();
-
-        if let Some(image_data) = &options.image_base64 {
-            content.push(AnthropicContent::Image {
-                source: AnthropicImageSource {
-                    source_type: "base64".to_string(),
-                    media_type: options.image_mime_type.clone().unwrap_or_else(|| "image/png".to_string()),
-                    data: image_data.clone(),
-                },
-            });
-        }
-
-        content.push(AnthropicContent::Text { text: prompt.to_string() });
-
-        let request = AnthropicRequest {
-            model: options.model.unwrap_or_else(|| self.default_model.clone()),
-            max_tokens: options.max_tokens.unwrap_or(2000),
-            system: options.system_prompt.unwrap_or_else(|| {
-                "You are a presentation assistant that generates markdown slides separated by ---.".to_string()
-            }),
-            messages: vec![AnthropicMessage {
-                role: "user".to_string(),
-                content,
-            }],
-        };
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
 
-        let response = self
-            .client
-            .post(format!("{}/v1/messages", self.base_url))
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
+        self.generate_conversation(&[ConversationMessage::user(prompt)], options).await
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "Anthropic API error ({}): {}",
-                status, body
-            )));
-        }
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+        let response = self.send_message(messages, options, false).await?;
 
         let result: AnthropicResponse = response
             .json()
@@ -165,23 +335,41 @@ impl AIProvider for AnthropicProvider {
             .join(""))
     }
 
+    async fn generate_stream(&self, prompt: &str, options: GenerateOptions) -> AppResult<BoxStream<'_, AppResult<String>>> {
+        let response = self.send_message(&[ConversationMessage::user(prompt)], options, true).await?;
+
+        let chunks = sse_data(response).filter_map(|data| async move {
+            let event: AnthropicStreamEvent = match data.and_then(|d| parse_event(&d)) {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e)),
+            };
+            match event.event_type.as_str() {
+                "content_block_delta" => event.delta.and_then(|d| d.text).map(Ok),
+                "error" => Some(Err(AppError::Internal(format!(
+                    "Anthropic stream error: {}",
+                    event.error.map(|e| e.to_string()).unwrap_or_default()
+                )))),
+                _ => None,
+            }
+        });
+        Ok(chunks.boxed())
+    }
+
     async fn list_models(&self) -> AppResult<Vec<ModelInfo>> {
         let response = self
             .client
             .get(format!("{}/v1/models", self.base_url))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
+            .headers(trace_headers())
             .send()
             .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+            .map_err(request_failed)?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "Anthropic API error ({}): {}",
-                status, body
-            )));
+            return Err(api_error("Anthropic", status, &body));
         }
 
         let result: AnthropicModelsResponse = response
@@ -210,13 +398,37 @@ pub struct OpenAIProvider {
 }
 
 impl OpenAIProvider {
-    pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
+    pub fn new(client: Client, api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
         Self {
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://api.openai.com".to_string()),
             default_model: model.unwrap_or_else(|| "gpt-4o".to_string()),
-            client: Client::new(),
+            client,
+        }
+    }
+
+    async fn send_chat(&self, messages: &[ConversationMessage], options: GenerateOptions, stream: bool) -> AppResult<reqwest::Response> {
+        let request = OpenAIRequest::new(messages, options, &self.default_model, stream);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("content-type", "application/json")
+            .json(&request)
+            .headers(trace_headers())
+            .timeout(GENERATION_TIMEOUT)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(api_error("OpenAI", status, &body));
         }
+
+        Ok(response)
     }
 }
 
@@ -226,6 +438,51 @@ struct OpenAIRequest {
     messages: Vec<OpenAIMessage>,
     max_tokens: u32,
     temperature: f32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+impl OpenAIRequest {
+    /// The system message followed by the conversation. User turns are sent as content
+    /// parts, so the image can go with the last one.
+    fn new(conversation: &[ConversationMessage], options: GenerateOptions, default_model: &str, stream: bool) -> Self {
+        let mut messages = vec![OpenAIMessage {
+            role: "system".to_string(),
+            content: serde_json::json!(options.system_prompt.unwrap_or_else(|| {
+                "You are a presentation assistant that generates markdown slides separated by ---.".to_string()
+            })),
+        }];
+
+        let last = conversation.len().saturating_sub(1);
+        for (i, message) in conversation.iter().enumerate() {
+            let content = match message.role {
+                ConversationRole::Assistant => serde_json::json!(message.content),
+                ConversationRole::User => {
+                    let mut parts = vec![serde_json::json!({ "type": "text", "text": message.content })];
+                    if let (true, Some(image_data)) = (i == last, &options.image_base64) {
+                        let mime = options.image_mime_type.as_deref().unwrap_or("image/png");
+                        parts.push(serde_json::json!({
+                            "type": "image_url",
+                            "image_url": { "url": format!("data:{};base64,{}", mime, image_data) }
+                        }));
+                    }
+                    serde_json::json!(parts)
+                }
+            };
+            messages.push(OpenAIMessage {
+                role: message.role.as_str().to_string(),
+                content,
+            });
+        }
+
+        Self {
+            model: options.model.unwrap_or_else(|| default_model.to_string()),
+            messages,
+            max_tokens: options.max_tokens.unwrap_or(2000),
+            temperature: options.temperature.unwrap_or(0.7),
+            stream,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -249,6 +506,16 @@ struct OpenAIMessageResponse {
     content: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct OpenAIStreamChunk {
+    choices: Vec<OpenAIStreamChoice>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIStreamChoice {
+    delta: OpenAIMessageResponse,
+}
+
 #[derive(Deserialize)]
 struct OpenAIModelsResponse {
     data: Vec<OpenAIModel>,
@@ -262,64 +529,22 @@ struct OpenAIModel {
 
 #[async_trait]
 impl AIProvider for OpenAIProvider {
-    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
-        let mut user_content = vec![serde_json::json!({ "type": "text", "text": prompt })];
-
-        if let Some(image_data) = &options.image_base64 {
-            let mime = options.image_mime_type.as_deref().unwrap_or("image/png");
-            user_content.push(serde_json::json!({
-                "type": "image_url",
-                "image_url": { "url": format!("data:{};base64,{}", mime, image_data) }
-            }));
-        }
-
-        let request = OpenAIRequest {
-            model: options.model.unwrap_or_else(|| self.default_model.clone()),
-            messages: vec![
-                OpenAIMessage {
-                    role: "system".to_string(),
-                    content: serde_json::json!(options.system_prompt.unwrap_or_else(|| {
-                        "You are a presentation assistant that generates markdown slides separated by ---.".to_string()
-                    })),
-                },
-                OpenAIMessage {
-                    role: "user".to_string(),
-                    content: serde_json::json!(user_content),
-                },
-            ],
-            max_tokens: options.max_tokens.unwrap_or(2000),
-            temperature: options.temperature.unwrap_or(0.7),
-        };
-
-        let response = self
-            .client
-            .post(format!("{}/v1/chat/completions", self.base_url))
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "OpenAI API error ({}): {}",
-                status, body
-            )));
-        }
+    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
+        self.generate_conversation(&[ConversationMessage::user(prompt)], options).await
+    }
 
-        let result: OpenAIResponse = response
-            .json()
-            .await
-            .map_err(|e| AppError::Internal(format!("Failed to parse response: {}", e)))?;
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+        let response = self.send_chat(messages, options, false).await?;
+        openai_content(response).await
+    }
 
-        Ok(result
-            .choices
-            .first()
-            .and_then(|c| c.message.content.clone())
-            .unwrap_or_default())
+    async fn generate_stream(&self, prompt: &str, options: GenerateOptions) -> AppResult<BoxStream<'_, AppResult<String>>> {
+        let response = self.send_chat(&[ConversationMessage::user(prompt)], options, true).await?;
+        Ok(openai_stream(response))
     }
 
     async fn list_models(&self) -> AppResult<Vec<ModelInfo>> {
@@ -327,17 +552,15 @@ impl AIProvider for OpenAIProvider {
             .client
             .get(format!("{}/v1/models", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
+            .headers(trace_headers())
             .send()
             .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+            .map_err(request_failed)?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "OpenAI API error ({}): {}",
-                status, body
-            )));
+            return Err(api_error("OpenAI", status, &body));
         }
 
         let result: OpenAIModelsResponse = response
@@ -366,6 +589,126 @@ impl AIProvider for OpenAIProvider {
     }
 }
 
+/// Text of a chat completions response.
+async fn openai_content(response: reqwest::Response) -> AppResult<String> {
+    let result: OpenAIResponse = response
+        .json()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to parse response: {}", e)))?;
+
+    Ok(result
+        .choices
+        .first()
+        .and_then(|c| c.message.content.clone())
+        .unwrap_or_default())
+}
+
+/// Text deltas of a streamed chat completions response, up to `data: [DONE]`.
+fn openai_stream(response: reqwest::Response) -> BoxStream<'static, AppResult<String>> {
+    sse_data(response)
+        .take_while(|data| std::future::ready(!matches!(data, Ok(d) if d == "[DONE]")))
+        .filter_map(|data| async move {
+            let chunk: OpenAIStreamChunk = match data.and_then(|d| parse_event(&d)) {
+                Ok(chunk) => chunk,
+                Err(e) => return Some(Err(e)),
+            };
+            chunk
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|c| c.delta.content)
+                .filter(|text| !text.is_empty())
+                .map(Ok)
+        })
+        .boxed()
+}
+
+// Azure OpenAI Provider
+const AZURE_API_VERSION: &str = "2024-02-01";
+
+/// Azure OpenAI serves a model through a named deployment, configured as the model.
+pub struct AzureOpenAIProvider {
+    api_key: String,
+    base_url: String,
+    deployment: String,
+    client: Client,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(client: Client, api_key: String, base_url: Option<String>, model: Option<String>) -> AppResult<Self> {
+        let base_url = base_url
+            .ok_or_else(|| AppError::BadRequest("Azure OpenAI requires a base URL (your resource endpoint)".to_string()))?;
+        let deployment = model
+            .ok_or_else(|| AppError::BadRequest("Azure OpenAI requires a deployment name as the model".to_string()))?;
+
+        Ok(Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            deployment,
+            client,
+        })
+    }
+
+    async fn send_chat(&self, messages: &[ConversationMessage], options: GenerateOptions, stream: bool) -> AppResult<reqwest::Response> {
+        // The deployment decides the model; a model in the body is ignored
+        let deployment = options.model.clone().unwrap_or_else(|| self.deployment.clone());
+        let request = OpenAIRequest::new(messages, options, &self.deployment, stream);
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/openai/deployments/{}/chat/completions?api-version={}",
+                self.base_url, deployment, AZURE_API_VERSION
+            ))
+            .header("api-key", &self.api_key)
+            .header("content-type", "application/json")
+            .json(&request)
+            .headers(trace_headers())
+            .timeout(GENERATION_TIMEOUT)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(api_error("Azure OpenAI", status, &body));
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl AIProvider for AzureOpenAIProvider {
+    fn default_model(&self) -> &str {
+        &self.deployment
+    }
+
+    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
+        self.generate_conversation(&[ConversationMessage::user(prompt)], options).await
+    }
+
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+        let response = self.send_chat(messages, options, false).await?;
+        openai_content(response).await
+    }
+
+    async fn generate_stream(&self, prompt: &str, options: GenerateOptions) -> AppResult<BoxStream<'_, AppResult<String>>> {
+        let response = self.send_chat(&[ConversationMessage::user(prompt)], options, true).await?;
+        Ok(openai_stream(response))
+    }
+
+    /// Azure has no endpoint listing what a key can use, so this is just the deployment.
+    async fn list_models(&self) -> AppResult<Vec<ModelInfo>> {
+        Ok(vec![ModelInfo {
+            id: self.deployment.clone(),
+            display_name: self.deployment.clone(),
+            created_at: None,
+        }])
+    }
+}
+
 // Gemini Provider
 pub struct GeminiProvider {
     api_key: String,
@@ -375,12 +718,12 @@ pub struct GeminiProvider {
 }
 
 impl GeminiProvider {
-    pub fn new(api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
+    pub fn new(client: Client, api_key: String, base_url: Option<String>, model: Option<String>) -> Self {
         Self {
             api_key,
             base_url: base_url.unwrap_or_else(|| "https://generativelanguage.googleapis.com".to_string()),
             default_model: model.unwrap_or_else(|| "gemini-2.0-flash".to_string()),
-            client: Client::new(),
+            client,
         }
     }
 }
@@ -394,6 +737,50 @@ struct GeminiRequest {
     generation_config: GeminiGenerationConfig,
 }
 
+impl GeminiRequest {
+    /// The conversation as Gemini contents, with the image on the last one.
+    fn new(conversation: &[ConversationMessage], options: GenerateOptions) -> Self {
+        let last = conversation.len().saturating_sub(1);
+        let contents = conversation
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut parts = vec![GeminiPart::Text { text: message.content.clone() }];
+                if let (true, Some(image_data)) = (i == last, &options.image_base64) {
+                    parts.push(GeminiPart::Image {
+                        inline_data: GeminiInlineData {
+                            mime_type: options.image_mime_type.clone().unwrap_or_else(|| "image/png".to_string()),
+                            data: image_data.clone(),
+                        },
+                    });
+                }
+                // Gemini calls the assistant "model"
+                let role = match message.role {
+                    ConversationRole::User => "user",
+                    ConversationRole::Assistant => "model",
+                };
+                GeminiContent {
+                    role: role.to_string(),
+                    parts,
+                }
+            })
+            .collect();
+
+        let system_instruction = options.system_prompt.map(|s| GeminiSystemInstruction {
+            parts: vec![GeminiPart::Text { text: s }],
+        });
+
+        Self {
+            contents,
+            system_instruction,
+            generation_config: GeminiGenerationConfig {
+                temperature: options.temperature.unwrap_or(0.7),
+                max_output_tokens: options.max_tokens.unwrap_or(2000),
+            },
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct GeminiSystemInstruction {
     parts: Vec<GeminiPart>,
@@ -459,35 +846,17 @@ struct GeminiModel {
 
 #[async_trait]
 impl AIProvider for GeminiProvider {
-    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
-        let model = options.model.as_deref().unwrap_or(&self.default_model);
-
-        let mut parts = vec![GeminiPart::Text { text: prompt.to_string() }];
-
-        if let Some(image_data) = &options.image_base64 {
-            parts.push(GeminiPart::Image {
-                inline_data: GeminiInlineData {
-                    mime_type: options.image_mime_type.clone().unwrap_or_else(|| "image/png".to_string()),
-                    data: image_data.clone(),
-                },
-            });
-        }
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
 
-        let system_instruction = options.system_prompt.map(|s| GeminiSystemInstruction {
-            parts: vec![GeminiPart::Text { text: s }],
-        });
+    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
+        self.generate_conversation(&[ConversationMessage::user(prompt)], options).await
+    }
 
-        let request = GeminiRequest {
-            contents: vec![GeminiContent {
-                role: "user".to_string(),
-                parts,
-            }],
-            system_instruction,
-            generation_config: GeminiGenerationConfig {
-                temperature: options.temperature.unwrap_or(0.7),
-                max_output_tokens: options.max_tokens.unwrap_or(2000),
-            },
-        };
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+        let model = options.model.clone().unwrap_or_else(|| self.default_model.clone());
+        let request = GeminiRequest::new(messages, options);
 
         let response = self
             .client
@@ -497,17 +866,16 @@ impl AIProvider for GeminiProvider {
             ))
             .header("content-type", "application/json")
             .json(&request)
+            .headers(trace_headers())
+            .timeout(GENERATION_TIMEOUT)
             .send()
             .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+            .map_err(request_failed)?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "Gemini API error ({}): {}",
-                status, body
-            )));
+            return Err(api_error("Gemini", status, &body));
         }
 
         let result: GeminiResponse = response
@@ -536,17 +904,15 @@ impl AIProvider for GeminiProvider {
                 "{}/v1beta/models?key={}",
                 self.base_url, self.api_key
             ))
+            .headers(trace_headers())
             .send()
             .await
-            .map_err(|e| AppError::Internal(format!("HTTP request failed: {}", e)))?;
+            .map_err(request_failed)?;
 
         if !response.status().is_success() {
             let status = response.status();
             let body = response.text().await.unwrap_or_default();
-            return Err(AppError::Internal(format!(
-                "Gemini API error ({}): {}",
-                status, body
-            )));
+            return Err(api_error("Gemini", status, &body));
         }
 
         let result: GeminiModelsResponse = response
@@ -572,12 +938,334 @@ impl AIProvider for GeminiProvider {
     }
 }
 
+// Ollama Provider
+pub struct OllamaProvider {
+    base_url: String,
+    default_model: String,
+    client: Client,
+}
+
+impl OllamaProvider {
+    pub fn new(client: Client, base_url: Option<String>, model: Option<String>) -> Self {
+        Self {
+            base_url: base_url.unwrap_or_else(|| "http://localhost:11434".to_string()),
+            default_model: model.unwrap_or_else(|| "llama3.2".to_string()),
+            client,
+        }
+    }
+
+    async fn send_chat(&self, conversation: &[ConversationMessage], options: GenerateOptions, stream: bool) -> AppResult<reqwest::Response> {
+        let mut messages = vec![OllamaMessage {
+            role: "system".to_string(),
+            content: options.system_prompt.unwrap_or_else(|| {
+                "You are a presentation assistant that generates markdown slides separated by ---.".to_string()
+            }),
+            images: Vec::new(),
+        }];
+        let mut image = options.image_base64;
+        let last = conversation.len().saturating_sub(1);
+        for (i, message) in conversation.iter().enumerate() {
+            messages.push(OllamaMessage {
+                role: message.role.as_str().to_string(),
+                content: message.content.clone(),
+                images: if i == last { image.take().into_iter().collect() } else { Vec::new() },
+            });
+        }
+
+        let request = OllamaRequest {
+            model: options.model.unwrap_or_else(|| self.default_model.clone()),
+            messages,
+            stream,
+            options: OllamaOptions {
+                temperature: options.temperature.unwrap_or(0.7),
+                num_predict: options.max_tokens.unwrap_or(2000),
+            },
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(&request)
+            .headers(trace_headers())
+            .timeout(GENERATION_TIMEOUT)
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(api_error("Ollama", status, &body));
+        }
+
+        Ok(response)
+    }
+}
+
+#[derive(Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    options: OllamaOptions,
+}
+
+#[derive(Serialize)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+    // Base64 images for multimodal models
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct OllamaOptions {
+    temperature: f32,
+    num_predict: u32,
+}
+
+// Both the whole response and each streamed line
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: Option<OllamaMessageResponse>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OllamaMessageResponse {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTagsResponse {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaModel {
+    name: String,
+    modified_at: Option<String>,
+}
+
+#[async_trait]
+impl AIProvider for OllamaProvider {
+    fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
+    async fn generate_content(&self, prompt: &str, options: GenerateOptions) -> AppResult<String> {
+        self.generate_conversation(&[ConversationMessage::user(prompt)], options).await
+    }
+
+    async fn generate_conversation(&self, messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+        let response = self.send_chat(messages, options, false).await?;
+
+        let result: OllamaResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to parse response: {}", e)))?;
+
+        if let Some(error) = result.error {
+            return Err(AppError::Internal(format!("Ollama error: {}", error)));
+        }
+        Ok(result.message.map(|m| m.content).unwrap_or_default())
+    }
+
+    /// Ollama streams one JSON object per line rather than server-sent events.
+    async fn generate_stream(&self, prompt: &str, options: GenerateOptions) -> AppResult<BoxStream<'_, AppResult<String>>> {
+        let response = self.send_chat(&[ConversationMessage::user(prompt)], options, true).await?;
+
+        let chunks = response_lines(response).filter_map(|line| async move {
+            let line = match line {
+                Ok(line) if line.is_empty() => return None,
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            match parse_event::<OllamaResponse>(&line) {
+                Ok(OllamaResponse { error: Some(error), .. }) => {
+                    Some(Err(AppError::Internal(format!("Ollama error: {}", error))))
+                }
+                Ok(OllamaResponse { message, .. }) => message.map(|m| m.content).filter(|text| !text.is_empty()).map(Ok),
+                Err(e) => Some(Err(e)),
+            }
+        });
+        Ok(chunks.boxed())
+    }
+
+    async fn list_models(&self) -> AppResult<Vec<ModelInfo>> {
+        let response = self
+            .client
+            .get(format!("{}/api/tags", self.base_url))
+            .headers(trace_headers())
+            .send()
+            .await
+            .map_err(request_failed)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(api_error("Ollama", status, &body));
+        }
+
+        let result: OllamaTagsResponse = response
+            .json()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to parse response: {}", e)))?;
+
+        Ok(result
+            .models
+            .into_iter()
+            .map(|m| ModelInfo {
+                id: m.name.clone(),
+                display_name: m.name,
+                created_at: m.modified_at,
+            })
+            .collect())
+    }
+}
+
 // Provider Factory
-pub fn create_provider(provider_name: &str, api_key: String, base_url: Option<String>, model: Option<String>) -> AppResult<Box<dyn AIProvider>> {
-    match provider_name {
-        "anthropic" => Ok(Box::new(AnthropicProvider::new(api_key, base_url, model))),
-        "openai" => Ok(Box::new(OpenAIProvider::new(api_key, base_url, model))),
-        "gemini" => Ok(Box::new(GeminiProvider::new(api_key, base_url, model))),
-        _ => Err(AppError::BadRequest(format!("Unknown AI provider: {}", provider_name))),
+type ProviderFactory = fn(Client, String, Option<String>, Option<String>) -> AppResult<Box<dyn AIProvider>>;
+
+/// Providers `create_provider` knows how to build, by name.
+const PROVIDERS: &[(&str, ProviderFactory)] = &[
+    ("anthropic", |client, api_key, base_url, model| {
+        Ok(Box::new(AnthropicProvider::new(client, api_key, base_url, model)))
+    }),
+    ("openai", |client, api_key, base_url, model| Ok(Box::new(OpenAIProvider::new(client, api_key, base_url, model)))),
+    ("gemini", |client, api_key, base_url, model| Ok(Box::new(GeminiProvider::new(client, api_key, base_url, model)))),
+    ("ollama", |client, _, base_url, model| Ok(Box::new(OllamaProvider::new(client, base_url, model)))),
+    ("azure-openai", |client, api_key, base_url, model| {
+        Ok(Box::new(AzureOpenAIProvider::new(client, api_key, base_url, model)?))
+    }),
+];
+
+/// Names of the providers `create_provider` knows how to build.
+pub fn provider_names() -> impl Iterator<Item = &'static str> {
+    PROVIDERS.iter().map(|(name, _)| *name)
+}
+
+/// Whether a provider needs an API key; local providers like Ollama don't.
+pub fn requires_api_key(provider_name: &str) -> bool {
+    provider_name != "ollama"
+}
+
+/// Builds a provider that sends its requests through `client`.
+pub fn create_provider(
+    client: Client,
+    provider_name: &str,
+    api_key: String,
+    base_url: Option<String>,
+    model: Option<String>,
+) -> AppResult<Box<dyn AIProvider>> {
+    match PROVIDERS.iter().find(|(name, _)| *name == provider_name) {
+        Some((_, create)) => create(client, api_key, base_url, model),
+        None => Err(AppError::BadRequest(format!("Unknown AI provider: {}", provider_name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lines_keep_characters_split_across_chunks() {
+        let body = "data: {\"text\": \"Größe\"}\r\ndata: ✓\n".as_bytes();
+        // Cut inside the two-byte "ö" and the three-byte "✓"
+        let cuts = [0, 19, 34, body.len()];
+        let chunks = cuts.windows(2).map(|w| Ok::<_, std::convert::Infallible>(body[w[0]..w[1]].to_vec()));
+        let lines: Vec<String> = lines(stream::iter(chunks)).map(Result::unwrap).collect().await;
+        assert_eq!(lines, ["data: {\"text\": \"Größe\"}", "data: ✓"]);
+    }
+
+    #[test]
+    fn test_openai_request_carries_the_conversation() {
+        let request = OpenAIRequest::new(&conversation(), image_options(), "gpt-4o", false);
+        let request = serde_json::to_value(request).unwrap();
+        let messages = request["messages"].as_array().unwrap();
+        let roles: Vec<_> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        assert_eq!(messages[0]["content"], "Be brief");
+        assert_eq!(messages[2]["content"], "# Tides");
+        // Only the latest user turn carries the image
+        assert_eq!(messages[1]["content"].as_array().unwrap().len(), 1);
+        assert_eq!(messages[3]["content"][1]["image_url"]["url"], "data:image/png;base64,aW1n");
+    }
+
+    fn conversation() -> [ConversationMessage; 3] {
+        [
+            ConversationMessage::user("Three slides on tides"),
+            ConversationMessage { role: ConversationRole::Assistant, content: "# Tides".to_string() },
+            ConversationMessage::user("Add a diagram"),
+        ]
+    }
+
+    fn image_options() -> GenerateOptions {
+        GenerateOptions {
+            system_prompt: Some("Be brief".to_string()),
+            image_base64: Some("aW1n".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_every_listed_provider_can_be_created() {
+        for name in provider_names() {
+            let (base_url, model) = (Some("http://localhost:1".to_string()), Some("m".to_string()));
+            assert!(create_provider(Client::new(), name, "key".to_string(), base_url, model).is_ok(), "{}", name);
+        }
+        let unknown = create_provider(Client::new(), "mistral", "key".to_string(), None, None);
+        assert!(matches!(unknown, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_errors_tell_a_rejected_key_from_a_network_failure() {
+        let kind = |e: AppError| match e {
+            AppError::Provider { kind, .. } => kind,
+            e => panic!("{:?}", e),
+        };
+
+        // Nothing listens on port 1
+        let base_url = Some("http://127.0.0.1:1".to_string());
+        let offline = create_provider(Client::new(), "openai", "key".to_string(), base_url, None).unwrap();
+        let error = offline.generate_content("hi", GenerateOptions::default()).await.unwrap_err();
+        assert_eq!(kind(error), ProviderErrorKind::Network);
+
+        for (status, expected) in [
+            (reqwest::StatusCode::UNAUTHORIZED, ProviderErrorKind::Auth),
+            (reqwest::StatusCode::FORBIDDEN, ProviderErrorKind::Auth),
+            (reqwest::StatusCode::TOO_MANY_REQUESTS, ProviderErrorKind::Provider),
+            (reqwest::StatusCode::INTERNAL_SERVER_ERROR, ProviderErrorKind::Provider),
+        ] {
+            assert_eq!(kind(api_error("OpenAI", status, "")), expected, "{}", status);
+        }
+    }
+
+    #[test]
+    fn test_anthropic_request_carries_the_conversation() {
+        let request = AnthropicRequest::new(&conversation(), image_options(), "claude", false);
+        let request = serde_json::to_value(request).unwrap();
+        assert_eq!(request["system"], "Be brief");
+        let messages = request["messages"].as_array().unwrap();
+        let roles: Vec<_> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "assistant", "user"]);
+        assert_eq!(messages[1]["content"][0]["text"], "# Tides");
+        // Only the latest user turn carries the image, ahead of its text
+        assert_eq!(messages[0]["content"].as_array().unwrap().len(), 1);
+        assert_eq!(messages[2]["content"][0]["source"]["data"], "aW1n");
+        assert_eq!(messages[2]["content"][1]["text"], "Add a diagram");
+    }
+
+    #[test]
+    fn test_gemini_request_carries_the_conversation() {
+        let request = serde_json::to_value(GeminiRequest::new(&conversation(), image_options())).unwrap();
+        assert_eq!(request["systemInstruction"]["parts"][0]["text"], "Be brief");
+        let contents = request["contents"].as_array().unwrap();
+        let roles: Vec<_> = contents.iter().map(|c| c["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["user", "model", "user"]);
+        assert_eq!(contents[1]["parts"][0]["text"], "# Tides");
+        assert_eq!(contents[0]["parts"].as_array().unwrap().len(), 1);
+        assert_eq!(contents[2]["parts"][1]["inline_data"]["data"], "aW1n");
     }
 }
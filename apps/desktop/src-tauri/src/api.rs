@@ -1,50 +1,181 @@
 use axum::{
-    body::Body,
-    extract::{Multipart, Path, State},
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     http::{header, StatusCode},
-    response::Response,
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{delete, get, post, put},
     Json, Router,
 };
+use futures::Stream;
 use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use uuid::Uuid;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use crate::ai::{create_provider, GenerateOptions};
-use crate::encryption::{decrypt, encrypt};
-use crate::error::{AppError, AppResult};
+use crate::ai::{
+    cached_models, create_provider, model_cache_ttl, provider_names, requires_api_key, ConversationRole, GenerateOptions,
+    ModelInfo, ModelList,
+};
+use crate::diff;
+use crate::encryption::{decrypt, encrypt, is_current_key, KEY_ROTATION};
+use crate::error::{AppError, AppResult, ProviderErrorKind, ValidationBuilder};
 use crate::models::*;
-use crate::SharedState;
-
+use crate::lint;
+use crate::markdown;
+use crate::media;
+use crate::mcp;
+use crate::palette::{self, ImageTheme, ThemeMode};
+use crate::middleware::{ai_requests_per_minute, RateLimitLayer};
+use crate::pipeline;
+use crate::placeholders;
+use crate::profiles::{self, ProfileRegistry};
+use crate::quick_search;
+use crate::related;
+use crate::safe_mode;
+use crate::slides;
+use crate::suggestions;
+use crate::trace;
+use crate::uploads::{self, RangeRequest};
+use crate::versioning;
+use crate::watchlists;
+use crate::{AppState, SharedState};
+
+/// The API routes, declared once and mounted under `/api/v1` and `/api` by `versioning::mount`.
 pub fn create_router(state: SharedState) -> Router {
     Router::new()
+        // Health
+        .route("/health", get(health))
+        .route("/version", get(version))
         // Presentations
         .route("/presentations", get(list_presentations))
         .route("/presentations", post(create_presentation))
+        .route("/presentations/search", get(search_presentations))
+        .route("/search/quick", get(quick_search))
+        .route("/presentations/import", post(import_presentation))
+        .route("/presentations/bulk", post(bulk_update_presentations))
+        .route("/presentations/merge", post(merge_presentations))
+        .route("/presentations/replace", post(replace_in_presentations))
+        .route("/presentations/deleted", get(list_deleted_presentations))
         .route("/presentations/{id}", get(get_presentation))
         .route("/presentations/{id}", put(update_presentation))
         .route("/presentations/{id}", delete(delete_presentation))
+        .route("/presentations/{id}/export", get(export_presentation))
+        .route("/presentations/{id}/health", get(get_presentation_health))
+        .route("/presentations/{id}/stats", get(get_presentation_stats))
+        .route("/presentations/{id}/lint", get(lint_presentation))
+        .route("/presentations/{id}/diff", post(diff_presentation))
+        .route("/presentations/{id}/related", get(get_related_presentations))
+        .route("/presentations/{id}/duplicate", post(duplicate_presentation))
+        .route("/presentations/{id}/restore", post(restore_presentation))
+        .route("/presentations/{id}/touch", post(touch_presentation))
+        .route("/presentations/{id}/archive", post(archive_presentation))
+        .route("/presentations/{id}/unarchive", post(unarchive_presentation))
+        .route("/presentations/{id}/favorite", post(favorite_presentation))
+        .route("/presentations/{id}/unfavorite", post(unfavorite_presentation))
+        .route("/presentations/{id}/draft", get(get_draft).put(save_draft).delete(discard_draft))
+        .route("/presentations/{id}/draft/commit", post(commit_draft))
+        .route("/presentations/{id}/lock", post(lock_presentation))
+        .route("/presentations/{id}/unlock", post(unlock_presentation))
+        .route("/presentations/{id}/permanent", delete(delete_presentation_permanently))
+        .route("/presentations/{id}/versions", get(list_presentation_versions))
+        .route("/presentations/{id}/versions/{version_id}/restore", post(restore_presentation_version))
+        .route("/presentations/{id}/revisions", get(list_presentation_versions))
+        .route("/presentations/{id}/revisions/{version_id}/restore", post(restore_presentation_version))
+        .route("/presentations/{id}/snapshots", get(list_presentation_snapshots))
+        .route("/presentations/{id}/snapshots/{snapshot_id}/restore", post(restore_presentation_snapshot))
+        .route("/presentations/{id}/slides", get(list_slides).post(insert_slide))
+        .route("/presentations/{id}/slides/reorder", post(reorder_slides))
+        .route(
+            "/presentations/{id}/slides/{index}",
+            get(get_slide).put(update_slide).post(insert_slide_before).delete(delete_slide),
+        )
+        .route("/presentations/{id}/placeholders", get(list_placeholders))
+        .route("/presentations/{id}/fill-placeholders", post(fill_placeholders))
+        .route("/presentations/{id}/tags", post(add_presentation_tag))
+        .route("/presentations/{id}/tags/{tag}", delete(remove_presentation_tag))
+        .route("/presentations/{id}/watchlist", get(list_watchlist_sources).post(create_watchlist_source))
+        .route("/presentations/{id}/watchlist/refresh", post(refresh_watchlist))
+        .route(
+            "/presentations/{id}/watchlist/{source_id}",
+            put(update_watchlist_source).delete(delete_watchlist_source),
+        )
+        .route("/tags", get(list_tags))
         // Themes & Layout
         .route("/themes", get(list_themes))
         .route("/themes", post(create_theme))
+        .route(
+            "/themes/from-image",
+            post(theme_from_image).layer(DefaultBodyLimit::max(uploads::MAX_SINGLE_UPLOAD_BYTES + 64 * 1024)),
+        )
         .route("/themes/{id}", get(get_theme).put(update_theme).delete(delete_theme))
+        .route("/themes/{id}/duplicate", post(duplicate_theme))
+        .route("/themes/{id}/export", get(export_theme))
+        .route("/themes/{id}/usage", get(theme_usage))
+        .route("/themes/import", post(import_theme))
+        .route("/themes/{id}/reset", post(reset_theme))
+        .route("/themes/reset-defaults", post(reset_default_themes))
         .route("/layout-rules", get(list_layout_rules))
+        // Pipelines
+        .route("/pipelines", get(list_pipelines).post(create_pipeline))
+        .route("/pipelines/{id}", get(get_pipeline).put(update_pipeline).delete(delete_pipeline))
+        .route("/pipelines/{id}/run", post(run_pipeline))
+        // Admin
+        .route("/admin/rotate-key", post(rotate_encryption_key))
+        .route("/admin/backup", post(create_backup))
+        .route("/admin/backups", get(list_backups))
+        // Profiles
+        .route("/profiles", get(list_profiles).post(create_profile))
+        .route("/profiles/{name}", delete(delete_profile))
+        .route("/profiles/{name}/activate", post(activate_profile))
         // Media
         .route("/media", get(list_media))
-        .route("/media", post(upload_media))
+        .route(
+            "/media",
+            // Room for the multipart framing around a file of the maximum size
+            post(upload_media).layer(DefaultBodyLimit::max(uploads::MAX_SINGLE_UPLOAD_BYTES + 64 * 1024)),
+        )
         .route("/media/{id}", delete(delete_media))
+        .route("/media/uploads", post(create_upload_session))
+        .route("/media/uploads/{id}", get(get_upload_session))
+        .route(
+            "/media/uploads/{id}/chunks/{index}",
+            put(put_upload_chunk).layer(DefaultBodyLimit::max(uploads::MAX_CHUNK_BYTES as usize)),
+        )
+        .route("/media/uploads/{id}/complete", post(complete_upload_session))
         .route("/uploads/{filename}", get(serve_upload))
+        // Settings
+        .route("/settings", get(get_app_settings).put(update_app_settings))
+        .route("/settings/mcp-tools", get(get_mcp_tool_settings).put(update_mcp_tool_settings))
+        .route("/settings/health-weights", get(get_health_weights).put(update_health_weights))
         // AI Config
         .route("/ai-config", get(list_ai_configs))
         .route("/ai-config", post(create_ai_config))
         .route("/ai-config/{provider}/models", get(list_provider_models))
         .route("/ai-config/{id}", put(update_ai_config))
         .route("/ai-config/{id}", delete(delete_ai_config))
+        .merge(ai_routes())
+        .layer(middleware::from_fn_with_state(state.clone(), profiles::reject_while_switching))
+        .with_state(state)
+}
+
+/// Routes under `/ai/`, which reach the providers and so are rate limited per client.
+fn ai_routes() -> Router<SharedState> {
+    Router::new()
+        .route("/ai/models", get(list_models))
+        .route("/ai/providers/{provider}/models", get(list_provider_models))
+        .route("/ai/providers/{provider}/test", post(test_ai_provider))
         // AI Operations
         .route("/ai/generate", post(ai_generate))
+        .route("/ai/stream", post(ai_stream))
+        .route("/ai/conversation", post(ai_conversation))
         .route("/ai/improve", post(ai_improve))
         .route("/ai/suggest-style", post(ai_suggest_style))
+        .route("/ai/suggestions", post(ai_suggestions))
         .route("/ai/generate-theme", post(ai_generate_theme))
         .route("/ai/speaker-notes", post(ai_speaker_notes))
         .route("/ai/generate-diagram", post(ai_generate_diagram))
@@ -52,15 +183,106 @@ pub fn create_router(state: SharedState) -> Router {
         .route("/ai/outline-to-slides", post(ai_outline_to_slides))
         .route("/ai/visual-review", post(ai_visual_review))
         .route("/ai/visual-improve", post(ai_visual_improve))
-        .with_state(state)
+        .layer(RateLimitLayer::new(ai_requests_per_minute()))
 }
 
-async fn list_presentations(State(state): State<SharedState>) -> AppResult<Json<Vec<Presentation>>> {
+async fn health(
+    State(state): State<SharedState>,
+    Query(query): Query<HealthQuery>,
+) -> (StatusCode, Json<serde_json::Value>) {
     let state = state.read().await;
-    let presentations = state.db.list_presentations().await?;
+    let tool_settings: AppResult<McpToolSettings> = async {
+        state.db.ping().await?;
+        state.db.get_mcp_tool_settings().await
+    }
+    .await;
+    let tool_settings = match tool_settings {
+        Ok(settings) => settings,
+        Err(e) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(json!({ "status": "error", "db": e.to_string() })),
+            )
+        }
+    };
+    let mcp_tools: Vec<String> = mcp::tool_names()
+        .into_iter()
+        .filter(|tool| mcp::tool_enabled(&tool_settings, tool))
+        .collect();
+
+    let mut body = json!({
+        "status": "ok",
+        "db": "ok",
+        "profile": state.profile,
+        "safeMode": state.safe_mode,
+        "mcpTools": mcp_tools,
+        "apiVersion": versioning::API_VERSION,
+        "timestamp": chrono::Utc::now(),
+    });
+    if !query.integrity {
+        return (StatusCode::OK, Json(body));
+    }
+
+    match state.db.pragma_integrity_check().await {
+        Ok(true) => {
+            body["integrity"] = json!("ok");
+            (StatusCode::OK, Json(body))
+        }
+        Ok(false) => {
+            body["status"] = json!("error");
+            body["integrity"] = json!("failed");
+            (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+        }
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "error", "db": e.to_string() })),
+        ),
+    }
+}
+
+async fn version() -> Json<serde_json::Value> {
+    // Set by build.rs as seconds since the epoch
+    let build_time = option_env!("SLIDES_BUILD_TIME")
+        .and_then(|secs| secs.parse::<i64>().ok())
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0));
+
+    Json(json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "apiVersion": versioning::API_VERSION,
+        "buildTime": build_time,
+    }))
+}
+
+async fn list_presentations(
+    State(state): State<SharedState>,
+    Query(query): Query<ListPresentationsQuery>,
+) -> AppResult<Json<PaginatedResult<Presentation>>> {
+    let state = state.read().await;
+    let presentations = state.db.list_presentations(query).await?;
     Ok(Json(presentations))
 }
 
+async fn search_presentations(
+    State(state): State<SharedState>,
+    Query(query): Query<SearchPresentationsQuery>,
+) -> AppResult<Json<PaginatedResult<Presentation>>> {
+    let state = state.read().await;
+    let results = state
+        .db
+        .search_presentations(&query.q, query.page, query.per_page)
+        .await?;
+    Ok(Json(results))
+}
+
+async fn quick_search(
+    State(state): State<SharedState>,
+    Query(query): Query<QuickSearchQuery>,
+) -> AppResult<Json<Vec<QuickSearchResult>>> {
+    let state = state.read().await;
+    let results = quick_search::search(&state, &query.q, query.limit).await?;
+    Ok(Json(results))
+}
+
 async fn get_presentation(
     State(state): State<SharedState>,
     Path(id): Path<String>,
@@ -75,85 +297,1087 @@ async fn create_presentation(
     Json(data): Json<CreatePresentation>,
 ) -> AppResult<Json<Presentation>> {
     let state = state.read().await;
-    let presentation = state.db.create_presentation(data).await?;
-    Ok(Json(presentation))
+    let presentation = state.db.create_presentation(data).await?;
+    Ok(Json(presentation))
+}
+
+async fn update_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<UpdatePresentation>,
+) -> AppResult<Json<PresentationUpdate>> {
+    let state = state.read().await;
+    let update = state.db.update_presentation(&id, data).await?;
+    Ok(Json(update))
+}
+
+async fn delete_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<()> {
+    let state = state.read().await;
+    state.db.delete_presentation(&id).await?;
+    Ok(())
+}
+
+async fn bulk_update_presentations(
+    State(state): State<SharedState>,
+    Json(data): Json<BulkPresentations>,
+) -> AppResult<Json<BulkResult>> {
+    let state = state.read().await;
+    let result = state.db.bulk_update_presentations(data).await?;
+    Ok(Json(result))
+}
+
+async fn merge_presentations(
+    State(state): State<SharedState>,
+    Json(data): Json<MergePresentations>,
+) -> AppResult<(StatusCode, Json<Presentation>)> {
+    let state = state.read().await;
+    let presentation = state.db.merge_presentations(data).await?;
+    Ok((StatusCode::CREATED, Json(presentation)))
+}
+
+/// Find and replace across presentations; see `Database::replace_in_presentations`.
+async fn replace_in_presentations(
+    State(state): State<SharedState>,
+    Json(data): Json<ReplaceRequest>,
+) -> AppResult<Json<ReplaceResult>> {
+    let state = state.read().await;
+    let result = state.db.replace_in_presentations(data).await?;
+    Ok(Json(result))
+}
+
+async fn delete_presentation_permanently(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<StatusCode> {
+    let state = state.read().await;
+    state.db.delete_presentation_permanently(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_deleted_presentations(State(state): State<SharedState>) -> AppResult<Json<Vec<Presentation>>> {
+    let state = state.read().await;
+    let presentations = state.db.list_deleted_presentations().await?;
+    Ok(Json(presentations))
+}
+
+/// Called by the UI when a deck is opened, for recently-used ordering.
+async fn touch_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.touch_presentation(&id).await?;
+    Ok(Json(presentation))
+}
+
+async fn archive_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_archived(&id, true).await?;
+    Ok(Json(presentation))
+}
+
+async fn unarchive_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_archived(&id, false).await?;
+    Ok(Json(presentation))
+}
+
+async fn favorite_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_favorite(&id, true).await?;
+    Ok(Json(presentation))
+}
+
+async fn unfavorite_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_favorite(&id, false).await?;
+    Ok(Json(presentation))
+}
+
+async fn get_draft(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<PresentationDraft>> {
+    let state = state.read().await;
+    let draft = state.db.get_draft(&id).await?;
+    Ok(Json(draft))
+}
+
+async fn save_draft(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<SaveDraft>,
+) -> AppResult<Json<PresentationDraft>> {
+    let state = state.read().await;
+    let draft = state.db.save_draft(&id, data.content).await?;
+    Ok(Json(draft))
+}
+
+async fn commit_draft(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<PresentationUpdate>> {
+    let state = state.read().await;
+    let update = state.db.commit_draft(&id).await?;
+    Ok(Json(update))
+}
+
+async fn discard_draft(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<()> {
+    let state = state.read().await;
+    state.db.discard_draft(&id).await?;
+    Ok(())
+}
+
+async fn lock_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_locked(&id, true).await?;
+    Ok(Json(presentation))
+}
+
+async fn unlock_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.set_locked(&id, false).await?;
+    Ok(Json(presentation))
+}
+
+async fn restore_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.restore_presentation(&id).await?;
+    Ok(Json(presentation))
+}
+
+async fn list_presentation_versions(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<PresentationVersion>>> {
+    let state = state.read().await;
+    let versions = state.db.list_versions(&id).await?;
+    Ok(Json(versions))
+}
+
+async fn restore_presentation_version(
+    State(state): State<SharedState>,
+    Path((id, version_id)): Path<(String, String)>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.restore_version(&id, &version_id).await?;
+    Ok(Json(presentation))
+}
+
+async fn list_presentation_snapshots(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<PresentationSnapshot>>> {
+    let state = state.read().await;
+    let snapshots = state.db.list_snapshots(&id).await?;
+    Ok(Json(snapshots))
+}
+
+async fn restore_presentation_snapshot(
+    State(state): State<SharedState>,
+    Path((id, snapshot_id)): Path<(String, String)>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.restore_snapshot(&id, &snapshot_id).await?;
+    Ok(Json(presentation))
+}
+
+async fn duplicate_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    data: Option<Json<DuplicatePresentation>>,
+) -> AppResult<(StatusCode, Json<Presentation>)> {
+    let title = data.and_then(|Json(d)| d.title);
+    let state = state.read().await;
+    let presentation = state.db.duplicate_presentation(&id, title).await?;
+    Ok((StatusCode::CREATED, Json(presentation)))
+}
+
+// Slide handlers
+async fn list_slides(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<SlideItem>>> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+    Ok(Json(slides::slide_items(&presentation.content)))
+}
+
+async fn get_slide(
+    State(state): State<SharedState>,
+    Path((id, index)): Path<(String, usize)>,
+) -> AppResult<Json<SlideDetail>> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+    Ok(Json(slides::get_slide(&presentation.content, index)?))
+}
+
+async fn update_slide(
+    State(state): State<SharedState>,
+    Path((id, index)): Path<(String, usize)>,
+    Json(data): Json<SlideContent>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.replace_slide(&id, index, &data.content).await?;
+    Ok(Json(presentation))
+}
+
+async fn insert_slide(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<InsertSlide>,
+) -> AppResult<(StatusCode, Json<Presentation>)> {
+    let state = state.read().await;
+    let presentation = state.db.insert_slide(&id, data.position, &data.content).await?;
+    Ok((StatusCode::CREATED, Json(presentation)))
+}
+
+async fn insert_slide_before(
+    State(state): State<SharedState>,
+    Path((id, index)): Path<(String, usize)>,
+    Json(data): Json<SlideContent>,
+) -> AppResult<(StatusCode, Json<Presentation>)> {
+    let state = state.read().await;
+    let presentation = state.db.insert_slide(&id, Some(index), &data.content).await?;
+    Ok((StatusCode::CREATED, Json(presentation)))
+}
+
+async fn delete_slide(
+    State(state): State<SharedState>,
+    Path((id, index)): Path<(String, usize)>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.delete_slide(&id, index).await?;
+    Ok(Json(presentation))
+}
+
+async fn reorder_slides(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(order): Json<Vec<usize>>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.reorder_slides(&id, &order).await?;
+    Ok(Json(presentation))
+}
+
+/// Creates a presentation from an uploaded `.md` file (multipart field `file`). With `upsert`,
+/// a file exported from a presentation that still exists updates it instead; see
+/// `upsert_import`.
+async fn import_presentation(
+    State(state): State<SharedState>,
+    Query(query): Query<ImportPresentationQuery>,
+    mut multipart: Multipart,
+) -> AppResult<Response> {
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::BadRequest(format!("Failed to read multipart field: {}", e))
+    })? {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let file_name = field.file_name().unwrap_or("presentation.md").to_string();
+        let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+        let extension = std::path::Path::new(&file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        let markdown_name = matches!(extension.as_deref(), Some("md" | "markdown" | "txt"));
+        // Browsers often send .md files without a specific type
+        let text_file = content_type.starts_with("text/") || (content_type == "application/octet-stream" && markdown_name);
+
+        if !text_file {
+            return Err(AppError::BadRequest("Only markdown or plain text files can be imported".to_string()));
+        }
+
+        let data = field.bytes().await.map_err(|e| {
+            AppError::BadRequest(format!("Failed to read file data: {}", e))
+        })?;
+        let text = String::from_utf8(data.to_vec())
+            .ok()
+            .filter(|text| !text.contains('\0'))
+            .ok_or_else(|| AppError::BadRequest("File is not UTF-8 text".to_string()))?;
+
+        let parsed = markdown::parse_markdown(&text);
+        let title = parsed.title.clone().unwrap_or_else(|| {
+            std::path::Path::new(&file_name)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Imported presentation")
+                .to_string()
+        });
+
+        let state = state.read().await;
+        // Themes from other tools (e.g. Marp's `gaia`) may not exist here
+        let theme = match parsed.theme {
+            Some(ref theme) if state.db.get_theme_by_name(theme).await.is_ok() => Some(theme.clone()),
+            _ => None,
+        };
+
+        if let Some(id) = parsed.id.as_deref().filter(|_| query.upsert) {
+            match state.db.get_presentation(id).await {
+                Ok(existing) => return upsert_import(&state, existing, &parsed, title, theme).await,
+                Err(AppError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let presentation = state
+            .db
+            .create_presentation(CreatePresentation {
+                title,
+                content: Some(parsed.content),
+                theme,
+                if_not_exists: false,
+                on_conflict: None,
+            })
+            .await?;
+        let presentation = if parsed.favorite {
+            state.db.set_favorite(&presentation.id, true).await?
+        } else {
+            presentation
+        };
+        return Ok((StatusCode::CREATED, Json(presentation)).into_response());
+    }
+
+    Err(AppError::BadRequest("No file provided".to_string()))
+}
+
+/// Applies a re-imported export to the presentation it came from. A file that wasn't edited
+/// since the export changes nothing; an edited one is written over the presentation if the
+/// presentation hasn't changed since, and is a 409 with both hashes if it has.
+async fn upsert_import(
+    state: &AppState,
+    existing: Presentation,
+    parsed: &markdown::MarkdownFile,
+    title: String,
+    theme: Option<String>,
+) -> AppResult<Response> {
+    let file_hash = crate::db::content_hash(&parsed.content);
+    // Exports may have made upload URLs relative; the deck itself keeps them absolute
+    let content = markdown::absolute_upload_paths(&parsed.content);
+    if parsed.hash.as_ref() == Some(&file_hash) || existing.content == content {
+        return Ok(Json(existing).into_response());
+    }
+
+    // Checked again as part of the write, so an edit landing in between isn't overwritten
+    let stored_unchanged = |stored: &Presentation| {
+        parsed
+            .hash
+            .as_deref()
+            .is_some_and(|hash| markdown::matches_export(&stored.content, hash))
+    };
+    let update = state
+        .db
+        .update_presentation_if(
+            &existing.id,
+            UpdatePresentation {
+                title: Some(title),
+                content: Some(content),
+                theme,
+                settings: None,
+            },
+            stored_unchanged,
+        )
+        .await;
+
+    match update {
+        Ok(update) => Ok(Json(update.presentation).into_response()),
+        Err(AppError::Conflict(_)) => {
+            let stored = state.db.get_presentation(&existing.id).await?;
+            let conflict = ImportConflict {
+                error: format!("Presentation {} changed since it was exported", stored.id),
+                id: stored.id,
+                exported_hash: parsed.hash.clone(),
+                file_hash,
+                stored_hash: crate::db::content_hash(&stored.content),
+            };
+            Ok((StatusCode::CONFLICT, Json(conflict)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn export_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Query(query): Query<ExportPresentationQuery>,
+) -> AppResult<Response> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+
+    let (body, content_type, filename) = match query.format {
+        ExportFormat::Markdown => (
+            markdown::export_markdown(&presentation, query.inline),
+            "text/markdown; charset=utf-8",
+            markdown::export_filename(&presentation.title),
+        ),
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .body(Body::from(body))
+        .unwrap())
+}
+
+async fn get_presentation_health(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<DeckHealth>> {
+    let state = state.read().await;
+    let health = state.db.get_presentation_health(&id).await?;
+    Ok(Json(health))
+}
+
+async fn get_presentation_stats(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<PresentationStats>> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+    Ok(Json(slides::presentation_stats(&presentation.content)))
+}
+
+async fn lint_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<LintIssue>>> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+    let issues = lint::lint_content(&state, &presentation.content).await?;
+    Ok(Json(issues))
+}
+
+async fn diff_presentation(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(request): Json<DiffPresentationRequest>,
+) -> AppResult<Json<PresentationDiff>> {
+    let state = state.read().await;
+    let diff = diff::diff_presentation(&state, &id, &request).await?;
+    Ok(Json(diff))
+}
+
+async fn get_related_presentations(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Query(query): Query<RelatedPresentationsQuery>,
+) -> AppResult<Json<Vec<RelatedPresentation>>> {
+    let state = state.read().await;
+    let related = related::find(&state, &id, query.limit).await?;
+    Ok(Json(related))
+}
+
+async fn list_placeholders(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<Placeholder>>> {
+    let state = state.read().await;
+    let presentation = state.db.get_presentation(&id).await?;
+    Ok(Json(placeholders::find_placeholders(&presentation.content)))
+}
+
+async fn fill_placeholders(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<FillPlaceholders>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.fill_placeholders(&id, &data.values).await?;
+    Ok(Json(presentation))
+}
+
+async fn list_tags(State(state): State<SharedState>) -> AppResult<Json<Vec<Tag>>> {
+    let state = state.read().await;
+    let tags = state.db.list_tags().await?;
+    Ok(Json(tags))
+}
+
+async fn add_presentation_tag(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<AddTagRequest>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.add_tag_to_presentation(&id, &data.tag).await?;
+    Ok(Json(presentation))
+}
+
+async fn remove_presentation_tag(
+    State(state): State<SharedState>,
+    Path((id, tag)): Path<(String, String)>,
+) -> AppResult<Json<Presentation>> {
+    let state = state.read().await;
+    let presentation = state.db.remove_tag_from_presentation(&id, &tag).await?;
+    Ok(Json(presentation))
+}
+
+async fn list_themes(
+    State(state): State<SharedState>,
+    Query(query): Query<SafeModeQuery>,
+) -> AppResult<Json<Vec<Theme>>> {
+    let state = state.read().await;
+    let mut themes = state.db.list_themes().await?;
+    if state.safe_mode {
+        themes = safe_mode::themes(themes, query.include_disabled_by_safe_mode);
+    }
+    Ok(Json(themes))
+}
+
+async fn get_theme(
+    State(state): State<SharedState>,
+    Path(id_or_name): Path<String>,
+    Query(query): Query<SafeModeQuery>,
+) -> AppResult<Json<Theme>> {
+    let state = state.read().await;
+    let mut theme = state.db.find_theme(&id_or_name).await?;
+    if state.safe_mode {
+        theme = safe_mode::theme(theme, query.include_disabled_by_safe_mode)?;
+    }
+    Ok(Json(theme))
+}
+
+async fn theme_usage(
+    State(state): State<SharedState>,
+    Path(id_or_name): Path<String>,
+) -> AppResult<Json<ThemeUsage>> {
+    let state = state.read().await;
+    let theme = state.db.find_theme(&id_or_name).await?;
+    let usage = state.db.theme_usage(&theme.id).await?;
+    Ok(Json(usage))
+}
+
+/// Creates a custom theme. A taken name is a 409 unless `onConflict=rename` asks for the
+/// first free `name-2`, `name-3`, ... instead.
+async fn create_theme(
+    State(state): State<SharedState>,
+    Query(query): Query<CreateThemeQuery>,
+    Json(data): Json<CreateTheme>,
+) -> AppResult<(StatusCode, Json<Theme>)> {
+    let state = state.read().await;
+    let theme = state.db.create_theme(data, query.on_conflict).await?;
+    Ok((StatusCode::CREATED, Json(theme)))
+}
+
+async fn duplicate_theme(
+    State(state): State<SharedState>,
+    Path(id_or_name): Path<String>,
+    data: Option<Json<DuplicateTheme>>,
+) -> AppResult<(StatusCode, Json<Theme>)> {
+    let data = data.map(|Json(d)| d).unwrap_or_default();
+    let state = state.read().await;
+    let id = state.db.find_theme(&id_or_name).await?.id;
+    let theme = state.db.duplicate_theme(&id, data).await?;
+    Ok((StatusCode::CREATED, Json(theme)))
+}
+
+async fn export_theme(
+    State(state): State<SharedState>,
+    Path(id_or_name): Path<String>,
+    Query(query): Query<SafeModeQuery>,
+) -> AppResult<Response> {
+    let state = state.read().await;
+    let mut theme = state.db.find_theme(&id_or_name).await?;
+    if state.safe_mode {
+        theme = safe_mode::theme(theme, query.include_disabled_by_safe_mode)?;
+    }
+    let id = theme.id;
+    let export = state.db.export_theme(&id).await?;
+    // Theme names are kebab-case, so they are safe in the header as they are
+    let disposition = format!("attachment; filename=\"{}.theme.json\"", export.name);
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], Json(export)).into_response())
+}
+
+async fn import_theme(
+    State(state): State<SharedState>,
+    Query(query): Query<ImportThemeQuery>,
+    Json(data): Json<ThemeExport>,
+) -> AppResult<(StatusCode, Json<Theme>)> {
+    let state = state.read().await;
+    let theme = state.db.import_theme(data, query.on_conflict).await?;
+    Ok((StatusCode::CREATED, Json(theme)))
+}
+
+/// Generates a variant of a base theme in a logo's colours. Takes a multipart form with the
+/// image as `file` or an uploaded image's `mediaId`, and optionally `baseTheme` (name or ID,
+/// default `default`), `mode` (`light` or `dark`), `name`, `displayName`, `colors` (clusters
+/// to extract), `seed` and `save`. The theme is only stored when `save` is true.
+async fn theme_from_image(
+    State(state): State<SharedState>,
+    mut multipart: Multipart,
+) -> AppResult<(StatusCode, Json<ImageTheme>)> {
+    let mut upload: Option<Bytes> = None;
+    let mut fields: HashMap<String, String> = HashMap::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::BadRequest(format!("Failed to read multipart field: {}", e))
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+        if name == "file" {
+            let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+            let data = field.bytes().await.map_err(|e| {
+                if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                    return uploads::upload_too_large();
+                }
+                AppError::BadRequest(format!("Failed to read file data: {}", e))
+            })?;
+            uploads::check_upload_size(data.len())?;
+            if !is_raster_image(&content_type) || !media::validate_file_magic(&data, &content_type) {
+                return Err(AppError::BadRequest("File must be a PNG, JPEG, GIF, WebP, BMP, ICO or TIFF image".to_string()));
+            }
+            upload = Some(data);
+        } else {
+            let value = field.text().await.map_err(|e| {
+                AppError::BadRequest(format!("Failed to read multipart field: {}", e))
+            })?;
+            fields.insert(name, value.trim().to_string());
+        }
+    }
+    let field = |name: &str| fields.get(name).map(String::as_str).filter(|v| !v.is_empty());
+
+    let mut validation = ValidationBuilder::new();
+    let mode = match field("mode") {
+        None | Some("light") => ThemeMode::Light,
+        Some("dark") => ThemeMode::Dark,
+        Some(_) => {
+            validation.error("mode", "must be light or dark");
+            ThemeMode::Light
+        }
+    };
+    let colors = field("colors").map_or(Some(palette::DEFAULT_COLORS), |v| v.parse().ok());
+    validation.check(
+        colors.is_some_and(|k| (2..=palette::MAX_COLORS).contains(&k)),
+        "colors",
+        format!("must be a number from 2 to {}", palette::MAX_COLORS),
+    );
+    let seed = field("seed").map_or(Some(0), |v| v.parse::<u64>().ok());
+    validation.check(seed.is_some(), "seed", "must be a non-negative integer");
+    let save = field("save").map_or(Some(false), |v| v.parse::<bool>().ok());
+    validation.check(save.is_some(), "save", "must be true or false");
+    if let Some(name) = field("name") {
+        let slug = name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        validation.check(slug, "name", "may only contain lowercase letters, digits and hyphens");
+    }
+    validation.check(
+        upload.is_some() || field("mediaId").is_some(),
+        "file",
+        "an image file or mediaId is required",
+    );
+    validation.finish()?;
+    let (colors, seed, save) = (colors.unwrap_or_default(), seed.unwrap_or_default(), save.unwrap_or_default());
+
+    let state = state.read().await;
+    let base_name = field("baseTheme").unwrap_or("default");
+    let base = match state.db.get_theme_by_id(base_name).await {
+        Ok(theme) => theme,
+        Err(_) => state.db.get_theme_by_name(base_name).await?,
+    };
+
+    let data = match upload {
+        Some(data) => data.to_vec(),
+        None => {
+            let media_id = field("mediaId").unwrap_or_default();
+            let media = state
+                .db
+                .get_media(media_id)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+            if !is_raster_image(&media.mime_type) {
+                return Err(AppError::BadRequest("Media is not a PNG, JPEG, GIF, WebP, BMP, ICO or TIFF image".to_string()));
+            }
+            fs::read(state.uploads_dir.join(&media.filename))
+                .await
+                .map_err(|e| AppError::Internal(format!("Failed to read media file: {}", e)))?
+        }
+    };
+
+    let swatches = tokio::task::spawn_blocking(move || {
+        image::load_from_memory(&data).map(|image| palette::extract(&image, colors, seed))
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Palette extraction failed: {}", e)))?
+    .map_err(|e| AppError::BadRequest(format!("Could not decode image: {}", e)))?;
+    if swatches.is_empty() {
+        return Err(AppError::BadRequest("Image has no opaque pixels".to_string()));
+    }
+
+    let theme_colors = palette::assign_roles(&swatches, mode);
+    let mut name = field("name").map_or_else(|| format!("{}-brand", base.name), str::to_string);
+    let display_name = field("displayName").map_or_else(|| format!("{} Brand", base.display_name), str::to_string);
+    let mut css_content = palette::variant_css(&base.css_content, &base.name, &name, &theme_colors);
+
+    let theme = if save {
+        let mut theme = state
+            .db
+            .create_theme(
+                CreateTheme {
+                    name: name.clone(),
+                    display_name: display_name.clone(),
+                    css_content: css_content.clone(),
+                    center_content: Some(base.center_content),
+                    fonts: base.fonts.clone(),
+                },
+                NameConflict::Rename,
+            )
+            .await?;
+        // A taken name gets a numbered suffix, which the selectors have to follow
+        if theme.name != name {
+            name = theme.name.clone();
+            css_content = palette::variant_css(&base.css_content, &base.name, &name, &theme_colors);
+            theme = state
+                .db
+                .update_theme(&theme.id, UpdateTheme {
+                    display_name: None,
+                    css_content: Some(css_content.clone()),
+                    center_content: None,
+                    fonts: None,
+                })
+                .await?;
+        }
+        Some(theme)
+    } else {
+        None
+    };
+
+    let status = if theme.is_some() { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(ImageTheme {
+        name,
+        display_name,
+        css_content,
+        center_content: base.center_content,
+        theme,
+        palette: swatches,
+        colors: theme_colors,
+    })))
+}
+
+/// Image types the `image` crate can decode for palette extraction.
+fn is_raster_image(mime_type: &str) -> bool {
+    let mime = mime_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    matches!(
+        mime.as_str(),
+        "image/png" | "image/jpeg" | "image/jpg" | "image/gif" | "image/webp" | "image/bmp"
+            | "image/x-icon" | "image/vnd.microsoft.icon" | "image/tiff"
+    )
+}
+
+async fn update_theme(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<UpdateTheme>,
+) -> AppResult<Json<Theme>> {
+    let state = state.read().await;
+    let theme = state.db.update_theme(&id, data).await?;
+    Ok(Json(theme))
+}
+
+async fn reset_theme(
+    State(state): State<SharedState>,
+    Path(id_or_name): Path<String>,
+) -> AppResult<Json<Theme>> {
+    let state = state.read().await;
+    let id = state.db.find_theme(&id_or_name).await?.id;
+    let theme = state.db.reset_theme(&id).await?;
+    Ok(Json(theme))
+}
+
+async fn reset_default_themes(State(state): State<SharedState>) -> AppResult<Json<Vec<Theme>>> {
+    let state = state.read().await;
+    let themes = state.db.reset_default_themes().await?;
+    Ok(Json(themes))
+}
+
+async fn delete_theme(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Query(query): Query<DeleteThemeQuery>,
+) -> AppResult<StatusCode> {
+    let state = state.read().await;
+    state.db.delete_theme(&id, query.force).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_layout_rules(
+    State(state): State<SharedState>,
+    Query(query): Query<SafeModeQuery>,
+) -> AppResult<Json<Vec<LayoutRuleResponse>>> {
+    let state = state.read().await;
+    let rules = state.db.list_layout_rules().await?;
+    let mut responses: Vec<LayoutRuleResponse> = rules.into_iter().map(Into::into).collect();
+    if state.safe_mode {
+        responses = safe_mode::layout_rules(responses, query.include_disabled_by_safe_mode);
+    }
+    Ok(Json(responses))
+}
+
+// Watchlist handlers
+async fn list_watchlist_sources(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Vec<WatchlistSource>>> {
+    let state = state.read().await;
+    let sources = state.db.list_watchlist_sources(&id).await?;
+    Ok(Json(sources))
+}
+
+async fn create_watchlist_source(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<CreateWatchlistSource>,
+) -> AppResult<(StatusCode, Json<WatchlistSource>)> {
+    watchlists::validate_source_url(&data.url)?;
+    let state = state.read().await;
+    let source = state.db.create_watchlist_source(&id, data).await?;
+    Ok((StatusCode::CREATED, Json(source)))
+}
+
+async fn update_watchlist_source(
+    State(state): State<SharedState>,
+    Path((id, source_id)): Path<(String, String)>,
+    Json(data): Json<UpdateWatchlistSource>,
+) -> AppResult<Json<WatchlistSource>> {
+    if let Some(url) = &data.url {
+        watchlists::validate_source_url(url)?;
+    }
+    let state = state.read().await;
+    let source = state.db.update_watchlist_source(&id, &source_id, data).await?;
+    Ok(Json(source))
+}
+
+async fn delete_watchlist_source(
+    State(state): State<SharedState>,
+    Path((id, source_id)): Path<(String, String)>,
+) -> AppResult<StatusCode> {
+    let state = state.read().await;
+    state.db.delete_watchlist_source(&id, &source_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn refresh_watchlist(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    data: Option<Json<RefreshWatchlistRequest>>,
+) -> AppResult<Json<WatchlistRefreshResult>> {
+    let requested = data.and_then(|Json(d)| d.provider);
+    let provider_name = match requested {
+        Some(name) => Some(name),
+        None => {
+            let state = state.read().await;
+            state.db.list_ai_provider_configs().await?.into_iter().next().map(|c| c.provider_name)
+        }
+    };
+
+    let provider = match provider_name {
+        Some(name) => match get_provider_for_request(&state, &name).await {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                tracing::debug!(error = %e, "Watchlist summaries without AI");
+                None
+            }
+        },
+        None => None,
+    };
+
+    let result = watchlists::refresh(&state, &id, provider.as_deref()).await?;
+    Ok(Json(result))
+}
+
+// Pipeline handlers
+async fn list_pipelines(State(state): State<SharedState>) -> AppResult<Json<Vec<PipelineResponse>>> {
+    let state = state.read().await;
+    let pipelines = state.db.list_pipelines().await?;
+    let responses: Vec<PipelineResponse> = pipelines.into_iter().map(Into::into).collect();
+    Ok(Json(responses))
+}
+
+async fn get_pipeline(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<PipelineResponse>> {
+    let state = state.read().await;
+    let pipeline = state.db.get_pipeline(&id).await?;
+    Ok(Json(pipeline.into()))
+}
+
+async fn create_pipeline(
+    State(state): State<SharedState>,
+    Json(data): Json<CreatePipeline>,
+) -> AppResult<(StatusCode, Json<PipelineResponse>)> {
+    pipeline::validate_steps(&data.steps)?;
+    let state = state.read().await;
+    let pipeline = state.db.create_pipeline(data).await?;
+    Ok((StatusCode::CREATED, Json(pipeline.into())))
+}
+
+async fn update_pipeline(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(data): Json<UpdatePipeline>,
+) -> AppResult<Json<PipelineResponse>> {
+    if let Some(steps) = &data.steps {
+        pipeline::validate_steps(steps)?;
+    }
+    let state = state.read().await;
+    let pipeline = state.db.update_pipeline(&id, data).await?;
+    Ok(Json(pipeline.into()))
 }
 
-async fn update_presentation(
+async fn delete_pipeline(
     State(state): State<SharedState>,
     Path(id): Path<String>,
-    Json(data): Json<UpdatePresentation>,
-) -> AppResult<Json<Presentation>> {
+) -> AppResult<StatusCode> {
     let state = state.read().await;
-    let presentation = state.db.update_presentation(&id, data).await?;
-    Ok(Json(presentation))
+    state.db.delete_pipeline(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-async fn delete_presentation(
+async fn run_pipeline(
     State(state): State<SharedState>,
     Path(id): Path<String>,
-) -> AppResult<()> {
-    let state = state.read().await;
-    state.db.delete_presentation(&id).await?;
-    Ok(())
+    data: Option<Json<RunPipelineRequest>>,
+) -> AppResult<Json<PipelineRunResult>> {
+    let input = data.map(|Json(d)| d.input).unwrap_or_default();
+    let result = pipeline::run_pipeline(&state, &id, input).await?;
+    Ok(Json(result))
 }
 
-async fn list_themes(State(state): State<SharedState>) -> AppResult<Json<Vec<Theme>>> {
+// Profile handlers
+async fn list_profiles(State(state): State<SharedState>) -> AppResult<Json<Vec<Profile>>> {
     let state = state.read().await;
-    let themes = state.db.list_themes().await?;
-    Ok(Json(themes))
+    let registry = ProfileRegistry::load(&state.app_data_dir)?;
+    Ok(Json(registry.list()))
 }
 
-async fn get_theme(
+async fn rotate_encryption_key(
     State(state): State<SharedState>,
-    Path(id_or_name): Path<String>,
-) -> AppResult<Json<Theme>> {
+    Json(data): Json<RotateKeyRequest>,
+) -> AppResult<Json<KeyRotation>> {
+    if !is_current_key(&data.old_key) {
+        return Err(AppError::Forbidden("oldKey does not match the configured encryption key".to_string()));
+    }
+    if data.new_key.is_empty() {
+        return Err(AppError::BadRequest("newKey must not be empty".to_string()));
+    }
+
+    let rotation = profiles::rotate_encryption_key(&state, &data.old_key, &data.new_key).await?;
+    Ok(Json(rotation))
+}
+
+/// Backups go next to the profiles, in `app_data_dir/backups/<profile>`.
+const BACKUP_DIR: &str = "backups";
+const BACKUP_PREFIX: &str = "slides-backup-";
+
+/// Where the active profile's backups are kept. Profiles never see each other's backups.
+fn backup_dir(state: &AppState) -> std::path::PathBuf {
+    state.app_data_dir.join(BACKUP_DIR).join(&state.profile)
+}
+
+async fn create_backup(State(state): State<SharedState>) -> AppResult<Json<Backup>> {
     let state = state.read().await;
-    // Try by ID first, then by name
-    match state.db.get_theme_by_id(&id_or_name).await {
-        Ok(theme) => Ok(Json(theme)),
-        Err(_) => {
-            let theme = state.db.get_theme_by_name(&id_or_name).await?;
-            Ok(Json(theme))
+    let dir = backup_dir(&state);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::Internal(format!("Failed to create backup directory: {}", e)))?;
+
+    // Millisecond timestamps keep backups taken in quick succession apart
+    let path = dir.join(format!("{}{}.db", BACKUP_PREFIX, chrono::Utc::now().format("%Y%m%d-%H%M%S%3f")));
+    state.db.backup_to_path(&path).await?;
+
+    let size = fs::metadata(&path)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read backup: {}", e)))?
+        .len();
+    Ok(Json(Backup { path: path.display().to_string(), size }))
+}
+
+async fn list_backups(State(state): State<SharedState>) -> AppResult<Json<Vec<BackupFile>>> {
+    let dir = backup_dir(&*state.read().await);
+    let mut entries = match fs::read_dir(&dir).await {
+        Ok(entries) => entries,
+        // No backup taken yet
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Json(Vec::new())),
+        Err(e) => return Err(AppError::Internal(format!("Failed to read backup directory: {}", e))),
+    };
+
+    let mut backups = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read backup directory: {}", e)))?
+    {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with(BACKUP_PREFIX) || !name.ends_with(".db") {
+            continue;
         }
+        let Ok(metadata) = entry.metadata().await else {
+            continue;
+        };
+        // Not every filesystem records creation times
+        let created = metadata.created().or_else(|_| metadata.modified());
+        backups.push(BackupFile {
+            name,
+            path: entry.path().display().to_string(),
+            size: metadata.len(),
+            created_at: created.map(Into::into).unwrap_or_else(|_| chrono::Utc::now()),
+        });
     }
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.name.cmp(&a.name)));
+    Ok(Json(backups))
 }
 
-async fn create_theme(
+async fn create_profile(
     State(state): State<SharedState>,
-    Json(data): Json<CreateTheme>,
-) -> AppResult<(StatusCode, Json<Theme>)> {
-    let state = state.read().await;
-    let theme = state.db.create_theme(data).await?;
-    Ok((StatusCode::CREATED, Json(theme)))
+    Json(data): Json<CreateProfile>,
+) -> AppResult<(StatusCode, Json<Profile>)> {
+    let profile = profiles::create(&state, &data.name).await?;
+    Ok((StatusCode::CREATED, Json(profile)))
 }
 
-async fn update_theme(
+async fn activate_profile(
     State(state): State<SharedState>,
-    Path(id): Path<String>,
-    Json(data): Json<UpdateTheme>,
-) -> AppResult<Json<Theme>> {
-    let state = state.read().await;
-    let theme = state.db.update_theme(&id, data).await?;
-    Ok(Json(theme))
+    Path(name): Path<String>,
+) -> AppResult<Json<Profile>> {
+    let profile = profiles::activate(&state, &name).await?;
+    Ok(Json(profile))
 }
 
-async fn delete_theme(
+async fn delete_profile(
     State(state): State<SharedState>,
-    Path(id): Path<String>,
+    Path(name): Path<String>,
+    Json(data): Json<DeleteProfile>,
 ) -> AppResult<StatusCode> {
-    let state = state.read().await;
-    state.db.delete_theme(&id).await?;
+    profiles::remove(&state, &name, &data.confirm).await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
-async fn list_layout_rules(State(state): State<SharedState>) -> AppResult<Json<Vec<LayoutRuleResponse>>> {
-    let state = state.read().await;
-    let rules = state.db.list_layout_rules().await?;
-    let responses: Vec<LayoutRuleResponse> = rules.into_iter().map(Into::into).collect();
-    Ok(Json(responses))
-}
-
 // Media handlers
 async fn list_media(State(state): State<SharedState>) -> AppResult<Json<Vec<Media>>> {
     let state = state.read().await;
@@ -189,29 +1413,31 @@ async fn upload_media(
         let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
 
         // Validate mime type (only allow image, video, audio)
-        if !content_type.starts_with("image/")
-            && !content_type.starts_with("video/")
-            && !content_type.starts_with("audio/") {
-            return Err(AppError::BadRequest("Only image, video, and audio files are allowed".to_string()));
-        }
+        uploads::validate_media_type(&content_type)?;
 
         // Read the file data
         let data = field.bytes().await.map_err(|e| {
+            if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                return uploads::upload_too_large();
+            }
             AppError::BadRequest(format!("Failed to read file data: {}", e))
         })?;
+        uploads::check_upload_size(data.len())?;
+
+        if !media::validate_file_magic(&data, &content_type) {
+            return Err(AppError::BadRequest("File content does not match declared MIME type".to_string()));
+        }
 
         let size = data.len() as i64;
 
+        // A file already in the library isn't stored again
+        let content_hash = uploads::sha256_bytes(data.clone()).await?;
+        if let Some(existing) = state.read().await.db.find_media_by_hash(&content_hash).await? {
+            return Ok(Json(existing));
+        }
+
         // Generate unique filename
-        let ext = std::path::Path::new(&original_name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("bin");
-        let unique_name = format!("{}-{}.{}",
-            chrono::Utc::now().timestamp_millis(),
-            Uuid::new_v4().to_string().split('-').next().unwrap_or("x"),
-            ext
-        );
+        let unique_name = uploads::media_filename(&original_name);
 
         // Write file to disk
         let file_path = uploads_dir.join(&unique_name);
@@ -222,8 +1448,10 @@ async fn upload_media(
             AppError::Internal(format!("Failed to write file: {}", e))
         })?;
 
+        let metadata = media::read_metadata(file_path.clone(), &content_type).await;
+
         // Create database record
-        let url = format!("/api/uploads/{}", unique_name);
+        let url = uploads::upload_url(&unique_name);
         let state = state.read().await;
         let media = state.db.create_media(
             unique_name,
@@ -231,7 +1459,12 @@ async fn upload_media(
             content_type,
             size,
             url,
+            metadata,
+            content_hash,
         ).await?;
+        if media.deduplicated {
+            let _ = fs::remove_file(&file_path).await;
+        }
 
         return Ok(Json(media));
     }
@@ -239,6 +1472,179 @@ async fn upload_media(
     Err(AppError::BadRequest("No file provided".to_string()))
 }
 
+// Chunked uploads, for files too large for a single request
+async fn create_upload_session(
+    State(state): State<SharedState>,
+    Json(data): Json<CreateUploadSession>,
+) -> AppResult<(StatusCode, Json<UploadSession>)> {
+    uploads::validate_media_type(&data.mime_type)?;
+    let chunk_size = uploads::session_chunk_size(data.size, &data.sha256, data.chunk_size)?;
+
+    let state = state.read().await;
+    let expires_at = chrono::Utc::now() + uploads::SESSION_TTL;
+    let session = state.db.create_upload_session(data, chunk_size, expires_at).await?;
+
+    let path = uploads::partial_path(&state.uploads_dir, &session.id);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await.map_err(|e| {
+            AppError::Internal(format!("Failed to create uploads directory: {}", e))
+        })?;
+    }
+    fs::File::create(&path).await.map_err(|e| {
+        AppError::Internal(format!("Failed to create file: {}", e))
+    })?;
+
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// The session with the chunks received so far, for resuming an interrupted upload.
+async fn get_upload_session(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<UploadSession>> {
+    let state = state.read().await;
+    let session = state.db.get_upload_session(&id).await?;
+    Ok(Json(session))
+}
+
+async fn put_upload_chunk(
+    State(state): State<SharedState>,
+    Path((id, index)): Path<(String, i64)>,
+    body: Bytes,
+) -> AppResult<Json<UploadSession>> {
+    let state = state.read().await;
+    let session = state.db.get_upload_session(&id).await?;
+
+    if index < 0 || index >= session.chunk_count {
+        return Err(AppError::BadRequest(format!(
+            "Chunk index must be between 0 and {}",
+            session.chunk_count - 1
+        )));
+    }
+    let offset = index * session.chunk_size;
+    let expected = session.chunk_size.min(session.size - offset);
+    if body.len() as i64 != expected {
+        return Err(AppError::BadRequest(format!(
+            "Chunk {} must be {} bytes, got {}",
+            index,
+            expected,
+            body.len()
+        )));
+    }
+
+    let path = uploads::partial_path(&state.uploads_dir, &id);
+    let mut file = fs::OpenOptions::new().write(true).open(&path).await.map_err(|e| {
+        AppError::Internal(format!("Failed to open upload file: {}", e))
+    })?;
+    file.seek(std::io::SeekFrom::Start(offset as u64)).await.map_err(|e| {
+        AppError::Internal(format!("Failed to write chunk: {}", e))
+    })?;
+    file.write_all(&body).await.map_err(|e| {
+        AppError::Internal(format!("Failed to write chunk: {}", e))
+    })?;
+    file.flush().await.map_err(|e| {
+        AppError::Internal(format!("Failed to write chunk: {}", e))
+    })?;
+
+    let expires_at = chrono::Utc::now() + uploads::SESSION_TTL;
+    let session = state.db.record_upload_chunk(&id, index, expires_at).await?;
+    Ok(Json(session))
+}
+
+/// Verifies the assembled file against the declared size and hash and moves it into the
+/// media library. A hash mismatch discards the upload.
+async fn complete_upload_session(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> AppResult<Json<Media>> {
+    // Hashing and moving a file of up to MAX_UPLOAD_BYTES takes a while, so the state lock is
+    // only held to look up the session and, at the end, to record the media
+    let (db, uploads_dir, profile) = {
+        let state = state.read().await;
+        (state.db.clone(), state.uploads_dir.clone(), state.profile.clone())
+    };
+    let session = db.get_upload_session(&id).await?;
+
+    let missing: Vec<String> = (0..session.chunk_count)
+        .filter(|index| !session.received_chunks.contains(index))
+        .map(|index| index.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::Conflict(format!(
+            "Upload is missing chunks: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let path = uploads::partial_path(&uploads_dir, &id);
+    let size = fs::metadata(&path).await.map_err(|e| {
+        AppError::Internal(format!("Failed to read upload file: {}", e))
+    })?.len() as i64;
+    if size != session.size {
+        return Err(AppError::BadRequest(format!(
+            "Upload is {} bytes, expected {}",
+            size, session.size
+        )));
+    }
+
+    if uploads::sha256_file(path.clone()).await? != session.sha256 {
+        db.delete_upload_session(&id).await?;
+        let _ = fs::remove_file(&path).await;
+        return Err(AppError::BadRequest(
+            "SHA-256 of the uploaded file does not match; the upload was discarded".to_string(),
+        ));
+    }
+    uploads::validate_media_type(&session.mime_type)?;
+    let mut head = Vec::with_capacity(media::MAGIC_LEN);
+    fs::File::open(&path)
+        .await
+        .map(|file| file.take(media::MAGIC_LEN as u64))
+        .map_err(|e| AppError::Internal(format!("Failed to read upload file: {}", e)))?
+        .read_to_end(&mut head)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to read upload file: {}", e)))?;
+    if !media::validate_file_magic(&head, &session.mime_type) {
+        db.delete_upload_session(&id).await?;
+        let _ = fs::remove_file(&path).await;
+        return Err(AppError::BadRequest("File content does not match declared MIME type".to_string()));
+    }
+
+    if let Some(existing) = db.find_media_by_hash(&session.sha256).await? {
+        db.delete_upload_session(&id).await?;
+        let _ = fs::remove_file(&path).await;
+        return Ok(Json(existing));
+    }
+
+    let unique_name = uploads::media_filename(&session.filename);
+    let file_path = uploads_dir.join(&unique_name);
+    fs::rename(&path, &file_path).await.map_err(|e| {
+        AppError::Internal(format!("Failed to move upload into the library: {}", e))
+    })?;
+    let metadata = media::read_metadata(file_path.clone(), &session.mime_type).await;
+
+    let state = state.read().await;
+    if state.profile != profile {
+        let _ = fs::remove_file(&file_path).await;
+        return Err(AppError::Conflict("The profile was switched while the upload was completing".to_string()));
+    }
+    let url = uploads::upload_url(&unique_name);
+    let media = state.db.create_media(
+        unique_name,
+        session.filename,
+        session.mime_type,
+        session.size,
+        url,
+        metadata,
+        session.sha256,
+    ).await?;
+    state.db.delete_upload_session(&id).await?;
+    if media.deduplicated {
+        let _ = fs::remove_file(&file_path).await;
+    }
+
+    Ok(Json(media))
+}
+
 async fn delete_media(
     State(state): State<SharedState>,
     Path(id): Path<String>,
@@ -266,21 +1672,34 @@ async fn delete_media(
 async fn serve_upload(
     State(state): State<SharedState>,
     Path(filename): Path<String>,
+    headers: header::HeaderMap,
 ) -> Result<Response, AppError> {
-    let uploads_dir = {
+    let (file_path, content_hash) = {
         let state = state.read().await;
-        state.uploads_dir.clone()
+        let file_path = uploads::resolve_upload_path(&state.uploads_dir, &filename)?;
+        (file_path, state.db.get_media_content_hash(&filename).await?)
     };
 
-    let file_path = uploads_dir.join(&filename);
-
-    if !file_path.exists() {
-        return Err(AppError::NotFound("File not found".to_string()));
-    }
-
-    let data = fs::read(&file_path).await.map_err(|e| {
+    let mut file = fs::File::open(&file_path).await.map_err(|e| {
+        AppError::Internal(format!("Failed to read file: {}", e))
+    })?;
+    let file_meta = file.metadata().await.map_err(|e| {
         AppError::Internal(format!("Failed to read file: {}", e))
     })?;
+    let total = file_meta.len();
+
+    // Files uploaded before content hashes were recorded fall back to size and mtime
+    let etag = match content_hash {
+        Some(hash) => format!("\"{}\"", hash),
+        None => {
+            let modified = file_meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            format!("\"{:x}-{:x}\"", total, modified)
+        }
+    };
 
     // Determine content type from extension
     let content_type = match file_path.extension().and_then(|e| e.to_str()) {
@@ -297,14 +1716,129 @@ async fn serve_upload(
         _ => "application/octet-stream",
     };
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
+    let response = Response::builder()
         .header(header::CONTENT_TYPE, content_type)
         .header(header::CACHE_CONTROL, "public, max-age=31536000")
-        .body(Body::from(data))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag);
+
+    let if_none_match = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|tags| uploads::etag_matches(tags, &etag)) {
+        return Ok(response.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap());
+    }
+
+    let requested = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    let range = match requested.map_or(RangeRequest::Full, |range| uploads::parse_range(range, total)) {
+        RangeRequest::Partial(range) => range,
+        RangeRequest::Full => {
+            return Ok(response
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, total)
+                .body(file_body(file, total))
+                .unwrap());
+        }
+        RangeRequest::Unsatisfiable => {
+            return Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", total))
+                .body(Body::empty())
+                .unwrap());
+        }
+    };
+
+    file.seek(std::io::SeekFrom::Start(range.start)).await.map_err(|e| {
+        AppError::Internal(format!("Failed to read file: {}", e))
+    })?;
+    Ok(response
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", range.start, range.end, total))
+        .header(header::CONTENT_LENGTH, range.byte_count())
+        .body(file_body(file, range.byte_count()))
         .unwrap())
 }
 
+/// Streams `len` bytes from the file's current position.
+fn file_body(file: fs::File, len: u64) -> Body {
+    let mut reader = file.take(len);
+    Body::from_stream(async_stream::stream! {
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            match reader.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => yield Ok::<_, std::io::Error>(Bytes::copy_from_slice(&buf[..n])),
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+    })
+}
+
+// Settings handlers
+async fn get_app_settings(State(state): State<SharedState>) -> AppResult<Json<AppSettings>> {
+    let state = state.read().await;
+    let settings = state.db.get_app_settings().await?;
+    Ok(Json(settings))
+}
+
+async fn update_app_settings(
+    State(state): State<SharedState>,
+    Json(data): Json<AppSettings>,
+) -> AppResult<Json<AppSettings>> {
+    let state = state.read().await;
+    state.db.set_app_settings(&data).await?;
+    Ok(Json(data))
+}
+
+async fn get_mcp_tool_settings(State(state): State<SharedState>) -> AppResult<Json<McpToolSettings>> {
+    let state = state.read().await;
+    let settings = state.db.get_mcp_tool_settings().await?;
+    Ok(Json(settings))
+}
+
+async fn get_health_weights(State(state): State<SharedState>) -> AppResult<Json<HealthWeights>> {
+    let state = state.read().await;
+    let weights = state.db.get_health_weights().await?;
+    Ok(Json(weights))
+}
+
+async fn update_health_weights(
+    State(state): State<SharedState>,
+    Json(data): Json<HealthWeights>,
+) -> AppResult<Json<HealthWeights>> {
+    let weights = [
+        data.empty_slide,
+        data.missing_notes,
+        data.long_slide,
+        data.missing_alt_text,
+        data.oversized_media,
+        data.unfilled_placeholder,
+    ];
+    if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+        return Err(AppError::BadRequest("Health weights must be non-negative numbers".to_string()));
+    }
+
+    let state = state.read().await;
+    state.db.set_health_weights(&data).await?;
+    Ok(Json(data))
+}
+
+async fn update_mcp_tool_settings(
+    State(state): State<SharedState>,
+    Json(data): Json<McpToolSettings>,
+) -> AppResult<Json<McpToolSettings>> {
+    let known = mcp::tool_names();
+    let listed = data.allow.iter().flatten().chain(data.deny.iter());
+    if let Some(unknown) = listed.into_iter().find(|name| !known.contains(name)) {
+        return Err(AppError::BadRequest(format!("Unknown MCP tool: {}", unknown)));
+    }
+
+    let state = state.read().await;
+    state.db.set_mcp_tool_settings(&data).await?;
+    Ok(Json(data))
+}
+
 // AI Config handlers
 async fn list_ai_configs(State(state): State<SharedState>) -> AppResult<Json<Vec<AiProviderConfigResponse>>> {
     let state = state.read().await;
@@ -317,20 +1851,48 @@ async fn create_ai_config(
     State(state): State<SharedState>,
     Json(data): Json<CreateAiProviderConfig>,
 ) -> AppResult<Json<AiProviderConfigResponse>> {
-    // Validate: need either API key or base URL
-    if data.api_key.is_none() && data.base_url.is_none() {
-        return Err(AppError::BadRequest("apiKey or baseUrl required".to_string()));
-    }
+    validate_ai_config(&data)?;
 
     // Use placeholder when using proxy without API key
     let effective_api_key = data.api_key.clone().unwrap_or_else(|| "not-needed".to_string());
+    let rotation = KEY_ROTATION.read().await;
     let api_key_encrypted = encrypt(&effective_api_key)?;
 
-    let state = state.read().await;
-    let config = state.db.upsert_ai_provider_config(data, api_key_encrypted).await?;
+    let state_read = state.read().await;
+    let config = state_read.db.upsert_ai_provider_config(data, api_key_encrypted).await?;
+    drop(state_read);
+    drop(rotation);
+
+    refresh_models_in_background(&state, &config.provider_name);
     Ok(Json(config.into()))
 }
 
+fn validate_ai_config(data: &CreateAiProviderConfig) -> AppResult<()> {
+    let mut validation = ValidationBuilder::new();
+    validation.check(
+        provider_names().any(|name| name == data.provider_name),
+        "providerName",
+        format!("must be one of {}", provider_names().collect::<Vec<_>>().join(", ")),
+    );
+    if let Some(api_key) = &data.api_key {
+        validation.check(!api_key.trim().is_empty(), "apiKey", "must not be empty");
+    }
+    if let Some(base_url) = &data.base_url {
+        let valid = url::Url::parse(base_url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+        validation.check(valid, "baseUrl", "must be an http or https URL");
+    }
+    // Need either API key or base URL, unless the provider runs locally
+    if data.api_key.is_none() && data.base_url.is_none() && requires_api_key(&data.provider_name) {
+        validation.error("apiKey", "required unless baseUrl is set");
+    }
+    // Azure URLs are per resource, and the model names the deployment to call
+    if data.provider_name == "azure-openai" {
+        validation.check(data.base_url.is_some(), "baseUrl", "required for Azure OpenAI");
+        validation.check(data.model.is_some(), "model", "the deployment name is required for Azure OpenAI");
+    }
+    validation.finish()
+}
+
 async fn update_ai_config(
     State(state): State<SharedState>,
     Path(id): Path<String>,
@@ -346,6 +1908,7 @@ async fn update_ai_config(
     drop(state_read);
 
     // Prepare update values
+    let rotation = KEY_ROTATION.read().await;
     let api_key_encrypted = if let Some(api_key) = &data.api_key {
         Some(encrypt(api_key)?)
     } else {
@@ -357,6 +1920,10 @@ async fn update_ai_config(
         .db
         .update_ai_provider_config(&id, data.model.clone(), data.base_url.clone(), api_key_encrypted)
         .await?;
+    drop(state_read);
+    drop(rotation);
+
+    refresh_models_in_background(&state, &config.provider_name);
     Ok(Json(config.into()))
 }
 
@@ -365,6 +1932,9 @@ async fn delete_ai_config(
     Path(id): Path<String>,
 ) -> AppResult<()> {
     let state = state.read().await;
+    if let Some(config) = state.db.get_ai_provider_config_by_id(&id).await? {
+        state.db.invalidate_cached_models(&config.provider_name).await?;
+    }
     state.db.delete_ai_provider_config(&id).await?;
     Ok(())
 }
@@ -372,19 +1942,78 @@ async fn delete_ai_config(
 async fn list_provider_models(
     State(state): State<SharedState>,
     Path(provider): Path<String>,
-) -> AppResult<Json<Vec<crate::ai::ModelInfo>>> {
-    let state_read = state.read().await;
-    let config = state_read
-        .db
-        .get_ai_provider_config(&provider)
-        .await?
+) -> AppResult<Json<Vec<ModelInfo>>> {
+    let list = provider_models(&state, &provider, model_cache_ttl()).await?;
+    Ok(Json(list.models))
+}
+
+async fn list_models(
+    State(state): State<SharedState>,
+    Query(query): Query<ModelListQuery>,
+) -> AppResult<Json<ModelList>> {
+    let list = provider_models(&state, &query.provider, model_cache_ttl()).await?;
+    Ok(Json(list))
+}
+
+/// Sends a one-token request with the stored configuration. Provider and network failures
+/// are reported as `{"ok": false}` rather than as an error status, so the settings screen
+/// can show them next to the key, with an `errorKind` of `auth`, `network` or `provider`.
+async fn test_ai_provider(
+    State(state): State<SharedState>,
+    Path(provider): Path<String>,
+) -> AppResult<Json<serde_json::Value>> {
+    let ai_provider = get_provider_for_request(&state, &provider).await?;
+    let model = ai_provider.default_model().to_string();
+
+    let result = ai_provider
+        .generate_content("hi", GenerateOptions {
+            max_tokens: Some(1),
+            ..Default::default()
+        })
+        .await;
+
+    Ok(Json(match result {
+        Ok(_) => json!({ "ok": true, "model": model }),
+        Err(e) => {
+            let (kind, message) = match e {
+                AppError::Provider { kind, message } => (kind, message),
+                e => (ProviderErrorKind::Provider, e.to_string()),
+            };
+            json!({ "ok": false, "model": model, "error": message, "errorKind": kind })
+        }
+    }))
+}
+
+/// Lists a configured provider's models through the model cache.
+async fn provider_models(state: &SharedState, provider: &str, ttl: chrono::Duration) -> AppResult<ModelList> {
+    // The provider may take a while to answer; a profile switch shouldn't wait on it
+    let (db, client, config) = {
+        let state = state.read().await;
+        let config = state.db.get_ai_provider_config(provider).await?;
+        (state.db.clone(), state.http_client.clone(), config)
+    };
+    let config = config
         .ok_or_else(|| AppError::BadRequest(format!("No {} configuration found. Add your API key in settings.", provider)))?;
 
-    let api_key = decrypt(&config.api_key_encrypted)?;
-    let ai_provider = create_provider(&provider, api_key, config.base_url, config.model)?;
+    let base_url = config.base_url.clone();
+    cached_models(&db, provider, base_url.as_deref(), ttl, || async {
+        let api_key = decrypt(&config.api_key_encrypted)?;
+        let ai_provider = create_provider(client, provider, api_key, config.base_url, config.model)?;
+        ai_provider.list_models().await
+    })
+    .await
+}
 
-    let models = ai_provider.list_models().await?;
-    Ok(Json(models))
+/// Re-fetches a provider's models after its configuration changed, without holding up
+/// the request.
+fn refresh_models_in_background(state: &SharedState, provider: &str) {
+    let state = state.clone();
+    let provider = provider.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = provider_models(&state, &provider, chrono::Duration::zero()).await {
+            tracing::warn!(provider = %provider, error = %e, "Background model refresh failed");
+        }
+    });
 }
 
 // AI Operation helpers
@@ -424,7 +2053,7 @@ async fn get_provider_for_request(state: &SharedState, provider_name: &str) -> A
         .ok_or_else(|| AppError::BadRequest(format!("No {} configuration found. Add your API key in settings.", provider_name)))?;
 
     let api_key = decrypt(&config.api_key_encrypted)?;
-    create_provider(provider_name, api_key, config.base_url, config.model)
+    create_provider(state.http_client.clone(), provider_name, api_key, config.base_url, config.model)
 }
 
 async fn ai_generate(
@@ -433,17 +2062,93 @@ async fn ai_generate(
 ) -> AppResult<Json<serde_json::Value>> {
     let provider = get_provider_for_request(&state, &data.provider).await?;
 
-    let system_prompt = format!(
+    let content = provider
+        .generate_content(&data.prompt, GenerateOptions {
+            system_prompt: Some(generate_system_prompt(data.context)),
+            ..Default::default()
+        })
+        .await?;
+
+    Ok(Json(json!({ "content": content })))
+}
+
+/// Streams generated slides as server-sent events: `{"delta": "..."}` messages as text
+/// arrives, then a `done` event, or an `error` event if the provider fails mid-stream.
+async fn ai_stream(
+    State(state): State<SharedState>,
+    Json(data): Json<AiStreamRequest>,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let provider = get_provider_for_request(&state, &data.provider).await?;
+    let options = GenerateOptions {
+        system_prompt: Some(generate_system_prompt(data.context)),
+        ..Default::default()
+    };
+
+    // The stream is polled after this handler returns, outside the request's trace scope
+    let trace_id = trace::current().unwrap_or_else(trace::new_id);
+
+    let stream = async_stream::stream! {
+        let chunks = match trace::scope(trace_id, provider.generate_stream(&data.prompt, options)).await {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                yield Ok(Event::default().event("error").data(json!({ "error": e.to_string() }).to_string()));
+                return;
+            }
+        };
+
+        for await chunk in chunks {
+            match chunk {
+                Ok(text) => yield Ok(Event::default().data(json!({ "delta": text }).to_string())),
+                Err(e) => {
+                    yield Ok(Event::default().event("error").data(json!({ "error": e.to_string() }).to_string()));
+                    return;
+                }
+            }
+        }
+
+        yield Ok(Event::default().event("done").data("{}"));
+    };
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn generate_system_prompt(context: Option<String>) -> String {
+    format!(
         "You are a presentation assistant. Generate markdown slides separated by '---'.\n\
         Each slide should be concise. Use the full range of supported layout features when appropriate.\n\n\
         {}\n{}",
         SLIDE_FORMAT_GUIDE,
-        data.context.map(|c| format!("\nContext about the presentation:\n{}", c)).unwrap_or_default()
+        context.map(|c| format!("\nContext about the presentation:\n{}", c)).unwrap_or_default()
+    )
+}
+
+/// Replies to a chat kept by the client, which sends the full history each turn.
+async fn ai_conversation(
+    State(state): State<SharedState>,
+    Json(data): Json<AiConversationRequest>,
+) -> AppResult<Json<serde_json::Value>> {
+    let mut validation = ValidationBuilder::new();
+    validation.check(
+        data.messages.last().is_some_and(|m| m.role == ConversationRole::User),
+        "messages",
+        "must end with a user message",
     );
+    for (i, message) in data.messages.iter().enumerate() {
+        // Providers expect user and assistant turns to alternate, starting with the user
+        let expected = if i % 2 == 0 { ConversationRole::User } else { ConversationRole::Assistant };
+        validation.check(
+            message.role == expected,
+            &format!("messages[{}].role", i),
+            format!("must be {}", expected.as_str()),
+        );
+        validation.check(!message.content.trim().is_empty(), &format!("messages[{}].content", i), "must not be empty");
+    }
+    validation.finish()?;
 
+    let provider = get_provider_for_request(&state, &data.provider).await?;
     let content = provider
-        .generate_content(&data.prompt, GenerateOptions {
-            system_prompt: Some(system_prompt),
+        .generate_conversation(&data.messages, GenerateOptions {
+            system_prompt: data.system_prompt,
             ..Default::default()
         })
         .await?;
@@ -473,6 +2178,37 @@ async fn ai_improve(
     Ok(Json(json!({ "content": content })))
 }
 
+/// Suggestions for the slide being edited. An unconfigured or failing provider degrades to
+/// the rule-based suggestions rather than an error.
+async fn ai_suggestions(
+    State(state): State<SharedState>,
+    Json(data): Json<AiSuggestionsRequest>,
+) -> AppResult<Json<AiSuggestions>> {
+    let presentation = {
+        let state = state.read().await;
+        state.db.get_presentation(&data.presentation_id).await?
+    };
+
+    let provider = match get_provider_for_request(&state, &data.provider).await {
+        Ok(provider) => Some(provider),
+        Err(e) => {
+            tracing::debug!(error = %e, "Suggestions without AI");
+            None
+        }
+    };
+
+    let result = suggestions::suggest(
+        &presentation.id,
+        &presentation.content,
+        data.slide_index,
+        &data.provider,
+        provider.as_deref(),
+        suggestions::AI_TIMEOUT,
+    )
+    .await?;
+    Ok(Json(result))
+}
+
 async fn ai_suggest_style(
     State(state): State<SharedState>,
     Json(data): Json<AiSuggestStyleRequest>,
@@ -505,7 +2241,7 @@ async fn ai_generate_theme(
         r#"You are a CSS theme designer for a presentation slide application.
 Generate a complete CSS theme following this exact pattern. The theme name should be a kebab-case identifier derived from the description.
 
-IMPORTANT: Return ONLY a JSON object with these fields: name, displayName, cssContent. No markdown, no explanation.
+IMPORTANT: Return ONLY a JSON object with these fields: name, displayName, cssContent, fonts. No markdown, no explanation.
 
 The cssContent must follow this selector pattern (replace THEME_NAME with your chosen name):
 
@@ -516,6 +2252,9 @@ The cssContent must follow this selector pattern (replace THEME_NAME with your c
 [data-theme="THEME_NAME"] h1, [data-theme="THEME_NAME"] h2, [data-theme="THEME_NAME"] h3 {{
   font-family: '...', sans-serif; color: var(--slide-heading);
 }}
+
+fonts lists the font stacks the CSS uses: {{"heading": "'...', sans-serif", "body": "'...', sans-serif", "mono": null, "source": "google"}}.
+Use "source": "google" for Google Fonts families and "system" for fonts that need no loading; leave a stack null when the CSS doesn't set it.
 {}"#,
         data.existing_css.map(|c| format!("\nHere is an existing theme CSS for reference:\n{}", c)).unwrap_or_default()
     );
@@ -534,8 +2273,13 @@ The cssContent must follow this selector pattern (replace THEME_NAME with your c
 
     match json_match {
         Some(json_str) => {
-            let parsed: serde_json::Value = serde_json::from_str(json_str)
+            let mut parsed: serde_json::Value = serde_json::from_str(json_str)
                 .map_err(|_| AppError::Internal("AI returned invalid theme format".to_string()))?;
+            // Fonts are optional; ones in the wrong shape are dropped rather than failing the theme
+            if let Some(theme) = parsed.as_object_mut() {
+                let fonts = theme.remove("fonts").and_then(|fonts| serde_json::from_value::<ThemeFonts>(fonts).ok());
+                theme.insert("fonts".to_string(), json!(fonts.unwrap_or_default()));
+            }
             Ok(Json(parsed))
         }
         None => Err(AppError::Internal("AI returned invalid theme format".to_string())),
@@ -622,11 +2366,17 @@ async fn ai_outline_to_slides(
     State(state): State<SharedState>,
     Json(data): Json<AiOutlineToSlidesRequest>,
 ) -> AppResult<Json<serde_json::Value>> {
-    let provider = get_provider_for_request(&state, &data.provider).await?;
+    let content = outline_to_slides(&state, &data.provider, &data.outline).await?;
+    Ok(Json(json!({ "content": content })))
+}
 
-    let prompt = format!("Convert this outline into a full presentation:\n\n{}", data.outline);
+/// Converts an outline into slide markdown. Shared by the HTTP handler and pipeline steps.
+pub(crate) async fn outline_to_slides(state: &SharedState, provider_name: &str, outline: &str) -> AppResult<String> {
+    let provider = get_provider_for_request(state, provider_name).await?;
 
-    let content = provider
+    let prompt = format!("Convert this outline into a full presentation:\n\n{}", outline);
+
+    provider
         .generate_content(&prompt, GenerateOptions {
             system_prompt: Some(format!(
                 "You are a presentation assistant. Convert the outline into well-structured \
@@ -636,9 +2386,7 @@ async fn ai_outline_to_slides(
             )),
             ..Default::default()
         })
-        .await?;
-
-    Ok(Json(json!({ "content": content })))
+        .await
 }
 
 async fn ai_visual_review(
@@ -721,3 +2469,115 @@ Return ONLY the improved markdown, nothing else."#,
 
     Ok(Json(json!({ "content": content })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, http::Method};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_unknown_ai_provider_is_rejected() {
+        let router = create_router(Arc::new(RwLock::new(crate::AppState::for_tests().await)));
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/ai-config")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "providerName": "mistral", "apiKey": "key" }).to_string()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["errors"][0]["field"], "providerName");
+        assert_eq!(body["errors"][0]["message"], "must be one of anthropic, openai, gemini, ollama, azure-openai");
+    }
+
+    #[tokio::test]
+    async fn test_health_lists_enabled_mcp_tools() {
+        let state = crate::AppState::for_tests().await;
+        state
+            .db
+            .set_mcp_tool_settings(&McpToolSettings {
+                read_only: true,
+                deny: vec!["search_presentations".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let router = create_router(Arc::new(RwLock::new(state)));
+        let request = Request::builder().uri("/health").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let tools: Vec<&str> = body["mcpTools"].as_array().unwrap().iter().map(|t| t.as_str().unwrap()).collect();
+        assert!(tools.contains(&"list_presentations"));
+        assert!(!tools.contains(&"search_presentations"));
+        assert!(!tools.contains(&"delete_presentation"));
+    }
+
+    #[tokio::test]
+    async fn test_theme_from_large_image() {
+        // Noise doesn't compress, so this PNG is a few MB: over axum's default body limit
+        let mut seed = 1u32;
+        let logo = image::RgbaImage::from_fn(900, 900, |_, _| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            image::Rgba(seed.to_le_bytes())
+        });
+        let mut png = Vec::new();
+        logo.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+        assert!(png.len() > 3 * 1024 * 1024);
+
+        let mut body = b"--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"logo.png\"\r\n\
+                         Content-Type: image/png\r\n\r\n"
+            .to_vec();
+        body.extend_from_slice(&png);
+        body.extend_from_slice(b"\r\n--b--\r\n");
+        let router = create_router(Arc::new(RwLock::new(crate::AppState::for_tests().await)));
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/themes/from-image")
+            .header("content-type", "multipart/form-data; boundary=b")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_backups_are_kept_per_profile() {
+        let root = std::env::temp_dir().join(format!("slides-backups-{}", uuid::Uuid::new_v4()));
+        let state = Arc::new(RwLock::new(crate::AppState {
+            app_data_dir: root.clone(),
+            ..crate::AppState::for_tests().await
+        }));
+        let router = create_router(state.clone());
+        let backups = || async {
+            let request = Request::builder().uri("/admin/backups").body(Body::empty()).unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<Vec<serde_json::Value>>(&bytes).unwrap().len()
+        };
+        let backup = || async {
+            let request = Request::builder().method(Method::POST).uri("/admin/backup").body(Body::empty()).unwrap();
+            assert_eq!(router.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+        };
+
+        backup().await;
+        assert_eq!(backups().await, 1);
+        state.write().await.profile = "client-a".to_string();
+        assert_eq!(backups().await, 0);
+        backup().await;
+        backup().await;
+        assert_eq!(backups().await, 2);
+        state.write().await.profile = crate::profiles::DEFAULT_PROFILE.to_string();
+        assert_eq!(backups().await, 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,12 +1,153 @@
-use chrono::Utc;
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
+// SQLite storage. The REST API, the MCP server and background tasks share the database, so it
+// runs in WAL mode: readers no longer block on a writer and a writer doesn't wait for
+// readers, at the cost of `-wal`/`-shm` files next to the database and a checkpoint now and
+// then. WAL only helps between connections; SQLite still allows a single writer at a time.
+// `synchronous=NORMAL` is the usual pairing with WAL: a power cut can lose the last commits
+// but can't corrupt the file. Each connection holds its own page cache, so a larger read pool
+// (SLIDES_DB_POOL_MAX) buys concurrent reads at the cost of memory; SLIDES_DB_POOL_MIN
+// connections are kept open even when idle.
+//
+// Reads and writes use separate pools so interactive reads never queue behind a long write
+// such as a bulk update or an import. All writes go through a single connection (`WritePool`),
+// which serializes them inside the process; readers (`ReadPool`) keep seeing the last
+// committed state meanwhile. Statements that only read go to the read pool; writes, and every
+// statement of a transaction, go to the write pool. Single writes run through
+// `WritePool::run` and transactions start with `WritePool::begin`, both of which retry while
+// another process holds the write lock. In-memory databases can't be shared between pools,
+// so there both wrap the same pool.
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{
+    query::Query,
+    sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePoolOptions, SqliteQueryResult},
+    Pool, Sqlite, SqliteConnection, Transaction,
+};
+use libsqlite3_sys as ffi;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::future::Future;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::error::{AppError, AppResult};
+use crate::encryption;
+use crate::error::{AppError, AppResult, ValidationBuilder};
+use crate::events::{AppEvent, EventBus};
 use crate::models::*;
+use crate::health;
+use crate::media::MediaMetadata;
+use crate::placeholders;
+use crate::replace::Replacer;
+use crate::slides;
+use crate::theme_css;
+use crate::watchlists;
 
+const DEFAULT_POOL_MAX: u32 = 5;
+const DEFAULT_POOL_MIN: u32 = 1;
+
+// Attempts at a write while SQLite reports the database busy, and the base of the backoff
+// between them. The write connection waits WRITE_BUSY_TIMEOUT inside SQLite before each
+// attempt gives up.
+const WRITE_BUSY_ATTEMPTS: u32 = 5;
+const WRITE_BUSY_BACKOFF_MS: u64 = 50;
+const WRITE_BUSY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Applied to every new write connection; synchronous and foreign_keys are per-connection settings
+const CONNECTION_PRAGMAS: &[&str] = &[
+    "PRAGMA journal_mode=WAL",
+    "PRAGMA synchronous=NORMAL",
+    "PRAGMA foreign_keys=ON",
+];
+
+// Default number of versions kept per presentation; older snapshots are pruned on update.
+// Override with SLIDES_MAX_REVISIONS.
+const DEFAULT_MAX_VERSIONS_PER_PRESENTATION: i64 = 50;
+
+// Automatic snapshots kept per presentation; the oldest are pruned as new ones are taken
+pub const MAX_SNAPSHOTS_PER_PRESENTATION: i64 = 20;
+
+// Most presentations a single bulk request may touch
+pub const MAX_BULK_IDS: usize = 100;
+
+// Attempts at a read-modify-write before reporting a conflict
+const MAX_EDIT_ATTEMPTS: u32 = 5;
+
+// Attempts at finding a free name (`name`, `name-2`, ...) before giving up with a conflict
+const MAX_UNIQUE_NAME_ATTEMPTS: u32 = 20;
+
+// Presentations named in the error when a theme in use can't be deleted
+const MAX_NAMED_USERS: usize = 3;
+
+// Longest font stack a theme may declare
+const MAX_FONT_STACK_LEN: usize = 200;
+
+// Settings keys
+const APP_SETTING: &str = "app";
+pub(crate) const MCP_TOOLS_SETTING: &str = "mcp_tools";
+const HEALTH_WEIGHTS_SETTING: &str = "health_weights";
+
+// Columns selected into `Presentation`, qualified so they can be used in joins. Tags are
+// collected into a JSON array sorted by name.
+const PRESENTATION_COLUMNS: &str = "p.id, p.title, p.content, p.theme, p.user_id, p.created_at, p.updated_at, p.deleted_at, \
+     p.slide_count, p.word_count, p.has_speaker_notes, p.health_score, p.archived, p.last_opened_at, p.settings, p.is_favorite, p.locked, \
+     p.draft_content IS NOT NULL AS has_draft, \
+     (SELECT json_group_array(name) FROM (SELECT t.name FROM presentation_tags pt JOIN tags t ON t.id = pt.tag_id \
+     WHERE pt.presentation_id = p.id ORDER BY t.name)) AS tags";
+
+fn pool_size_from_env(var: &str, default: u32) -> u32 {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(default)
+}
+
+/// Connections for statements that only read. On file databases they're opened read-only,
+/// so a write sent here fails instead of competing with the writer.
+#[derive(Clone)]
+struct ReadPool(Pool<Sqlite>);
+
+impl ReadPool {
+    fn pool(&self) -> &Pool<Sqlite> {
+        &self.0
+    }
+}
+
+/// The one connection that writes.
+#[derive(Clone)]
+struct WritePool(Pool<Sqlite>);
+
+impl WritePool {
+    fn pool(&self) -> &Pool<Sqlite> {
+        &self.0
+    }
+
+    /// Runs a single write, retrying while the database is busy.
+    async fn run<'p, T, F, Fut>(&'p self, mut write: F) -> AppResult<T>
+    where
+        F: FnMut(&'p Pool<Sqlite>) -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        retry_busy(|| write(&self.0)).await
+    }
+
+    /// Starts a transaction holding the write lock from the start, so its statements can't
+    /// fail with SQLITE_BUSY halfway through.
+    async fn begin(&self) -> AppResult<Transaction<'static, Sqlite>> {
+        retry_busy(|| self.0.begin_with("BEGIN IMMEDIATE")).await
+    }
+}
+
+/// Handle to a profile's database. Clones share the same pools, so a clone taken out of the
+/// state lock is closed along with the original when the profile switches.
+#[derive(Clone)]
 pub struct Database {
-    pool: Pool<Sqlite>,
+    read: ReadPool,
+    write: WritePool,
+    max_versions: i64,
+    events: EventBus,
 }
 
 impl Database {
@@ -17,12 +158,123 @@ impl Database {
     }
 
     pub async fn new_with_url(database_url: &str) -> AppResult<Self> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
+        let max_connections = pool_size_from_env("SLIDES_DB_POOL_MAX", DEFAULT_POOL_MAX).max(1);
+        let min_connections = pool_size_from_env("SLIDES_DB_POOL_MIN", DEFAULT_POOL_MIN).min(max_connections);
+        let options = SqliteConnectOptions::from_str(database_url)?;
+        let in_memory = database_url.contains(":memory:") || database_url.contains("mode=memory");
+
+        let write_pool = SqlitePoolOptions::new()
+            .max_connections(if in_memory { max_connections } else { 1 })
+            .min_connections(if in_memory { min_connections } else { 1 })
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    for pragma in CONNECTION_PRAGMAS {
+                        sqlx::query(pragma).execute(&mut *conn).await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect_with(options.clone().busy_timeout(WRITE_BUSY_TIMEOUT))
             .await?;
 
-        Ok(Self { pool })
+        // Opened after the writer, which creates the file and switches it to WAL
+        let read_pool = if in_memory {
+            write_pool.clone()
+        } else {
+            SqlitePoolOptions::new()
+                .max_connections(max_connections)
+                .min_connections(min_connections)
+                .connect_with(options.read_only(true))
+                .await?
+        };
+
+        let max_versions = std::env::var("SLIDES_MAX_REVISIONS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_MAX_VERSIONS_PER_PRESENTATION);
+
+        Ok(Self {
+            read: ReadPool(read_pool),
+            write: WritePool(write_pool),
+            max_versions,
+            events: EventBus::default(),
+        })
+    }
+
+    /// Publishes this database's changes on `events` instead of its own bus, so subscribers
+    /// carry over when the database is replaced.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Where committed changes are announced.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    pub async fn close(&self) {
+        self.write.0.close().await;
+        self.read.0.close().await;
+    }
+
+    /// Round-trips a trivial query to check the pool can reach the database.
+    pub async fn ping(&self) -> AppResult<()> {
+        sqlx::query("SELECT 1").execute(self.read.pool()).await?;
+        Ok(())
+    }
+
+    /// Runs `PRAGMA integrity_check`, returning whether SQLite found the file intact. Reads
+    /// the whole database, so it's only run on request.
+    pub async fn pragma_integrity_check(&self) -> AppResult<bool> {
+        let results: Vec<(String,)> = sqlx::query_as("PRAGMA integrity_check").fetch_all(self.read.pool()).await?;
+        Ok(results.len() == 1 && results[0].0 == "ok")
+    }
+
+    /// Copies the database to `dest` with SQLite's online backup API. The copy runs on a read
+    /// connection in a single step, so it is a consistent snapshot and, in WAL mode, writes
+    /// carry on meanwhile. The step blocks for as long as the copy takes, so it runs on the
+    /// blocking pool. An existing file at `dest` is overwritten.
+    pub async fn backup_to_path(&self, dest: &Path) -> AppResult<()> {
+        let dest = CString::new(dest.to_string_lossy().as_bytes())
+            .map_err(|_| AppError::BadRequest(format!("Invalid backup path {}", dest.display())))?;
+
+        let mut conn = self.read.pool().acquire().await?;
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || {
+            let mut handle = runtime.block_on(conn.lock_handle())?;
+            let source = handle.as_raw_handle().as_ptr();
+
+            // SAFETY: `source` stays valid while `handle` holds the connection lock, and the
+            // target connection and backup object are released before returning.
+            unsafe {
+                let mut target = std::ptr::null_mut();
+                let rc = ffi::sqlite3_open(dest.as_ptr(), &mut target);
+                if rc != ffi::SQLITE_OK {
+                    ffi::sqlite3_close(target);
+                    return Err(backup_error(rc));
+                }
+
+                let backup = ffi::sqlite3_backup_init(target, c"main".as_ptr(), source, c"main".as_ptr());
+                if backup.is_null() {
+                    let rc = ffi::sqlite3_errcode(target);
+                    ffi::sqlite3_close(target);
+                    return Err(backup_error(rc));
+                }
+
+                let rc = ffi::sqlite3_backup_step(backup, -1);
+                ffi::sqlite3_backup_finish(backup);
+                ffi::sqlite3_close(target);
+                if rc != ffi::SQLITE_DONE {
+                    return Err(backup_error(rc));
+                }
+            }
+
+            Ok(())
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("Backup task failed: {}", e)))?
     }
 
     pub async fn migrate(&self) -> AppResult<()> {
@@ -35,7 +287,45 @@ impl Database {
                 theme TEXT NOT NULL DEFAULT 'default',
                 user_id TEXT NOT NULL DEFAULT 'local',
                 created_at TEXT NOT NULL,
-                updated_at TEXT NOT NULL
+                updated_at TEXT NOT NULL,
+                deleted_at TEXT,
+                slide_count INTEGER NOT NULL DEFAULT 1,
+                word_count INTEGER NOT NULL DEFAULT 0,
+                has_speaker_notes INTEGER NOT NULL DEFAULT 0,
+                health_score INTEGER,
+                health_json TEXT,
+                archived INTEGER NOT NULL DEFAULT 0,
+                last_opened_at TEXT,
+                settings TEXT NOT NULL DEFAULT '{}',
+                is_favorite INTEGER NOT NULL DEFAULT 0,
+                locked INTEGER NOT NULL DEFAULT 0,
+                draft_content TEXT,
+                draft_saved_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS presentation_versions (
+                id TEXT PRIMARY KEY,
+                presentation_id TEXT NOT NULL REFERENCES presentations(id) ON DELETE CASCADE,
+                title TEXT,
+                content TEXT NOT NULL,
+                theme TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                created_by TEXT NOT NULL DEFAULT 'local'
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_presentation_versions_presentation
+                ON presentation_versions(presentation_id, created_at);
+
+            CREATE TABLE IF NOT EXISTS tags (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS presentation_tags (
+                presentation_id TEXT NOT NULL REFERENCES presentations(id) ON DELETE CASCADE,
+                tag_id TEXT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                PRIMARY KEY (presentation_id, tag_id)
             );
 
             CREATE TABLE IF NOT EXISTS themes (
@@ -45,6 +335,7 @@ impl Database {
                 css_content TEXT NOT NULL,
                 is_default INTEGER NOT NULL DEFAULT 0,
                 center_content INTEGER NOT NULL DEFAULT 1,
+                fonts TEXT NOT NULL DEFAULT '{}',
                 user_id TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
@@ -58,7 +349,28 @@ impl Database {
                 size INTEGER NOT NULL,
                 url TEXT NOT NULL,
                 user_id TEXT NOT NULL DEFAULT 'local',
-                created_at TEXT NOT NULL
+                created_at TEXT NOT NULL,
+                width INTEGER,
+                height INTEGER,
+                duration_seconds REAL,
+                content_hash TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS upload_sessions (
+                id TEXT PRIMARY KEY,
+                filename TEXT NOT NULL,
+                mime_type TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                chunk_size INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS upload_chunks (
+                upload_id TEXT NOT NULL REFERENCES upload_sessions(id) ON DELETE CASCADE,
+                chunk_index INTEGER NOT NULL,
+                PRIMARY KEY (upload_id, chunk_index)
             );
 
             CREATE TABLE IF NOT EXISTS layout_rules (
@@ -77,6 +389,29 @@ impl Database {
                 updated_at TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS pipelines (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                description TEXT,
+                steps TEXT NOT NULL,
+                user_id TEXT NOT NULL DEFAULT 'local',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS model_cache (
+                provider_name TEXT PRIMARY KEY,
+                base_url TEXT,
+                models TEXT NOT NULL,
+                fetched_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+
             CREATE TABLE IF NOT EXISTS ai_provider_configs (
                 id TEXT PRIMARY KEY,
                 provider_name TEXT NOT NULL,
@@ -88,9 +423,39 @@ impl Database {
                 updated_at TEXT NOT NULL,
                 UNIQUE(user_id, provider_name)
             );
+
+            CREATE TABLE IF NOT EXISTS watchlist_sources (
+                id TEXT PRIMARY KEY,
+                presentation_id TEXT NOT NULL REFERENCES presentations(id) ON DELETE CASCADE,
+                url TEXT NOT NULL,
+                title TEXT,
+                created_at TEXT NOT NULL,
+                last_refreshed_at TEXT,
+                UNIQUE(presentation_id, url)
+            );
+
+            CREATE TABLE IF NOT EXISTS watchlist_items (
+                presentation_id TEXT NOT NULL REFERENCES presentations(id) ON DELETE CASCADE,
+                item_hash TEXT NOT NULL,
+                imported_at TEXT NOT NULL,
+                PRIMARY KEY (presentation_id, item_hash)
+            );
+
+            CREATE TABLE IF NOT EXISTS presentation_snapshots (
+                id TEXT PRIMARY KEY,
+                presentation_id TEXT NOT NULL REFERENCES presentations(id) ON DELETE CASCADE,
+                title TEXT NOT NULL,
+                content TEXT NOT NULL,
+                theme TEXT NOT NULL,
+                content_hash TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_presentation_snapshots_presentation
+                ON presentation_snapshots(presentation_id, created_at);
             "#,
         )
-        .execute(&self.pool)
+        .execute(self.write.pool())
         .await?;
 
         // Run migrations for schema updates
@@ -108,189 +473,396 @@ impl Database {
         let columns: Vec<(String,)> = sqlx::query_as(
             "SELECT name FROM pragma_table_info('themes') WHERE name = 'center_content'"
         )
-        .fetch_all(&self.pool)
+        .fetch_all(self.write.pool())
         .await?;
 
         if columns.is_empty() {
             sqlx::query("ALTER TABLE themes ADD COLUMN center_content INTEGER NOT NULL DEFAULT 1")
-                .execute(&self.pool)
+                .execute(self.write.pool())
                 .await?;
         }
 
-        Ok(())
-    }
+        // Add theme fonts, filling in the ones the seeded themes' CSS uses
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('themes') WHERE name = 'fonts'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
 
-    async fn seed_defaults(&self) -> AppResult<()> {
-        let theme_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM themes")
-            .fetch_one(&self.pool)
-            .await?;
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE themes ADD COLUMN fonts TEXT NOT NULL DEFAULT '{}'")
+                .execute(self.write.pool())
+                .await?;
+            for builtin in &BUILTIN_THEMES {
+                sqlx::query("UPDATE themes SET fonts = ? WHERE name = ? AND user_id IS NULL")
+                    .bind(sqlx::types::Json(builtin.theme_fonts()))
+                    .bind(builtin.name)
+                    .execute(self.write.pool())
+                    .await?;
+            }
+        }
 
-        if theme_count.0 == 0 {
-            self.seed_themes().await?;
+        // Add deleted_at column to presentations for soft deletes
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'deleted_at'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentations ADD COLUMN deleted_at TEXT")
+                .execute(self.write.pool())
+                .await?;
         }
 
-        let rule_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM layout_rules")
-            .fetch_one(&self.pool)
-            .await?;
+        // Add title column to presentation_versions so restores bring back the title too
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentation_versions') WHERE name = 'title'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
 
-        if rule_count.0 == 0 {
-            self.seed_layout_rules().await?;
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentation_versions ADD COLUMN title TEXT")
+                .execute(self.write.pool())
+                .await?;
         }
 
-        Ok(())
-    }
+        // Add content stats to presentations, computed on write, and backfill existing rows
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'slide_count'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
 
-    async fn seed_themes(&self) -> AppResult<()> {
-        // (name, display_name, css, is_default, center_content)
-        let themes = vec![
-            ("default", "Default", r#"
-.slide-content[data-theme="default"], [data-theme="default"] .slide-content, [data-theme="default"] .slide {
-  --slide-bg: #ffffff; --slide-text: #333333; --slide-heading: #1a1a1a; --slide-accent: #0066cc;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="default"] h1, [data-theme="default"] h2, [data-theme="default"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="default"] code { background: #f5f5f5; padding: 0.2em 0.4em; border-radius: 3px; }
-[data-theme="default"] a { color: var(--slide-accent); }
-"#, true, true),
-            ("dark", "Dark Mode", r#"
-.slide-content[data-theme="dark"], [data-theme="dark"] .slide-content, [data-theme="dark"] .slide {
-  --slide-bg: #1e1e2e; --slide-text: #cdd6f4; --slide-heading: #cba6f7; --slide-accent: #89b4fa;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="dark"] h1, [data-theme="dark"] h2, [data-theme="dark"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="dark"] code { background: #313244; padding: 0.2em 0.4em; border-radius: 3px; color: #a6e3a1; }
-[data-theme="dark"] a { color: var(--slide-accent); }
-"#, false, true),
-            ("minimal", "Minimal", r#"
-.slide-content[data-theme="minimal"], [data-theme="minimal"] .slide-content, [data-theme="minimal"] .slide {
-  --slide-bg: #fafafa; --slide-text: #222; --slide-heading: #000; --slide-accent: #555;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif; padding: 4rem;
-}
-[data-theme="minimal"] h1 { font-size: 3rem; font-weight: 300; letter-spacing: -0.02em; }
-[data-theme="minimal"] h2 { font-size: 2rem; font-weight: 300; }
-[data-theme="minimal"] code { background: #eee; padding: 0.2em 0.4em; border-radius: 3px; }
-"#, false, true),
-            ("corporate", "Corporate", r#"
-.slide-content[data-theme="corporate"], [data-theme="corporate"] .slide-content, [data-theme="corporate"] .slide {
-  --slide-bg: #ffffff; --slide-text: #2c3e50; --slide-heading: #1a365d; --slide-accent: #2b6cb0;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
-  border-top: 4px solid var(--slide-accent);
-}
-[data-theme="corporate"] h1, [data-theme="corporate"] h2 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading); border-bottom: 2px solid #e2e8f0; padding-bottom: 0.5rem;
-}
-[data-theme="corporate"] code { background: #edf2f7; padding: 0.2em 0.4em; border-radius: 3px; }
-"#, false, true),
-            ("creative", "Creative", r#"
-.slide-content[data-theme="creative"], [data-theme="creative"] .slide-content, [data-theme="creative"] .slide {
-  --slide-bg: #0f0c29; --slide-text: #e0e0e0; --slide-heading: #f857a6; --slide-accent: #ff5858;
-  background: linear-gradient(135deg, #0f0c29, #302b63, #24243e); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="creative"] h1, [data-theme="creative"] h2 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-  background: linear-gradient(90deg, #f857a6, #ff5858); -webkit-background-clip: text; -webkit-text-fill-color: transparent;
-}
-[data-theme="creative"] code { background: rgba(255,255,255,0.1); padding: 0.2em 0.4em; border-radius: 3px; }
-[data-theme="creative"] a { color: var(--slide-accent); }
-"#, false, true),
-            ("ocean", "Ocean", r#"
-.slide-content[data-theme="ocean"], [data-theme="ocean"] .slide-content, [data-theme="ocean"] .slide {
-  --slide-bg: #0b1929; --slide-text: #b2c8df; --slide-heading: #5eead4; --slide-accent: #38bdf8;
-  background: linear-gradient(180deg, #0b1929 0%, #0d2137 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="ocean"] h1, [data-theme="ocean"] h2, [data-theme="ocean"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="ocean"] code { background: rgba(56,189,248,0.1); padding: 0.2em 0.4em; border-radius: 3px; color: #7dd3fc; }
-[data-theme="ocean"] a { color: var(--slide-accent); }
-[data-theme="ocean"] blockquote { border-left: 3px solid #5eead4; padding-left: 1rem; color: #7dd3fc; }
-"#, false, true),
-            ("sunset", "Sunset", r#"
-.slide-content[data-theme="sunset"], [data-theme="sunset"] .slide-content, [data-theme="sunset"] .slide {
-  --slide-bg: #1c1017; --slide-text: #e8d5ce; --slide-heading: #fb923c; --slide-accent: #f472b6;
-  background: linear-gradient(135deg, #1c1017 0%, #2a1520 50%, #1e1422 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="sunset"] h1, [data-theme="sunset"] h2, [data-theme="sunset"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="sunset"] h1 { background: linear-gradient(90deg, #fb923c, #f472b6); -webkit-background-clip: text; -webkit-text-fill-color: transparent; }
-[data-theme="sunset"] code { background: rgba(251,146,60,0.12); padding: 0.2em 0.4em; border-radius: 3px; color: #fdba74; }
-[data-theme="sunset"] a { color: var(--slide-accent); }
-"#, false, true),
-            ("forest", "Forest", r#"
-.slide-content[data-theme="forest"], [data-theme="forest"] .slide-content, [data-theme="forest"] .slide {
-  --slide-bg: #0f1a0f; --slide-text: #c8d6c0; --slide-heading: #4ade80; --slide-accent: #86efac;
-  background: linear-gradient(180deg, #0f1a0f 0%, #162016 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="forest"] h1, [data-theme="forest"] h2, [data-theme="forest"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="forest"] code { background: rgba(74,222,128,0.1); padding: 0.2em 0.4em; border-radius: 3px; color: #86efac; }
-[data-theme="forest"] a { color: var(--slide-accent); }
-[data-theme="forest"] strong { color: #bbf7d0; }
-"#, false, true),
-            ("noir", "Noir", r#"
-.slide-content[data-theme="noir"], [data-theme="noir"] .slide-content, [data-theme="noir"] .slide {
-  --slide-bg: #0a0a0a; --slide-text: #a3a3a3; --slide-heading: #fafafa; --slide-accent: #e5e5e5;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="noir"] h1, [data-theme="noir"] h2, [data-theme="noir"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading); font-weight: 700; letter-spacing: -0.02em;
-}
-[data-theme="noir"] h1 { font-size: 3.2rem; }
-[data-theme="noir"] code { background: #1a1a1a; padding: 0.2em 0.4em; border-radius: 3px; color: #d4d4d4; }
-[data-theme="noir"] a { color: var(--slide-accent); text-decoration: underline; }
-[data-theme="noir"] blockquote { border-left: 3px solid #404040; padding-left: 1rem; color: #d4d4d4; }
-"#, false, true),
-            ("lavender", "Lavender", r#"
-.slide-content[data-theme="lavender"], [data-theme="lavender"] .slide-content, [data-theme="lavender"] .slide {
-  --slide-bg: #faf5ff; --slide-text: #4a3563; --slide-heading: #7c3aed; --slide-accent: #a78bfa;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
-}
-[data-theme="lavender"] h1, [data-theme="lavender"] h2, [data-theme="lavender"] h3 {
-  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
-}
-[data-theme="lavender"] code { background: #ede9fe; padding: 0.2em 0.4em; border-radius: 3px; color: #6d28d9; }
-[data-theme="lavender"] a { color: var(--slide-accent); }
-[data-theme="lavender"] blockquote { border-left: 3px solid #c4b5fd; padding-left: 1rem; }
-"#, false, true),
-            ("cyberpunk", "Cyberpunk", r#"
-.slide-content[data-theme="cyberpunk"], [data-theme="cyberpunk"] .slide-content, [data-theme="cyberpunk"] .slide {
-  --slide-bg: #0a0014; --slide-text: #d4d4d8; --slide-heading: #e4ff1a; --slide-accent: #06b6d4;
-  background: var(--slide-bg); color: var(--slide-text); font-family: 'JetBrains Mono', 'Fira Code', monospace;
-}
-[data-theme="cyberpunk"] h1, [data-theme="cyberpunk"] h2, [data-theme="cyberpunk"] h3 {
-  color: var(--slide-heading); text-transform: uppercase; letter-spacing: 0.05em;
-}
-[data-theme="cyberpunk"] h1 { text-shadow: 0 0 20px rgba(228,255,26,0.3); }
-[data-theme="cyberpunk"] code { background: rgba(6,182,212,0.12); padding: 0.2em 0.4em; border-radius: 3px; color: #22d3ee; }
-[data-theme="cyberpunk"] a { color: var(--slide-accent); }
-[data-theme="cyberpunk"] strong { color: #e4ff1a; }
-"#, false, true),
-        ];
+        if columns.is_empty() {
+            let mut tx = self.write.begin().await?;
 
-        for (name, display_name, css, is_default, center_content) in themes {
-            let now = Utc::now().to_rfc3339();
             sqlx::query(
-                "INSERT INTO themes (id, name, display_name, css_content, is_default, center_content, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+                r#"
+                ALTER TABLE presentations ADD COLUMN slide_count INTEGER NOT NULL DEFAULT 1;
+                ALTER TABLE presentations ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+                ALTER TABLE presentations ADD COLUMN has_speaker_notes INTEGER NOT NULL DEFAULT 0;
+                "#,
             )
-            .bind(Uuid::new_v4().to_string())
-            .bind(name)
-            .bind(display_name)
-            .bind(css)
-            .bind(is_default)
-            .bind(center_content)
-            .bind(&now)
-            .bind(&now)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
-        }
 
-        Ok(())
+            let rows: Vec<(String, String)> = sqlx::query_as("SELECT id, content FROM presentations")
+                .fetch_all(&mut *tx)
+                .await?;
+
+            for (id, content) in rows {
+                let stats = slides::deck_stats(&content);
+                sqlx::query("UPDATE presentations SET slide_count = ?, word_count = ?, has_speaker_notes = ? WHERE id = ?")
+                    .bind(stats.slide_count)
+                    .bind(stats.word_count)
+                    .bind(stats.has_speaker_notes)
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+        }
+
+        // Add cached health scores to presentations, computed on write
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'health_score'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query(
+                r#"
+                ALTER TABLE presentations ADD COLUMN health_score INTEGER;
+                ALTER TABLE presentations ADD COLUMN health_json TEXT;
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+
+            self.refresh_health().await?;
+        }
+
+        // Add archived flag to presentations
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'archived'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentations ADD COLUMN archived INTEGER NOT NULL DEFAULT 0")
+                .execute(self.write.pool())
+                .await?;
+        }
+
+        // Add per-presentation settings
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'settings'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentations ADD COLUMN settings TEXT NOT NULL DEFAULT '{}'")
+                .execute(self.write.pool())
+                .await?;
+        }
+
+        // Add favorite flag to presentations
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'is_favorite'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentations ADD COLUMN is_favorite INTEGER NOT NULL DEFAULT 0")
+                .execute(self.write.pool())
+                .await?;
+        }
+
+        // Add read-only lock flag to presentations
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'locked'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE presentations ADD COLUMN locked INTEGER NOT NULL DEFAULT 0")
+                .execute(self.write.pool())
+                .await?;
+        }
+
+        // Add the autosave draft channel to presentations
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'draft_content'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query(
+                r#"
+                ALTER TABLE presentations ADD COLUMN draft_content TEXT;
+                ALTER TABLE presentations ADD COLUMN draft_saved_at TEXT;
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+        }
+
+        // Add image dimensions and audio/video duration to media
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('media') WHERE name = 'width'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query(
+                r#"
+                ALTER TABLE media ADD COLUMN width INTEGER;
+                ALTER TABLE media ADD COLUMN height INTEGER;
+                ALTER TABLE media ADD COLUMN duration_seconds REAL;
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+        }
+
+        // Add content hashes to media so duplicate uploads can be detected
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('media') WHERE name = 'content_hash'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query("ALTER TABLE media ADD COLUMN content_hash TEXT")
+                .execute(self.write.pool())
+                .await?;
+        }
+
+        // One library entry per file. The index was not unique at first, so entries that
+        // slipped in twice keep only the oldest one's hash
+        let unique: Vec<(i64,)> = sqlx::query_as(
+            "SELECT \"unique\" FROM pragma_index_list('media') WHERE name = 'idx_media_content_hash'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if !unique.iter().any(|(unique,)| *unique == 1) {
+            sqlx::query(
+                r#"
+                UPDATE media SET content_hash = NULL WHERE content_hash IS NOT NULL AND rowid NOT IN (
+                    SELECT MIN(rowid) FROM media WHERE content_hash IS NOT NULL GROUP BY content_hash
+                );
+                DROP INDEX IF EXISTS idx_media_content_hash;
+                CREATE UNIQUE INDEX idx_media_content_hash ON media(content_hash);
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+        }
+
+        // Track when presentations were last opened; existing decks count as opened when
+        // they were last edited
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'last_opened_at'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            sqlx::query(
+                r#"
+                ALTER TABLE presentations ADD COLUMN last_opened_at TEXT;
+                UPDATE presentations SET last_opened_at = updated_at;
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+        }
+
+        // Store a hash of each presentation's content, set on write, so automatic snapshots
+        // can tell which decks changed without reading them
+        let columns: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM pragma_table_info('presentations') WHERE name = 'content_hash'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if columns.is_empty() {
+            let mut tx = self.write.begin().await?;
+
+            sqlx::query("ALTER TABLE presentations ADD COLUMN content_hash TEXT")
+                .execute(&mut *tx)
+                .await?;
+
+            let rows: Vec<(String, String)> = sqlx::query_as("SELECT id, content FROM presentations")
+                .fetch_all(&mut *tx)
+                .await?;
+
+            for (id, content) in rows {
+                sqlx::query("UPDATE presentations SET content_hash = ? WHERE id = ?")
+                    .bind(content_hash(&content))
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+        }
+
+        // Full-text index over presentation titles and content, kept in sync by triggers
+        let fts_tables: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'fts_presentations'"
+        )
+        .fetch_all(self.write.pool())
+        .await?;
+
+        if fts_tables.is_empty() {
+            sqlx::query(
+                r#"
+                CREATE VIRTUAL TABLE fts_presentations USING fts5(
+                    title,
+                    content,
+                    content='presentations',
+                    content_rowid='rowid'
+                );
+
+                INSERT INTO fts_presentations(fts_presentations) VALUES ('rebuild');
+                "#,
+            )
+            .execute(self.write.pool())
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS presentations_fts_insert AFTER INSERT ON presentations BEGIN
+                INSERT INTO fts_presentations(rowid, title, content) VALUES (new.rowid, new.title, new.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS presentations_fts_delete AFTER DELETE ON presentations BEGIN
+                INSERT INTO fts_presentations(fts_presentations, rowid, title, content) VALUES ('delete', old.rowid, old.title, old.content);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS presentations_fts_update AFTER UPDATE OF title, content ON presentations BEGIN
+                INSERT INTO fts_presentations(fts_presentations, rowid, title, content) VALUES ('delete', old.rowid, old.title, old.content);
+                INSERT INTO fts_presentations(rowid, title, content) VALUES (new.rowid, new.title, new.content);
+            END;
+            "#,
+        )
+        .execute(self.write.pool())
+        .await?;
+
+        Ok(())
+    }
+
+    async fn seed_defaults(&self) -> AppResult<()> {
+        let theme_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM themes")
+            .fetch_one(self.write.pool())
+            .await?;
+
+        if theme_count.0 == 0 {
+            self.seed_themes().await?;
+        }
+
+        let rule_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM layout_rules")
+            .fetch_one(self.write.pool())
+            .await?;
+
+        if rule_count.0 == 0 {
+            self.seed_layout_rules().await?;
+        }
+
+        let pipeline_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pipelines")
+            .fetch_one(self.write.pool())
+            .await?;
+
+        if pipeline_count.0 == 0 {
+            self.seed_pipelines().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn seed_pipelines(&self) -> AppResult<()> {
+        let steps = r#"[
+            {"op":"outline_to_slides","params":{"provider":"{{input.provider}}","outline":"{{input.outline}}"}},
+            {"op":"create_presentation","params":{"title":"{{input.title}}","content":"{{previous.content}}","theme":"default"}}
+        ]"#;
+        let steps: Vec<PipelineStep> = serde_json::from_str(steps)
+            .map_err(|e| AppError::Internal(format!("Invalid seed pipeline: {}", e)))?;
+
+        self.create_pipeline(CreatePipeline {
+            name: "outline-to-deck".to_string(),
+            description: Some(
+                "Generates slides from an outline with AI and saves them as a new presentation. \
+                Input: provider, outline, title."
+                    .to_string(),
+            ),
+            steps,
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn seed_themes(&self) -> AppResult<()> {
+        let mut tx = self.write.begin().await?;
+        for theme in &BUILTIN_THEMES {
+            insert_builtin_theme(&mut tx, theme).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
     }
 
     async fn seed_layout_rules(&self) -> AppResult<()> {
@@ -375,7 +947,7 @@ impl Database {
             .bind(css)
             .bind(&now)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(self.write.pool())
             .await?;
         }
 
@@ -383,453 +955,4174 @@ impl Database {
     }
 
     // Presentations
-    pub async fn list_presentations(&self) -> AppResult<Vec<Presentation>> {
-        let presentations = sqlx::query_as::<_, Presentation>(
-            "SELECT id, title, content, theme, user_id, created_at, updated_at FROM presentations ORDER BY updated_at DESC"
+    pub async fn list_presentations(&self, query: ListPresentationsQuery) -> AppResult<PaginatedResult<Presentation>> {
+        let page = query.page.max(1);
+        let per_page = query.per_page.clamp(1, MAX_PER_PAGE);
+        let offset = (page as i64 - 1) * per_page as i64;
+
+        // Presentations must carry every requested tag
+        let tags: Vec<String> = query.tags.iter().map(|tag| normalize_tag(tag)).collect();
+        let mut filter = if tags.is_empty() {
+            String::new()
+        } else {
+            format!(
+                " AND p.id IN (SELECT pt.presentation_id FROM presentation_tags pt JOIN tags t ON t.id = pt.tag_id \
+                 WHERE t.name IN ({}) GROUP BY pt.presentation_id HAVING COUNT(DISTINCT t.name) = {})",
+                vec!["?"; tags.len()].join(", "),
+                tags.len()
+            )
+        };
+        if !query.include_archived {
+            filter.push_str(" AND p.archived = 0");
+        }
+
+        let count_sql = format!("SELECT COUNT(*) FROM presentations p WHERE p.deleted_at IS NULL{}", filter);
+        let mut count_query = sqlx::query_as::<_, (i64,)>(&count_sql);
+        for tag in &tags {
+            count_query = count_query.bind(tag);
+        }
+        let total = count_query.fetch_one(self.read.pool()).await?;
+
+        // Sort column and direction come from enums, never from user input directly
+        let order_column = match query.sort_by {
+            PresentationSortField::Title => "p.title COLLATE NOCASE",
+            PresentationSortField::CreatedAt => "p.created_at",
+            PresentationSortField::UpdatedAt => "p.updated_at",
+            PresentationSortField::LastOpenedAt => "p.last_opened_at",
+        };
+        let order_dir = match query.sort_dir {
+            SortDir::Asc => "ASC",
+            SortDir::Desc => "DESC",
+        };
+
+        let favorites_first = if query.favorites_first { "p.is_favorite DESC, " } else { "" };
+
+        let list_sql = format!(
+            "SELECT {} FROM presentations p WHERE p.deleted_at IS NULL{} ORDER BY {}{} {}, p.id LIMIT ? OFFSET ?",
+            PRESENTATION_COLUMNS, filter, favorites_first, order_column, order_dir
+        );
+        let mut list_query = sqlx::query_as::<_, Presentation>(&list_sql);
+        for tag in &tags {
+            list_query = list_query.bind(tag);
+        }
+        let presentations = list_query
+            .bind(per_page as i64)
+            .bind(offset)
+            .fetch_all(self.read.pool())
+            .await?;
+
+        Ok(PaginatedResult::new(presentations, total.0, page, per_page))
+    }
+
+    pub async fn search_presentations(&self, query: &str, page: u32, per_page: u32) -> AppResult<PaginatedResult<Presentation>> {
+        let match_expr = fts_match_expression(query)
+            .ok_or_else(|| AppError::BadRequest("Search query must not be empty".to_string()))?;
+        let page = page.max(1);
+        let per_page = per_page.clamp(1, MAX_PER_PAGE);
+        let offset = (page as i64 - 1) * per_page as i64;
+
+        let total: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM fts_presentations JOIN presentations p ON p.rowid = fts_presentations.rowid WHERE fts_presentations MATCH ? AND p.deleted_at IS NULL"
         )
-        .fetch_all(&self.pool)
+        .bind(&match_expr)
+        .fetch_one(self.read.pool())
         .await?;
-        Ok(presentations)
+
+        let presentations = sqlx::query_as::<_, Presentation>(&format!(
+            "SELECT {} FROM fts_presentations JOIN presentations p ON p.rowid = fts_presentations.rowid WHERE fts_presentations MATCH ? AND p.deleted_at IS NULL ORDER BY bm25(fts_presentations) LIMIT ? OFFSET ?",
+            PRESENTATION_COLUMNS
+        ))
+        .bind(&match_expr)
+        .bind(per_page as i64)
+        .bind(offset)
+        .fetch_all(self.read.pool())
+        .await?;
+
+        Ok(PaginatedResult::new(presentations, total.0, page, per_page))
     }
 
     pub async fn get_presentation(&self, id: &str) -> AppResult<Presentation> {
-        sqlx::query_as::<_, Presentation>(
-            "SELECT id, title, content, theme, user_id, created_at, updated_at FROM presentations WHERE id = ?"
-        )
+        sqlx::query_as::<_, Presentation>(&format!(
+            "SELECT {} FROM presentations p WHERE p.id = ? AND p.deleted_at IS NULL",
+            PRESENTATION_COLUMNS
+        ))
         .bind(id)
-        .fetch_optional(&self.pool)
+        .fetch_optional(self.read.pool())
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Presentation {} not found", id)))
     }
 
-    pub async fn create_presentation(&self, data: CreatePresentation) -> AppResult<Presentation> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        let content = data.content.unwrap_or_default();
-        let theme = data.theme.unwrap_or_else(|| "default".to_string());
+    /// Every presentation outside the trash, archived ones included.
+    pub async fn list_all_presentations(&self) -> AppResult<Vec<Presentation>> {
+        let presentations = sqlx::query_as::<_, Presentation>(&format!(
+            "SELECT {} FROM presentations p WHERE p.deleted_at IS NULL ORDER BY p.created_at",
+            PRESENTATION_COLUMNS
+        ))
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(presentations)
+    }
 
-        sqlx::query(
-            "INSERT INTO presentations (id, title, content, theme, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, 'local', ?, ?)"
+    /// A value that changes whenever a presentation is created, edited, trashed, restored or
+    /// (un)archived, for telling when workspace-wide caches are stale. The rowid sums catch
+    /// one deck being swapped for another without the count or latest edit changing.
+    pub async fn presentations_fingerprint(&self) -> AppResult<String> {
+        let (fingerprint,): (String,) = sqlx::query_as(
+            "SELECT COUNT(*) || ':' || IFNULL(MAX(updated_at), '') || ':' || TOTAL(rowid) || ':' || TOTAL(rowid * archived) \
+             FROM presentations WHERE deleted_at IS NULL"
         )
-        .bind(&id)
-        .bind(&data.title)
-        .bind(&content)
-        .bind(&theme)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
+        .fetch_one(self.read.pool())
         .await?;
-
-        self.get_presentation(&id).await
+        Ok(fingerprint)
     }
 
-    pub async fn update_presentation(&self, id: &str, data: UpdatePresentation) -> AppResult<Presentation> {
-        let existing = self.get_presentation(id).await?;
-        let now = Utc::now();
+    /// Creates a presentation. With a title-conflict mode, the lookup of an existing deck with
+    /// the same title and the insert share one transaction, so two concurrent requests can't
+    /// both create it.
+    pub async fn create_presentation(&self, data: CreatePresentation) -> AppResult<Presentation> {
+        let conflict = data.title_conflict();
+        let row = self.new_presentation(data.title, data.content, data.theme).await?;
 
-        let title = data.title.unwrap_or(existing.title);
-        let content = data.content.unwrap_or(existing.content);
-        let theme = data.theme.unwrap_or(existing.theme);
+        let mut tx = self.write.begin().await?;
+        if let Some(conflict) = conflict {
+            if let Some(id) = find_presentation_by_title(&mut tx, &row.title).await? {
+                drop(tx);
+                let existing = self.get_presentation(&id).await?;
+                return match conflict {
+                    TitleConflict::Return => Ok(existing),
+                    TitleConflict::Error => Err(AppError::Conflict(format!(
+                        "A presentation titled \"{}\" already exists ({})",
+                        existing.title, existing.id
+                    ))),
+                };
+            }
+        }
+        row.insert().execute(&mut *tx).await?;
+        tx.commit().await?;
 
-        sqlx::query("UPDATE presentations SET title = ?, content = ?, theme = ?, updated_at = ? WHERE id = ?")
-            .bind(&title)
-            .bind(&content)
-            .bind(&theme)
-            .bind(now)
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+        self.events.publish(AppEvent::PresentationCreated { id: row.id.clone() });
+        self.get_presentation(&row.id).await
+    }
 
-        self.get_presentation(id).await
+    /// Inserts a new presentation without announcing it; callers publish once it's complete.
+    async fn insert_presentation(&self, title: String, content: Option<String>, theme: Option<String>) -> AppResult<Presentation> {
+        let row = self.new_presentation(title, content, theme).await?;
+        self.write.run(|pool| row.insert().execute(pool)).await?;
+        self.get_presentation(&row.id).await
     }
 
-    pub async fn delete_presentation(&self, id: &str) -> AppResult<()> {
-        let result = sqlx::query("DELETE FROM presentations WHERE id = ?")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    /// The row for a new presentation, with its stats and health computed.
+    async fn new_presentation(&self, title: String, content: Option<String>, theme: Option<String>) -> AppResult<NewPresentation> {
+        let content = content.unwrap_or_default();
+        let stats = slides::deck_stats(&content);
+        let (health_score, health_json) = self.assess_health(&content).await?;
+        Ok(NewPresentation {
+            id: Uuid::new_v4().to_string(),
+            title,
+            content,
+            theme: match theme {
+                Some(theme) => theme,
+                None => self.default_theme().await?,
+            },
+            now: Utc::now(),
+            stats,
+            health_score,
+            health_json,
+        })
+    }
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+    /// Creates a presentation from the slides of `source_ids`, in order. With `delete_sources`
+    /// the sources are moved to the trash in the same transaction, so either both happen or
+    /// neither does.
+    pub async fn merge_presentations(&self, request: MergePresentations) -> AppResult<Presentation> {
+        let MergePresentations { source_ids, title, theme, delete_sources } = request;
+        if source_ids.len() < 2 {
+            return Err(AppError::BadRequest("sourceIds must name at least two presentations".to_string()));
+        }
+        if source_ids.len() > MAX_BULK_IDS {
+            return Err(AppError::BadRequest(format!(
+                "At most {} presentations can be merged in one request",
+                MAX_BULK_IDS
+            )));
+        }
+        if source_ids.iter().collect::<HashSet<_>>().len() != source_ids.len() {
+            return Err(AppError::BadRequest("sourceIds must not repeat a presentation".to_string()));
+        }
+        if title.trim().is_empty() {
+            return Err(AppError::BadRequest("title must not be empty".to_string()));
         }
 
-        Ok(())
+        let mut sources = Vec::with_capacity(source_ids.len());
+        for id in &source_ids {
+            let source = self.get_presentation(id).await?;
+            if delete_sources {
+                ensure_unlocked(&source)?;
+            }
+            sources.push(source);
+        }
+        let theme = match theme {
+            Some(theme) => match self.get_theme_by_name(&theme).await {
+                Ok(_) => theme,
+                Err(AppError::NotFound(_)) => {
+                    return Err(AppError::BadRequest(format!("Theme '{}' not found", theme)))
+                }
+                Err(e) => return Err(e),
+            },
+            None => sources[0].theme.clone(),
+        };
+
+        let content = slides::merge_decks(sources.iter().map(|p| p.content.as_str()));
+        let row = self.new_presentation(title, Some(content), Some(theme)).await?;
+
+        let mut tx = self.write.begin().await?;
+        row.insert().execute(&mut *tx).await?;
+        if delete_sources {
+            for id in &source_ids {
+                let result = sqlx::query("UPDATE presentations SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL AND locked = 0")
+                    .bind(row.now)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                // Deleted or locked since it was read; dropping the transaction undoes the merge
+                if result.rows_affected() == 0 {
+                    return Err(AppError::Conflict(format!(
+                        "Presentation {} was modified during the merge, try again",
+                        id
+                    )));
+                }
+            }
+        }
+        tx.commit().await?;
+
+        self.events.publish(AppEvent::PresentationCreated { id: row.id.clone() });
+        if delete_sources {
+            for id in source_ids {
+                self.events.publish(AppEvent::PresentationDeleted { id, permanent: false });
+            }
+        }
+        self.get_presentation(&row.id).await
     }
 
-    // Themes
-    pub async fn list_themes(&self) -> AppResult<Vec<Theme>> {
-        let themes = sqlx::query_as::<_, Theme>(
-            "SELECT id, name, display_name, css_content, is_default, center_content, user_id, created_at, updated_at FROM themes ORDER BY is_default DESC, name"
-        )
-        .fetch_all(&self.pool)
+    /// Copies a presentation's content, theme and settings into a new presentation titled
+    /// "Copy of <title>" unless `new_title` is given.
+    pub async fn duplicate_presentation(&self, id: &str, new_title: Option<String>) -> AppResult<Presentation> {
+        let source = self.get_presentation(id).await?;
+        let title = new_title.unwrap_or_else(|| format!("Copy of {}", source.title));
+
+        let copy = self.insert_presentation(title, Some(source.content), Some(source.theme)).await?;
+
+        self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET settings = ? WHERE id = ?")
+                .bind(sqlx::types::Json(&source.settings))
+                .bind(&copy.id)
+                .execute(pool)
+        })
         .await?;
-        Ok(themes)
+
+        self.events.publish(AppEvent::PresentationCreated { id: copy.id.clone() });
+        self.get_presentation(&copy.id).await
     }
 
-    pub async fn get_theme_by_name(&self, name: &str) -> AppResult<Theme> {
-        sqlx::query_as::<_, Theme>(
-            "SELECT id, name, display_name, css_content, is_default, center_content, user_id, created_at, updated_at FROM themes WHERE name = ?"
-        )
-        .bind(name)
-        .fetch_one(&self.pool)
+    pub async fn update_presentation(&self, id: &str, data: UpdatePresentation) -> AppResult<PresentationUpdate> {
+        self.edit_presentation(id, |_| Ok(data.clone())).await
+    }
+
+    /// `update_presentation`, applied only while `expected` holds for the stored row; a
+    /// Conflict otherwise. The check sees the same row the write replaces.
+    pub async fn update_presentation_if<P>(&self, id: &str, data: UpdatePresentation, expected: P) -> AppResult<PresentationUpdate>
+    where
+        P: Fn(&Presentation) -> bool,
+    {
+        self.edit_presentation(id, |existing| {
+            if !expected(existing) {
+                return Err(AppError::Conflict(format!("Presentation {} changed since it was read", id)));
+            }
+            Ok(data.clone())
+        })
         .await
-        .map_err(|_| AppError::NotFound("Theme not found".to_string()))
     }
 
-    pub async fn get_theme_by_id(&self, id: &str) -> AppResult<Theme> {
-        sqlx::query_as::<_, Theme>(
-            "SELECT id, name, display_name, css_content, is_default, center_content, user_id, created_at, updated_at FROM themes WHERE id = ?"
-        )
-        .bind(id)
-        .fetch_one(&self.pool)
+    // Slide-level edits, applied to the latest content
+    pub async fn replace_slide(&self, id: &str, index: usize, markdown: &str) -> AppResult<Presentation> {
+        self.edit_content(id, |content| slides::replace_slide(content, index, markdown)).await
+    }
+
+    /// Inserts a slide before `position`, or appends it when no position is given.
+    pub async fn insert_slide(&self, id: &str, position: Option<usize>, markdown: &str) -> AppResult<Presentation> {
+        self.edit_content(id, |content| {
+            let position = position.unwrap_or_else(|| slides::slide_count(content));
+            slides::insert_slide(content, position, markdown)
+        })
         .await
-        .map_err(|_| AppError::NotFound("Theme not found".to_string()))
     }
 
-    pub async fn create_theme(&self, data: CreateTheme) -> AppResult<Theme> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
-        let center_content = data.center_content.unwrap_or(true);
+    pub async fn delete_slide(&self, id: &str, index: usize) -> AppResult<Presentation> {
+        self.edit_content(id, |content| slides::delete_slide(content, index)).await
+    }
 
-        sqlx::query(
-            "INSERT INTO themes (id, name, display_name, css_content, is_default, center_content, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, 0, ?, 'local', ?, ?)"
-        )
-        .bind(&id)
-        .bind(&data.name)
-        .bind(&data.display_name)
-        .bind(&data.css_content)
-        .bind(center_content)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await?;
+    pub async fn reorder_slides(&self, id: &str, order: &[usize]) -> AppResult<Presentation> {
+        self.edit_content(id, |content| slides::reorder_slides(content, order)).await
+    }
 
-        Ok(Theme {
-            id,
-            name: data.name,
-            display_name: data.display_name,
-            css_content: data.css_content,
-            is_default: false,
-            center_content,
-            user_id: Some("local".to_string()),
-            created_at: now,
-            updated_at: now,
-        })
+    pub async fn fill_placeholders(&self, id: &str, values: &HashMap<String, serde_json::Value>) -> AppResult<Presentation> {
+        self.edit_content(id, |content| placeholders::fill_placeholders(content, values)).await
     }
 
-    pub async fn update_theme(&self, id: &str, data: UpdateTheme) -> AppResult<Theme> {
-        let existing = self.get_theme_by_id(id).await?;
+    async fn edit_content<F>(&self, id: &str, edit: F) -> AppResult<Presentation>
+    where
+        F: Fn(&str) -> AppResult<String>,
+    {
+        self.edit_presentation(id, |existing| {
+            Ok(UpdatePresentation {
+                title: None,
+                content: Some(edit(&existing.content)?),
+                theme: None,
+                settings: None,
+            })
+        })
+        .await
+        .map(|update| update.presentation)
+    }
 
-        if existing.is_default {
-            return Err(AppError::Forbidden("Cannot modify default themes".to_string()));
+    /// Read-modify-write of a presentation. `edit` builds the update from the current row;
+    /// if another write lands between the read and the write, the row is re-read and `edit`
+    /// runs again, so concurrent edits are never silently overwritten. An update that
+    /// wouldn't change anything writes nothing. Locked presentations can't be edited.
+    async fn edit_presentation<F>(&self, id: &str, edit: F) -> AppResult<PresentationUpdate>
+    where
+        F: Fn(&Presentation) -> AppResult<UpdatePresentation>,
+    {
+        for _ in 0..MAX_EDIT_ATTEMPTS {
+            let existing = self.get_presentation(id).await?;
+            ensure_unlocked(&existing)?;
+            let data = edit(&existing)?;
+            if is_unchanged(&existing, &data)? {
+                return Ok(PresentationUpdate { presentation: existing, changed: false });
+            }
+            if self.write_presentation(&existing, data).await? {
+                self.events.publish(AppEvent::PresentationUpdated { id: id.to_string() });
+                let presentation = self.get_presentation(id).await?;
+                return Ok(PresentationUpdate { presentation, changed: true });
+            }
         }
 
-        let now = Utc::now();
-        let display_name = data.display_name.unwrap_or(existing.display_name);
-        let css_content = data.css_content.unwrap_or(existing.css_content);
-        let center_content = data.center_content.unwrap_or(existing.center_content);
+        Err(AppError::Conflict(format!(
+            "Presentation {} is being modified concurrently, try again",
+            id
+        )))
+    }
 
-        sqlx::query(
-            "UPDATE themes SET display_name = ?, css_content = ?, center_content = ?, updated_at = ? WHERE id = ?"
+    /// Writes `data` over `existing`, snapshotting the previous state. Returns false (and
+    /// writes nothing) if the row changed or was locked since `existing` was read.
+    async fn write_presentation(&self, existing: &Presentation, data: UpdatePresentation) -> AppResult<bool> {
+        let title = data.title.unwrap_or_else(|| existing.title.clone());
+        let content = data.content.unwrap_or_else(|| existing.content.clone());
+        let theme = data.theme.unwrap_or_else(|| existing.theme.clone());
+        let settings = match data.settings {
+            Some(changes) => merge_settings(&existing.settings, changes)?,
+            None => existing.settings.clone(),
+        };
+        let health = self.assess_health(&content).await?;
+        let new = PresentationWrite { title, content, theme, settings, health };
+
+        let mut tx = self.write.begin().await?;
+        if !overwrite_presentation(&mut tx, existing, &new, self.max_versions, Utc::now()).await? {
+            // Dropping the transaction rolls back the version snapshot
+            return Ok(false);
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // Drafts
+    pub async fn get_draft(&self, id: &str) -> AppResult<PresentationDraft> {
+        let row: Option<(Option<String>, Option<DateTime<Utc>>)> = sqlx::query_as(
+            "SELECT draft_content, draft_saved_at FROM presentations WHERE id = ? AND deleted_at IS NULL"
         )
-        .bind(&display_name)
-        .bind(&css_content)
-        .bind(center_content)
-        .bind(now)
         .bind(id)
-        .execute(&self.pool)
+        .fetch_optional(self.read.pool())
         .await?;
 
-        Ok(Theme {
-            id: existing.id,
-            name: existing.name,
-            display_name,
-            css_content,
-            is_default: existing.is_default,
-            center_content,
-            user_id: existing.user_id,
-            created_at: existing.created_at,
-            updated_at: now,
-        })
+        match row {
+            Some((Some(content), Some(saved_at))) => Ok(PresentationDraft {
+                presentation_id: id.to_string(),
+                content,
+                saved_at,
+            }),
+            Some(_) => Err(AppError::NotFound(format!("Presentation {} has no draft", id))),
+            None => Err(AppError::NotFound(format!("Presentation {} not found", id))),
+        }
     }
 
-    pub async fn delete_theme(&self, id: &str) -> AppResult<()> {
-        let existing = self.get_theme_by_id(id).await?;
+    /// Replaces the presentation's draft. Only the draft columns are written, so autosaving
+    /// leaves `updated_at`, the history and workspace caches alone.
+    pub async fn save_draft(&self, id: &str, content: String) -> AppResult<PresentationDraft> {
+        let saved_at = Utc::now();
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET draft_content = ?, draft_saved_at = ? WHERE id = ? AND deleted_at IS NULL AND locked = 0")
+                .bind(&content)
+                .bind(saved_at)
+                .bind(id)
+                .execute(pool)
+        })
+        .await?;
 
-        if existing.is_default {
-            return Err(AppError::Forbidden("Cannot delete default themes".to_string()));
+        if result.rows_affected() == 0 {
+            ensure_unlocked(&self.get_presentation(id).await?)?;
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
         }
 
-        sqlx::query("DELETE FROM themes WHERE id = ? AND is_default = 0")
-            .bind(id)
-            .execute(&self.pool)
+        Ok(PresentationDraft { presentation_id: id.to_string(), content, saved_at })
+    }
+
+    /// Makes the draft the presentation's content, as a normal update would (so the previous
+    /// content is kept as a version), then clears it.
+    pub async fn commit_draft(&self, id: &str) -> AppResult<PresentationUpdate> {
+        let draft = self.get_draft(id).await?;
+        let update = self
+            .edit_presentation(id, |_| {
+                Ok(UpdatePresentation {
+                    title: None,
+                    content: Some(draft.content.clone()),
+                    theme: None,
+                    settings: None,
+                })
+            })
             .await?;
 
-        Ok(())
+        // A newer draft saved in the meantime is kept
+        self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET draft_content = NULL, draft_saved_at = NULL WHERE id = ? AND draft_saved_at = ?")
+                .bind(id)
+                .bind(draft.saved_at)
+                .execute(pool)
+        })
+        .await?;
+
+        Ok(PresentationUpdate {
+            presentation: self.get_presentation(id).await?,
+            changed: update.changed,
+        })
     }
 
-    // Layout Rules
-    pub async fn list_layout_rules(&self) -> AppResult<Vec<LayoutRule>> {
-        let rules = sqlx::query_as::<_, LayoutRule>(
-            "SELECT id, name, display_name, description, priority, enabled, is_default, user_id, conditions, transform, css_content, created_at, updated_at FROM layout_rules ORDER BY priority"
-        )
-        .fetch_all(&self.pool)
+    pub async fn discard_draft(&self, id: &str) -> AppResult<()> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET draft_content = NULL, draft_saved_at = NULL WHERE id = ? AND deleted_at IS NULL")
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
-        Ok(rules)
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+        Ok(())
     }
 
-    // AI Provider Configs
-    pub async fn list_ai_provider_configs(&self) -> AppResult<Vec<AiProviderConfig>> {
-        let configs = sqlx::query_as::<_, AiProviderConfig>(
-            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE user_id = 'local' ORDER BY provider_name"
-        )
-        .fetch_all(&self.pool)
+    /// Archives or unarchives a presentation. Leaves `updated_at` alone, since the deck
+    /// itself doesn't change.
+    pub async fn set_archived(&self, id: &str, archived: bool) -> AppResult<Presentation> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET archived = ? WHERE id = ? AND deleted_at IS NULL")
+                .bind(archived)
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
-        Ok(configs)
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PresentationUpdated { id: id.to_string() });
+        self.get_presentation(id).await
     }
 
-    pub async fn get_ai_provider_config(&self, provider_name: &str) -> AppResult<Option<AiProviderConfig>> {
-        let config = sqlx::query_as::<_, AiProviderConfig>(
-            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE user_id = 'local' AND provider_name = ?"
-        )
-        .bind(provider_name)
-        .fetch_optional(&self.pool)
+    /// Stars or unstars a presentation. Leaves `updated_at` alone, like archiving.
+    pub async fn set_favorite(&self, id: &str, favorite: bool) -> AppResult<Presentation> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET is_favorite = ? WHERE id = ? AND deleted_at IS NULL")
+                .bind(favorite)
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
-        Ok(config)
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PresentationUpdated { id: id.to_string() });
+        self.get_presentation(id).await
     }
 
-    pub async fn get_ai_provider_config_by_id(&self, id: &str) -> AppResult<Option<AiProviderConfig>> {
-        let config = sqlx::query_as::<_, AiProviderConfig>(
-            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE id = ? AND user_id = 'local'"
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
+    /// Locks or unlocks a presentation. While locked it can't be edited or deleted, through
+    /// the API or MCP. Leaves `updated_at` alone, like archiving.
+    pub async fn set_locked(&self, id: &str, locked: bool) -> AppResult<Presentation> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET locked = ? WHERE id = ? AND deleted_at IS NULL")
+                .bind(locked)
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
-        Ok(config)
-    }
 
-    pub async fn update_ai_provider_config(
-        &self,
-        id: &str,
-        model: Option<String>,
-        base_url: Option<String>,
-        api_key_encrypted: Option<String>,
-    ) -> AppResult<AiProviderConfig> {
-        let existing = self.get_ai_provider_config_by_id(id).await?
-            .ok_or_else(|| AppError::NotFound("AI config not found".to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
 
-        let now = Utc::now();
-        let new_model = model.or(existing.model);
-        let new_base_url = base_url.or(existing.base_url);
-        let new_api_key = api_key_encrypted.unwrap_or(existing.api_key_encrypted);
+        self.events.publish(AppEvent::PresentationUpdated { id: id.to_string() });
+        self.get_presentation(id).await
+    }
 
-        sqlx::query(
-            "UPDATE ai_provider_configs SET api_key_encrypted = ?, model = ?, base_url = ?, updated_at = ? WHERE id = ?"
-        )
-        .bind(&new_api_key)
-        .bind(&new_model)
-        .bind(&new_base_url)
-        .bind(now)
-        .bind(id)
-        .execute(&self.pool)
+    /// Records that a presentation was opened. Leaves `updated_at` alone, like archiving.
+    pub async fn touch_presentation(&self, id: &str) -> AppResult<Presentation> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET last_opened_at = ? WHERE id = ? AND deleted_at IS NULL")
+                .bind(Utc::now())
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
 
-        Ok(AiProviderConfig {
-            id: existing.id,
-            provider_name: existing.provider_name,
-            api_key_encrypted: new_api_key,
-            model: new_model,
-            base_url: new_base_url,
-            user_id: existing.user_id,
-            created_at: existing.created_at,
-            updated_at: now,
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PresentationUpdated { id: id.to_string() });
+        self.get_presentation(id).await
+    }
+
+    /// Moves a presentation to the trash. It can be brought back with `restore_presentation`.
+    /// Locked presentations can't be deleted.
+    pub async fn delete_presentation(&self, id: &str) -> AppResult<()> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL AND locked = 0")
+                .bind(Utc::now())
+                .bind(id)
+                .execute(pool)
         })
+        .await?;
+
+        if result.rows_affected() == 0 {
+            // Either missing or locked; tell the two apart
+            ensure_unlocked(&self.get_presentation(id).await?)?;
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PresentationDeleted { id: id.to_string(), permanent: false });
+        Ok(())
     }
 
-    pub async fn upsert_ai_provider_config(&self, data: CreateAiProviderConfig, api_key_encrypted: String) -> AppResult<AiProviderConfig> {
+    /// Applies one action to many presentations in a single transaction, reporting each id's
+    /// outcome. Deletes skip ids that don't exist (or are already in the trash) and commit the
+    /// rest; archive and set_theme are all-or-nothing, so a missing id rolls back the batch.
+    pub async fn bulk_update_presentations(&self, request: BulkPresentations) -> AppResult<BulkResult> {
+        let BulkPresentations { action, ids, theme } = request;
+        if ids.is_empty() {
+            return Err(AppError::BadRequest("ids must not be empty".to_string()));
+        }
+        if ids.len() > MAX_BULK_IDS {
+            return Err(AppError::BadRequest(format!(
+                "At most {} ids can be changed in one request",
+                MAX_BULK_IDS
+            )));
+        }
+
+        let theme = match (action, theme) {
+            (BulkAction::SetTheme, Some(theme)) if !theme.trim().is_empty() => {
+                match self.get_theme_by_name(&theme).await {
+                    Ok(_) => theme,
+                    Err(AppError::NotFound(_)) => {
+                        return Err(AppError::BadRequest(format!("Theme '{}' not found", theme)))
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            (BulkAction::SetTheme, _) => {
+                return Err(AppError::BadRequest("theme is required for set_theme".to_string()))
+            }
+            _ => String::new(),
+        };
+
         let now = Utc::now();
+        let mut seen = HashSet::new();
+        let mut results = Vec::new();
+        let mut tx = self.write.begin().await?;
 
-        // Check if exists
-        let existing = self.get_ai_provider_config(&data.provider_name).await?;
+        for id in ids {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
 
-        if let Some(existing) = existing {
-            // Update
-            sqlx::query(
-                "UPDATE ai_provider_configs SET api_key_encrypted = ?, model = ?, base_url = ?, updated_at = ? WHERE id = ?"
-            )
-            .bind(&api_key_encrypted)
-            .bind(&data.model)
-            .bind(&data.base_url)
-            .bind(now)
-            .bind(&existing.id)
-            .execute(&self.pool)
-            .await?;
+            let result = match action {
+                BulkAction::Delete => {
+                    sqlx::query("UPDATE presentations SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL AND locked = 0")
+                        .bind(now)
+                        .bind(&id)
+                        .execute(&mut *tx)
+                        .await?
+                }
+                BulkAction::Archive => {
+                    sqlx::query("UPDATE presentations SET archived = 1 WHERE id = ? AND deleted_at IS NULL")
+                        .bind(&id)
+                        .execute(&mut *tx)
+                        .await?
+                }
+                BulkAction::SetTheme => {
+                    // Snapshot the previous theme, as a single update would
+                    sqlx::query(
+                        "INSERT INTO presentation_versions (id, presentation_id, title, content, theme, created_at, created_by) \
+                         SELECT ?, id, title, content, theme, ?, 'local' FROM presentations WHERE id = ? AND deleted_at IS NULL AND locked = 0 AND theme != ?"
+                    )
+                    .bind(Uuid::new_v4().to_string())
+                    .bind(now)
+                    .bind(&id)
+                    .bind(&theme)
+                    .execute(&mut *tx)
+                    .await?;
+                    prune_versions(&mut tx, &id, self.max_versions).await?;
 
-            Ok(AiProviderConfig {
-                id: existing.id,
-                provider_name: data.provider_name,
-                api_key_encrypted,
-                model: data.model,
-                base_url: data.base_url,
-                user_id: "local".to_string(),
-                created_at: existing.created_at,
-                updated_at: now,
-            })
+                    sqlx::query("UPDATE presentations SET theme = ?, updated_at = ? WHERE id = ? AND deleted_at IS NULL AND locked = 0")
+                        .bind(&theme)
+                        .bind(now)
+                        .bind(&id)
+                        .execute(&mut *tx)
+                        .await?
+                }
+            };
+
+            let success = result.rows_affected() > 0;
+            let error = if success {
+                None
+            } else {
+                let locked: Option<bool> =
+                    sqlx::query_scalar("SELECT locked FROM presentations WHERE id = ? AND deleted_at IS NULL")
+                        .bind(&id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                Some(match locked {
+                    Some(true) => "Presentation is locked".to_string(),
+                    _ => "Presentation not found".to_string(),
+                })
+            };
+            results.push(BulkItemResult { id, success, error });
+        }
+
+        let committed = action == BulkAction::Delete || results.iter().all(|r| r.success);
+        if committed {
+            tx.commit().await?;
+            for result in results.iter().filter(|r| r.success) {
+                let id = result.id.clone();
+                self.events.publish(match action {
+                    BulkAction::Delete => AppEvent::PresentationDeleted { id, permanent: false },
+                    BulkAction::Archive | BulkAction::SetTheme => AppEvent::PresentationUpdated { id },
+                });
+            }
         } else {
-            // Insert
-            let id = Uuid::new_v4().to_string();
-            sqlx::query(
-                "INSERT INTO ai_provider_configs (id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 'local', ?, ?)"
-            )
-            .bind(&id)
-            .bind(&data.provider_name)
-            .bind(&api_key_encrypted)
-            .bind(&data.model)
-            .bind(&data.base_url)
-            .bind(now)
-            .bind(now)
-            .execute(&self.pool)
-            .await?;
+            // Dropping the transaction rolls back the rows already changed
+            for result in results.iter_mut().filter(|r| r.success) {
+                result.success = false;
+                result.error = Some("Not applied: the batch was rolled back".to_string());
+            }
+        }
 
-            Ok(AiProviderConfig {
-                id,
-                provider_name: data.provider_name,
-                api_key_encrypted,
-                model: data.model,
-                base_url: data.base_url,
-                user_id: "local".to_string(),
-                created_at: now,
-                updated_at: now,
-            })
+        let succeeded = results.iter().filter(|r| r.success).count();
+        Ok(BulkResult {
+            committed,
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        })
+    }
+
+    /// Finds and replaces in the given presentations, or every unlocked one outside the trash.
+    /// Naming a locked presentation is Forbidden. Unless it's a dry run, the changed decks are
+    /// written in one transaction, each with a version snapshot, so either all of them change
+    /// or none do.
+    pub async fn replace_in_presentations(&self, request: ReplaceRequest) -> AppResult<ReplaceResult> {
+        let replacer = Replacer::new(&request)?;
+        let ids: Vec<String> = match request.ids {
+            Some(ids) => {
+                if ids.is_empty() {
+                    return Err(AppError::BadRequest("ids must not be empty".to_string()));
+                }
+                if ids.len() > MAX_BULK_IDS {
+                    return Err(AppError::BadRequest(format!(
+                        "At most {} presentations can be changed in one request",
+                        MAX_BULK_IDS
+                    )));
+                }
+                let mut seen = HashSet::new();
+                ids.into_iter().filter(|id| seen.insert(id.clone())).collect()
+            }
+            None => {
+                sqlx::query_scalar("SELECT id FROM presentations WHERE deleted_at IS NULL AND locked = 0 ORDER BY created_at")
+                    .fetch_all(self.read.pool())
+                    .await?
+            }
+        };
+
+        let mut presentations = Vec::new();
+        let mut changed = Vec::new();
+        for id in ids {
+            let existing = self.get_presentation(&id).await?;
+            ensure_unlocked(&existing)?;
+            let (content, slides) = replacer.apply(&existing.content);
+            if slides.is_empty() {
+                continue;
+            }
+            presentations.push(PresentationMatches {
+                id: existing.id.clone(),
+                title: existing.title.clone(),
+                matches: slides.iter().map(|s| s.matches).sum(),
+                slides,
+            });
+            changed.push((existing, content));
+        }
+        let total_matches = presentations.iter().map(|p| p.matches).sum();
+
+        if !request.dry_run && !changed.is_empty() {
+            let mut health = Vec::with_capacity(changed.len());
+            for (_, content) in &changed {
+                health.push(self.assess_health(content).await?);
+            }
+
+            let now = Utc::now();
+            let mut tx = self.write.begin().await?;
+            for ((existing, content), health) in changed.iter().zip(health) {
+                let new = PresentationWrite {
+                    title: existing.title.clone(),
+                    content: content.clone(),
+                    theme: existing.theme.clone(),
+                    settings: existing.settings.clone(),
+                    health,
+                };
+                // Edited or locked since it was read; dropping the transaction rolls back the batch
+                if !overwrite_presentation(&mut tx, existing, &new, self.max_versions, now).await? {
+                    return Err(AppError::Conflict(format!(
+                        "Presentation {} was modified during the replace, try again",
+                        existing.id
+                    )));
+                }
+            }
+            tx.commit().await?;
+
+            for (existing, _) in changed {
+                self.events.publish(AppEvent::PresentationUpdated { id: existing.id });
+            }
         }
+
+        Ok(ReplaceResult {
+            dry_run: request.dry_run,
+            total_matches,
+            presentations,
+        })
     }
 
-    pub async fn delete_ai_provider_config(&self, id: &str) -> AppResult<()> {
-        sqlx::query("DELETE FROM ai_provider_configs WHERE id = ? AND user_id = 'local'")
+    pub async fn delete_presentation_permanently(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.write.begin().await?;
+
+        sqlx::query("DELETE FROM presentation_versions WHERE presentation_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM presentation_tags WHERE presentation_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM watchlist_sources WHERE presentation_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM watchlist_items WHERE presentation_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM presentation_snapshots WHERE presentation_id = ?")
             .bind(id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
+
+        let result = sqlx::query("DELETE FROM presentations WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} not found", id)));
+        }
+
+        tx.commit().await?;
+        self.events.publish(AppEvent::PresentationDeleted { id: id.to_string(), permanent: true });
         Ok(())
     }
 
-    // Media
-    pub async fn list_media(&self) -> AppResult<Vec<Media>> {
-        let media = sqlx::query_as::<_, Media>(
-            "SELECT id, filename, original_name, mime_type, size, url, user_id, created_at FROM media WHERE user_id = 'local' ORDER BY created_at DESC"
-        )
-        .fetch_all(&self.pool)
+    pub async fn list_deleted_presentations(&self) -> AppResult<Vec<Presentation>> {
+        let presentations = sqlx::query_as::<_, Presentation>(&format!(
+            "SELECT {} FROM presentations p WHERE p.deleted_at IS NOT NULL ORDER BY p.deleted_at DESC",
+            PRESENTATION_COLUMNS
+        ))
+        .fetch_all(self.read.pool())
         .await?;
-        Ok(media)
+        Ok(presentations)
     }
 
-    pub async fn get_media(&self, id: &str) -> AppResult<Option<Media>> {
-        let media = sqlx::query_as::<_, Media>(
-            "SELECT id, filename, original_name, mime_type, size, url, user_id, created_at FROM media WHERE id = ? AND user_id = 'local'"
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
+    pub async fn restore_presentation(&self, id: &str) -> AppResult<Presentation> {
+        let result = self.write.run(|pool| {
+            sqlx::query("UPDATE presentations SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL")
+                .bind(id)
+                .execute(pool)
+        })
         .await?;
-        Ok(media)
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Deleted presentation {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PresentationRestored { id: id.to_string() });
+        self.get_presentation(id).await
     }
 
-    pub async fn create_media(&self, filename: String, original_name: String, mime_type: String, size: i64, url: String) -> AppResult<Media> {
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now();
+    // Presentation versions
+    pub async fn list_versions(&self, presentation_id: &str) -> AppResult<Vec<PresentationVersion>> {
+        // Ensure the presentation exists (and isn't deleted)
+        self.get_presentation(presentation_id).await?;
 
-        sqlx::query(
-            "INSERT INTO media (id, filename, original_name, mime_type, size, url, user_id, created_at) VALUES (?, ?, ?, ?, ?, ?, 'local', ?)"
-        )
-        .bind(&id)
-        .bind(&filename)
-        .bind(&original_name)
-        .bind(&mime_type)
-        .bind(size)
-        .bind(&url)
-        .bind(now)
-        .execute(&self.pool)
+        let versions = sqlx::query_as::<_, PresentationVersion>(
+            "SELECT id, presentation_id, title, content, theme, created_at, created_by FROM presentation_versions WHERE presentation_id = ? ORDER BY created_at DESC, rowid DESC"
+        )
+        .bind(presentation_id)
+        .fetch_all(self.read.pool())
         .await?;
+        Ok(versions)
+    }
 
-        Ok(Media {
-            id,
-            filename,
-            original_name,
-            mime_type,
-            size,
-            url,
-            user_id: "local".to_string(),
-            created_at: now,
+    pub async fn get_version(&self, presentation_id: &str, version_id: &str) -> AppResult<PresentationVersion> {
+        sqlx::query_as::<_, PresentationVersion>(
+            "SELECT id, presentation_id, title, content, theme, created_at, created_by FROM presentation_versions WHERE id = ? AND presentation_id = ?"
+        )
+        .bind(version_id)
+        .bind(presentation_id)
+        .fetch_optional(self.read.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Version {} not found", version_id)))
+    }
+
+    /// Restores a presentation's title, content and theme from a version. The current state is
+    /// snapshotted first, so a restore can itself be undone.
+    pub async fn restore_version(&self, presentation_id: &str, version_id: &str) -> AppResult<Presentation> {
+        let version = self.get_version(presentation_id, version_id).await?;
+
+        self.update_presentation(presentation_id, UpdatePresentation {
+            // Versions recorded before titles were tracked leave the title alone
+            title: version.title,
+            content: Some(version.content),
+            theme: Some(version.theme),
+            settings: None,
         })
+        .await
+        .map(|update| update.presentation)
     }
 
-    pub async fn delete_media(&self, id: &str) -> AppResult<Option<Media>> {
-        let media = self.get_media(id).await?;
-        if media.is_some() {
-            sqlx::query("DELETE FROM media WHERE id = ? AND user_id = 'local'")
-                .bind(id)
-                .execute(&self.pool)
-                .await?;
+    // Automatic snapshots
+    /// Snapshots every presentation outside the trash whose content changed since its last
+    /// snapshot, keeping the newest `MAX_SNAPSHOTS_PER_PRESENTATION` of each. Returns how
+    /// many snapshots were taken.
+    pub async fn snapshot_changed_presentations(&self) -> AppResult<usize> {
+        // Compares the hash stored on write with the latest snapshot's, so unchanged decks
+        // are never read
+        let changed: Vec<(String, String, String, String, String)> = sqlx::query_as(
+            "SELECT p.id, p.title, p.content, p.theme, p.content_hash FROM presentations p \
+             WHERE p.deleted_at IS NULL AND p.content_hash IS NOT ( \
+                 SELECT content_hash FROM presentation_snapshots WHERE presentation_id = p.id ORDER BY created_at DESC, rowid DESC LIMIT 1 \
+             )"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        if changed.is_empty() {
+            return Ok(0);
         }
-        Ok(media)
-    }
 
-    // Layout Rules
-    pub async fn create_layout_rule(
-        &self,
-        name: String,
-        display_name: String,
-        description: Option<String>,
-        priority: i32,
-        conditions: String,
-        transform: String,
-        css_content: String,
-    ) -> AppResult<LayoutRule> {
-        let id = Uuid::new_v4().to_string();
         let now = Utc::now();
+        let mut tx = self.write.begin().await?;
+        for (id, title, content, theme, hash) in &changed {
+            sqlx::query(
+                "INSERT INTO presentation_snapshots (id, presentation_id, title, content, theme, content_hash, created_at) VALUES (?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(id)
+            .bind(title)
+            .bind(content)
+            .bind(theme)
+            .bind(hash)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "DELETE FROM presentation_snapshots WHERE presentation_id = ? AND id NOT IN (SELECT id FROM presentation_snapshots WHERE presentation_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ?)"
+            )
+            .bind(id)
+            .bind(id)
+            .bind(MAX_SNAPSHOTS_PER_PRESENTATION)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(changed.len())
+    }
+
+    pub async fn list_snapshots(&self, presentation_id: &str) -> AppResult<Vec<PresentationSnapshot>> {
+        self.get_presentation(presentation_id).await?;
+
+        let snapshots = sqlx::query_as::<_, PresentationSnapshot>(
+            "SELECT id, presentation_id, title, content, theme, content_hash, created_at FROM presentation_snapshots WHERE presentation_id = ? ORDER BY created_at DESC, rowid DESC"
+        )
+        .bind(presentation_id)
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(snapshots)
+    }
+
+    /// Restores a presentation's title, content and theme from a snapshot. Like restoring a
+    /// version, the current state is kept as a version first.
+    pub async fn restore_snapshot(&self, presentation_id: &str, snapshot_id: &str) -> AppResult<Presentation> {
+        let snapshot = sqlx::query_as::<_, PresentationSnapshot>(
+            "SELECT id, presentation_id, title, content, theme, content_hash, created_at FROM presentation_snapshots WHERE id = ? AND presentation_id = ?"
+        )
+        .bind(snapshot_id)
+        .bind(presentation_id)
+        .fetch_optional(self.read.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Snapshot {} not found", snapshot_id)))?;
+
+        self.update_presentation(presentation_id, UpdatePresentation {
+            title: Some(snapshot.title),
+            content: Some(snapshot.content),
+            theme: Some(snapshot.theme),
+            settings: None,
+        })
+        .await
+        .map(|update| update.presentation)
+    }
+
+    // Tags
+    pub async fn list_tags(&self) -> AppResult<Vec<Tag>> {
+        let tags = sqlx::query_as::<_, Tag>("SELECT id, name, created_at FROM tags ORDER BY name")
+            .fetch_all(self.read.pool())
+            .await?;
+        Ok(tags)
+    }
+
+    /// Tags a presentation, creating the tag if it doesn't exist yet. Tag names are
+    /// trimmed and lowercased.
+    pub async fn add_tag_to_presentation(&self, presentation_id: &str, tag: &str) -> AppResult<Presentation> {
+        let name = normalize_tag(tag);
+        if name.is_empty() {
+            return Err(AppError::BadRequest("Tag must not be empty".to_string()));
+        }
+        self.get_presentation(presentation_id).await?;
+
+        let mut tx = self.write.begin().await?;
+
+        sqlx::query("INSERT OR IGNORE INTO tags (id, name, created_at) VALUES (?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(&name)
+            .bind(Utc::now())
+            .execute(&mut *tx)
+            .await?;
 
         sqlx::query(
-            "INSERT INTO layout_rules (id, name, display_name, description, priority, enabled, is_default, user_id, conditions, transform, css_content, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 1, 0, 'local', ?, ?, ?, ?, ?)"
+            "INSERT OR IGNORE INTO presentation_tags (presentation_id, tag_id) SELECT ?, id FROM tags WHERE name = ?"
         )
-        .bind(&id)
+        .bind(presentation_id)
         .bind(&name)
-        .bind(&display_name)
-        .bind(&description)
-        .bind(priority)
-        .bind(&conditions)
-        .bind(&transform)
-        .bind(&css_content)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        Ok(LayoutRule {
+        tx.commit().await?;
+
+        self.events.publish(AppEvent::PresentationUpdated { id: presentation_id.to_string() });
+        self.get_presentation(presentation_id).await
+    }
+
+    pub async fn remove_tag_from_presentation(&self, presentation_id: &str, tag: &str) -> AppResult<Presentation> {
+        let name = normalize_tag(tag);
+        self.get_presentation(presentation_id).await?;
+
+        let result = self.write.run(|pool| {
+            sqlx::query(
+                "DELETE FROM presentation_tags WHERE presentation_id = ? AND tag_id = (SELECT id FROM tags WHERE name = ?)"
+            )
+            .bind(presentation_id)
+            .bind(&name)
+            .execute(pool)
+        })
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Presentation {} is not tagged '{}'", presentation_id, name)));
+        }
+
+        self.events.publish(AppEvent::PresentationUpdated { id: presentation_id.to_string() });
+        self.get_presentation(presentation_id).await
+    }
+
+    // Settings, stored as JSON values by key
+    async fn get_setting<T: DeserializeOwned + Default>(&self, key: &str) -> AppResult<T> {
+        let row: Option<(String,)> = sqlx::query_as("SELECT value FROM settings WHERE key = ?")
+            .bind(key)
+            .fetch_optional(self.read.pool())
+            .await?;
+
+        match row {
+            Some((value,)) => serde_json::from_str(&value)
+                .map_err(|e| AppError::Internal(format!("Invalid value for setting {}: {}", key, e))),
+            None => Ok(T::default()),
+        }
+    }
+
+    async fn set_setting<T: Serialize>(&self, key: &str, value: &T) -> AppResult<()> {
+        let value = serde_json::to_string(value)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize setting {}: {}", key, e)))?;
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?) ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at"
+            )
+            .bind(key)
+            .bind(&value)
+            .bind(Utc::now())
+            .execute(pool)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_app_settings(&self) -> AppResult<AppSettings> {
+        self.get_setting(APP_SETTING).await
+    }
+
+    /// Stores new app settings. The default theme must name an existing theme.
+    pub async fn set_app_settings(&self, settings: &AppSettings) -> AppResult<()> {
+        match self.get_theme_by_name(&settings.default_theme).await {
+            Ok(_) => {}
+            Err(AppError::NotFound(_)) => {
+                return Err(AppError::BadRequest(format!("Theme '{}' not found", settings.default_theme)))
+            }
+            Err(e) => return Err(e),
+        }
+
+        self.set_setting(APP_SETTING, settings).await?;
+        self.events.publish(AppEvent::SettingsChanged { key: APP_SETTING.to_string() });
+        Ok(())
+    }
+
+    /// The configured theme for new presentations, or "default" if that theme has since been
+    /// deleted or renamed.
+    async fn default_theme(&self) -> AppResult<String> {
+        let theme = self.get_app_settings().await?.default_theme;
+        match self.get_theme_by_name(&theme).await {
+            Ok(_) => Ok(theme),
+            Err(AppError::NotFound(_)) => Ok(AppSettings::default().default_theme),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn get_mcp_tool_settings(&self) -> AppResult<McpToolSettings> {
+        self.get_setting(MCP_TOOLS_SETTING).await
+    }
+
+    pub async fn set_mcp_tool_settings(&self, settings: &McpToolSettings) -> AppResult<()> {
+        self.set_setting(MCP_TOOLS_SETTING, settings).await?;
+        self.events.publish(AppEvent::SettingsChanged { key: MCP_TOOLS_SETTING.to_string() });
+        Ok(())
+    }
+
+    pub async fn get_health_weights(&self) -> AppResult<HealthWeights> {
+        self.get_setting(HEALTH_WEIGHTS_SETTING).await
+    }
+
+    /// Stores new weights and rescores every presentation with them.
+    pub async fn set_health_weights(&self, weights: &HealthWeights) -> AppResult<()> {
+        self.set_setting(HEALTH_WEIGHTS_SETTING, weights).await?;
+        self.refresh_health().await?;
+        self.events.publish(AppEvent::SettingsChanged { key: HEALTH_WEIGHTS_SETTING.to_string() });
+        Ok(())
+    }
+
+    // Health
+    /// The cached health breakdown of a presentation.
+    pub async fn get_presentation_health(&self, id: &str) -> AppResult<DeckHealth> {
+        let (content, health_json): (String, Option<String>) =
+            sqlx::query_as("SELECT content, health_json FROM presentations WHERE id = ?")
+                .bind(id)
+                .fetch_optional(self.read.pool())
+                .await?
+                .ok_or_else(|| AppError::NotFound(format!("Presentation {} not found", id)))?;
+
+        if let Some(health) = health_json.and_then(|json| serde_json::from_str(&json).ok()) {
+            return Ok(health);
+        }
+
+        let media_sizes = self.media_sizes(&content).await?;
+        let weights = self.get_health_weights().await?;
+        Ok(health::assess(&content, &media_sizes, &weights))
+    }
+
+    /// Rescores all presentations, e.g. after the weights changed, and periodically from the
+    /// maintenance task as media is replaced or deleted. Rows written concurrently keep the
+    /// score their own write computed.
+    pub async fn refresh_health(&self) -> AppResult<u64> {
+        // Every deck is scored, so load every size once
+        let media_sizes: HashMap<String, i64> = sqlx::query_as::<_, (String, i64)>("SELECT filename, size FROM media")
+            .fetch_all(self.read.pool())
+            .await?
+            .into_iter()
+            .collect();
+        let weights = self.get_health_weights().await?;
+        let rows: Vec<(String, String, DateTime<Utc>)> =
+            sqlx::query_as("SELECT id, content, updated_at FROM presentations")
+                .fetch_all(self.read.pool())
+                .await?;
+
+        let mut updated = 0;
+        for (id, content, updated_at) in rows {
+            let (score, json) = health_columns(health::assess(&content, &media_sizes, &weights))?;
+            updated += self.write.run(|pool| {
+                sqlx::query("UPDATE presentations SET health_score = ?, health_json = ? WHERE id = ? AND updated_at = ?")
+                    .bind(score)
+                    .bind(&json)
+                    .bind(&id)
+                    .bind(updated_at)
+                    .execute(pool)
+            })
+            .await?
+            .rows_affected();
+        }
+
+        Ok(updated)
+    }
+
+    async fn assess_health(&self, content: &str) -> AppResult<(i64, String)> {
+        let media_sizes = self.media_sizes(content).await?;
+        let weights = self.get_health_weights().await?;
+        health_columns(health::assess(content, &media_sizes, &weights))
+    }
+
+    /// Sizes in bytes of the uploads `content` references, by filename.
+    pub async fn media_sizes(&self, content: &str) -> AppResult<HashMap<String, i64>> {
+        let filenames: HashSet<&str> = health::upload_references(content).into_iter().collect();
+        if filenames.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let sql = format!("SELECT filename, size FROM media WHERE filename IN ({})", vec!["?"; filenames.len()].join(", "));
+        let mut query = sqlx::query_as::<_, (String, i64)>(&sql);
+        for filename in filenames {
+            query = query.bind(filename);
+        }
+        Ok(query.fetch_all(self.read.pool()).await?.into_iter().collect())
+    }
+
+    // Themes
+    pub async fn list_themes(&self) -> AppResult<Vec<Theme>> {
+        let themes = sqlx::query_as::<_, Theme>(
+            "SELECT t.id, t.name, t.display_name, t.css_content, t.is_default, t.center_content, t.fonts, t.user_id, t.created_at, t.updated_at, \
+             COALESCE(u.usage_count, 0) AS usage_count \
+             FROM themes t LEFT JOIN (SELECT theme, COUNT(*) AS usage_count FROM presentations GROUP BY theme) u ON u.theme = t.name \
+             ORDER BY t.is_default DESC, t.name"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(themes)
+    }
+
+    pub async fn get_theme_by_name(&self, name: &str) -> AppResult<Theme> {
+        sqlx::query_as::<_, Theme>(
+            "SELECT id, name, display_name, css_content, is_default, center_content, fonts, user_id, created_at, updated_at FROM themes WHERE name = ?"
+        )
+        .bind(name)
+        .fetch_one(self.read.pool())
+        .await
+        .map_err(|_| AppError::NotFound("Theme not found".to_string()))
+    }
+
+    pub async fn get_theme_by_id(&self, id: &str) -> AppResult<Theme> {
+        sqlx::query_as::<_, Theme>(
+            "SELECT id, name, display_name, css_content, is_default, center_content, fonts, user_id, created_at, updated_at FROM themes WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_one(self.read.pool())
+        .await
+        .map_err(|_| AppError::NotFound("Theme not found".to_string()))
+    }
+
+    /// A theme by id, or failing that by name.
+    pub async fn find_theme(&self, id_or_name: &str) -> AppResult<Theme> {
+        match self.get_theme_by_id(id_or_name).await {
+            Ok(theme) => Ok(theme),
+            Err(_) => self.get_theme_by_name(id_or_name).await,
+        }
+    }
+
+    pub async fn theme_usage(&self, id: &str) -> AppResult<ThemeUsage> {
+        let theme = self.get_theme_by_id(id).await?;
+        let presentations = sqlx::query_as::<_, ThemeUser>(
+            "SELECT id, title, updated_at, deleted_at, locked FROM presentations WHERE theme = ? ORDER BY updated_at DESC"
+        )
+        .bind(&theme.name)
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(ThemeUsage { count: presentations.len(), presentations })
+    }
+
+    /// Creates a custom theme. Names must be kebab-case and the CSS must pass
+    /// `theme_css::validate`. A taken name is a Conflict, or with `NameConflict::Rename` gets
+    /// a numeric suffix, which the CSS selectors follow.
+    pub async fn create_theme(&self, data: CreateTheme, on_conflict: NameConflict) -> AppResult<Theme> {
+        let mut validation = ValidationBuilder::new();
+        validation.check(is_kebab_case(&data.name), "name", "must be kebab-case, e.g. \"my-theme\"");
+        validation.check(!data.display_name.trim().is_empty(), "displayName", "must not be empty");
+        check_theme_css(&mut validation, &data.css_content, &data.name);
+        check_theme_fonts(&mut validation, &data.fonts);
+        validation.finish()?;
+
+        let name = data.name.clone();
+        self.insert_theme(data, &name, on_conflict).await
+    }
+
+    /// Copies a theme (built-in or not) as a new custom theme, pointing its
+    /// `[data-theme="..."]` selectors at the copy's name.
+    pub async fn duplicate_theme(&self, id: &str, data: DuplicateTheme) -> AppResult<Theme> {
+        let source = self.get_theme_by_id(id).await?;
+        let base = data.name.unwrap_or_else(|| format!("{}-copy", source.name));
+        let display_name = data.display_name.unwrap_or_else(|| format!("{} Copy", source.display_name));
+
+        let mut validation = ValidationBuilder::new();
+        validation.check(is_kebab_case(&base), "name", "must be kebab-case, e.g. \"my-theme\"");
+        validation.check(!display_name.trim().is_empty(), "displayName", "must not be empty");
+        validation.finish()?;
+
+        let copy = CreateTheme {
+            name: base,
+            display_name,
+            css_content: source.css_content,
+            center_content: Some(source.center_content),
+            fonts: source.fonts,
+        };
+        self.insert_theme(copy, &source.name, data.on_conflict).await
+    }
+
+    pub async fn export_theme(&self, id: &str) -> AppResult<ThemeExport> {
+        let theme = self.get_theme_by_id(id).await?;
+        let mut export = ThemeExport {
+            format_version: THEME_FORMAT_VERSION,
+            name: theme.name,
+            display_name: theme.display_name,
+            css_content: theme.css_content,
+            center_content: theme.center_content,
+            fonts: theme.fonts,
+            checksum: String::new(),
+        };
+        export.checksum = theme_checksum(&export);
+        Ok(export)
+    }
+
+    /// Stores an exported theme. A name in use is suffixed (with the CSS selectors following
+    /// it) or, with `Overwrite`, replaces that custom theme in place.
+    pub async fn import_theme(&self, data: ThemeExport, on_conflict: ImportThemeConflict) -> AppResult<Theme> {
+        if data.format_version > THEME_FORMAT_VERSION {
+            return Err(AppError::BadRequest(format!(
+                "Theme file format {} is newer than this version supports ({})",
+                data.format_version, THEME_FORMAT_VERSION
+            )));
+        }
+        if data.checksum != theme_checksum(&data) {
+            return Err(AppError::BadRequest("Theme file checksum does not match; the file was modified".to_string()));
+        }
+
+        if on_conflict == ImportThemeConflict::Overwrite {
+            if let Ok(existing) = self.get_theme_by_name(&data.name).await {
+                if existing.is_default {
+                    return Err(AppError::Forbidden(format!("Theme '{}' is built in and cannot be modified", existing.name)));
+                }
+                return self
+                    .update_theme(&existing.id, UpdateTheme {
+                        display_name: Some(data.display_name),
+                        css_content: Some(data.css_content),
+                        center_content: Some(data.center_content),
+                        fonts: Some(data.fonts),
+                    })
+                    .await;
+            }
+        }
+
+        let mut validation = ValidationBuilder::new();
+        validation.check(is_kebab_case(&data.name), "name", "must be kebab-case, e.g. \"my-theme\"");
+        validation.check(!data.display_name.trim().is_empty(), "displayName", "must not be empty");
+        check_theme_css(&mut validation, &data.css_content, &data.name);
+        check_theme_fonts(&mut validation, &data.fonts);
+        validation.finish()?;
+
+        let name = data.name.clone();
+        let theme = CreateTheme {
+            name: data.name,
+            display_name: data.display_name,
+            css_content: data.css_content,
+            center_content: Some(data.center_content),
+            fonts: data.fonts,
+        };
+        self.insert_theme(theme, &name, NameConflict::Rename).await
+    }
+
+    /// Inserts a custom theme. The `[data-theme="<css_theme>"]` selectors in its CSS are
+    /// rewritten to the name the theme ends up with, which differs from the requested one when
+    /// that is taken and `on_conflict` is `Rename`.
+    async fn insert_theme(&self, data: CreateTheme, css_theme: &str, on_conflict: NameConflict) -> AppResult<Theme> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let center_content = data.center_content.unwrap_or(true);
+        let insert = |name: String| {
+            let css_content = rename_theme_selectors(&data.css_content, css_theme, &name);
+            let (id, display_name, fonts) = (&id, &data.display_name, sqlx::types::Json(&data.fonts));
+            async move {
+                sqlx::query(
+                    "INSERT INTO themes (id, name, display_name, css_content, is_default, center_content, fonts, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, 0, ?, ?, 'local', ?, ?)"
+                )
+                .bind(id)
+                .bind(name)
+                .bind(display_name)
+                .bind(css_content)
+                .bind(center_content)
+                .bind(fonts)
+                .bind(now)
+                .bind(now)
+                .execute(self.write.pool())
+                .await
+            }
+        };
+        let name = match on_conflict {
+            NameConflict::Rename => insert_with_unique_name(&data.name, insert).await?,
+            NameConflict::Error => match retry_busy(|| insert(data.name.clone())).await {
+                Ok(_) => data.name.clone(),
+                Err(AppError::Conflict(_)) => {
+                    return Err(AppError::Conflict(format!("Theme '{}' already exists", data.name)))
+                }
+                Err(e) => return Err(e),
+            },
+        };
+
+        self.events.publish(AppEvent::ThemeChanged { id: id.clone() });
+        Ok(Theme {
             id,
+            css_content: rename_theme_selectors(&data.css_content, css_theme, &name),
             name,
-            display_name,
-            description,
-            priority,
-            enabled: true,
+            display_name: data.display_name,
             is_default: false,
+            center_content,
+            fonts: data.fonts,
             user_id: Some("local".to_string()),
-            conditions,
-            transform,
-            css_content,
             created_at: now,
             updated_at: now,
+            usage_count: None,
+            disabled_by_safe_mode: false,
         })
     }
 
-    pub async fn delete_layout_rule(&self, id: &str) -> AppResult<()> {
-        // Only delete non-default rules
-        let result = sqlx::query("DELETE FROM layout_rules WHERE id = ? AND is_default = 0")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
+    pub async fn update_theme(&self, id: &str, data: UpdateTheme) -> AppResult<Theme> {
+        let existing = self.get_theme_by_id(id).await?;
 
-        if result.rows_affected() == 0 {
-            return Err(AppError::BadRequest("Cannot delete default layout rule or rule not found".to_string()));
+        if existing.is_default {
+            return Err(AppError::Forbidden(format!("Theme '{}' is built in and cannot be modified", existing.name)));
         }
+        let mut validation = ValidationBuilder::new();
+        if let Some(css_content) = &data.css_content {
+            check_theme_css(&mut validation, css_content, &existing.name);
+        }
+        if let Some(fonts) = &data.fonts {
+            check_theme_fonts(&mut validation, fonts);
+        }
+        validation.finish()?;
 
-        Ok(())
+        let now = Utc::now();
+        let display_name = data.display_name.unwrap_or(existing.display_name);
+        let css_content = data.css_content.unwrap_or(existing.css_content);
+        let center_content = data.center_content.unwrap_or(existing.center_content);
+        let fonts = data.fonts.unwrap_or(existing.fonts);
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "UPDATE themes SET display_name = ?, css_content = ?, center_content = ?, fonts = ?, updated_at = ? WHERE id = ?"
+            )
+            .bind(&display_name)
+            .bind(&css_content)
+            .bind(center_content)
+            .bind(sqlx::types::Json(&fonts))
+            .bind(now)
+            .bind(id)
+            .execute(pool)
+        })
+        .await?;
+
+        self.events.publish(AppEvent::ThemeChanged { id: id.to_string() });
+        Ok(Theme {
+            id: existing.id,
+            name: existing.name,
+            display_name,
+            css_content,
+            is_default: existing.is_default,
+            center_content,
+            fonts,
+            user_id: existing.user_id,
+            created_at: existing.created_at,
+            updated_at: now,
+            usage_count: None,
+            disabled_by_safe_mode: false,
+        })
+    }
+
+    /// Restores a seeded theme's display name, CSS, centering and fonts to the ones it shipped
+    /// with. Themes that weren't seeded have nothing to go back to and are a BadRequest.
+    pub async fn reset_theme(&self, id: &str) -> AppResult<Theme> {
+        let existing = self.get_theme_by_id(id).await?;
+        let builtin = BUILTIN_THEMES
+            .iter()
+            .find(|builtin| existing.user_id.is_none() && builtin.name == existing.name)
+            .ok_or_else(|| AppError::BadRequest(format!("Theme '{}' is not a built-in theme", existing.name)))?;
+
+        let now = Utc::now();
+        let fonts = builtin.theme_fonts();
+        self.write.run(|pool| {
+            sqlx::query(
+                "UPDATE themes SET display_name = ?, css_content = ?, center_content = ?, fonts = ?, updated_at = ? WHERE id = ?"
+            )
+            .bind(builtin.display_name)
+            .bind(builtin.css)
+            .bind(builtin.center_content)
+            .bind(sqlx::types::Json(&fonts))
+            .bind(now)
+            .bind(id)
+            .execute(pool)
+        })
+        .await?;
+
+        self.events.publish(AppEvent::ThemeChanged { id: id.to_string() });
+        self.get_theme_by_id(id).await
+    }
+
+    /// Seeds again the built-in themes that were deleted, returning them. A theme whose name
+    /// was taken by a custom theme since is left out.
+    pub async fn reset_default_themes(&self) -> AppResult<Vec<Theme>> {
+        let mut tx = self.write.begin().await?;
+        let mut ids = Vec::new();
+        for theme in &BUILTIN_THEMES {
+            let (taken,): (bool,) = sqlx::query_as("SELECT EXISTS(SELECT 1 FROM themes WHERE name = ?)")
+                .bind(theme.name)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !taken {
+                ids.push(insert_builtin_theme(&mut tx, theme).await?);
+            }
+        }
+        tx.commit().await?;
+
+        let mut themes = Vec::with_capacity(ids.len());
+        for id in ids {
+            self.events.publish(AppEvent::ThemeChanged { id: id.clone() });
+            themes.push(self.get_theme_by_id(&id).await?);
+        }
+        Ok(themes)
+    }
+
+    /// Deletes a custom theme. While presentations (trashed ones included) still use it, the
+    /// delete is a Conflict unless `force` is set, which switches them to the default theme
+    /// for new presentations first, saving a version of each as a theme change would.
+    pub async fn delete_theme(&self, id: &str, force: bool) -> AppResult<()> {
+        let theme = self.get_theme_by_id(id).await?;
+        if theme.is_default {
+            return Err(AppError::Forbidden(format!("Theme '{}' is built in and cannot be deleted", theme.name)));
+        }
+
+        let users = self.theme_usage(id).await?.presentations;
+        if !users.is_empty() && !force {
+            return Err(AppError::Conflict(format!(
+                "Theme '{}' is used by {}; delete with force to switch them to the default theme",
+                theme.name,
+                describe_users(&users)
+            )));
+        }
+        if let Some(user) = users.iter().find(|user| user.locked) {
+            return Err(AppError::Forbidden(format!(
+                "Theme '{}' is used by locked presentation \"{}\" ({})",
+                theme.name, user.title, user.id
+            )));
+        }
+
+        let fallback = match self.default_theme().await? {
+            default if default == theme.name => AppSettings::default().default_theme,
+            default => default,
+        };
+        let now = Utc::now();
+        let mut tx = self.write.begin().await?;
+        for user in &users {
+            sqlx::query(
+                "INSERT INTO presentation_versions (id, presentation_id, title, content, theme, created_at, created_by) \
+                 SELECT ?, id, title, content, theme, ?, 'local' FROM presentations WHERE id = ?"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(now)
+            .bind(&user.id)
+            .execute(&mut *tx)
+            .await?;
+            prune_versions(&mut tx, &user.id, self.max_versions).await?;
+        }
+
+        // Presentations that took up the theme or were locked since the check hold the
+        // delete back; dropping the transaction undoes the reassignment
+        let reassigned = sqlx::query("UPDATE presentations SET theme = ?, updated_at = ? WHERE theme = ? AND locked = 0")
+            .bind(&fallback)
+            .bind(now)
+            .bind(&theme.name)
+            .execute(&mut *tx)
+            .await?;
+        if reassigned.rows_affected() != users.len() as u64 {
+            return Err(AppError::Conflict(format!(
+                "Presentations using theme '{}' changed during the delete, try again",
+                theme.name
+            )));
+        }
+        sqlx::query("DELETE FROM themes WHERE id = ? AND is_default = 0")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        for user in users {
+            self.events.publish(AppEvent::PresentationUpdated { id: user.id });
+        }
+        self.events.publish(AppEvent::ThemeChanged { id: id.to_string() });
+        Ok(())
+    }
+
+    // Layout Rules
+    pub async fn list_layout_rules(&self) -> AppResult<Vec<LayoutRule>> {
+        let rules = sqlx::query_as::<_, LayoutRule>(
+            "SELECT id, name, display_name, description, priority, enabled, is_default, user_id, conditions, transform, css_content, created_at, updated_at FROM layout_rules ORDER BY priority"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(rules)
+    }
+
+    // Pipelines
+    pub async fn list_pipelines(&self) -> AppResult<Vec<Pipeline>> {
+        let pipelines = sqlx::query_as::<_, Pipeline>(
+            "SELECT id, name, description, steps, user_id, created_at, updated_at FROM pipelines WHERE user_id = 'local' ORDER BY name"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(pipelines)
+    }
+
+    pub async fn get_pipeline(&self, id_or_name: &str) -> AppResult<Pipeline> {
+        sqlx::query_as::<_, Pipeline>(
+            "SELECT id, name, description, steps, user_id, created_at, updated_at FROM pipelines WHERE (id = ? OR name = ?) AND user_id = 'local'"
+        )
+        .bind(id_or_name)
+        .bind(id_or_name)
+        .fetch_optional(self.read.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Pipeline {} not found", id_or_name)))
+    }
+
+    pub async fn create_pipeline(&self, data: CreatePipeline) -> AppResult<Pipeline> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let steps = serde_json::to_string(&data.steps)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize steps: {}", e)))?;
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "INSERT INTO pipelines (id, name, description, steps, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, 'local', ?, ?)"
+            )
+            .bind(&id)
+            .bind(&data.name)
+            .bind(&data.description)
+            .bind(&steps)
+            .bind(now)
+            .bind(now)
+            .execute(pool)
+        })
+        .await?;
+
+        self.events.publish(AppEvent::PipelineChanged { id: id.clone() });
+        Ok(Pipeline {
+            id,
+            name: data.name,
+            description: data.description,
+            steps,
+            user_id: "local".to_string(),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    pub async fn update_pipeline(&self, id: &str, data: UpdatePipeline) -> AppResult<Pipeline> {
+        let existing = self.get_pipeline(id).await?;
+        let now = Utc::now();
+
+        let name = data.name.unwrap_or(existing.name);
+        let description = data.description.or(existing.description);
+        let steps = match data.steps {
+            Some(steps) => serde_json::to_string(&steps)
+                .map_err(|e| AppError::Internal(format!("Failed to serialize steps: {}", e)))?,
+            None => existing.steps,
+        };
+
+        self.write.run(|pool| {
+            sqlx::query("UPDATE pipelines SET name = ?, description = ?, steps = ?, updated_at = ? WHERE id = ?")
+                .bind(&name)
+                .bind(&description)
+                .bind(&steps)
+                .bind(now)
+                .bind(&existing.id)
+                .execute(pool)
+        })
+        .await?;
+
+        self.events.publish(AppEvent::PipelineChanged { id: existing.id.clone() });
+        Ok(Pipeline {
+            id: existing.id,
+            name,
+            description,
+            steps,
+            user_id: existing.user_id,
+            created_at: existing.created_at,
+            updated_at: now,
+        })
+    }
+
+    pub async fn delete_pipeline(&self, id: &str) -> AppResult<()> {
+        let result = self.write.run(|pool| {
+            sqlx::query("DELETE FROM pipelines WHERE id = ? AND user_id = 'local'")
+                .bind(id)
+                .execute(pool)
+        })
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Pipeline {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::PipelineChanged { id: id.to_string() });
+        Ok(())
+    }
+
+    // Watchlists
+    pub async fn list_watchlist_sources(&self, presentation_id: &str) -> AppResult<Vec<WatchlistSource>> {
+        self.get_presentation(presentation_id).await?;
+        let sources = sqlx::query_as::<_, WatchlistSource>(
+            "SELECT id, presentation_id, url, title, created_at, last_refreshed_at FROM watchlist_sources WHERE presentation_id = ? ORDER BY created_at, url"
+        )
+        .bind(presentation_id)
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(sources)
+    }
+
+    pub async fn get_watchlist_source(&self, presentation_id: &str, id: &str) -> AppResult<WatchlistSource> {
+        sqlx::query_as::<_, WatchlistSource>(
+            "SELECT id, presentation_id, url, title, created_at, last_refreshed_at FROM watchlist_sources WHERE id = ? AND presentation_id = ?"
+        )
+        .bind(id)
+        .bind(presentation_id)
+        .fetch_optional(self.read.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Watchlist source {} not found", id)))
+    }
+
+    pub async fn create_watchlist_source(&self, presentation_id: &str, data: CreateWatchlistSource) -> AppResult<WatchlistSource> {
+        self.get_presentation(presentation_id).await?;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "INSERT INTO watchlist_sources (id, presentation_id, url, title, created_at) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(&id)
+            .bind(presentation_id)
+            .bind(&data.url)
+            .bind(&data.title)
+            .bind(now)
+            .execute(pool)
+        })
+        .await
+        .map_err(|e| watchlist_url_conflict(e, &data.url))?;
+
+        self.events.publish(AppEvent::WatchlistChanged { presentation_id: presentation_id.to_string() });
+        Ok(WatchlistSource {
+            id,
+            presentation_id: presentation_id.to_string(),
+            url: data.url,
+            title: data.title,
+            created_at: now,
+            last_refreshed_at: None,
+        })
+    }
+
+    pub async fn update_watchlist_source(&self, presentation_id: &str, id: &str, data: UpdateWatchlistSource) -> AppResult<WatchlistSource> {
+        let existing = self.get_watchlist_source(presentation_id, id).await?;
+        let url = data.url.unwrap_or(existing.url);
+        let title = data.title.or(existing.title);
+
+        self.write.run(|pool| {
+            sqlx::query("UPDATE watchlist_sources SET url = ?, title = ? WHERE id = ?")
+                .bind(&url)
+                .bind(&title)
+                .bind(id)
+                .execute(pool)
+        })
+        .await
+        .map_err(|e| watchlist_url_conflict(e, &url))?;
+
+        self.events.publish(AppEvent::WatchlistChanged { presentation_id: presentation_id.to_string() });
+        Ok(WatchlistSource { url, title, ..existing })
+    }
+
+    pub async fn delete_watchlist_source(&self, presentation_id: &str, id: &str) -> AppResult<()> {
+        let result = self.write.run(|pool| {
+            sqlx::query("DELETE FROM watchlist_sources WHERE id = ? AND presentation_id = ?")
+                .bind(id)
+                .bind(presentation_id)
+                .execute(pool)
+        })
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(format!("Watchlist source {} not found", id)));
+        }
+
+        self.events.publish(AppEvent::WatchlistChanged { presentation_id: presentation_id.to_string() });
+        Ok(())
+    }
+
+    pub async fn mark_watchlist_source_refreshed(&self, id: &str) -> AppResult<()> {
+        self.write.run(|pool| {
+            sqlx::query("UPDATE watchlist_sources SET last_refreshed_at = ? WHERE id = ?")
+                .bind(Utc::now())
+                .bind(id)
+                .execute(pool)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Records feed items as imported into a presentation and returns the hashes that
+    /// weren't recorded before, so two overlapping refreshes never stage the same item.
+    pub async fn claim_watchlist_items(&self, presentation_id: &str, hashes: &[String]) -> AppResult<Vec<String>> {
+        let now = Utc::now();
+        let mut tx = self.write.begin().await?;
+        let mut claimed = Vec::new();
+
+        for hash in hashes {
+            let result = sqlx::query(
+                "INSERT OR IGNORE INTO watchlist_items (presentation_id, item_hash, imported_at) VALUES (?, ?, ?)"
+            )
+            .bind(presentation_id)
+            .bind(hash)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                claimed.push(hash.clone());
+            }
+        }
+
+        tx.commit().await?;
+        Ok(claimed)
+    }
+
+    /// Forgets claimed items whose slides never made it into the deck.
+    pub async fn release_watchlist_items(&self, presentation_id: &str, hashes: &[String]) -> AppResult<()> {
+        let mut tx = self.write.begin().await?;
+
+        for hash in hashes {
+            sqlx::query("DELETE FROM watchlist_items WHERE presentation_id = ? AND item_hash = ?")
+                .bind(presentation_id)
+                .bind(hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Appends slides to the end of the deck's Inbox section, creating the section if needed.
+    pub async fn stage_inbox_slides(&self, id: &str, markdown: &[String]) -> AppResult<Presentation> {
+        self.edit_content(id, |content| watchlists::stage_in_inbox(content, markdown)).await
+    }
+
+    // AI Provider Configs
+    pub async fn list_ai_provider_configs(&self) -> AppResult<Vec<AiProviderConfig>> {
+        let configs = sqlx::query_as::<_, AiProviderConfig>(
+            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE user_id = 'local' ORDER BY provider_name"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(configs)
+    }
+
+    pub async fn get_ai_provider_config(&self, provider_name: &str) -> AppResult<Option<AiProviderConfig>> {
+        let config = sqlx::query_as::<_, AiProviderConfig>(
+            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE user_id = 'local' AND provider_name = ?"
+        )
+        .bind(provider_name)
+        .fetch_optional(self.read.pool())
+        .await?;
+        Ok(config)
+    }
+
+    pub async fn get_ai_provider_config_by_id(&self, id: &str) -> AppResult<Option<AiProviderConfig>> {
+        let config = sqlx::query_as::<_, AiProviderConfig>(
+            "SELECT id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at FROM ai_provider_configs WHERE id = ? AND user_id = 'local'"
+        )
+        .bind(id)
+        .fetch_optional(self.read.pool())
+        .await?;
+        Ok(config)
+    }
+
+    pub async fn update_ai_provider_config(
+        &self,
+        id: &str,
+        model: Option<String>,
+        base_url: Option<String>,
+        api_key_encrypted: Option<String>,
+    ) -> AppResult<AiProviderConfig> {
+        let existing = self.get_ai_provider_config_by_id(id).await?
+            .ok_or_else(|| AppError::NotFound("AI config not found".to_string()))?;
+
+        let now = Utc::now();
+        let new_model = model.or(existing.model);
+        let new_base_url = base_url.or(existing.base_url);
+        let new_api_key = api_key_encrypted.unwrap_or(existing.api_key_encrypted);
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "UPDATE ai_provider_configs SET api_key_encrypted = ?, model = ?, base_url = ?, updated_at = ? WHERE id = ?"
+            )
+            .bind(&new_api_key)
+            .bind(&new_model)
+            .bind(&new_base_url)
+            .bind(now)
+            .bind(id)
+            .execute(pool)
+        })
+        .await?;
+
+        self.events.publish(AppEvent::AiProvidersChanged);
+        Ok(AiProviderConfig {
+            id: existing.id,
+            provider_name: existing.provider_name,
+            api_key_encrypted: new_api_key,
+            model: new_model,
+            base_url: new_base_url,
+            user_id: existing.user_id,
+            created_at: existing.created_at,
+            updated_at: now,
+        })
+    }
+
+    pub async fn upsert_ai_provider_config(&self, data: CreateAiProviderConfig, api_key_encrypted: String) -> AppResult<AiProviderConfig> {
+        let now = Utc::now();
+
+        // Check if exists
+        let existing = self.get_ai_provider_config(&data.provider_name).await?;
+
+        if let Some(existing) = existing {
+            // Update
+            self.write.run(|pool| {
+                sqlx::query(
+                    "UPDATE ai_provider_configs SET api_key_encrypted = ?, model = ?, base_url = ?, updated_at = ? WHERE id = ?"
+                )
+                .bind(&api_key_encrypted)
+                .bind(&data.model)
+                .bind(&data.base_url)
+                .bind(now)
+                .bind(&existing.id)
+                .execute(pool)
+            })
+            .await?;
+
+            self.events.publish(AppEvent::AiProvidersChanged);
+            Ok(AiProviderConfig {
+                id: existing.id,
+                provider_name: data.provider_name,
+                api_key_encrypted,
+                model: data.model,
+                base_url: data.base_url,
+                user_id: "local".to_string(),
+                created_at: existing.created_at,
+                updated_at: now,
+            })
+        } else {
+            // Insert
+            let id = Uuid::new_v4().to_string();
+            self.write.run(|pool| {
+                sqlx::query(
+                    "INSERT INTO ai_provider_configs (id, provider_name, api_key_encrypted, model, base_url, user_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 'local', ?, ?)"
+                )
+                .bind(&id)
+                .bind(&data.provider_name)
+                .bind(&api_key_encrypted)
+                .bind(&data.model)
+                .bind(&data.base_url)
+                .bind(now)
+                .bind(now)
+                .execute(pool)
+            })
+            .await?;
+
+            self.events.publish(AppEvent::AiProvidersChanged);
+            Ok(AiProviderConfig {
+                id,
+                provider_name: data.provider_name,
+                api_key_encrypted,
+                model: data.model,
+                base_url: data.base_url,
+                user_id: "local".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+        }
+    }
+
+    /// Re-encrypts every stored API key from `old_key` to `new_key` in one transaction,
+    /// returning how many were rewritten. Nothing changes if any key fails to decrypt.
+    pub async fn rotate_encryption_key(&self, old_key: &str, new_key: &str) -> AppResult<usize> {
+        let (old_key, new_key) = (encryption::derive_key(old_key), encryption::derive_key(new_key));
+        let mut tx = self.write.begin().await?;
+
+        let configs: Vec<(String, String, String)> =
+            sqlx::query_as("SELECT id, provider_name, api_key_encrypted FROM ai_provider_configs")
+                .fetch_all(&mut *tx)
+                .await?;
+
+        for (id, provider_name, api_key_encrypted) in &configs {
+            let api_key = encryption::decrypt_with(&old_key, api_key_encrypted).map_err(|_| {
+                AppError::BadRequest(format!("The {} API key can't be decrypted with the old key", provider_name))
+            })?;
+            sqlx::query("UPDATE ai_provider_configs SET api_key_encrypted = ? WHERE id = ?")
+                .bind(encryption::encrypt_with(&new_key, &api_key)?)
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        self.events.publish(AppEvent::AiProvidersChanged);
+        Ok(configs.len())
+    }
+
+    pub async fn delete_ai_provider_config(&self, id: &str) -> AppResult<()> {
+        let result = self.write.run(|pool| {
+            sqlx::query("DELETE FROM ai_provider_configs WHERE id = ? AND user_id = 'local'")
+                .bind(id)
+                .execute(pool)
+        })
+        .await?;
+
+        if result.rows_affected() > 0 {
+            self.events.publish(AppEvent::AiProvidersChanged);
+        }
+        Ok(())
+    }
+
+    // Model cache
+    pub async fn get_cached_models(&self, provider_name: &str) -> AppResult<Option<ModelCacheEntry>> {
+        let entry = sqlx::query_as::<_, ModelCacheEntry>(
+            "SELECT provider_name, base_url, models, fetched_at FROM model_cache WHERE provider_name = ?"
+        )
+        .bind(provider_name)
+        .fetch_optional(self.read.pool())
+        .await?;
+        Ok(entry)
+    }
+
+    pub async fn store_cached_models(
+        &self,
+        provider_name: &str,
+        base_url: Option<&str>,
+        models: &[crate::ai::ModelInfo],
+    ) -> AppResult<DateTime<Utc>> {
+        let models = serde_json::to_string(models)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize models: {}", e)))?;
+        let now = Utc::now();
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "INSERT INTO model_cache (provider_name, base_url, models, fetched_at) VALUES (?, ?, ?, ?) ON CONFLICT(provider_name) DO UPDATE SET base_url = excluded.base_url, models = excluded.models, fetched_at = excluded.fetched_at"
+            )
+            .bind(provider_name)
+            .bind(base_url)
+            .bind(&models)
+            .bind(now)
+            .execute(pool)
+        })
+        .await?;
+
+        Ok(now)
+    }
+
+    pub async fn invalidate_cached_models(&self, provider_name: &str) -> AppResult<()> {
+        self.write.run(|pool| {
+            sqlx::query("DELETE FROM model_cache WHERE provider_name = ?")
+                .bind(provider_name)
+                .execute(pool)
+        })
+        .await?;
+        Ok(())
+    }
+
+    // Media
+    pub async fn list_media(&self) -> AppResult<Vec<Media>> {
+        let media = sqlx::query_as::<_, Media>(
+            "SELECT id, filename, original_name, mime_type, size, url, user_id, created_at, width, height, duration_seconds, content_hash FROM media WHERE user_id = 'local' ORDER BY created_at DESC"
+        )
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(media)
+    }
+
+    pub async fn get_media(&self, id: &str) -> AppResult<Option<Media>> {
+        let media = sqlx::query_as::<_, Media>(
+            "SELECT id, filename, original_name, mime_type, size, url, user_id, created_at, width, height, duration_seconds, content_hash FROM media WHERE id = ? AND user_id = 'local'"
+        )
+        .bind(id)
+        .fetch_optional(self.read.pool())
+        .await?;
+        Ok(media)
+    }
+
+    /// The recorded SHA-256 of a file in the uploads directory, if known.
+    pub async fn get_media_content_hash(&self, filename: &str) -> AppResult<Option<String>> {
+        let hash: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT content_hash FROM media WHERE filename = ? AND user_id = 'local'")
+                .bind(filename)
+                .fetch_optional(self.read.pool())
+                .await?;
+        Ok(hash.and_then(|(hash,)| hash))
+    }
+
+    /// The library entry holding a file with this SHA-256, if any.
+    pub async fn find_media_by_hash(&self, content_hash: &str) -> AppResult<Option<Media>> {
+        let media = sqlx::query_as::<_, Media>(
+            "SELECT id, filename, original_name, mime_type, size, url, user_id, created_at, width, height, duration_seconds, content_hash FROM media WHERE content_hash = ? AND user_id = 'local' ORDER BY created_at LIMIT 1"
+        )
+        .bind(content_hash)
+        .fetch_optional(self.read.pool())
+        .await?;
+        Ok(media.map(|media| Media { deduplicated: true, ..media }))
+    }
+
+    /// Adds a file to the library. If a file with the same hash is already there, the
+    /// existing entry is returned with `deduplicated` set and the caller should remove
+    /// its own copy of the file.
+    pub async fn create_media(
+        &self,
+        filename: String,
+        original_name: String,
+        mime_type: String,
+        size: i64,
+        url: String,
+        metadata: MediaMetadata,
+        content_hash: String,
+    ) -> AppResult<Media> {
+        if let Some(existing) = self.find_media_by_hash(&content_hash).await? {
+            return Ok(existing);
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        // An upload of the same file that finished since the lookup wins
+        let inserted = self.write.run(|pool| {
+            sqlx::query(
+                "INSERT OR IGNORE INTO media (id, filename, original_name, mime_type, size, url, user_id, created_at, width, height, duration_seconds, content_hash) VALUES (?, ?, ?, ?, ?, ?, 'local', ?, ?, ?, ?, ?)"
+            )
+            .bind(&id)
+            .bind(&filename)
+            .bind(&original_name)
+            .bind(&mime_type)
+            .bind(size)
+            .bind(&url)
+            .bind(now)
+            .bind(metadata.width)
+            .bind(metadata.height)
+            .bind(metadata.duration_seconds)
+            .bind(&content_hash)
+            .execute(pool)
+        })
+        .await?;
+        if inserted.rows_affected() == 0 {
+            return self
+                .find_media_by_hash(&content_hash)
+                .await?
+                .ok_or_else(|| AppError::Conflict("A file with the same content is already stored".to_string()));
+        }
+
+        self.events.publish(AppEvent::MediaAdded { id: id.clone() });
+        Ok(Media {
+            id,
+            filename,
+            original_name,
+            mime_type,
+            size,
+            url,
+            user_id: "local".to_string(),
+            created_at: now,
+            width: metadata.width,
+            height: metadata.height,
+            duration_seconds: metadata.duration_seconds,
+            content_hash: Some(content_hash),
+            deduplicated: false,
+        })
+    }
+
+    // Chunked upload sessions
+    pub async fn create_upload_session(
+        &self,
+        data: CreateUploadSession,
+        chunk_size: i64,
+        expires_at: DateTime<Utc>,
+    ) -> AppResult<UploadSession> {
+        let id = Uuid::new_v4().to_string();
+
+        self.write.run(|pool| {
+            sqlx::query(
+                "INSERT INTO upload_sessions (id, filename, mime_type, size, chunk_size, sha256, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+            .bind(&id)
+            .bind(&data.filename)
+            .bind(&data.mime_type)
+            .bind(data.size)
+            .bind(chunk_size)
+            .bind(data.sha256.to_ascii_lowercase())
+            .bind(Utc::now())
+            .bind(expires_at)
+            .execute(pool)
+        })
+        .await?;
+
+        self.get_upload_session(&id).await
+    }
+
+    /// An unexpired upload session with the chunks received so far.
+    pub async fn get_upload_session(&self, id: &str) -> AppResult<UploadSession> {
+        let mut session = sqlx::query_as::<_, UploadSession>(
+            "SELECT id, filename, mime_type, size, chunk_size, sha256, created_at, expires_at FROM upload_sessions WHERE id = ? AND expires_at > ?"
+        )
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_optional(self.read.pool())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Upload {} not found", id)))?;
+
+        session.chunk_count = (session.size + session.chunk_size - 1) / session.chunk_size;
+        session.received_chunks = sqlx::query_scalar(
+            "SELECT chunk_index FROM upload_chunks WHERE upload_id = ? ORDER BY chunk_index"
+        )
+        .bind(id)
+        .fetch_all(self.read.pool())
+        .await?;
+        Ok(session)
+    }
+
+    /// Marks a chunk as received and pushes back the session's expiry.
+    pub async fn record_upload_chunk(&self, id: &str, index: i64, expires_at: DateTime<Utc>) -> AppResult<UploadSession> {
+        let mut tx = self.write.begin().await?;
+
+        sqlx::query("INSERT OR IGNORE INTO upload_chunks (upload_id, chunk_index) VALUES (?, ?)")
+            .bind(id)
+            .bind(index)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE upload_sessions SET expires_at = ? WHERE id = ?")
+            .bind(expires_at)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        self.get_upload_session(id).await
+    }
+
+    pub async fn delete_upload_session(&self, id: &str) -> AppResult<()> {
+        let mut tx = self.write.begin().await?;
+
+        sqlx::query("DELETE FROM upload_chunks WHERE upload_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM upload_sessions WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes expired upload sessions, returning their ids so their temp files can be removed.
+    pub async fn delete_expired_upload_sessions(&self) -> AppResult<Vec<String>> {
+        let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM upload_sessions WHERE expires_at <= ?")
+            .bind(Utc::now())
+            .fetch_all(self.read.pool())
+            .await?;
+
+        for id in &ids {
+            self.delete_upload_session(id).await?;
+        }
+        Ok(ids)
+    }
+
+    pub async fn delete_media(&self, id: &str) -> AppResult<Option<Media>> {
+        let media = self.get_media(id).await?;
+        if media.is_some() {
+            self.write.run(|pool| {
+                sqlx::query("DELETE FROM media WHERE id = ? AND user_id = 'local'")
+                    .bind(id)
+                    .execute(pool)
+            })
+            .await?;
+            self.events.publish(AppEvent::MediaDeleted { id: id.to_string() });
+        }
+        Ok(media)
+    }
+
+    // Layout Rules
+    pub async fn create_layout_rule(
+        &self,
+        name: String,
+        display_name: String,
+        description: Option<String>,
+        priority: i32,
+        conditions: String,
+        transform: String,
+        css_content: String,
+    ) -> AppResult<LayoutRule> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+
+        let name = insert_with_unique_name(&name, |name| {
+            sqlx::query(
+                "INSERT INTO layout_rules (id, name, display_name, description, priority, enabled, is_default, user_id, conditions, transform, css_content, created_at, updated_at) VALUES (?, ?, ?, ?, ?, 1, 0, 'local', ?, ?, ?, ?, ?)"
+            )
+            .bind(&id)
+            .bind(name)
+            .bind(&display_name)
+            .bind(&description)
+            .bind(priority)
+            .bind(&conditions)
+            .bind(&transform)
+            .bind(&css_content)
+            .bind(now)
+            .bind(now)
+            .execute(self.write.pool())
+        })
+        .await?;
+
+        self.events.publish(AppEvent::LayoutRulesChanged);
+        Ok(LayoutRule {
+            id,
+            name,
+            display_name,
+            description,
+            priority,
+            enabled: true,
+            is_default: false,
+            user_id: Some("local".to_string()),
+            conditions,
+            transform,
+            css_content,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    pub async fn delete_layout_rule(&self, id: &str) -> AppResult<()> {
+        // Only delete non-default rules
+        let result = self.write.run(|pool| {
+            sqlx::query("DELETE FROM layout_rules WHERE id = ? AND is_default = 0")
+                .bind(id)
+                .execute(pool)
+        })
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::BadRequest("Cannot delete default layout rule or rule not found".to_string()));
+        }
+
+        self.events.publish(AppEvent::LayoutRulesChanged);
+        Ok(())
+    }
+}
+
+/// A presentation row ready to insert, from `Database::new_presentation`.
+struct NewPresentation {
+    id: String,
+    title: String,
+    content: String,
+    theme: String,
+    now: DateTime<Utc>,
+    stats: slides::DeckStats,
+    health_score: i64,
+    health_json: String,
+}
+
+impl NewPresentation {
+    fn insert(&self) -> Query<'_, Sqlite, SqliteArguments<'_>> {
+        sqlx::query(
+            "INSERT INTO presentations (id, title, content, content_hash, theme, user_id, created_at, updated_at, last_opened_at, slide_count, word_count, has_speaker_notes, health_score, health_json) VALUES (?, ?, ?, ?, ?, 'local', ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&self.id)
+        .bind(&self.title)
+        .bind(&self.content)
+        .bind(content_hash(&self.content))
+        .bind(&self.theme)
+        .bind(self.now)
+        .bind(self.now)
+        .bind(self.now)
+        .bind(self.stats.slide_count)
+        .bind(self.stats.word_count)
+        .bind(self.stats.has_speaker_notes)
+        .bind(self.health_score)
+        .bind(&self.health_json)
+    }
+}
+
+/// Runs `insert` on the write pool with `base` as the name, retrying with `base-2`,
+/// `base-3`, ... while it fails on a UNIQUE constraint. Inserting first (rather than checking for the name
+/// beforehand) keeps concurrent creates from racing. Returns the name that was used.
+async fn insert_with_unique_name<F, Fut>(base: &str, mut insert: F) -> AppResult<String>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<SqliteQueryResult, sqlx::Error>>,
+{
+    for attempt in 1..=MAX_UNIQUE_NAME_ATTEMPTS {
+        let name = if attempt == 1 {
+            base.to_string()
+        } else {
+            format!("{}-{}", base, attempt)
+        };
+
+        match retry_busy(|| insert(name.clone())).await {
+            Ok(_) => return Ok(name),
+            // Inserts only conflict on a UNIQUE constraint
+            Err(AppError::Conflict(_)) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(AppError::Conflict(format!(
+        "Name {} and its first {} numbered variants are already taken",
+        base, MAX_UNIQUE_NAME_ATTEMPTS
+    )))
+}
+
+/// Runs `op` until it succeeds, fails for a reason other than the database being busy or
+/// locked, or runs out of attempts. Waits an exponentially growing, jittered delay between
+/// attempts so competing writers don't retry in lockstep.
+async fn retry_busy<T, F, Fut>(mut op: F) -> AppResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    for attempt in 0..WRITE_BUSY_ATTEMPTS {
+        match op().await {
+            Err(e) if is_busy(&e) => {
+                let base = WRITE_BUSY_BACKOFF_MS << attempt;
+                let jitter = rand::thread_rng().gen_range(0..base);
+                tokio::time::sleep(Duration::from_millis(base + jitter)).await;
+            }
+            result => return result.map_err(AppError::from),
+        }
+    }
+
+    Err(AppError::Unavailable(
+        "The database is busy with another write, try again".to_string(),
+    ))
+}
+
+/// SQLITE_BUSY or SQLITE_LOCKED, including their extended codes.
+fn is_busy(e: &sqlx::Error) -> bool {
+    const SQLITE_BUSY: i32 = 5;
+    const SQLITE_LOCKED: i32 = 6;
+    let sqlx::Error::Database(db) = e else {
+        return false;
+    };
+    db.code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+fn backup_error(rc: i32) -> AppError {
+    // SAFETY: sqlite3_errstr returns a static string for any result code
+    let message = unsafe { std::ffi::CStr::from_ptr(ffi::sqlite3_errstr(rc)) }.to_string_lossy();
+    if matches!(rc & 0xff, ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED) {
+        AppError::Unavailable(format!("Backup failed, the database is busy: {}", message))
+    } else {
+        AppError::Internal(format!("Backup failed: {}", message))
+    }
+}
+
+/// Reports a second source with the same URL on one presentation as a conflict.
+fn watchlist_url_conflict(e: AppError, url: &str) -> AppError {
+    match e {
+        AppError::Conflict(_) => AppError::Conflict(format!("The watchlist already has a source for {}", url)),
+        e => e,
+    }
+}
+
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db) if db.is_unique_violation())
+}
+
+/// Applies the changed keys to `current`; a `null` value resets a key to its default.
+/// Unknown keys and invalid values are rejected.
+fn merge_settings(
+    current: &PresentationSettings,
+    changes: serde_json::Map<String, serde_json::Value>,
+) -> AppResult<PresentationSettings> {
+    let mut merged = match serde_json::to_value(current) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    for (key, value) in changes {
+        if value.is_null() {
+            merged.remove(&key);
+        } else {
+            merged.insert(key, value);
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(merged))
+        .map_err(|e| AppError::BadRequest(format!("Invalid settings: {}", e)))
+}
+
+/// Fails with Forbidden if `presentation` is locked against changes.
+/// Id of the oldest presentation outside the trash whose title matches, ignoring surrounding
+/// whitespace and the case of ASCII letters.
+async fn find_presentation_by_title(conn: &mut SqliteConnection, title: &str) -> AppResult<Option<String>> {
+    let id = sqlx::query_scalar(
+        "SELECT id FROM presentations WHERE trim(title) = ? COLLATE NOCASE AND deleted_at IS NULL ORDER BY created_at LIMIT 1"
+    )
+    .bind(title.trim())
+    .fetch_optional(conn)
+    .await?;
+    Ok(id)
+}
+
+fn ensure_unlocked(presentation: &Presentation) -> AppResult<()> {
+    if presentation.locked {
+        return Err(AppError::Forbidden(format!(
+            "Presentation {} is locked, unlock it first",
+            presentation.id
+        )));
+    }
+    Ok(())
+}
+
+/// Whether applying `data` would leave `existing` exactly as it is. Values are compared
+/// verbatim, so whitespace-only edits still count as changes.
+fn is_unchanged(existing: &Presentation, data: &UpdatePresentation) -> AppResult<bool> {
+    let same = |new: &Option<String>, current: &String| new.as_ref().is_none_or(|new| new == current);
+    let same_settings = match &data.settings {
+        Some(changes) => merge_settings(&existing.settings, changes.clone())? == existing.settings,
+        None => true,
+    };
+    Ok(same(&data.title, &existing.title)
+        && same(&data.content, &existing.content)
+        && same(&data.theme, &existing.theme)
+        && same_settings)
+}
+
+/// Lowercase letters and digits in hyphen-separated words, like `my-theme-2`.
+fn is_kebab_case(name: &str) -> bool {
+    name.split('-')
+        .all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()))
+}
+
+/// `presentation "Intro"`, or `5 presentations: "Intro", "Q3 review", "Roadmap" and 2 more`,
+/// naming up to `MAX_NAMED_USERS` of a theme's presentations.
+fn describe_users(users: &[ThemeUser]) -> String {
+    let mut titles: Vec<String> = users.iter().take(MAX_NAMED_USERS).map(|user| format!("\"{}\"", user.title)).collect();
+    if users.len() > MAX_NAMED_USERS {
+        titles.push(format!("{} more", users.len() - MAX_NAMED_USERS));
+    }
+    let last = titles.pop().unwrap_or_default();
+    let list = if titles.is_empty() { last } else { format!("{} and {}", titles.join(", "), last) };
+    match users.len() {
+        1 => format!("presentation {}", list),
+        n => format!("{} presentations: {}", n, list),
+    }
+}
+
+/// Records each problem with a theme's CSS as a `cssContent` error.
+fn check_theme_css(validation: &mut ValidationBuilder, css: &str, theme: &str) {
+    for problem in theme_css::validate(css, theme) {
+        validation.error("cssContent", problem.to_string());
+    }
+}
+
+/// Checksum of a theme export's fields other than `checksum` itself.
+fn theme_checksum(export: &ThemeExport) -> String {
+    let mut fields = vec![
+        serde_json::json!(export.format_version),
+        serde_json::json!(export.name),
+        serde_json::json!(export.display_name),
+        serde_json::json!(export.css_content),
+        serde_json::json!(export.center_content),
+    ];
+    // Version 1 files have no fonts
+    if export.format_version > 1 {
+        fields.push(serde_json::json!(export.fonts));
+    }
+    content_hash(&serde_json::Value::from(fields).to_string())
+}
+
+/// Font stacks end up in CSS and font URLs, so they may only name families.
+fn check_theme_fonts(validation: &mut ValidationBuilder, fonts: &ThemeFonts) {
+    for (field, stack) in [("fonts.heading", &fonts.heading), ("fonts.body", &fonts.body), ("fonts.mono", &fonts.mono)] {
+        if let Some(stack) = stack {
+            validation.check(
+                !stack.trim().is_empty() && stack.len() <= MAX_FONT_STACK_LEN,
+                field,
+                format!("must be a font stack of 1-{} characters", MAX_FONT_STACK_LEN),
+            );
+            validation.check(
+                !stack.contains([';', '{', '}', '<', '>', '(', ')', '\\', '\n']),
+                field,
+                "may only contain font family names, quotes and commas",
+            );
+        }
+    }
+}
+
+/// A theme seeded into new databases, which reset_theme restores.
+struct BuiltinTheme {
+    name: &'static str,
+    display_name: &'static str,
+    css: &'static str,
+    is_default: bool,
+    center_content: bool,
+    fonts: BuiltinFonts,
+}
+
+/// The font stacks a built-in theme's CSS uses, all loaded from Google Fonts.
+struct BuiltinFonts {
+    heading: Option<&'static str>,
+    body: Option<&'static str>,
+    mono: Option<&'static str>,
+}
+
+impl BuiltinTheme {
+    fn theme_fonts(&self) -> ThemeFonts {
+        ThemeFonts {
+            heading: self.fonts.heading.map(str::to_string),
+            body: self.fonts.body.map(str::to_string),
+            mono: self.fonts.mono.map(str::to_string),
+            source: FontSource::Google,
+        }
+    }
+}
+
+// Poppins headings over Inter text, which most built-in themes use
+const POPPINS_INTER: BuiltinFonts = BuiltinFonts {
+    heading: Some("'Poppins', sans-serif"),
+    body: Some("'Inter', sans-serif"),
+    mono: None,
+};
+
+const BUILTIN_THEMES: [BuiltinTheme; 11] = [
+    BuiltinTheme {
+        name: "default",
+        display_name: "Default",
+        css: r#"
+.slide-content[data-theme="default"], [data-theme="default"] .slide-content, [data-theme="default"] .slide {
+  --slide-bg: #ffffff; --slide-text: #333333; --slide-heading: #1a1a1a; --slide-accent: #0066cc;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="default"] h1, [data-theme="default"] h2, [data-theme="default"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="default"] code { background: #f5f5f5; padding: 0.2em 0.4em; border-radius: 3px; }
+[data-theme="default"] a { color: var(--slide-accent); }
+"#,
+        is_default: true,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "dark",
+        display_name: "Dark Mode",
+        css: r#"
+.slide-content[data-theme="dark"], [data-theme="dark"] .slide-content, [data-theme="dark"] .slide {
+  --slide-bg: #1e1e2e; --slide-text: #cdd6f4; --slide-heading: #cba6f7; --slide-accent: #89b4fa;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="dark"] h1, [data-theme="dark"] h2, [data-theme="dark"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="dark"] code { background: #313244; padding: 0.2em 0.4em; border-radius: 3px; color: #a6e3a1; }
+[data-theme="dark"] a { color: var(--slide-accent); }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "minimal",
+        display_name: "Minimal",
+        css: r#"
+.slide-content[data-theme="minimal"], [data-theme="minimal"] .slide-content, [data-theme="minimal"] .slide {
+  --slide-bg: #fafafa; --slide-text: #222; --slide-heading: #000; --slide-accent: #555;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif; padding: 4rem;
+}
+[data-theme="minimal"] h1 { font-size: 3rem; font-weight: 300; letter-spacing: -0.02em; }
+[data-theme="minimal"] h2 { font-size: 2rem; font-weight: 300; }
+[data-theme="minimal"] code { background: #eee; padding: 0.2em 0.4em; border-radius: 3px; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: BuiltinFonts {
+            heading: None,
+            body: Some("'Inter', sans-serif"),
+            mono: None,
+        },
+    },
+    BuiltinTheme {
+        name: "corporate",
+        display_name: "Corporate",
+        css: r#"
+.slide-content[data-theme="corporate"], [data-theme="corporate"] .slide-content, [data-theme="corporate"] .slide {
+  --slide-bg: #ffffff; --slide-text: #2c3e50; --slide-heading: #1a365d; --slide-accent: #2b6cb0;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
+  border-top: 4px solid var(--slide-accent);
+}
+[data-theme="corporate"] h1, [data-theme="corporate"] h2 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading); border-bottom: 2px solid #e2e8f0; padding-bottom: 0.5rem;
+}
+[data-theme="corporate"] code { background: #edf2f7; padding: 0.2em 0.4em; border-radius: 3px; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "creative",
+        display_name: "Creative",
+        css: r#"
+.slide-content[data-theme="creative"], [data-theme="creative"] .slide-content, [data-theme="creative"] .slide {
+  --slide-bg: #0f0c29; --slide-text: #e0e0e0; --slide-heading: #f857a6; --slide-accent: #ff5858;
+  background: linear-gradient(135deg, #0f0c29, #302b63, #24243e); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="creative"] h1, [data-theme="creative"] h2 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+  background: linear-gradient(90deg, #f857a6, #ff5858); -webkit-background-clip: text; -webkit-text-fill-color: transparent;
+}
+[data-theme="creative"] code { background: rgba(255,255,255,0.1); padding: 0.2em 0.4em; border-radius: 3px; }
+[data-theme="creative"] a { color: var(--slide-accent); }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "ocean",
+        display_name: "Ocean",
+        css: r#"
+.slide-content[data-theme="ocean"], [data-theme="ocean"] .slide-content, [data-theme="ocean"] .slide {
+  --slide-bg: #0b1929; --slide-text: #b2c8df; --slide-heading: #5eead4; --slide-accent: #38bdf8;
+  background: linear-gradient(180deg, #0b1929 0%, #0d2137 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="ocean"] h1, [data-theme="ocean"] h2, [data-theme="ocean"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="ocean"] code { background: rgba(56,189,248,0.1); padding: 0.2em 0.4em; border-radius: 3px; color: #7dd3fc; }
+[data-theme="ocean"] a { color: var(--slide-accent); }
+[data-theme="ocean"] blockquote { border-left: 3px solid #5eead4; padding-left: 1rem; color: #7dd3fc; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "sunset",
+        display_name: "Sunset",
+        css: r#"
+.slide-content[data-theme="sunset"], [data-theme="sunset"] .slide-content, [data-theme="sunset"] .slide {
+  --slide-bg: #1c1017; --slide-text: #e8d5ce; --slide-heading: #fb923c; --slide-accent: #f472b6;
+  background: linear-gradient(135deg, #1c1017 0%, #2a1520 50%, #1e1422 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="sunset"] h1, [data-theme="sunset"] h2, [data-theme="sunset"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="sunset"] h1 { background: linear-gradient(90deg, #fb923c, #f472b6); -webkit-background-clip: text; -webkit-text-fill-color: transparent; }
+[data-theme="sunset"] code { background: rgba(251,146,60,0.12); padding: 0.2em 0.4em; border-radius: 3px; color: #fdba74; }
+[data-theme="sunset"] a { color: var(--slide-accent); }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "forest",
+        display_name: "Forest",
+        css: r#"
+.slide-content[data-theme="forest"], [data-theme="forest"] .slide-content, [data-theme="forest"] .slide {
+  --slide-bg: #0f1a0f; --slide-text: #c8d6c0; --slide-heading: #4ade80; --slide-accent: #86efac;
+  background: linear-gradient(180deg, #0f1a0f 0%, #162016 100%); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="forest"] h1, [data-theme="forest"] h2, [data-theme="forest"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="forest"] code { background: rgba(74,222,128,0.1); padding: 0.2em 0.4em; border-radius: 3px; color: #86efac; }
+[data-theme="forest"] a { color: var(--slide-accent); }
+[data-theme="forest"] strong { color: #bbf7d0; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "noir",
+        display_name: "Noir",
+        css: r#"
+.slide-content[data-theme="noir"], [data-theme="noir"] .slide-content, [data-theme="noir"] .slide {
+  --slide-bg: #0a0a0a; --slide-text: #a3a3a3; --slide-heading: #fafafa; --slide-accent: #e5e5e5;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="noir"] h1, [data-theme="noir"] h2, [data-theme="noir"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading); font-weight: 700; letter-spacing: -0.02em;
+}
+[data-theme="noir"] h1 { font-size: 3.2rem; }
+[data-theme="noir"] code { background: #1a1a1a; padding: 0.2em 0.4em; border-radius: 3px; color: #d4d4d4; }
+[data-theme="noir"] a { color: var(--slide-accent); text-decoration: underline; }
+[data-theme="noir"] blockquote { border-left: 3px solid #404040; padding-left: 1rem; color: #d4d4d4; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "lavender",
+        display_name: "Lavender",
+        css: r#"
+.slide-content[data-theme="lavender"], [data-theme="lavender"] .slide-content, [data-theme="lavender"] .slide {
+  --slide-bg: #faf5ff; --slide-text: #4a3563; --slide-heading: #7c3aed; --slide-accent: #a78bfa;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'Inter', sans-serif;
+}
+[data-theme="lavender"] h1, [data-theme="lavender"] h2, [data-theme="lavender"] h3 {
+  font-family: 'Poppins', sans-serif; color: var(--slide-heading);
+}
+[data-theme="lavender"] code { background: #ede9fe; padding: 0.2em 0.4em; border-radius: 3px; color: #6d28d9; }
+[data-theme="lavender"] a { color: var(--slide-accent); }
+[data-theme="lavender"] blockquote { border-left: 3px solid #c4b5fd; padding-left: 1rem; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: POPPINS_INTER,
+    },
+    BuiltinTheme {
+        name: "cyberpunk",
+        display_name: "Cyberpunk",
+        css: r#"
+.slide-content[data-theme="cyberpunk"], [data-theme="cyberpunk"] .slide-content, [data-theme="cyberpunk"] .slide {
+  --slide-bg: #0a0014; --slide-text: #d4d4d8; --slide-heading: #e4ff1a; --slide-accent: #06b6d4;
+  background: var(--slide-bg); color: var(--slide-text); font-family: 'JetBrains Mono', 'Fira Code', monospace;
+}
+[data-theme="cyberpunk"] h1, [data-theme="cyberpunk"] h2, [data-theme="cyberpunk"] h3 {
+  color: var(--slide-heading); text-transform: uppercase; letter-spacing: 0.05em;
+}
+[data-theme="cyberpunk"] h1 { text-shadow: 0 0 20px rgba(228,255,26,0.3); }
+[data-theme="cyberpunk"] code { background: rgba(6,182,212,0.12); padding: 0.2em 0.4em; border-radius: 3px; color: #22d3ee; }
+[data-theme="cyberpunk"] a { color: var(--slide-accent); }
+[data-theme="cyberpunk"] strong { color: #e4ff1a; }
+"#,
+        is_default: false,
+        center_content: true,
+        fonts: BuiltinFonts {
+            heading: None,
+            body: Some("'JetBrains Mono', 'Fira Code', monospace"),
+            mono: Some("'JetBrains Mono', 'Fira Code', monospace"),
+        },
+    },
+];
+
+async fn insert_builtin_theme(conn: &mut SqliteConnection, theme: &BuiltinTheme) -> AppResult<String> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    sqlx::query(
+        "INSERT INTO themes (id, name, display_name, css_content, is_default, center_content, fonts, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(theme.name)
+    .bind(theme.display_name)
+    .bind(theme.css)
+    .bind(theme.is_default)
+    .bind(theme.center_content)
+    .bind(sqlx::types::Json(theme.theme_fonts()))
+    .bind(&now)
+    .bind(&now)
+    .execute(conn)
+    .await?;
+    Ok(id)
+}
+
+/// `css` with the `[data-theme="from"]` selectors rewritten to `[data-theme="to"]`.
+fn rename_theme_selectors(css: &str, from: &str, to: &str) -> String {
+    css.replace(&format!("[data-theme=\"{}\"]", from), &format!("[data-theme=\"{}\"]", to))
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// A presentation's new state, as written by `overwrite_presentation`.
+struct PresentationWrite {
+    title: String,
+    content: String,
+    theme: String,
+    settings: PresentationSettings,
+    health: (i64, String),
+}
+
+/// Writes `new` over `existing`, first snapshotting the previous state if the title, content
+/// or theme change. Returns false if the row changed or was locked since `existing` was read,
+/// in which case the caller should roll back.
+async fn overwrite_presentation(
+    conn: &mut SqliteConnection,
+    existing: &Presentation,
+    new: &PresentationWrite,
+    max_versions: i64,
+    now: DateTime<Utc>,
+) -> AppResult<bool> {
+    if new.title != existing.title || new.content != existing.content || new.theme != existing.theme {
+        sqlx::query(
+            "INSERT INTO presentation_versions (id, presentation_id, title, content, theme, created_at, created_by) VALUES (?, ?, ?, ?, ?, ?, 'local')"
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&existing.id)
+        .bind(&existing.title)
+        .bind(&existing.content)
+        .bind(&existing.theme)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+        prune_versions(conn, &existing.id, max_versions).await?;
+    }
+
+    let stats = slides::deck_stats(&new.content);
+    let result = sqlx::query(
+        "UPDATE presentations SET title = ?, content = ?, content_hash = ?, theme = ?, settings = ?, updated_at = ?, slide_count = ?, word_count = ?, has_speaker_notes = ?, health_score = ?, health_json = ? WHERE id = ? AND updated_at = ? AND locked = 0"
+    )
+    .bind(&new.title)
+    .bind(&new.content)
+    .bind(content_hash(&new.content))
+    .bind(&new.theme)
+    .bind(sqlx::types::Json(&new.settings))
+    .bind(now)
+    .bind(stats.slide_count)
+    .bind(stats.word_count)
+    .bind(stats.has_speaker_notes)
+    .bind(new.health.0)
+    .bind(&new.health.1)
+    .bind(&existing.id)
+    .bind(existing.updated_at)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drops all but the newest `keep` versions of a presentation.
+async fn prune_versions(conn: &mut SqliteConnection, id: &str, keep: i64) -> AppResult<()> {
+    sqlx::query(
+        "DELETE FROM presentation_versions WHERE presentation_id = ? AND id NOT IN (SELECT id FROM presentation_versions WHERE presentation_id = ? ORDER BY created_at DESC, rowid DESC LIMIT ?)"
+    )
+    .bind(id)
+    .bind(id)
+    .bind(keep)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Hex SHA-256 of a presentation's content, for telling whether it changed since a snapshot
+/// or an export.
+pub(crate) fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// The `health_score` and `health_json` column values for an assessment.
+fn health_columns(health: DeckHealth) -> AppResult<(i64, String)> {
+    let json = serde_json::to_string(&health)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize deck health: {}", e)))?;
+    Ok((health.score, json))
+}
+
+/// Turns free-form user input into an FTS5 MATCH expression. Each whitespace-separated
+/// term is quoted (so punctuation can't be parsed as FTS syntax) and prefix-matched,
+/// and all terms must match. Returns `None` when there is nothing to search for.
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_db() -> Database {
+        let db = Database::new_with_url("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        db
+    }
+
+    // The tables as the first release created them
+    const BASELINE_SCHEMA: &str = r#"
+        CREATE TABLE presentations (
+            id TEXT PRIMARY KEY, title TEXT NOT NULL, content TEXT NOT NULL DEFAULT '',
+            theme TEXT NOT NULL DEFAULT 'default', user_id TEXT NOT NULL DEFAULT 'local',
+            created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+        );
+        CREATE TABLE themes (
+            id TEXT PRIMARY KEY, name TEXT NOT NULL UNIQUE, display_name TEXT NOT NULL,
+            css_content TEXT NOT NULL, is_default INTEGER NOT NULL DEFAULT 0,
+            center_content INTEGER NOT NULL DEFAULT 1, user_id TEXT,
+            created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+        );
+        CREATE TABLE media (
+            id TEXT PRIMARY KEY, filename TEXT NOT NULL, original_name TEXT NOT NULL,
+            mime_type TEXT NOT NULL, size INTEGER NOT NULL, url TEXT NOT NULL,
+            user_id TEXT NOT NULL DEFAULT 'local', created_at TEXT NOT NULL
+        );
+        CREATE TABLE layout_rules (
+            id TEXT PRIMARY KEY, name TEXT NOT NULL UNIQUE, display_name TEXT NOT NULL,
+            description TEXT, priority INTEGER NOT NULL DEFAULT 100, enabled INTEGER NOT NULL DEFAULT 1,
+            is_default INTEGER NOT NULL DEFAULT 0, user_id TEXT, conditions TEXT NOT NULL,
+            transform TEXT NOT NULL, css_content TEXT NOT NULL,
+            created_at TEXT NOT NULL, updated_at TEXT NOT NULL
+        );
+        CREATE TABLE ai_provider_configs (
+            id TEXT PRIMARY KEY, provider_name TEXT NOT NULL, api_key_encrypted TEXT NOT NULL,
+            model TEXT, base_url TEXT, user_id TEXT NOT NULL DEFAULT 'local',
+            created_at TEXT NOT NULL, updated_at TEXT NOT NULL, UNIQUE(user_id, provider_name)
+        );
+        INSERT INTO presentations (id, title, content, created_at, updated_at)
+            VALUES ('p1', 'Old deck', '# Kept', '2024-01-01T00:00:00Z', '2024-01-01T00:00:00Z');
+        INSERT INTO media (id, filename, original_name, mime_type, size, url, created_at)
+            VALUES ('m1', 'a.png', 'a.png', 'image/png', 3, '/api/uploads/a.png', '2024-01-01T00:00:00Z');
+    "#;
+
+    #[tokio::test]
+    async fn test_migrates_baseline_schema() {
+        let db = Database::new_with_url("sqlite::memory:").await.unwrap();
+        sqlx::raw_sql(BASELINE_SCHEMA).execute(db.write.pool()).await.unwrap();
+        db.migrate().await.unwrap();
+
+        let deck = db.get_presentation("p1").await.unwrap();
+        assert_eq!(deck.content, "# Kept");
+        assert_eq!(db.get_media("m1").await.unwrap().unwrap().content_hash, None);
+        assert!(!db.list_themes().await.unwrap().is_empty());
+        // Existing decks get a content hash, so they are snapshotted once and then left alone
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 1);
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 0);
+
+        // Migrating again is a no-op, and the hash index keeps one entry per file
+        db.migrate().await.unwrap();
+        let hash = content_hash("png");
+        let upload = |name: &str| {
+            db.create_media(
+                name.to_string(),
+                name.to_string(),
+                "image/png".to_string(),
+                3,
+                format!("/api/uploads/{}", name),
+                MediaMetadata::default(),
+                hash.clone(),
+            )
+        };
+        assert!(!upload("b.png").await.unwrap().deduplicated);
+        assert!(upload("c.png").await.unwrap().deduplicated);
+        let duplicate = sqlx::query("INSERT INTO media (id, filename, original_name, mime_type, size, url, created_at, content_hash) VALUES ('m2', 'd', 'd', 'image/png', 3, 'u', 'now', ?)")
+            .bind(&hash)
+            .execute(db.write.pool())
+            .await;
+        assert!(duplicate.is_err());
+    }
+
+    async fn create(db: &Database, title: &str, content: &str) -> Presentation {
+        db.create_presentation(CreatePresentation {
+            title: title.to_string(),
+            content: Some(content.to_string()),
+            theme: None,
+            if_not_exists: false,
+            on_conflict: None,
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_connections_use_wal() {
+        let dir = std::env::temp_dir().join(format!("slides-db-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new_with_url(&format!("sqlite:{}?mode=rwc", dir.join("slides.db").display()))
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+
+        let (journal_mode,): (String,) = sqlx::query_as("PRAGMA journal_mode").fetch_one(db.write.pool()).await.unwrap();
+        assert_eq!(journal_mode, "wal");
+        let (foreign_keys,): (i64,) = sqlx::query_as("PRAGMA foreign_keys").fetch_one(db.write.pool()).await.unwrap();
+        assert_eq!(foreign_keys, 1);
+        assert!(db.pragma_integrity_check().await.unwrap());
+
+        db.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_backup_to_path() {
+        let dir = std::env::temp_dir().join(format!("slides-db-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new_with_url(&format!("sqlite:{}?mode=rwc", dir.join("slides.db").display()))
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+        let deck = create(&db, "Backed up", "# One").await;
+
+        let dest = dir.join("backup.db");
+        db.backup_to_path(&dest).await.unwrap();
+        // Later writes don't reach the copy
+        db.delete_presentation_permanently(&deck.id).await.unwrap();
+
+        let restored = Database::new_with_url(&format!("sqlite:{}", dest.display())).await.unwrap();
+        assert!(restored.pragma_integrity_check().await.unwrap());
+        let presentation = restored.get_presentation(&deck.id).await.unwrap();
+        assert_eq!(presentation.title, "Backed up");
+        assert_eq!(presentation.content, "# One");
+
+        let missing_dir = dir.join("missing").join("backup.db");
+        assert!(matches!(db.backup_to_path(&missing_dir).await, Err(AppError::Internal(_))));
+
+        restored.close().await;
+        db.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn content_update(content: &str) -> UpdatePresentation {
+        UpdatePresentation { title: None, content: Some(content.to_string()), theme: None, settings: None }
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_only_changed_presentations() {
+        let db = test_db().await;
+        let first = create(&db, "First", "# One").await;
+        let second = create(&db, "Second", "# Two").await;
+        let trashed = create(&db, "Trashed", "# Gone").await;
+        db.delete_presentation(&trashed.id).await.unwrap();
+
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 2);
+        // Nothing changed since
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 0);
+
+        // Changing only the title leaves the content hash alone
+        db.update_presentation(&second.id, UpdatePresentation { title: Some("Renamed".to_string()), ..content_update("# Two") })
+            .await
+            .unwrap();
+        db.update_presentation(&first.id, content_update("# One, edited")).await.unwrap();
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 1);
+
+        let snapshots = db.list_snapshots(&first.id).await.unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].content, "# One, edited");
+        assert_eq!(snapshots[0].content_hash, content_hash("# One, edited"));
+        assert_eq!(snapshots[1].content, "# One");
+        assert!(db.list_snapshots(&trashed.id).await.is_err());
+
+        // Editing back to an earlier state is still a change from the last snapshot
+        db.update_presentation(&first.id, content_update("# One")).await.unwrap();
+        assert_eq!(db.snapshot_changed_presentations().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_are_pruned() {
+        let db = test_db().await;
+        let deck = create(&db, "Deck", "# 0").await;
+        let other = create(&db, "Other", "# Other").await;
+
+        let rounds = MAX_SNAPSHOTS_PER_PRESENTATION + 3;
+        for i in 1..=rounds {
+            db.snapshot_changed_presentations().await.unwrap();
+            db.update_presentation(&deck.id, content_update(&format!("# {}", i))).await.unwrap();
+        }
+        db.snapshot_changed_presentations().await.unwrap();
+
+        let snapshots = db.list_snapshots(&deck.id).await.unwrap();
+        assert_eq!(snapshots.len() as i64, MAX_SNAPSHOTS_PER_PRESENTATION);
+        assert_eq!(snapshots[0].content, format!("# {}", rounds));
+        assert_eq!(
+            snapshots.last().unwrap().content,
+            format!("# {}", rounds - MAX_SNAPSHOTS_PER_PRESENTATION + 1)
+        );
+        // Pruning one deck leaves the others alone
+        assert_eq!(db.list_snapshots(&other.id).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot() {
+        let db = test_db().await;
+        let deck = create(&db, "Deck", "# Before the crash").await;
+        db.snapshot_changed_presentations().await.unwrap();
+        let snapshot = db.list_snapshots(&deck.id).await.unwrap().remove(0);
+
+        db.update_presentation(&deck.id, content_update("")).await.unwrap();
+        let restored = db.restore_snapshot(&deck.id, &snapshot.id).await.unwrap();
+        assert_eq!(restored.content, "# Before the crash");
+        // The state it replaced is kept as a version
+        assert_eq!(db.list_versions(&deck.id).await.unwrap()[0].content, "");
+
+        let other = create(&db, "Other", "").await;
+        assert!(matches!(db.restore_snapshot(&other.id, &snapshot.id).await, Err(AppError::NotFound(_))));
+
+        db.delete_presentation_permanently(&deck.id).await.unwrap();
+        let (left,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM presentation_snapshots")
+            .fetch_one(db.read.pool())
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_not_blocked_by_an_open_write() {
+        let dir = std::env::temp_dir().join(format!("slides-db-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = Database::new_with_url(&format!("sqlite:{}?mode=rwc", dir.join("slides.db").display()))
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+        let deck = create(&db, "Before", "# One").await;
+
+        // A large write, left uncommitted while the reads run
+        let mut tx = db.write.begin().await.unwrap();
+        for i in 0..2000 {
+            sqlx::query("INSERT INTO tags (id, name, created_at) VALUES (?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(format!("bulk-{}", i))
+                .bind(Utc::now())
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+        sqlx::query("UPDATE presentations SET title = 'After' WHERE id = ?")
+            .bind(&deck.id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+
+        let reads = async {
+            let presentations = db.list_presentations(ListPresentationsQuery::default()).await.unwrap();
+            (db.get_presentation(&deck.id).await.unwrap(), presentations.total, db.list_tags().await.unwrap())
+        };
+        let (presentation, total, tags) = tokio::time::timeout(Duration::from_secs(1), reads)
+            .await
+            .expect("reads waited for the write transaction");
+        assert_eq!(presentation.title, "Before");
+        assert_eq!(total, 1);
+        assert!(tags.is_empty());
+
+        // The read pool can't be used to write
+        let err = sqlx::query("DELETE FROM tags").execute(db.read.pool()).await.unwrap_err();
+        assert!(err.to_string().contains("readonly"), "{}", err);
+
+        tx.commit().await.unwrap();
+        assert_eq!(db.get_presentation(&deck.id).await.unwrap().title, "After");
+        assert_eq!(db.list_tags().await.unwrap().len(), 2000);
+
+        db.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_fts_match_expression() {
+        assert_eq!(fts_match_expression("rust async"), Some("\"rust\"* \"async\"*".to_string()));
+        assert_eq!(fts_match_expression("say \"hi\""), Some("\"say\"* \"hi\"*".to_string()));
+        assert_eq!(fts_match_expression("   "), None);
+    }
+
+    #[tokio::test]
+    async fn test_search_presentations_tracks_writes() {
+        let db = test_db().await;
+        let rust = create(&db, "Rust in Production", "# Ownership\n\n---\n\n# Borrowing").await;
+        create(&db, "Quarterly Review", "# Revenue").await;
+
+        let results = db.search_presentations("borrow", 1, 20).await.unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.items[0].id, rust.id);
+
+        db.update_presentation(&rust.id, UpdatePresentation {
+            title: None,
+            content: Some("# Lifetimes".to_string()),
+            theme: None,
+            settings: None,
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.search_presentations("borrow", 1, 20).await.unwrap().total, 0);
+        assert_eq!(db.search_presentations("lifetimes", 1, 20).await.unwrap().total, 1);
+
+        db.delete_presentation_permanently(&rust.id).await.unwrap();
+        assert_eq!(db.search_presentations("lifetimes", 1, 20).await.unwrap().total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_versions_snapshot_and_restore() {
+        let db = test_db().await;
+        let deck = create(&db, "Versioned", "v0").await;
+
+        let update = |content: &str| UpdatePresentation {
+            title: None,
+            content: Some(content.to_string()),
+            theme: None,
+            settings: None,
+        };
+
+        db.update_presentation(&deck.id, update("v1")).await.unwrap();
+        db.update_presentation(&deck.id, UpdatePresentation {
+            title: Some("Renamed".to_string()),
+            content: None,
+            theme: None,
+            settings: None,
+        })
+        .await
+        .unwrap();
+        db.update_presentation(&deck.id, update("v2")).await.unwrap();
+        // Saving unchanged values doesn't create a version
+        db.update_presentation(&deck.id, update("v2")).await.unwrap();
+
+        let versions = db.list_versions(&deck.id).await.unwrap();
+        let contents: Vec<&str> = versions.iter().map(|v| v.content.as_str()).collect();
+        assert_eq!(contents, vec!["v1", "v1", "v0"]);
+        assert_eq!(versions[0].title.as_deref(), Some("Renamed"));
+        assert_eq!(versions[1].title.as_deref(), Some("Versioned"));
+
+        let restored = db.restore_version(&deck.id, &versions[2].id).await.unwrap();
+        assert_eq!(restored.content, "v0");
+        assert_eq!(restored.title, "Versioned");
+        // The restore snapshotted "v2" so it can be undone
+        assert_eq!(db.list_versions(&deck.id).await.unwrap()[0].content, "v2");
+
+        for i in 0..DEFAULT_MAX_VERSIONS_PER_PRESENTATION + 5 {
+            db.update_presentation(&deck.id, update(&format!("bulk {}", i))).await.unwrap();
+        }
+        let versions = db.list_versions(&deck.id).await.unwrap();
+        assert_eq!(versions.len() as i64, DEFAULT_MAX_VERSIONS_PER_PRESENTATION);
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_updates_write_nothing() {
+        let db = test_db().await;
+        let deck = create(&db, "Deck", "# One").await;
+        let update = |title: &str, content: &str| UpdatePresentation {
+            title: Some(title.to_string()),
+            content: Some(content.to_string()),
+            theme: Some(deck.theme.clone()),
+            settings: Some(serde_json::Map::new()),
+        };
+
+        let same = db.update_presentation(&deck.id, update("Deck", "# One")).await.unwrap();
+        assert!(!same.changed);
+        assert_eq!(same.presentation.updated_at, deck.updated_at);
+        assert!(db.list_versions(&deck.id).await.unwrap().is_empty());
+
+        // Whitespace is content too
+        for (title, content) in [("Deck", "# One\n"), ("Deck ", "# One\n"), ("Deck ", "#  One\n")] {
+            let update = db.update_presentation(&deck.id, update(title, content)).await.unwrap();
+            assert!(update.changed, "{:?} {:?}", title, content);
+            assert_eq!(update.presentation.content, content);
+        }
+        assert_eq!(db.list_versions(&deck.id).await.unwrap().len(), 3);
+
+        let json = serde_json::to_value(db.update_presentation(&deck.id, update("Deck ", "#  One\n")).await.unwrap()).unwrap();
+        assert_eq!(json["changed"], false);
+        assert_eq!(json["id"], deck.id.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_stats_computed_on_write_and_backfilled() {
+        let db = test_db().await;
+        let p = create(&db, "Deck", "# One\n\n---\n\n# Two").await;
+        assert_eq!((p.slide_count, p.word_count, p.has_speaker_notes), (2, 2, false));
+
+        let p = db.replace_slide(&p.id, 1, "# Two\n\n<!-- notes -->\nHi\n<!-- /notes -->").await.unwrap();
+        assert_eq!((p.slide_count, p.word_count, p.has_speaker_notes), (2, 2, true));
+
+        // Simulate a database from before the stats columns existed
+        for column in ["slide_count", "word_count", "has_speaker_notes"] {
+            sqlx::query(&format!("ALTER TABLE presentations DROP COLUMN {}", column))
+                .execute(db.write.pool())
+                .await
+                .unwrap();
+        }
+        db.migrate().await.unwrap();
+
+        let p = db.get_presentation(&p.id).await.unwrap();
+        assert_eq!((p.slide_count, p.word_count, p.has_speaker_notes), (2, 2, true));
+    }
+
+    #[tokio::test]
+    async fn test_create_with_title_conflict() {
+        let db = test_db().await;
+        let original = create(&db, "Q3 Review", "# Q3").await;
+        let create_with = |title: &str, if_not_exists, on_conflict| CreatePresentation {
+            title: title.to_string(),
+            content: Some("# Retry".to_string()),
+            theme: None,
+            if_not_exists,
+            on_conflict,
+        };
+
+        let existing = db.create_presentation(create_with("  q3 review ", true, None)).await.unwrap();
+        assert_eq!(existing.id, original.id);
+        assert_eq!(existing.content, "# Q3");
+
+        let result = db.create_presentation(create_with("Q3 REVIEW", false, Some(TitleConflict::Error))).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+
+        // Without a mode, and once the original is in the trash, a new deck is created
+        let duplicate = db.create_presentation(create_with("Q3 Review", false, None)).await.unwrap();
+        assert_ne!(duplicate.id, original.id);
+        db.delete_presentation(&original.id).await.unwrap();
+        db.delete_presentation(&duplicate.id).await.unwrap();
+        let fresh = db.create_presentation(create_with("Q3 Review", true, None)).await.unwrap();
+        assert_ne!(fresh.id, original.id);
+        assert_eq!(fresh.content, "# Retry");
+    }
+
+    #[tokio::test]
+    async fn test_default_theme_setting() {
+        let db = test_db().await;
+        assert_eq!(create(&db, "Before", "").await.theme, "default");
+
+        let acme = db
+            .create_theme(
+                CreateTheme {
+                    name: "acme".to_string(),
+                    display_name: "Acme".to_string(),
+                    css_content: "[data-theme=\"acme\"] h1 { color: red; }".to_string(),
+                    center_content: None,
+                    fonts: Default::default(),
+                },
+                NameConflict::Error,
+            )
+            .await
+            .unwrap();
+        let settings = |theme: &str| AppSettings { default_theme: theme.to_string() };
+        db.set_app_settings(&settings("acme")).await.unwrap();
+        assert_eq!(db.get_app_settings().await.unwrap().default_theme, "acme");
+        assert_eq!(create(&db, "After", "").await.theme, "acme");
+
+        let result = db.set_app_settings(&settings("missing")).await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert_eq!(db.get_app_settings().await.unwrap().default_theme, "acme");
+
+        // A deleted default falls back to the built-in one
+        db.delete_theme(&acme.id, true).await.unwrap();
+        assert_eq!(create(&db, "Later", "").await.theme, "default");
+    }
+
+    #[tokio::test]
+    async fn test_delete_theme_in_use() {
+        let db = test_db().await;
+        let theme = |name: &str| CreateTheme {
+            name: name.to_string(),
+            display_name: "Brand".to_string(),
+            css_content: format!("[data-theme=\"{}\"] h1 {{ color: red; }}", name),
+            center_content: None,
+            fonts: Default::default(),
+        };
+        for name in ["Brand Theme", "brand-"] {
+            let result = db.create_theme(theme(name), NameConflict::Error).await;
+            assert!(matches!(result, Err(AppError::Validation(_))), "{}", name);
+        }
+        let brand = db.create_theme(theme("brand"), NameConflict::Error).await.unwrap();
+
+        // A taken name is a conflict unless a rename is asked for
+        let taken = db.create_theme(theme("brand"), NameConflict::default()).await;
+        assert!(matches!(taken, Err(AppError::Conflict(_))));
+        let renamed = db.create_theme(theme("brand"), NameConflict::Rename).await.unwrap();
+        assert_eq!(renamed.name, "brand-2");
+        assert!(renamed.css_content.contains("[data-theme=\"brand-2\"] h1"));
+
+        let talk = create(&db, "Talk", "# Hi").await;
+        let rethemed = UpdatePresentation { title: None, content: None, theme: Some("brand".to_string()), settings: None };
+        db.update_presentation(&talk.id, rethemed).await.unwrap();
+        let versions = db.list_versions(&talk.id).await.unwrap().len();
+
+        assert!(matches!(db.delete_theme(&brand.id, false).await, Err(AppError::Conflict(_))));
+        db.set_locked(&talk.id, true).await.unwrap();
+        assert!(matches!(db.delete_theme(&brand.id, true).await, Err(AppError::Forbidden(_))));
+        db.set_locked(&talk.id, false).await.unwrap();
+
+        db.delete_theme(&brand.id, true).await.unwrap();
+        assert_eq!(db.get_presentation(&talk.id).await.unwrap().theme, "default");
+        assert_eq!(db.list_versions(&talk.id).await.unwrap().len(), versions + 1);
+        assert!(matches!(db.get_theme_by_id(&brand.id).await, Err(AppError::NotFound(_))));
+
+        let builtin = db.get_theme_by_name("default").await.unwrap();
+        assert!(matches!(db.delete_theme(&builtin.id, true).await, Err(AppError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_theme() {
+        let db = test_db().await;
+        let ocean = db.get_theme_by_name("ocean").await.unwrap();
+        let named = |name: &str, on_conflict| DuplicateTheme {
+            name: Some(name.to_string()),
+            display_name: None,
+            on_conflict,
+        };
+
+        let copy = db.duplicate_theme(&ocean.id, named("reef", NameConflict::Rename)).await.unwrap();
+        assert_eq!((copy.name.as_str(), copy.display_name.as_str()), ("reef", "Ocean Copy"));
+        assert!(!copy.is_default);
+        assert_eq!(copy.user_id.as_deref(), Some("local"));
+        assert_eq!(copy.center_content, ocean.center_content);
+        assert!(copy.css_content.contains("[data-theme=\"reef\"] h1"));
+        assert!(!copy.css_content.contains("\"ocean\""));
+        assert_eq!(db.get_theme_by_id(&copy.id).await.unwrap().css_content, copy.css_content);
+
+        // A taken name is suffixed, and the selectors follow the name that was used
+        let again = db.duplicate_theme(&ocean.id, named("reef", NameConflict::Rename)).await.unwrap();
+        assert_eq!(again.name, "reef-2");
+        assert!(again.css_content.contains("[data-theme=\"reef-2\"] h1"));
+        let err = db.duplicate_theme(&ocean.id, named("reef", NameConflict::Error)).await.unwrap_err();
+        assert!(matches!(err, AppError::Conflict(msg) if msg == "Theme 'reef' already exists"));
+
+        assert_eq!(db.duplicate_theme(&ocean.id, DuplicateTheme::default()).await.unwrap().name, "ocean-copy");
+        assert!(matches!(
+            db.duplicate_theme(&ocean.id, named("Reef", NameConflict::Rename)).await,
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reset_themes() {
+        let db = test_db().await;
+        let dark = db.get_theme_by_name("dark").await.unwrap();
+        let edited = UpdateTheme {
+            display_name: Some("Broken".to_string()),
+            css_content: Some("[data-theme=\"dark\"] h1 { color: red }".to_string()),
+            center_content: Some(false),
+            fonts: None,
+        };
+        db.update_theme(&dark.id, edited).await.unwrap();
+
+        let reset = db.reset_theme(&dark.id).await.unwrap();
+        assert_eq!(reset.display_name, dark.display_name);
+        assert_eq!(reset.css_content, dark.css_content);
+        assert!(reset.center_content);
+
+        let custom = db.duplicate_theme(&dark.id, DuplicateTheme::default()).await.unwrap();
+        assert!(matches!(db.reset_theme(&custom.id).await, Err(AppError::BadRequest(_))));
+
+        // Only deleted built-ins come back
+        db.delete_theme(&dark.id, false).await.unwrap();
+        let restored = db.reset_default_themes().await.unwrap();
+        assert_eq!(restored.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["dark"]);
+        assert_eq!(restored[0].css_content, dark.css_content);
+        assert_eq!(restored[0].fonts, dark.fonts);
+        assert!(db.reset_default_themes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_theme_usage() {
+        let db = test_db().await;
+        let rethemed = |theme: &str| UpdatePresentation { title: None, content: None, theme: Some(theme.to_string()), settings: None };
+        let mut decks = Vec::new();
+        for title in ["Intro", "Q3 review", "Roadmap", "Retro", "Offsite"] {
+            let deck = create(&db, title, "# Hi").await;
+            db.update_presentation(&deck.id, rethemed("ocean")).await.unwrap();
+            decks.push(deck);
+        }
+        db.update_presentation(&decks[4].id, rethemed("dark")).await.unwrap();
+        db.delete_presentation(&decks[3].id).await.unwrap();
+
+        let counts: HashMap<String, Option<i64>> =
+            db.list_themes().await.unwrap().into_iter().map(|t| (t.name, t.usage_count)).collect();
+        assert_eq!((counts["ocean"], counts["dark"], counts["noir"]), (Some(4), Some(1), Some(0)));
+        assert_eq!(db.get_theme_by_name("ocean").await.unwrap().usage_count, None);
+
+        // Trashed decks still count, most recently updated first
+        let ocean = db.get_theme_by_name("ocean").await.unwrap();
+        let usage = db.theme_usage(&ocean.id).await.unwrap();
+        assert_eq!(usage.count, 4);
+        assert_eq!(usage.presentations[0].id, decks[3].id);
+        assert!(usage.presentations[0].deleted_at.is_some());
+
+        let dark = db.get_theme_by_name("dark").await.unwrap();
+        assert_eq!(describe_users(&db.theme_usage(&dark.id).await.unwrap().presentations), "presentation \"Offsite\"");
+        assert_eq!(
+            describe_users(&usage.presentations),
+            "4 presentations: \"Retro\", \"Roadmap\", \"Q3 review\" and 1 more"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_theme_export_round_trips() {
+        let db = test_db().await;
+        let css = "/* Brand */\r\n[data-theme=\"brand\"] h1 {\tcolor: #c00; }\n\n";
+        let brand = db
+            .create_theme(
+                CreateTheme {
+                    name: "brand".to_string(),
+                    display_name: "Brand".to_string(),
+                    css_content: css.to_string(),
+                    center_content: Some(false),
+                    fonts: Default::default(),
+                },
+                NameConflict::Error,
+            )
+            .await
+            .unwrap();
+        let export = db.export_theme(&brand.id).await.unwrap();
+        assert_eq!(export.format_version, THEME_FORMAT_VERSION);
+        let file = serde_json::to_string(&export).unwrap();
+        let export: ThemeExport = serde_json::from_str(&file).unwrap();
+
+        // The name is taken here, so the import is suffixed and its selectors follow
+        let renamed = db.import_theme(export.clone(), ImportThemeConflict::Rename).await.unwrap();
+        assert_eq!(renamed.name, "brand-2");
+        assert_eq!(renamed.css_content, css.replace("\"brand\"", "\"brand-2\""));
+        assert!(!renamed.center_content);
+
+        db.delete_theme(&brand.id, false).await.unwrap();
+        let imported = db.import_theme(export.clone(), ImportThemeConflict::Rename).await.unwrap();
+        assert_eq!((imported.name.as_str(), imported.css_content.as_str()), ("brand", css));
+
+        let edited = ThemeExport { display_name: "Brand 2".to_string(), ..export.clone() };
+        let err = db.import_theme(edited.clone(), ImportThemeConflict::Overwrite).await.unwrap_err();
+        assert!(matches!(err, AppError::BadRequest(msg) if msg.contains("checksum")));
+
+        let edited = ThemeExport { checksum: theme_checksum(&edited), ..edited };
+        let overwritten = db.import_theme(edited, ImportThemeConflict::Overwrite).await.unwrap();
+        assert_eq!((overwritten.id.as_str(), overwritten.display_name.as_str()), (imported.id.as_str(), "Brand 2"));
+
+        // Files from before fonts were exported still import, with the fonts left unset
+        let css = "[data-theme=\"old\"] h1 { color: blue; }";
+        let v1 = ThemeExport {
+            format_version: 1,
+            name: "old".to_string(),
+            display_name: "Old".to_string(),
+            css_content: css.to_string(),
+            center_content: true,
+            fonts: Default::default(),
+            checksum: content_hash(&serde_json::json!([1, "old", "Old", css, true]).to_string()),
+        };
+        let old = db.import_theme(v1, ImportThemeConflict::Rename).await.unwrap();
+        assert_eq!((old.name.as_str(), &old.fonts), ("old", &ThemeFonts::default()));
+
+        let default = db.get_theme_by_name("default").await.unwrap();
+        let builtin = db.export_theme(&default.id).await.unwrap();
+        let err = db.import_theme(builtin, ImportThemeConflict::Overwrite).await.unwrap_err();
+        assert!(matches!(err, AppError::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn test_theme_fonts() {
+        let db = test_db().await;
+        let default = db.get_theme_by_name("default").await.unwrap();
+        assert_eq!(default.fonts.heading.as_deref(), Some("'Poppins', sans-serif"));
+        assert_eq!(default.fonts.source, FontSource::Google);
+
+        let brand = db
+            .create_theme(
+                CreateTheme {
+                    name: "brand".to_string(),
+                    display_name: "Brand".to_string(),
+                    css_content: "[data-theme=\"brand\"] h1 { font-family: 'Lora', serif; }".to_string(),
+                    center_content: None,
+                    fonts: Default::default(),
+                },
+                NameConflict::Error,
+            )
+            .await
+            .unwrap();
+        assert_eq!(brand.fonts, ThemeFonts::default());
+
+        // Fonts change without touching the CSS
+        let fonts = ThemeFonts { heading: Some("'Lora', serif".to_string()), source: FontSource::Google, ..Default::default() };
+        let update = |fonts| UpdateTheme { display_name: None, css_content: None, center_content: None, fonts: Some(fonts) };
+        let updated = db.update_theme(&brand.id, update(fonts.clone())).await.unwrap();
+        assert_eq!((updated.fonts.clone(), updated.css_content), (fonts.clone(), brand.css_content));
+        assert_eq!(db.get_theme_by_id(&brand.id).await.unwrap().fonts, fonts);
+
+        let injected = ThemeFonts { body: Some("x; } body { display: none".to_string()), ..Default::default() };
+        assert!(matches!(db.update_theme(&brand.id, update(injected)).await, Err(AppError::Validation(_))));
+
+        // Upgrading fills in the seeded themes' fonts and leaves custom ones empty
+        sqlx::query("ALTER TABLE themes DROP COLUMN fonts").execute(db.write.pool()).await.unwrap();
+        db.migrate().await.unwrap();
+        assert_eq!(db.get_theme_by_name("default").await.unwrap().fonts, default.fonts);
+        assert_eq!(db.get_theme_by_name("cyberpunk").await.unwrap().fonts.heading, None);
+        assert_eq!(db.get_theme_by_id(&brand.id).await.unwrap().fonts, ThemeFonts::default());
+    }
+
+    #[tokio::test]
+    async fn test_settings_are_merged_and_validated() {
+        let db = test_db().await;
+        let deck = create(&db, "Deck", "# Hi").await;
+        assert_eq!(deck.settings, PresentationSettings::default());
+
+        let update = |settings: serde_json::Value| UpdatePresentation {
+            title: None,
+            content: None,
+            theme: None,
+            settings: settings.as_object().cloned(),
+        };
+        db.update_presentation(&deck.id, update(serde_json::json!({ "aspectRatio": "4:3", "footer": "ACME" })))
+            .await
+            .unwrap();
+        let updated = db
+            .update_presentation(&deck.id, update(serde_json::json!({ "transition": "morph" })))
+            .await
+            .unwrap()
+            .presentation;
+        assert_eq!(updated.settings, PresentationSettings {
+            aspect_ratio: AspectRatio::Standard,
+            transition: SlideTransition::Morph,
+            footer: Some("ACME".to_string()),
+        });
+
+        // null resets a key; unknown keys and values are rejected without writing anything
+        let updated = db
+            .update_presentation(&deck.id, update(serde_json::json!({ "footer": null })))
+            .await
+            .unwrap()
+            .presentation;
+        assert_eq!(updated.settings.footer, None);
+        for bad in [serde_json::json!({ "colour": "red" }), serde_json::json!({ "aspectRatio": "21:9" })] {
+            let result = db.update_presentation(&deck.id, update(bad)).await;
+            assert!(matches!(result, Err(AppError::BadRequest(_))));
+        }
+        assert_eq!(db.get_presentation(&deck.id).await.unwrap().settings, updated.settings);
+
+        let copy = db.duplicate_presentation(&deck.id, None).await.unwrap();
+        assert_eq!(copy.settings, updated.settings);
+
+        // Transitions go by the presenter's names; the WebGL effect names stored before still read
+        for (name, transition) in [("waveGL", SlideTransition::WaveGl), ("wipe", SlideTransition::Wipe)] {
+            assert_eq!(serde_json::to_value(transition).unwrap(), name);
+        }
+        let legacy: PresentationSettings = serde_json::from_str(r#"{ "transition": "directionalWipe" }"#).unwrap();
+        assert_eq!(legacy.transition, SlideTransition::Wipe);
+    }
+
+    #[tokio::test]
+    async fn test_touch_orders_by_last_opened() {
+        let db = test_db().await;
+        let old = create(&db, "Old", "").await;
+        create(&db, "New", "").await;
+        let by_last_opened = || ListPresentationsQuery {
+            sort_by: PresentationSortField::LastOpenedAt,
+            ..Default::default()
+        };
+        let titles = |page: PaginatedResult<Presentation>| -> Vec<String> {
+            page.items.into_iter().map(|p| p.title).collect()
+        };
+        assert_eq!(titles(db.list_presentations(by_last_opened()).await.unwrap()), ["New", "Old"]);
+
+        let touched = db.touch_presentation(&old.id).await.unwrap();
+        assert_eq!(touched.updated_at, old.updated_at);
+        assert!(touched.last_opened_at > old.last_opened_at);
+        assert_eq!(titles(db.list_presentations(by_last_opened()).await.unwrap()), ["Old", "New"]);
+        assert_eq!(titles(db.list_presentations(Default::default()).await.unwrap()), ["New", "Old"]);
+
+        // Upgrading backfills from updated_at
+        sqlx::query("ALTER TABLE presentations DROP COLUMN last_opened_at")
+            .execute(db.write.pool())
+            .await
+            .unwrap();
+        db.migrate().await.unwrap();
+        let p = db.get_presentation(&old.id).await.unwrap();
+        assert_eq!(p.last_opened_at, Some(p.updated_at));
+
+        assert!(matches!(db.touch_presentation("missing").await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_health_score_tracks_content_and_weights() {
+        let db = test_db().await;
+        let notes = "<!-- notes -->\nHi\n<!-- /notes -->";
+        let p = create(&db, "Deck", &format!("# {{{{title}}}}\n\n{}", notes)).await;
+        let placeholder_weight = HealthWeights::default().unfilled_placeholder;
+        assert_eq!(p.health_score, Some(100 - placeholder_weight as i64));
+
+        let values = HashMap::from([("title".to_string(), serde_json::json!("Launch"))]);
+        let p = db.fill_placeholders(&p.id, &values).await.unwrap();
+        assert_eq!(p.health_score, Some(100));
+        assert!(db.get_presentation_health(&p.id).await.unwrap().findings.is_empty());
+
+        let p = db.replace_slide(&p.id, 0, "# Launch").await.unwrap();
+        let health = db.get_presentation_health(&p.id).await.unwrap();
+        assert_eq!(health.findings[0].kind, HealthFindingKind::MissingNotes);
+
+        db.set_health_weights(&HealthWeights {
+            missing_notes: 40.0,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.get_presentation(&p.id).await.unwrap().health_score, Some(60));
+
+        // Only uploads the deck references are looked up
+        for (name, size) in [("big.mp4", 6 * 1024 * 1024), ("other.mp4", 9 * 1024 * 1024)] {
+            db.create_media(
+                name.to_string(),
+                name.to_string(),
+                "video/mp4".to_string(),
+                size,
+                format!("/api/uploads/{}", name),
+                MediaMetadata::default(),
+                name.to_string(),
+            )
+            .await
+            .unwrap();
+        }
+        let slide = format!("# Launch\n\n![Clip](/api/uploads/big.mp4)\n\n{}", notes);
+        let p = db.replace_slide(&p.id, 0, &slide).await.unwrap();
+        let health = db.get_presentation_health(&p.id).await.unwrap();
+        assert_eq!(health.findings.len(), 1);
+        assert_eq!(health.findings[0].kind, HealthFindingKind::OversizedMedia);
+        assert_eq!(health.findings[0].message, "big.mp4 is 6.0 MB");
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_and_restore() {
+        let db = test_db().await;
+        let deck = create(&db, "Trash Me", "# Hello").await;
+
+        db.delete_presentation(&deck.id).await.unwrap();
+        assert!(matches!(db.get_presentation(&deck.id).await, Err(AppError::NotFound(_))));
+        assert_eq!(db.list_presentations(ListPresentationsQuery::default()).await.unwrap().total, 0);
+        assert_eq!(db.search_presentations("hello", 1, 20).await.unwrap().total, 0);
+
+        let deleted = db.list_deleted_presentations().await.unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+
+        let restored = db.restore_presentation(&deck.id).await.unwrap();
+        assert!(restored.deleted_at.is_none());
+        assert_eq!(db.list_presentations(ListPresentationsQuery::default()).await.unwrap().total, 1);
+        assert!(db.restore_presentation(&deck.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_presentations() {
+        let db = test_db().await;
+        let first = create(&db, "Part 1", "# Intro\n\n---\n\n# Agenda\n\n---\n").await;
+        let empty = create(&db, "Part 2", "").await;
+        let last = create(&db, "Part 3", "# Wrap-up").await;
+        let ids = vec![first.id.clone(), empty.id.clone(), last.id.clone()];
+
+        let merged = db
+            .merge_presentations(MergePresentations {
+                source_ids: ids.clone(),
+                title: "Talk".to_string(),
+                theme: None,
+                delete_sources: false,
+            })
+            .await
+            .unwrap();
+        assert_eq!(merged.content, "# Intro\n\n---\n\n# Agenda\n\n---\n\n# Wrap-up");
+        assert_eq!(merged.slide_count, 3);
+        assert_eq!(merged.theme, first.theme);
+        assert!(db.get_presentation(&first.id).await.is_ok());
+
+        // A source trashed in the meantime rolls the whole merge back
+        db.delete_presentation(&last.id).await.unwrap();
+        let request = |theme: Option<&str>| MergePresentations {
+            source_ids: ids.clone(),
+            title: "Talk again".to_string(),
+            theme: theme.map(str::to_string),
+            delete_sources: true,
+        };
+        assert!(matches!(db.merge_presentations(request(None)).await, Err(AppError::NotFound(_))));
+        db.restore_presentation(&last.id).await.unwrap();
+        assert!(matches!(db.merge_presentations(request(Some("missing"))).await, Err(AppError::BadRequest(_))));
+
+        let merged = db.merge_presentations(request(Some("dark"))).await.unwrap();
+        assert_eq!(merged.theme, "dark");
+        for id in &ids {
+            assert!(matches!(db.get_presentation(id).await, Err(AppError::NotFound(_))));
+        }
+        assert_eq!(db.list_deleted_presentations().await.unwrap().len(), 3);
+
+        let repeated = MergePresentations { source_ids: vec![merged.id.clone(), merged.id], ..request(None) };
+        assert!(matches!(db.merge_presentations(repeated).await, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_replace_in_presentations() {
+        let db = test_db().await;
+        let first = create(&db, "One", "# Acme\n\n---\n\nAcme rocks").await;
+        let second = create(&db, "Two", "Made by Acme").await;
+        let other = create(&db, "Three", "Nothing here").await;
+        let request = |dry_run: bool| ReplaceRequest {
+            search: "Acme".to_string(),
+            replace: "Nimbus".to_string(),
+            ids: None,
+            regex: false,
+            dry_run,
+            include_code: false,
+        };
+
+        let preview = db.replace_in_presentations(request(true)).await.unwrap();
+        assert_eq!(preview.total_matches, 3);
+        assert_eq!(preview.presentations.len(), 2);
+        assert_eq!(preview.presentations[0].slides.iter().map(|s| s.index).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(db.get_presentation(&first.id).await.unwrap().content, first.content);
+
+        let result = db.replace_in_presentations(request(false)).await.unwrap();
+        assert_eq!(result.total_matches, 3);
+        assert_eq!(db.get_presentation(&first.id).await.unwrap().content, "# Nimbus\n\n---\n\nNimbus rocks");
+        assert_eq!(db.get_presentation(&second.id).await.unwrap().content, "Made by Nimbus");
+        assert_eq!(db.get_presentation(&other.id).await.unwrap().updated_at, other.updated_at);
+        // The previous content is kept as a version
+        let versions = db.list_versions(&second.id).await.unwrap();
+        assert_eq!(versions[0].content, "Made by Acme");
+
+        let invalid = ReplaceRequest { search: "(".to_string(), regex: true, ..request(true) };
+        assert!(matches!(db.replace_in_presentations(invalid).await, Err(AppError::BadRequest(_))));
+        let missing = ReplaceRequest { ids: Some(vec!["missing".to_string()]), ..request(true) };
+        assert!(matches!(db.replace_in_presentations(missing).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_tags_filter_presentations() {
+        let db = test_db().await;
+        let both = create(&db, "Both", "").await;
+        let one = create(&db, "One", "").await;
+        create(&db, "Untagged", "").await;
+
+        db.add_tag_to_presentation(&both.id, "Client A").await.unwrap();
+        db.add_tag_to_presentation(&both.id, "draft").await.unwrap();
+        let tagged = db.add_tag_to_presentation(&one.id, " client a ").await.unwrap();
+        assert_eq!(tagged.tags, vec!["client a"]);
+        // Tagging twice is a no-op
+        db.add_tag_to_presentation(&one.id, "client a").await.unwrap();
+        assert_eq!(db.list_tags().await.unwrap().len(), 2);
+
+        let filter = |tags: &[&str]| ListPresentationsQuery {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(db.list_presentations(filter(&[])).await.unwrap().total, 3);
+        assert_eq!(db.list_presentations(filter(&["client a"])).await.unwrap().total, 2);
+        let page = db.list_presentations(filter(&["client a", "DRAFT"])).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].tags, vec!["client a", "draft"]);
+
+        let untagged = db.remove_tag_from_presentation(&one.id, "client a").await.unwrap();
+        assert!(untagged.tags.is_empty());
+        assert!(db.remove_tag_from_presentation(&one.id, "client a").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_archived_hidden_from_list_only() {
+        let db = test_db().await;
+        let talk = create(&db, "Conference talk", "# Done").await;
+        create(&db, "Current", "").await;
+
+        let archived = db.set_archived(&talk.id, true).await.unwrap();
+        assert!(archived.archived);
+        assert_eq!(archived.updated_at, talk.updated_at);
+
+        let page = db.list_presentations(Default::default()).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].title, "Current");
+        let all = ListPresentationsQuery {
+            include_archived: true,
+            ..Default::default()
+        };
+        assert_eq!(db.list_presentations(all).await.unwrap().total, 2);
+
+        // Still retrievable and editable
+        let edited = db.replace_slide(&talk.id, 0, "# Done!").await.unwrap();
+        assert!(edited.archived);
+        assert!(!db.set_archived(&talk.id, false).await.unwrap().archived);
+    }
+
+    #[tokio::test]
+    async fn test_favorites_first() {
+        let db = test_db().await;
+        let old = create(&db, "Old", "").await;
+        create(&db, "New", "").await;
+        let titles = |page: PaginatedResult<Presentation>| -> Vec<String> {
+            page.items.into_iter().map(|p| p.title).collect()
+        };
+        let favorites_first = || ListPresentationsQuery {
+            favorites_first: true,
+            ..Default::default()
+        };
+
+        let starred = db.set_favorite(&old.id, true).await.unwrap();
+        assert!(starred.is_favorite);
+        assert_eq!(starred.updated_at, old.updated_at);
+        assert_eq!(titles(db.list_presentations(favorites_first()).await.unwrap()), ["Old", "New"]);
+        assert_eq!(titles(db.list_presentations(Default::default()).await.unwrap()), ["New", "Old"]);
+
+        assert!(!db.duplicate_presentation(&old.id, None).await.unwrap().is_favorite);
+        assert!(!db.set_favorite(&old.id, false).await.unwrap().is_favorite);
+        assert!(matches!(db.set_favorite("missing", true).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_draft_channel() {
+        let db = test_db().await;
+        let talk = create(&db, "Talk", "# Saved").await;
+
+        let draft = db.save_draft(&talk.id, "# Draft".to_string()).await.unwrap();
+        let draft = db.save_draft(&talk.id, format!("{} 2", draft.content)).await.unwrap();
+        let current = db.get_presentation(&talk.id).await.unwrap();
+        assert!(current.has_draft);
+        assert_eq!((current.content.as_str(), current.updated_at), ("# Saved", talk.updated_at));
+        assert!(db.list_versions(&talk.id).await.unwrap().is_empty());
+        assert_eq!(db.get_draft(&talk.id).await.unwrap().content, "# Draft 2");
+
+        let committed = db.commit_draft(&talk.id).await.unwrap();
+        assert!(committed.changed && !committed.presentation.has_draft);
+        assert_eq!(committed.presentation.content, draft.content);
+        assert_eq!(db.list_versions(&talk.id).await.unwrap()[0].content, "# Saved");
+        assert!(matches!(db.get_draft(&talk.id).await, Err(AppError::NotFound(_))));
+        assert!(matches!(db.commit_draft(&talk.id).await, Err(AppError::NotFound(_))));
+
+        db.save_draft(&talk.id, "# Scratch".to_string()).await.unwrap();
+        db.discard_draft(&talk.id).await.unwrap();
+        let current = db.get_presentation(&talk.id).await.unwrap();
+        assert!(!current.has_draft);
+        assert_eq!(current.content, "# Draft 2");
+
+        db.set_locked(&talk.id, true).await.unwrap();
+        assert!(matches!(db.save_draft(&talk.id, "# No".to_string()).await, Err(AppError::Forbidden(_))));
+        assert!(matches!(db.save_draft("missing", String::new()).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_locked_presentation_rejects_changes() {
+        let db = test_db().await;
+        let talk = create(&db, "Talk", "# Intro\n\n---\n\n# Outro").await;
+        let other = create(&db, "Other", "# Intro").await;
+
+        let locked = db.set_locked(&talk.id, true).await.unwrap();
+        assert!(locked.locked);
+        assert_eq!(locked.updated_at, talk.updated_at);
+
+        let forbidden = |result: AppResult<_>| matches!(result, Err(AppError::Forbidden(_)));
+        let edit = UpdatePresentation { title: Some("Renamed".to_string()), content: None, theme: None, settings: None };
+        assert!(forbidden(db.update_presentation(&talk.id, edit.clone()).await.map(|_| ())));
+        assert!(forbidden(db.replace_slide(&talk.id, 0, "# Hi").await.map(|_| ())));
+        assert!(forbidden(db.delete_slide(&talk.id, 1).await.map(|_| ())));
+        assert!(forbidden(db.delete_presentation(&talk.id).await));
+        assert!(matches!(db.delete_presentation("missing").await, Err(AppError::NotFound(_))));
+
+        // Workspace-wide replaces pass over locked decks; naming one is refused
+        let replace = |ids: Option<Vec<String>>| ReplaceRequest {
+            search: "Intro".to_string(),
+            replace: "Opening".to_string(),
+            ids,
+            regex: false,
+            dry_run: false,
+            include_code: false,
+        };
+        let result = db.replace_in_presentations(replace(None)).await.unwrap();
+        assert_eq!(result.presentations.iter().map(|p| p.id.as_str()).collect::<Vec<_>>(), [other.id.as_str()]);
+        assert!(forbidden(db.replace_in_presentations(replace(Some(vec![talk.id.clone()]))).await.map(|_| ())));
+
+        let bulk = db.bulk_update_presentations(BulkPresentations {
+            action: BulkAction::Delete,
+            ids: vec![talk.id.clone()],
+            theme: None,
+        })
+        .await
+        .unwrap();
+        assert_eq!(bulk.results[0].error.as_deref(), Some("Presentation is locked"));
+
+        // Metadata still changes, and unlocking allows edits again
+        assert!(db.set_favorite(&talk.id, true).await.unwrap().locked);
+        assert!(!db.set_locked(&talk.id, false).await.unwrap().locked);
+        assert_eq!(db.update_presentation(&talk.id, edit).await.unwrap().presentation.title, "Renamed");
+        assert!(!db.duplicate_presentation(&talk.id, None).await.unwrap().locked);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_presentations() {
+        let db = test_db().await;
+        let a = create(&db, "A", "# A").await;
+        let b = create(&db, "B", "# B").await;
+        let bulk = |action, ids: &[&str], theme: Option<&str>| BulkPresentations {
+            action,
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+            theme: theme.map(str::to_string),
+        };
+
+        // A missing id rolls back the whole batch
+        let result = db
+            .bulk_update_presentations(bulk(BulkAction::SetTheme, &[&a.id, "missing"], Some("dark")))
+            .await
+            .unwrap();
+        assert!(!result.committed);
+        assert_eq!((result.succeeded, result.failed), (0, 2));
+        assert_eq!(db.get_presentation(&a.id).await.unwrap().theme, a.theme);
+
+        let result = db
+            .bulk_update_presentations(bulk(BulkAction::SetTheme, &[&a.id, &b.id], Some("dark")))
+            .await
+            .unwrap();
+        assert_eq!(result.succeeded, 2);
+        assert_eq!(db.get_presentation(&b.id).await.unwrap().theme, "dark");
+        assert_eq!(db.list_versions(&b.id).await.unwrap().len(), 1);
+
+        let result = db
+            .bulk_update_presentations(bulk(BulkAction::Archive, &[&a.id], None))
+            .await
+            .unwrap();
+        assert!(result.committed);
+        assert!(db.get_presentation(&a.id).await.unwrap().archived);
+
+        // Deletes skip missing ids and keep the rest
+        let result = db
+            .bulk_update_presentations(bulk(BulkAction::Delete, &[&a.id, "missing", &b.id, &a.id], None))
+            .await
+            .unwrap();
+        assert!(result.committed);
+        assert_eq!((result.succeeded, result.failed), (2, 1));
+        assert_eq!(result.results[1].error.as_deref(), Some("Presentation not found"));
+        assert_eq!(db.list_deleted_presentations().await.unwrap().len(), 2);
+
+        let too_many: Vec<String> = (0..=MAX_BULK_IDS).map(|i| i.to_string()).collect();
+        let too_many: Vec<&str> = too_many.iter().map(String::as_str).collect();
+        for request in [
+            bulk(BulkAction::Delete, &too_many, None),
+            bulk(BulkAction::Delete, &[], None),
+            bulk(BulkAction::SetTheme, &[&a.id], None),
+            bulk(BulkAction::SetTheme, &[&a.id], Some("no-such-theme")),
+        ] {
+            assert!(matches!(
+                db.bulk_update_presentations(request).await,
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotate_encryption_key() {
+        let db = test_db().await;
+        let old_key = encryption::derive_key("old-key");
+        for (provider, api_key) in [("anthropic", "sk-ant"), ("openai", "sk-oai")] {
+            let config = CreateAiProviderConfig {
+                provider_name: provider.to_string(),
+                api_key: None,
+                model: None,
+                base_url: None,
+            };
+            db.upsert_ai_provider_config(config, encryption::encrypt_with(&old_key, api_key).unwrap())
+                .await
+                .unwrap();
+        }
+
+        // A wrong old key leaves everything as it was
+        assert!(matches!(
+            db.rotate_encryption_key("wrong-key", "new-key").await,
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(db.rotate_encryption_key("old-key", "new-key").await.unwrap(), 2);
+
+        let new_key = encryption::derive_key("new-key");
+        let configs = db.list_ai_provider_configs().await.unwrap();
+        let api_keys: Vec<String> = configs
+            .iter()
+            .map(|c| encryption::decrypt_with(&new_key, &c.api_key_encrypted).unwrap())
+            .collect();
+        assert_eq!(api_keys, vec!["sk-ant", "sk-oai"]);
+        assert!(encryption::decrypt_with(&old_key, &configs[0].api_key_encrypted).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_creates_get_distinct_names() {
+        // A file database so the pool's connections share state
+        let path = std::env::temp_dir().join(format!("slides-unique-{}.db", Uuid::new_v4()));
+        let db = std::sync::Arc::new(
+            Database::new_with_url(&format!("sqlite:{}?mode=rwc", path.display()))
+                .await
+                .unwrap(),
+        );
+        db.migrate().await.unwrap();
+
+        let creates = (0..8).map(|_| {
+            let db = db.clone();
+            tokio::spawn(async move {
+                db.create_theme(
+                    CreateTheme {
+                        name: "brand".to_string(),
+                        display_name: "Brand".to_string(),
+                        css_content: "[data-theme=\"brand\"] h1 { color: red; }".to_string(),
+                        center_content: None,
+                        fonts: Default::default(),
+                    },
+                    NameConflict::Rename,
+                )
+                .await
+            })
+        });
+
+        let mut names = Vec::new();
+        for create in creates.collect::<Vec<_>>() {
+            names.push(create.await.unwrap().unwrap().name);
+        }
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 8);
+        assert!(names.contains(&"brand".to_string()));
+        assert!(names.contains(&"brand-8".to_string()));
+
+        db.close().await;
+        std::fs::remove_file(path).ok();
     }
 }
@@ -0,0 +1,374 @@
+// Slide-level diffs between two versions of a deck, so a rewrite (by an AI tool, say) can be
+// reviewed before it's accepted. Slides are paired up before anything is diffed: identical
+// slides first, then slides with the same heading, then the most similar of what's left, so
+// an inserted slide shows up as one addition rather than every later slide being modified.
+// Paired slides that differ get a line-level unified diff.
+use std::collections::HashMap;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{DiffPresentationRequest, PresentationDiff, SlideChange, SlideChangeKind};
+use crate::slides;
+use crate::AppState;
+
+// Lines of unchanged context around each change in a unified diff
+const CONTEXT_LINES: usize = 3;
+// Share of words two slides need in common to be treated as the same slide edited
+const MIN_SIMILARITY: f64 = 0.5;
+// Longest slide, in lines, that gets a line-level diff; the LCS table grows with the
+// product of both sides' line counts
+const MAX_DIFF_LINES: usize = 2_000;
+
+/// Diffs what `request` asks for in presentation `id`.
+pub async fn diff_presentation(state: &AppState, id: &str, request: &DiffPresentationRequest) -> AppResult<PresentationDiff> {
+    let presentation = state.db.get_presentation(id).await?;
+
+    let (old, new) = match (&request.content, &request.from_version, &request.to_version) {
+        (Some(content), None, None) => (presentation.content, content.clone()),
+        (None, Some(from), to) => {
+            let old = state.db.get_version(id, from).await?.content;
+            let new = match to {
+                Some(to) => state.db.get_version(id, to).await?.content,
+                None => presentation.content,
+            };
+            (old, new)
+        }
+        (Some(_), _, _) => {
+            return Err(AppError::BadRequest("Provide either content or versions to compare, not both".to_string()))
+        }
+        (None, None, _) => {
+            return Err(AppError::BadRequest("Provide content, or fromVersion (and optionally toVersion)".to_string()))
+        }
+    };
+
+    Ok(diff(&old, &new))
+}
+
+struct Slide {
+    text: String,
+    heading: Option<String>,
+    words: HashMap<String, usize>,
+}
+
+impl Slide {
+    fn new(raw: &str) -> Self {
+        let text = raw.trim_matches('\n').to_string();
+        let heading = slides::heading(&text);
+
+        let mut words = HashMap::new();
+        for word in slides::strip_notes(&text).split_whitespace() {
+            *words.entry(word.to_lowercase()).or_default() += 1;
+        }
+
+        Self { text, heading, words }
+    }
+
+    /// Dice coefficient of the two slides' words.
+    fn similarity(&self, other: &Slide) -> f64 {
+        let total: usize = self.words.values().sum::<usize>() + other.words.values().sum::<usize>();
+        if total == 0 {
+            return 0.0;
+        }
+        let shared: usize = self
+            .words
+            .iter()
+            .map(|(word, count)| (*count).min(other.words.get(word).copied().unwrap_or(0)))
+            .sum();
+        2.0 * shared as f64 / total as f64
+    }
+}
+
+/// Slide-level diff from `old` to `new` deck content.
+pub fn diff(old: &str, new: &str) -> PresentationDiff {
+    let old: Vec<Slide> = slides::split_slides(old).iter().map(|s| Slide::new(s)).collect();
+    let new: Vec<Slide> = slides::split_slides(new).iter().map(|s| Slide::new(s)).collect();
+    let pairs = align(&old, &new);
+
+    let mut old_to_new = vec![None; old.len()];
+    for (j, i) in pairs.iter().enumerate() {
+        if let Some(i) = *i {
+            old_to_new[i] = Some(j);
+        }
+    }
+
+    // Sort key: position in the new deck, with removed slides just after the slide that
+    // preceded them in the old deck
+    let mut changes: Vec<((usize, usize, usize), SlideChange)> = Vec::new();
+    let mut unchanged = 0;
+
+    for (j, slide) in new.iter().enumerate() {
+        let change = match pairs[j] {
+            Some(i) if old[i].text == slide.text => {
+                unchanged += 1;
+                continue;
+            }
+            Some(i) => SlideChange {
+                kind: SlideChangeKind::Modified,
+                old_index: Some(i),
+                new_index: Some(j),
+                heading: slide.heading.clone(),
+                diff: unified_diff(Some((i, &old[i].text)), Some((j, &slide.text))),
+            },
+            None => SlideChange {
+                kind: SlideChangeKind::Added,
+                old_index: None,
+                new_index: Some(j),
+                heading: slide.heading.clone(),
+                diff: unified_diff(None, Some((j, &slide.text))),
+            },
+        };
+        changes.push(((j, 1, 0), change));
+    }
+
+    let mut position = 0;
+    for (i, slide) in old.iter().enumerate() {
+        match old_to_new[i] {
+            Some(j) => position = j + 1,
+            None => changes.push((
+                (position, 0, i),
+                SlideChange {
+                    kind: SlideChangeKind::Removed,
+                    old_index: Some(i),
+                    new_index: None,
+                    heading: slide.heading.clone(),
+                    diff: unified_diff(Some((i, &slide.text)), None),
+                },
+            )),
+        }
+    }
+
+    changes.sort_by_key(|(key, _)| *key);
+    let changes: Vec<SlideChange> = changes.into_iter().map(|(_, change)| change).collect();
+    let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+
+    PresentationDiff {
+        added: count(SlideChangeKind::Added),
+        removed: count(SlideChangeKind::Removed),
+        modified: count(SlideChangeKind::Modified),
+        unchanged,
+        changes,
+    }
+}
+
+/// For each new slide, the old slide it's a version of, if any.
+fn align(old: &[Slide], new: &[Slide]) -> Vec<Option<usize>> {
+    let mut pairs: Vec<Option<usize>> = vec![None; new.len()];
+    let mut taken = vec![false; old.len()];
+
+    let mut pair_by = |pairs: &mut Vec<Option<usize>>, same: &dyn Fn(&Slide, &Slide) -> bool| {
+        for (j, slide) in new.iter().enumerate() {
+            if pairs[j].is_some() {
+                continue;
+            }
+            if let Some(i) = (0..old.len()).find(|&i| !taken[i] && same(&old[i], slide)) {
+                taken[i] = true;
+                pairs[j] = Some(i);
+            }
+        }
+    };
+    pair_by(&mut pairs, &|a, b| a.text == b.text);
+    pair_by(&mut pairs, &|a, b| {
+        a.heading.is_some() && a.heading.as_deref().map(str::to_lowercase) == b.heading.as_deref().map(str::to_lowercase)
+    });
+
+    // Most similar pairs first; ties go to slides at nearby positions
+    let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+    for (j, slide) in new.iter().enumerate().filter(|(j, _)| pairs[*j].is_none()) {
+        for (i, other) in old.iter().enumerate().filter(|(i, _)| !taken[*i]) {
+            let similarity = slide.similarity(other);
+            if similarity >= MIN_SIMILARITY {
+                candidates.push((similarity, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.abs_diff(a.2).cmp(&b.1.abs_diff(b.2))));
+    for (_, i, j) in candidates {
+        if !taken[i] && pairs[j].is_none() {
+            taken[i] = true;
+            pairs[j] = Some(i);
+        }
+    }
+
+    pairs
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Unified diff of one slide. Either side may be missing, for added and removed slides.
+fn unified_diff(old: Option<(usize, &str)>, new: Option<(usize, &str)>) -> String {
+    let label = |side: Option<(usize, &str)>| match side {
+        Some((index, _)) => format!("slide {}", index + 1),
+        None => "/dev/null".to_string(),
+    };
+
+    let a: Vec<&str> = old.map(|(_, text)| text.lines().collect()).unwrap_or_default();
+    let b: Vec<&str> = new.map(|(_, text)| text.lines().collect()).unwrap_or_default();
+    let lines = diff_lines(&a, &b);
+
+    let mut out = format!("--- {}\n+++ {}\n", label(old), label(new));
+    for (start, end) in hunks(&lines) {
+        // Line numbers where the hunk starts on each side
+        let before = &lines[..start];
+        let old_start = before.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+        let new_start = before.iter().filter(|l| !matches!(l, Line::Removed(_))).count();
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+        let new_len = hunk.iter().filter(|l| !matches!(l, Line::Removed(_))).count();
+
+        // An empty side is numbered from the line before it, as diff(1) does
+        let range = |start: usize, len: usize| if len == 0 { format!("{},0", start) } else { format!("{},{}", start + 1, len) };
+        out.push_str(&format!("@@ -{} +{} @@\n", range(old_start, old_len), range(new_start, new_len)));
+        for line in hunk {
+            let (marker, text) = match line {
+                Line::Same(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            out.push(marker);
+            out.push_str(text);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Line edit script from `a` to `b` via longest common subsequence. Past `MAX_DIFF_LINES`
+/// on either side, every line of `a` is removed and every line of `b` added instead.
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Line<'a>> {
+    if a.len() > MAX_DIFF_LINES || b.len() > MAX_DIFF_LINES {
+        return a.iter().map(|line| Line::Removed(line)).chain(b.iter().map(|line| Line::Added(line))).collect();
+    }
+
+    // lcs[i][j]: length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(a.len() + b.len());
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(Line::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] > lcs[i + 1][j]) {
+            lines.push(Line::Added(b[j]));
+            j += 1;
+        } else {
+            lines.push(Line::Removed(a[i]));
+            i += 1;
+        }
+    }
+    lines
+}
+
+/// Ranges of `lines` to print: each change with its context, merging changes whose context
+/// overlaps.
+fn hunks(lines: &[Line]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        if matches!(line, Line::Same(_)) {
+            continue;
+        }
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + 1 + CONTEXT_LINES).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(diff: &PresentationDiff) -> Vec<(SlideChangeKind, Option<usize>, Option<usize>)> {
+        diff.changes.iter().map(|c| (c.kind, c.old_index, c.new_index)).collect()
+    }
+
+    #[test]
+    fn test_insertions_dont_shift_later_slides() {
+        let old = "# Intro\nWelcome\n\n---\n\n# Plan\n- one\n- two\n\n---\n\n# Thanks";
+        let new = "# Intro\nWelcome\n\n---\n\n# Agenda\nNew slide\n\n---\n\n# Plan\n- one\n- two\n- three\n\n---\n\n# Thanks";
+
+        let diff = diff(old, new);
+        assert_eq!(
+            kinds(&diff),
+            [(SlideChangeKind::Added, None, Some(1)), (SlideChangeKind::Modified, Some(1), Some(2))]
+        );
+        assert_eq!((diff.added, diff.removed, diff.modified, diff.unchanged), (1, 0, 1, 2));
+        assert_eq!(diff.changes[1].heading.as_deref(), Some("Plan"));
+        assert_eq!(
+            diff.changes[1].diff,
+            "--- slide 2\n+++ slide 3\n@@ -1,3 +1,4 @@\n # Plan\n - one\n - two\n+- three\n"
+        );
+        assert_eq!(diff.changes[0].diff, "--- /dev/null\n+++ slide 2\n@@ -0,0 +1,2 @@\n+# Agenda\n+New slide\n");
+    }
+
+    #[test]
+    fn test_renamed_slides_pair_by_content() {
+        let old = "# Results\nRevenue grew by ten percent this quarter across regions\n\n---\n\n# Old slide\nTo be removed";
+        let new = "# Outcome\nRevenue grew by twelve percent this quarter across regions\n\n---\n\n# Fresh\nSomething else entirely";
+
+        let diff = diff(old, new);
+        assert_eq!(
+            kinds(&diff),
+            [
+                (SlideChangeKind::Modified, Some(0), Some(0)),
+                (SlideChangeKind::Removed, Some(1), None),
+                (SlideChangeKind::Added, None, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removed_slides_keep_their_place() {
+        let old = "# A\n\n---\n\n# B\n\n---\n\n# C";
+        let new = "# C\n\n---\n\n# A";
+
+        let diff = diff(old, new);
+        // Moving a slide isn't a change
+        assert_eq!(kinds(&diff), [(SlideChangeKind::Removed, Some(1), None)]);
+        assert_eq!(diff.unchanged, 2);
+        assert_eq!(diff.changes[0].heading.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old: Vec<String> = (1..=12).map(|i| format!("line {}", i)).collect();
+        let mut new = old.clone();
+        new[1] = "changed 2".to_string();
+        new.remove(10);
+
+        let diff = unified_diff(Some((0, &old.join("\n"))), Some((0, &new.join("\n"))));
+        assert_eq!(
+            diff,
+            "--- slide 1\n+++ slide 1\n\
+             @@ -1,5 +1,5 @@\n line 1\n-line 2\n+changed 2\n line 3\n line 4\n line 5\n\
+             @@ -8,5 +8,4 @@\n line 8\n line 9\n line 10\n-line 11\n line 12\n"
+        );
+    }
+
+    #[test]
+    fn test_long_slides_are_replaced_whole() {
+        let old: Vec<String> = (0..=MAX_DIFF_LINES).map(|i| format!("line {}", i)).collect();
+        let mut new = old.clone();
+        new[0] = "changed".to_string();
+
+        let a: Vec<&str> = old.iter().map(String::as_str).collect();
+        let b: Vec<&str> = new.iter().map(String::as_str).collect();
+        let lines = diff_lines(&a, &b);
+        assert_eq!(lines.len(), 2 * old.len());
+        assert!(lines[..old.len()].iter().all(|line| matches!(line, Line::Removed(_))));
+        assert!(lines[old.len()..].iter().all(|line| matches!(line, Line::Added(_))));
+    }
+}
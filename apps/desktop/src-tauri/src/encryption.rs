@@ -4,14 +4,29 @@ use aes_gcm::{
 };
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::Rng;
+use std::sync::RwLock;
 
 use crate::error::{AppError, AppResult};
 
 const KEY_ENV: &str = "SLIDES_ENCRYPTION_KEY";
 const NONCE_SIZE: usize = 12;
 
-fn get_key() -> [u8; 32] {
-    let key_str = std::env::var(KEY_ENV).unwrap_or_else(|_| "slides-desktop-default-key-32b!".to_string());
+// Set by a key rotation; takes precedence over the environment until the app restarts
+static ROTATED_KEY: RwLock<Option<String>> = RwLock::new(None);
+
+/// Held for writing while a rotation re-encrypts stored keys, and for reading while a key is
+/// encrypted and saved, so nothing gets saved under a key that is being replaced.
+pub static KEY_ROTATION: tokio::sync::RwLock<()> = tokio::sync::RwLock::const_new(());
+
+fn current_key() -> String {
+    if let Some(key) = ROTATED_KEY.read().unwrap_or_else(|e| e.into_inner()).clone() {
+        return key;
+    }
+    std::env::var(KEY_ENV).unwrap_or_else(|_| "slides-desktop-default-key-32b!".to_string())
+}
+
+/// The cipher key for a configured key string: its first 32 bytes, zero-padded.
+pub fn derive_key(key_str: &str) -> [u8; 32] {
     let mut key = [0u8; 32];
     let bytes = key_str.as_bytes();
     let len = bytes.len().min(32);
@@ -19,9 +34,32 @@ fn get_key() -> [u8; 32] {
     key
 }
 
+fn get_key() -> [u8; 32] {
+    derive_key(&current_key())
+}
+
+/// Whether `key_str` is the key currently used for encryption.
+pub fn is_current_key(key_str: &str) -> bool {
+    let (candidate, current) = (derive_key(key_str), get_key());
+    // Compare every byte so the time taken doesn't reveal how much matched
+    candidate.iter().zip(current.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Switches to `key_str` after the stored data has been re-encrypted with it.
+pub fn set_current_key(key_str: &str) {
+    *ROTATED_KEY.write().unwrap_or_else(|e| e.into_inner()) = Some(key_str.to_string());
+}
+
 pub fn encrypt(plaintext: &str) -> AppResult<String> {
-    let key = get_key();
-    let cipher = Aes256Gcm::new_from_slice(&key)
+    encrypt_with(&get_key(), plaintext)
+}
+
+pub fn decrypt(encrypted: &str) -> AppResult<String> {
+    decrypt_with(&get_key(), encrypted)
+}
+
+pub fn encrypt_with(key: &[u8; 32], plaintext: &str) -> AppResult<String> {
+    let cipher = Aes256Gcm::new_from_slice(key)
         .map_err(|e| AppError::Internal(format!("Failed to create cipher: {}", e)))?;
 
     let mut nonce_bytes = [0u8; NONCE_SIZE];
@@ -38,9 +76,8 @@ pub fn encrypt(plaintext: &str) -> AppResult<String> {
     Ok(BASE64.encode(combined))
 }
 
-pub fn decrypt(encrypted: &str) -> AppResult<String> {
-    let key = get_key();
-    let cipher = Aes256Gcm::new_from_slice(&key)
+pub fn decrypt_with(key: &[u8; 32], encrypted: &str) -> AppResult<String> {
+    let cipher = Aes256Gcm::new_from_slice(key)
         .map_err(|e| AppError::Internal(format!("Failed to create cipher: {}", e)))?;
 
     let combined = BASE64
@@ -73,4 +110,15 @@ mod tests {
         let decrypted = decrypt(&encrypted).unwrap();
         assert_eq!(original, decrypted);
     }
+
+    #[test]
+    fn test_keys_are_not_interchangeable() {
+        let (old, new) = (derive_key("old-key"), derive_key("new-key"));
+        let encrypted = encrypt_with(&old, "my-secret-api-key").unwrap();
+        assert!(decrypt_with(&new, &encrypted).is_err());
+        assert_eq!(decrypt_with(&old, &encrypted).unwrap(), "my-secret-api-key");
+
+        assert!(is_current_key(&current_key()));
+        assert!(!is_current_key("not-the-key"));
+    }
 }
@@ -3,12 +3,13 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use serde::Serialize;
 use serde_json::json;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
     #[error("Database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(sqlx::Error),
 
     #[error("Not found: {0}")]
     NotFound(String),
@@ -16,11 +17,28 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// Input that failed validation, with what's wrong with each field
+    #[error("Validation failed: {}", describe(.0))]
+    Validation(Vec<FieldError>),
+
     #[error("Forbidden: {0}")]
     Forbidden(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Too large: {0}")]
+    TooLarge(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
+
+    /// A call to an AI provider failed
+    #[error("AI provider error: {message}")]
+    Provider { kind: ProviderErrorKind, message: String },
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
 }
 
 impl IntoResponse for AppError {
@@ -29,13 +47,172 @@ impl IntoResponse for AppError {
             AppError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::TooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg.clone()),
+            AppError::Internal(msg) | AppError::Provider { message: msg, .. } => {
+                (StatusCode::INTERNAL_SERVER_ERROR, msg.clone())
+            }
+            AppError::Unavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
         };
 
-        let body = Json(json!({ "error": message }));
-        (status, body).into_response()
+        let mut body = json!({ "error": message });
+        if let AppError::Validation(errors) = &self {
+            body["errors"] = json!(errors);
+        }
+        if let Some(trace_id) = crate::trace::current() {
+            body["traceId"] = json!(trace_id);
+        }
+        (status, Json(body)).into_response()
     }
 }
 
 pub type AppResult<T> = Result<T, AppError>;
+
+/// UNIQUE violations (SQLITE_CONSTRAINT_UNIQUE, 2067, or a duplicate primary key) become
+/// `Conflict`, naming the constrained columns; everything else is a `Database` error.
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if crate::db::is_unique_violation(&e) => {
+                // SQLite reports "UNIQUE constraint failed: table.column[, table.column...]"
+                let message = db.message();
+                let columns = message.split_once("constraint failed: ").map_or(message, |(_, columns)| columns);
+                AppError::Conflict(format!("An entry with the same {} already exists", columns))
+            }
+            _ => AppError::Database(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+/// Client errors from the remote end keep their meaning (404 as `NotFound`, other 4xx as
+/// `BadRequest`); failed connections and 5xx responses are internal.
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(reqwest::StatusCode::NOT_FOUND) => AppError::NotFound(e.to_string()),
+            Some(status) if status.is_client_error() => AppError::BadRequest(e.to_string()),
+            _ => AppError::Internal(e.to_string()),
+        }
+    }
+}
+
+/// Why a call to an AI provider failed, so the settings screen can tell a rejected key from
+/// a provider it can't reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderErrorKind {
+    /// The provider rejected the API key
+    Auth,
+    /// The provider couldn't be reached or the connection dropped
+    Network,
+    /// Any other error reported by the provider
+    Provider,
+}
+
+/// A problem with one input field, named as the client sent it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Collects field errors so a client learns about every bad field at once.
+#[derive(Debug, Default)]
+pub struct ValidationBuilder {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an error for `field`.
+    pub fn error(&mut self, field: &str, message: impl Into<String>) -> &mut Self {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Records an error for `field` unless `valid` holds.
+    pub fn check(&mut self, valid: bool, field: &str, message: impl Into<String>) -> &mut Self {
+        if !valid {
+            self.error(field, message);
+        }
+        self
+    }
+
+    /// Ok if nothing was recorded, otherwise a `Validation` error with everything that was.
+    pub fn finish(&mut self) -> AppResult<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(std::mem::take(&mut self.errors)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validation_errors_list_every_field() {
+        let mut validation = ValidationBuilder::new();
+        validation
+            .check(true, "title", "must not be empty")
+            .check(false, "apiKey", "must not be empty")
+            .error("baseUrl", "must be an http or https URL");
+        let error = validation.finish().unwrap_err();
+        assert_eq!(error.to_string(), "Validation failed: apiKey: must not be empty; baseUrl: must be an http or https URL");
+
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["errors"], json!([
+            { "field": "apiKey", "message": "must not be empty" },
+            { "field": "baseUrl", "message": "must be an http or https URL" },
+        ]));
+
+        assert!(ValidationBuilder::new().check(true, "title", "must not be empty").finish().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unique_violation_is_conflict() {
+        let pool = sqlx::SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query("CREATE TABLE themes (id TEXT PRIMARY KEY, name TEXT NOT NULL UNIQUE)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let insert = |id: &'static str| sqlx::query("INSERT INTO themes (id, name) VALUES (?, 'ocean')").bind(id).execute(&pool);
+        insert("1").await.unwrap();
+
+        let error = AppError::from(insert("2").await.unwrap_err());
+        assert!(matches!(&error, AppError::Conflict(msg) if msg == "An entry with the same themes.name already exists"));
+        assert_eq!(error.into_response().status(), StatusCode::CONFLICT);
+
+        // Other database errors stay internal
+        let error = AppError::from(sqlx::query("SELECT * FROM missing").execute(&pool).await.unwrap_err());
+        assert!(matches!(error, AppError::Database(_)));
+        assert_eq!(error.into_response().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
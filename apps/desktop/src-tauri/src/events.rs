@@ -0,0 +1,389 @@
+// Change notifications. Every write through `Database` publishes one `AppEvent` once it has
+// committed, and the desktop UI, MCP sessions and caches subscribe to the bus instead of
+// polling. Publishing never waits on subscribers: the bus is a bounded broadcast channel, so a
+// subscriber that falls behind loses the oldest events (and is told how many) rather than
+// holding up the writer. Derived or transient writes (health scores, the model cache, upload
+// sessions) don't publish.
+use std::future::Future;
+
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+// Events buffered per subscriber before the slowest one starts missing them
+const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum AppEvent {
+    PresentationCreated { id: String },
+    /// Content, metadata, tags, archive/favorite flags or last-opened time changed
+    PresentationUpdated { id: String },
+    /// Moved to the trash, or removed for good when `permanent`
+    PresentationDeleted { id: String, permanent: bool },
+    PresentationRestored { id: String },
+    MediaAdded { id: String },
+    MediaDeleted { id: String },
+    /// A theme was created, updated or deleted
+    ThemeChanged { id: String },
+    LayoutRulesChanged,
+    PipelineChanged { id: String },
+    /// A provider's config was saved or removed, or the stored keys were re-encrypted
+    AiProvidersChanged,
+    WatchlistChanged { presentation_id: String },
+    SettingsChanged { key: String },
+    /// A step of a long-running job finished
+    JobProgress { job: String, completed: usize, total: usize },
+    ProfileChanged { profile: String },
+    /// Handed to a `register`ed handler in place of `count` events it fell too far behind to
+    /// receive. Anything derived from events should be rebuilt from scratch.
+    EventsMissed { count: u64 },
+}
+
+impl AppEvent {
+    /// Whether the event changes the set or content of presentations.
+    pub fn affects_presentations(&self) -> bool {
+        matches!(
+            self,
+            AppEvent::PresentationCreated { .. }
+                | AppEvent::PresentationUpdated { .. }
+                | AppEvent::PresentationDeleted { .. }
+                | AppEvent::PresentationRestored { .. }
+                | AppEvent::ProfileChanged { .. }
+                | AppEvent::EventsMissed { .. }
+        )
+    }
+}
+
+/// Fan-out of `AppEvent`s. Clones share the same channel.
+#[derive(Clone)]
+pub struct EventBus(broadcast::Sender<AppEvent>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+}
+
+impl EventBus {
+    /// Sends `event` to every current subscriber without waiting for any of them.
+    pub fn publish(&self, event: AppEvent) {
+        tracing::debug!("Event: {:?}", event);
+        // Only fails when nobody is subscribed
+        let _ = self.0.send(event);
+    }
+
+    /// A receiver for events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.0.subscribe()
+    }
+
+    /// Runs `handler` on a background task for every event published from now on. Events are
+    /// handled one at a time; if the handler can't keep up, the ones it missed are skipped and
+    /// it gets one `AppEvent::EventsMissed` instead. The task ends when the returned handle is
+    /// aborted.
+    pub fn register<F, Fut>(&self, name: &'static str, mut handler: F) -> JoinHandle<()>
+    where
+        F: FnMut(AppEvent) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut events = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => handler(event).await,
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("Event subscriber {} fell behind and missed {} events", name, missed);
+                        handler(AppEvent::EventsMissed { count: missed }).await
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::encryption;
+    use crate::media::MediaMetadata;
+    use crate::models::*;
+    use std::time::Duration;
+
+    async fn db() -> Database {
+        let db = Database::new_with_url("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        db
+    }
+
+    /// The events published since the last call.
+    fn drain(events: &mut broadcast::Receiver<AppEvent>) -> Vec<AppEvent> {
+        std::iter::from_fn(|| events.try_recv().ok()).collect()
+    }
+
+    fn deck(title: &str) -> CreatePresentation {
+        CreatePresentation {
+            title: title.to_string(),
+            content: Some("# Hello".to_string()),
+            theme: None,
+            if_not_exists: false,
+            on_conflict: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_presentation_mutations_publish_one_event() {
+        let db = db().await;
+        let mut events = db.events().subscribe();
+        let updated = |id: &str| vec![AppEvent::PresentationUpdated { id: id.to_string() }];
+
+        let deck = db.create_presentation(deck("Deck")).await.unwrap();
+        let id = deck.id.as_str();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationCreated { id: id.to_string() }]);
+
+        // Returning the existing deck writes nothing
+        let mut again = self::deck("Deck");
+        again.on_conflict = Some(TitleConflict::Return);
+        db.create_presentation(again).await.unwrap();
+        assert_eq!(drain(&mut events), []);
+
+        let copy = db.duplicate_presentation(id, None).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationCreated { id: copy.id.clone() }]);
+
+        db.update_presentation(id, UpdatePresentation {
+            title: Some("Renamed".to_string()),
+            content: None,
+            theme: None,
+            settings: None,
+        })
+        .await
+        .unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+
+        db.insert_slide(id, None, "# Two").await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.replace_slide(id, 1, "# Second").await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.reorder_slides(id, &[1, 0]).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.delete_slide(id, 0).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.stage_inbox_slides(id, &["# News".to_string()]).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+
+        let version = db.list_versions(id).await.unwrap().remove(0);
+        db.restore_version(id, &version.id).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+
+        db.set_archived(id, true).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.set_favorite(id, true).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.touch_presentation(id).await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.add_tag_to_presentation(id, "demo").await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+        db.remove_tag_from_presentation(id, "demo").await.unwrap();
+        assert_eq!(drain(&mut events), updated(id));
+
+        db.delete_presentation(id).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationDeleted { id: id.to_string(), permanent: false }]);
+        db.restore_presentation(id).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationRestored { id: id.to_string() }]);
+        db.delete_presentation_permanently(id).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationDeleted { id: id.to_string(), permanent: true }]);
+
+        // Failed writes publish nothing
+        assert!(db.set_archived(id, false).await.is_err());
+        assert!(db.delete_presentation(id).await.is_err());
+        assert_eq!(drain(&mut events), []);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_updates_publish_per_applied_id() {
+        let db = db().await;
+        let a = db.create_presentation(deck("A")).await.unwrap().id;
+        let b = db.create_presentation(deck("B")).await.unwrap().id;
+        let mut events = db.events().subscribe();
+
+        let bulk = |action, ids: &[&str]| BulkPresentations {
+            action,
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+            theme: None,
+        };
+
+        // All-or-nothing actions that roll back publish nothing
+        db.bulk_update_presentations(bulk(BulkAction::Archive, &[&a, "missing"])).await.unwrap();
+        assert_eq!(drain(&mut events), []);
+
+        db.bulk_update_presentations(bulk(BulkAction::Archive, &[&a, &b])).await.unwrap();
+        assert_eq!(
+            drain(&mut events),
+            [AppEvent::PresentationUpdated { id: a.clone() }, AppEvent::PresentationUpdated { id: b.clone() }]
+        );
+
+        db.bulk_update_presentations(bulk(BulkAction::Delete, &[&a, "missing", &a])).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::PresentationDeleted { id: a, permanent: false }]);
+    }
+
+    #[tokio::test]
+    async fn test_other_mutations_publish_one_event() {
+        let db = db().await;
+        let deck = db.create_presentation(deck("Deck")).await.unwrap();
+        let mut events = db.events().subscribe();
+
+        let theme = db
+            .create_theme(
+                CreateTheme {
+                    name: "brand".to_string(),
+                    display_name: "Brand".to_string(),
+                    css_content: "[data-theme=\"brand\"] section { color: red; }".to_string(),
+                    center_content: None,
+                    fonts: Default::default(),
+                },
+                NameConflict::Error,
+            )
+            .await
+            .unwrap();
+        let theme_changed = || vec![AppEvent::ThemeChanged { id: theme.id.clone() }];
+        assert_eq!(drain(&mut events), theme_changed());
+        db.update_theme(&theme.id, UpdateTheme { display_name: Some("Brand 2".to_string()), css_content: None, center_content: None, fonts: None })
+            .await
+            .unwrap();
+        assert_eq!(drain(&mut events), theme_changed());
+        db.delete_theme(&theme.id, false).await.unwrap();
+        assert_eq!(drain(&mut events), theme_changed());
+
+        let rule = db
+            .create_layout_rule(
+                "rule".to_string(),
+                "Rule".to_string(),
+                None,
+                50,
+                "{}".to_string(),
+                "{}".to_string(),
+                String::new(),
+            )
+            .await
+            .unwrap();
+        db.delete_layout_rule(&rule.id).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::LayoutRulesChanged, AppEvent::LayoutRulesChanged]);
+
+        let pipeline = db
+            .create_pipeline(CreatePipeline { name: "p".to_string(), description: None, steps: vec![] })
+            .await
+            .unwrap();
+        db.update_pipeline(&pipeline.id, UpdatePipeline { name: None, description: Some("d".to_string()), steps: None })
+            .await
+            .unwrap();
+        db.delete_pipeline(&pipeline.id).await.unwrap();
+        assert_eq!(drain(&mut events), vec![AppEvent::PipelineChanged { id: pipeline.id.clone() }; 3]);
+
+        let source = db
+            .create_watchlist_source(&deck.id, CreateWatchlistSource { url: "https://example.com/feed".to_string(), title: None })
+            .await
+            .unwrap();
+        db.update_watchlist_source(&deck.id, &source.id, UpdateWatchlistSource { url: None, title: Some("Feed".to_string()) })
+            .await
+            .unwrap();
+        db.delete_watchlist_source(&deck.id, &source.id).await.unwrap();
+        assert_eq!(
+            drain(&mut events),
+            vec![AppEvent::WatchlistChanged { presentation_id: deck.id.clone() }; 3]
+        );
+
+        let key = encryption::encrypt_with(&encryption::derive_key("old-key"), "sk-test").unwrap();
+        let config = CreateAiProviderConfig { provider_name: "openai".to_string(), api_key: None, model: None, base_url: None };
+        let config = db.upsert_ai_provider_config(config, key).await.unwrap();
+        db.update_ai_provider_config(&config.id, Some("gpt".to_string()), None, None).await.unwrap();
+        db.rotate_encryption_key("old-key", "new-key").await.unwrap();
+        db.delete_ai_provider_config(&config.id).await.unwrap();
+        assert_eq!(drain(&mut events), vec![AppEvent::AiProvidersChanged; 4]);
+
+        let add = |hash: &str| {
+            db.create_media(
+                format!("{}.png", hash),
+                "logo.png".to_string(),
+                "image/png".to_string(),
+                3,
+                format!("/api/uploads/{}.png", hash),
+                MediaMetadata::default(),
+                hash.to_string(),
+            )
+        };
+        let media = add("abc").await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::MediaAdded { id: media.id.clone() }]);
+        // Deduplicated uploads add nothing
+        add("abc").await.unwrap();
+        assert_eq!(drain(&mut events), []);
+        db.delete_media(&media.id).await.unwrap();
+        assert_eq!(drain(&mut events), [AppEvent::MediaDeleted { id: media.id.clone() }]);
+
+        db.set_mcp_tool_settings(&McpToolSettings::default()).await.unwrap();
+        db.set_health_weights(&HealthWeights::default()).await.unwrap();
+        assert_eq!(
+            drain(&mut events),
+            [
+                AppEvent::SettingsChanged { key: "mcp_tools".to_string() },
+                AppEvent::SettingsChanged { key: "health_weights".to_string() },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscribers_dont_block_publishers() {
+        let bus = EventBus::default();
+        let mut idle = bus.subscribe();
+        let (handled, mut handled_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (release, released) = tokio::sync::watch::channel(false);
+        bus.register("slow", move |event| {
+            let handled = handled.clone();
+            let mut released = released.clone();
+            async move {
+                let _ = released.wait_for(|released| *released).await;
+                let _ = handled.send(event);
+            }
+        });
+
+        // Neither the idle receiver nor the stalled handler hold up publishing
+        let total = CAPACITY * 2;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            for i in 0..total {
+                bus.publish(AppEvent::PresentationUpdated { id: i.to_string() });
+            }
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(idle.try_recv(), Err(broadcast::error::TryRecvError::Lagged(_))));
+
+        // The stalled handler skips what it missed and carries on with the rest
+        release.send(true).unwrap();
+        let last = AppEvent::PresentationUpdated { id: (total - 1).to_string() };
+        let mut count = 0;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while let Some(event) = handled_rx.recv().await {
+                count += 1;
+                if event == last {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(count < total);
+    }
+
+    #[test]
+    fn test_events_serialize_with_type_tag() {
+        let event = AppEvent::PresentationDeleted { id: "p1".to_string(), permanent: true };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "type": "presentationDeleted", "id": "p1", "permanent": true })
+        );
+        let event = AppEvent::WatchlistChanged { presentation_id: "p1".to_string() };
+        assert_eq!(serde_json::to_value(&event).unwrap()["presentationId"], "p1");
+    }
+}
@@ -0,0 +1,174 @@
+// Deck health: a 0-100 score summarizing problems found in a presentation, with the findings
+// that make it up. Each finding costs its kind's weight (see `HealthWeights`).
+use std::collections::HashMap;
+
+use crate::models::{DeckHealth, HealthFinding, HealthFindingKind, HealthWeights};
+use crate::placeholders;
+use crate::slides;
+use crate::uploads;
+
+// Slides with more words than this are hard to read from the back of the room
+const MAX_SLIDE_WORDS: i64 = 120;
+// Media larger than this slows down loading and exported bundles
+const MAX_MEDIA_BYTES: i64 = 5 * 1024 * 1024;
+
+/// Scores `content`. `media_sizes` maps upload filenames to their size in bytes.
+pub fn assess(content: &str, media_sizes: &HashMap<String, i64>, weights: &HealthWeights) -> DeckHealth {
+    let mut findings = Vec::new();
+
+    for (index, slide) in slides::split_slides(content).iter().enumerate() {
+        let stats = slides::deck_stats(slide);
+
+        if slide.trim().is_empty() {
+            findings.push(finding(HealthFindingKind::EmptySlide, index, "Slide is empty".to_string()));
+            continue;
+        }
+        if !stats.has_speaker_notes {
+            findings.push(finding(HealthFindingKind::MissingNotes, index, "Slide has no speaker notes".to_string()));
+        }
+        if stats.word_count > MAX_SLIDE_WORDS {
+            findings.push(finding(
+                HealthFindingKind::LongSlide,
+                index,
+                format!("Slide has {} words (more than {})", stats.word_count, MAX_SLIDE_WORDS),
+            ));
+        }
+
+        for target in images_without_alt(slide) {
+            findings.push(finding(
+                HealthFindingKind::MissingAltText,
+                index,
+                format!("Image {} has no alt text", target),
+            ));
+        }
+
+        for filename in upload_references(slide) {
+            if let Some(&size) = media_sizes.get(filename).filter(|&&size| size > MAX_MEDIA_BYTES) {
+                findings.push(finding(
+                    HealthFindingKind::OversizedMedia,
+                    index,
+                    format!("{} is {:.1} MB", filename, size as f64 / (1024.0 * 1024.0)),
+                ));
+            }
+        }
+    }
+
+    for placeholder in placeholders::find_placeholders(content) {
+        for slide in placeholder.slides {
+            findings.push(finding(
+                HealthFindingKind::UnfilledPlaceholder,
+                slide,
+                format!("Placeholder {{{{{}}}}} is not filled in", placeholder.name),
+            ));
+        }
+    }
+    findings.sort_by_key(|f| f.slide);
+
+    let penalty: f64 = findings.iter().map(|f| weights.weight(f.kind)).sum();
+    DeckHealth {
+        score: (100.0 - penalty).clamp(0.0, 100.0).round() as i64,
+        findings,
+    }
+}
+
+fn finding(kind: HealthFindingKind, slide: usize, message: String) -> HealthFinding {
+    HealthFinding { kind, slide, message }
+}
+
+/// Targets of `![](target)` images whose alt text is blank.
+fn images_without_alt(slide: &str) -> Vec<&str> {
+    let mut targets = Vec::new();
+    let mut rest = slide;
+
+    while let Some(open) = rest.find("![") {
+        rest = &rest[open + 2..];
+        let Some(close) = rest.find("](") else { break };
+        let alt = &rest[..close];
+        let after = &rest[close + 2..];
+        let Some(end) = after.find(')') else { break };
+
+        if alt.trim().is_empty() {
+            targets.push(after[..end].trim());
+        }
+        rest = &after[end..];
+    }
+
+    targets
+}
+
+/// Filenames of uploads referenced from a slide.
+pub fn upload_references(slide: &str) -> Vec<&str> {
+    uploads::find_upload_prefixes(slide)
+        .into_iter()
+        .map(|(_, start)| {
+            let rest = &slide[start..];
+            let end = rest
+                .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '\'' | '>'))
+                .unwrap_or(rest.len());
+            &rest[..end]
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTES: &str = "<!-- notes -->\nIntro\n<!-- /notes -->";
+
+    fn kinds(health: &DeckHealth) -> Vec<(HealthFindingKind, usize)> {
+        health.findings.iter().map(|f| (f.kind, f.slide)).collect()
+    }
+
+    #[test]
+    fn test_healthy_deck_scores_full() {
+        let deck = format!("# Welcome\n\n{}\n\n---\n\n![Team photo](/api/uploads/team.png)\n\n{}", NOTES, NOTES);
+        let sizes = HashMap::from([("team.png".to_string(), 200_000)]);
+        let health = assess(&deck, &sizes, &HealthWeights::default());
+        assert_eq!(health.score, 100);
+        assert!(health.findings.is_empty());
+    }
+
+    #[test]
+    fn test_findings_point_at_slides() {
+        let long = "word ".repeat(150);
+        let deck = format!(
+            "# {{{{title}}}}\n\n{}\n\n---\n\n![](/api/v1/uploads/huge.mp4)\n\n---\n\n{}\n\n{}\n\n---\n\n",
+            NOTES, long, NOTES
+        );
+        let sizes = HashMap::from([("huge.mp4".to_string(), 50 * 1024 * 1024)]);
+        let weights = HealthWeights::default();
+        let health = assess(&deck, &sizes, &weights);
+
+        assert_eq!(
+            kinds(&health),
+            vec![
+                (HealthFindingKind::UnfilledPlaceholder, 0),
+                (HealthFindingKind::MissingNotes, 1),
+                (HealthFindingKind::MissingAltText, 1),
+                (HealthFindingKind::OversizedMedia, 1),
+                (HealthFindingKind::LongSlide, 2),
+                (HealthFindingKind::EmptySlide, 3),
+            ]
+        );
+        let penalty: f64 = health.findings.iter().map(|f| weights.weight(f.kind)).sum();
+        assert_eq!(health.score, (100.0 - penalty) as i64);
+    }
+
+    #[test]
+    fn test_weights_are_configurable() {
+        let deck = "# No notes here";
+        let ignore_notes = HealthWeights {
+            missing_notes: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(assess(deck, &HashMap::new(), &ignore_notes).score, 100);
+
+        let strict = HealthWeights {
+            missing_notes: 150.0,
+            ..Default::default()
+        };
+        assert_eq!(assess(deck, &HashMap::new(), &strict).score, 0);
+    }
+}
@@ -2,18 +2,108 @@
 pub mod ai;
 pub mod api;
 pub mod db;
+pub mod diff;
 pub mod encryption;
 pub mod error;
+pub mod events;
+pub mod health;
+pub mod lint;
+pub mod markdown;
 pub mod mcp;
+pub mod media;
+pub mod middleware;
 pub mod models;
+pub mod palette;
+pub mod pipeline;
+pub mod placeholders;
+pub mod profiles;
+pub mod quick_search;
+pub mod related;
+pub mod replace;
+pub mod safe_mode;
+pub mod slides;
+pub mod suggestions;
+pub mod theme_css;
+pub mod trace;
+pub mod uploads;
+pub mod versioning;
+pub mod watchlists;
 
 use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{watch, Mutex, RwLock};
 
+/// Backend state for the active profile. The write lock is only taken while switching
+/// profiles (see `profiles::activate`).
 pub struct AppState {
     pub db: db::Database,
     pub uploads_dir: PathBuf,
+    pub app_data_dir: PathBuf,
+    pub profile: String,
+    pub profile_changes: watch::Sender<String>,
+    /// Serializes changes to the profile registry and profile switches (see `profiles::activate`)
+    pub profile_registry: Arc<Mutex<()>>,
+    /// Started with safe mode on; only built-in themes and layout rules are served
+    pub safe_mode: bool,
+    /// Cached index behind the related-presentations lookup
+    pub related: related::RelatedCache,
+    /// In-memory index behind the command palette's search
+    pub quick_search: quick_search::QuickSearchIndex,
+    /// Shared by AI providers and downloads so they reuse pooled connections
+    pub http_client: reqwest::Client,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State over a fresh in-memory database, for tests.
+    pub(crate) async fn for_tests() -> Self {
+        let db = db::Database::new_with_url("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        Self::for_tests_with(db)
+    }
+
+    /// State over `db` with the default profile and files under the temp dir, for tests.
+    pub(crate) fn for_tests_with(db: db::Database) -> Self {
+        Self {
+            db,
+            uploads_dir: std::env::temp_dir(),
+            app_data_dir: std::env::temp_dir(),
+            profile: profiles::DEFAULT_PROFILE.to_string(),
+            profile_changes: watch::channel(profiles::DEFAULT_PROFILE.to_string()).0,
+            profile_registry: Default::default(),
+            safe_mode: false,
+            related: Default::default(),
+            quick_search: Default::default(),
+            http_client: Default::default(),
+        }
+    }
 }
 
 pub type SharedState = Arc<RwLock<AppState>>;
+
+/// Port the backend listens on unless `SLIDES_PORT` says otherwise.
+pub const DEFAULT_PORT: u16 = 3332;
+
+static LOCAL_PORT: OnceLock<u16> = OnceLock::new();
+
+/// Records the port the backend actually bound, which differs from the requested one for port 0.
+pub fn set_local_port(port: u16) {
+    let _ = LOCAL_PORT.set(port);
+}
+
+/// The port the backend is listening on, or `DEFAULT_PORT` before it has bound one.
+pub fn local_port() -> u16 {
+    LOCAL_PORT.get().copied().unwrap_or(DEFAULT_PORT)
+}
+
+// Default deadline for outgoing requests; AI generation sets a longer one per request
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The client behind `AppState::http_client`.
+pub fn http_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .user_agent(concat!("slides/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
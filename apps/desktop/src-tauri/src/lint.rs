@@ -0,0 +1,197 @@
+// Content lint: rule-based checks to run before presenting. Unlike the health score, which
+// grades a deck, lint flags concrete mistakes: uploads that won't load, card grids too crowded
+// to read, empty slides, notes blocks that swallow the rest of a slide and template
+// placeholders nobody filled in.
+use std::collections::HashSet;
+
+use crate::error::AppResult;
+use crate::health;
+use crate::models::{LintIssue, LintRule, LintSeverity};
+use crate::placeholders;
+use crate::slides;
+use crate::uploads;
+use crate::AppState;
+
+// Card grids get too narrow to read past this many cards
+const MAX_CARDS: usize = 4;
+
+/// Lints `content`, checking upload references against the media library and the files in
+/// the uploads directory. The content doesn't have to be saved yet.
+pub async fn lint_content(state: &AppState, content: &str) -> AppResult<Vec<LintIssue>> {
+    let media = state.db.media_sizes(content).await?;
+    Ok(lint(content, |filename| {
+        media.contains_key(filename) && uploads::resolve_upload_path(&state.uploads_dir, filename).is_ok()
+    }))
+}
+
+/// Issues in `content`, in slide order. `upload_exists` says whether an upload filename
+/// referenced from a slide can be served.
+pub fn lint(content: &str, upload_exists: impl Fn(&str) -> bool) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let placeholders = placeholders::find_placeholders(content);
+
+    for (index, slide) in slides::split_slides(content).iter().enumerate() {
+        if has_unclosed_notes(slide) {
+            issues.push(issue(
+                index,
+                LintRule::UnclosedNotes,
+                format!("{} has no closing {}; the rest of the slide is hidden", slides::NOTES_START, slides::NOTES_END),
+            ));
+        }
+
+        if slides::strip_notes(slide).trim().is_empty() {
+            issues.push(issue(index, LintRule::EmptySlide, "Slide has no content".to_string()));
+            continue;
+        }
+
+        let cards = card_count(slide);
+        if cards > MAX_CARDS {
+            issues.push(issue(
+                index,
+                LintRule::TooManyCards,
+                format!("Slide has {} cards (more than {}); split it or use a list", cards, MAX_CARDS),
+            ));
+        }
+
+        let mut checked = HashSet::new();
+        for filename in health::upload_references(slide) {
+            if checked.insert(filename) && !upload_exists(filename) {
+                issues.push(issue(
+                    index,
+                    LintRule::BrokenImage,
+                    format!("{} is not in the media library", uploads::upload_url(filename)),
+                ));
+            }
+        }
+
+        for placeholder in placeholders.iter().filter(|p| p.slides.contains(&index)) {
+            issues.push(issue(
+                index,
+                LintRule::UnfilledPlaceholder,
+                format!("{{{{{}}}}} has not been filled in", placeholder.name),
+            ));
+        }
+    }
+
+    issues
+}
+
+fn issue(slide_index: usize, rule: LintRule, message: String) -> LintIssue {
+    LintIssue { slide_index, severity: rule.severity(), rule, message }
+}
+
+impl LintRule {
+    fn severity(self) -> LintSeverity {
+        match self {
+            LintRule::BrokenImage | LintRule::UnclosedNotes | LintRule::UnfilledPlaceholder => LintSeverity::Error,
+            LintRule::TooManyCards | LintRule::EmptySlide => LintSeverity::Warning,
+        }
+    }
+}
+
+/// Whether a `<!-- notes -->` block runs to the end of the slide without being closed.
+fn has_unclosed_notes(slide: &str) -> bool {
+    let mut rest = slide;
+    while let Some(start) = rest.find(slides::NOTES_START) {
+        rest = &rest[start + slides::NOTES_START.len()..];
+        let next_start = rest.find(slides::NOTES_START).unwrap_or(rest.len());
+        match rest[..next_start].find(slides::NOTES_END) {
+            Some(end) => rest = &rest[end + slides::NOTES_END.len()..],
+            None => return true,
+        }
+    }
+    false
+}
+
+/// List items written as `**Title:** description`, which render as a card grid.
+fn card_count(slide: &str) -> usize {
+    slide
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            line.strip_prefix(['-', '*', '+']).or_else(|| {
+                let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                (digits > 0).then(|| &line[digits..]).and_then(|rest| rest.strip_prefix('.'))
+            })
+        })
+        .filter(|item| item.starts_with(' '))
+        .filter(|item| {
+            let item = item.trim_start();
+            item.starts_with("**") && item.contains(":**")
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(issues: &[LintIssue]) -> Vec<(usize, LintRule)> {
+        issues.iter().map(|i| (i.slide_index, i.rule)).collect()
+    }
+
+    #[test]
+    fn test_clean_deck_has_no_issues() {
+        let deck = "# Welcome\n\n![Team](/api/uploads/team.png)\n\n<!-- notes -->\nHi\n<!-- /notes -->\n\n---\n\n\
+                    - **Fast:** builds\n- **Safe:** types\n- **Small:** binaries\n- **Open:** source\n";
+        assert!(lint(deck, |name| name == "team.png").is_empty());
+    }
+
+    #[test]
+    fn test_rules() {
+        let cards = (1..=5).map(|i| format!("{}. **Card {}:** text", i, i)).collect::<Vec<_>>().join("\n");
+        let deck = format!(
+            "# Intro\n\n<!-- notes -->\nNever closed\n\n---\n\n\n\n---\n\n{}\n\n---\n\n\
+             ![Logo](/api/uploads/missing.png) and again /api/v1/uploads/missing.png\n\n<img src=\"/api/v1/uploads/team.png\">\n\n---\n\n\
+             <!-- notes -->\nOnly notes\n<!-- /notes -->",
+            cards
+        );
+
+        let issues = lint(&deck, |name| name == "team.png");
+        assert_eq!(
+            rules(&issues),
+            [
+                (0, LintRule::UnclosedNotes),
+                (1, LintRule::EmptySlide),
+                (2, LintRule::TooManyCards),
+                (3, LintRule::BrokenImage),
+                (4, LintRule::EmptySlide),
+            ]
+        );
+        assert_eq!(issues[0].severity, LintSeverity::Error);
+        assert_eq!(issues[1].severity, LintSeverity::Warning);
+        assert!(issues[2].message.contains("5 cards"));
+        assert!(issues[3].message.contains("missing.png"));
+
+        let json = serde_json::to_value(&issues[3]).unwrap();
+        assert_eq!(json["slideIndex"], 3);
+        assert_eq!(json["rule"], "broken-image");
+        assert_eq!(json["severity"], "error");
+    }
+
+    #[test]
+    fn test_unfilled_placeholders() {
+        let deck = "# {{client}} review
+
+---
+
+# Plan
+
+Shipping on {{launch:date}} for {{client}}
+
+---
+
+# Done";
+        let issues = lint(deck, |_| true);
+        assert_eq!(
+            rules(&issues),
+            [
+                (0, LintRule::UnfilledPlaceholder),
+                (1, LintRule::UnfilledPlaceholder),
+                (1, LintRule::UnfilledPlaceholder),
+            ]
+        );
+        assert_eq!(issues[0].message, "{{client}} has not been filled in");
+        assert!(issues.iter().all(|i| i.severity == LintSeverity::Error));
+    }
+}
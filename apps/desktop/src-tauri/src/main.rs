@@ -1,27 +1,83 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use axum::http::HeaderValue;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
-use tauri::Manager;
-use tokio::sync::RwLock;
+use tauri::menu::{Menu, MenuItem, Submenu};
+use tauri::{Emitter, Manager};
+use tokio::sync::{watch, RwLock};
 use tracing_subscriber;
 
-use slides_desktop_lib::{api, db, mcp, AppState};
+use slides_desktop_lib::error::{AppError, AppResult};
+use slides_desktop_lib::middleware::{RequestIdLayer, REQUEST_ID_HEADER};
+use slides_desktop_lib::{
+    api, db, mcp, profiles::ProfileRegistry, quick_search, related, safe_mode, uploads, versioning, AppState,
+    DEFAULT_PORT,
+};
+
+const SAFE_MODE_MENU_ID: &str = "restart-safe-mode";
+
+/// Address the backend is listening on, once it has bound one.
+type BackendAddr = watch::Receiver<Option<SocketAddr>>;
+
+/// Comma-separated origins allowed to call the API from a browser. Unset allows any origin.
+const CORS_ORIGINS_ENV: &str = "SLIDES_CORS_ORIGINS";
+
+/// Interface and port the backend listens on. Port 0 lets the OS pick a free one.
+const BIND_ADDR_ENV: &str = "SLIDES_BIND_ADDR";
+const PORT_ENV: &str = "SLIDES_PORT";
+const DEFAULT_BIND_ADDR: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+// How often the maintenance task runs
+const MAINTENANCE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+// How often changed presentations are snapshotted, unless SLIDES_SNAPSHOT_INTERVAL_MINUTES
+// says otherwise (0 turns snapshots off)
+const SNAPSHOT_INTERVAL_ENV: &str = "SLIDES_SNAPSHOT_INTERVAL_MINUTES";
+const DEFAULT_SNAPSHOT_INTERVAL_MINUTES: u64 = 5;
+// How often the related-presentations index is checked for changes
+const RELATED_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
 fn main() {
     tracing_subscriber::fmt::init();
 
+    let safe_mode = safe_mode::requested();
+    if safe_mode {
+        tracing::warn!("Starting in safe mode: custom themes and layout rules are disabled");
+    }
+
+    let (backend_addr_tx, backend_addr) = watch::channel(None);
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .manage::<BackendAddr>(backend_addr)
+        .invoke_handler(tauri::generate_handler![backend_url])
+        .menu(move |handle| {
+            let menu = Menu::default(handle)?;
+            let restart = MenuItem::with_id(
+                handle,
+                SAFE_MODE_MENU_ID,
+                "Restart in Safe Mode",
+                !safe_mode,
+                None::<&str>,
+            )?;
+            menu.append(&Submenu::with_items(handle, "Troubleshooting", true, &[&restart])?)?;
+            Ok(menu)
+        })
+        .on_menu_event(|app, event| {
+            if event.id().as_ref() == SAFE_MODE_MENU_ID {
+                restart_in_safe_mode(app);
+            }
+        })
+        .setup(move |app| {
             let app_handle = app.handle().clone();
 
             // Start the backend server in a separate thread
             tauri::async_runtime::spawn(async move {
                 tracing::info!("Starting backend server...");
-                match start_backend(app_handle).await {
+                match start_backend(app_handle, safe_mode, backend_addr_tx).await {
                     Ok(_) => tracing::info!("Backend server stopped"),
-                    Err(e) => tracing::error!("Failed to start backend: {:?}", e),
+                    Err(e) => tracing::error!(error = ?e, "Failed to start backend"),
                 }
             });
 
@@ -31,55 +87,267 @@ fn main() {
         .expect("error while running tauri application");
 }
 
-async fn start_backend(app_handle: tauri::AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+/// Base URL of the backend API, for the webview. Waits until the backend has bound its port.
+#[tauri::command]
+async fn backend_url(backend_addr: tauri::State<'_, BackendAddr>) -> Result<String, String> {
+    let mut backend_addr = backend_addr.inner().clone();
+    let addr = backend_addr
+        .wait_for(Option::is_some)
+        .await
+        .map_err(|_| "Backend failed to start".to_string())?
+        .expect("waited for an address");
+    // A wildcard bind is reachable over loopback
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    Ok(format!("http://{}", SocketAddr::new(ip, addr.port())))
+}
+
+/// Relaunches the app with the safe-mode flag and exits this instance.
+fn restart_in_safe_mode(app: &tauri::AppHandle) {
+    let relaunched = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .args(std::env::args().skip(1))
+            .arg(safe_mode::ARG)
+            .spawn()
+    });
+    match relaunched {
+        Ok(_) => app.exit(0),
+        Err(e) => tracing::error!(error = %e, "Failed to restart in safe mode"),
+    }
+}
+
+async fn start_backend(
+    app_handle: tauri::AppHandle,
+    safe_mode: bool,
+    backend_addr: watch::Sender<Option<SocketAddr>>,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Get app data directory for database storage
     let app_data_dir = app_handle.path().app_data_dir()?;
     std::fs::create_dir_all(&app_data_dir)?;
-    let db_path = app_data_dir.join("slides.db");
-    let database_url = format!("sqlite:{}?mode=rwc", db_path.display());
-    tracing::info!("Using database at: {}", database_url);
+
+    // Resolve the active profile's storage
+    let registry = ProfileRegistry::load(&app_data_dir)?;
+    let profile = registry.active().to_string();
+    let paths = registry.paths(&profile);
+    tracing::info!(profile = %profile, "Using profile");
+
+    let database_url = paths.database_url();
+    tracing::info!(database_url = %database_url, "Using database");
 
     // Create uploads directory
-    let uploads_dir = app_data_dir.join("uploads");
+    let uploads_dir = paths.uploads_dir;
     std::fs::create_dir_all(&uploads_dir)?;
-    tracing::info!("Using uploads directory at: {}", uploads_dir.display());
+    tracing::info!(uploads_dir = %uploads_dir.display(), "Using uploads directory");
 
     // Initialize database
     let db = db::Database::new_with_url(&database_url).await?;
     db.migrate().await?;
+    let events = db.events().clone();
+
+    let (profile_changes, mut profile_rx) = watch::channel(profile.clone());
+    let state = Arc::new(RwLock::new(AppState {
+        db,
+        uploads_dir,
+        app_data_dir,
+        profile,
+        profile_changes,
+        profile_registry: Default::default(),
+        safe_mode,
+        related: Default::default(),
+        quick_search: Default::default(),
+        http_client: slides_desktop_lib::http_client()?,
+    }));
+
+    // Let the UI reload (and retitle its window) when the active profile changes
+    let profile_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        while profile_rx.changed().await.is_ok() {
+            let profile = profile_rx.borrow_and_update().clone();
+            if let Err(e) = profile_handle.emit("profile-changed", profile) {
+                tracing::error!(error = %e, "Failed to emit profile change");
+            }
+        }
+    });
+
+    // Forward changes to the UI so open views refresh, whether the REST API, an MCP client
+    // or a background task made them
+    events.register("ui", move |event| {
+        if let Err(e) = app_handle.emit("app-event", &event) {
+            tracing::error!(?event, error = %e, "Failed to emit app event");
+        }
+        std::future::ready(())
+    });
+
+    // Snapshot decks that changed, so a crash mid-edit loses at most one interval of work
+    if let Some(period) = snapshot_interval() {
+        let snapshot_state = state.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let state = snapshot_state.read().await;
+                match state.db.snapshot_changed_presentations().await {
+                    Ok(0) => {}
+                    Ok(n) => tracing::debug!(count = n, "Snapshotted presentations"),
+                    Err(e) => tracing::error!(error = %e, "Failed to snapshot presentations"),
+                }
+            }
+        });
+    }
+
+    // Rebuild the related-presentations index as soon as decks change
+    let related_events_state = state.clone();
+    events.register("related", move |event| {
+        let state = related_events_state.clone();
+        async move {
+            if event.affects_presentations() {
+                if let Err(e) = related::refresh(&state).await {
+                    tracing::error!(error = %e, "Failed to refresh the related presentations index");
+                }
+            }
+        }
+    });
+
+    // Build the command palette's index, then keep it current as things change
+    if let Err(e) = quick_search::rebuild(&*state.read().await).await {
+        tracing::error!(error = %e, "Failed to build the quick search index");
+    }
+    let quick_search_state = state.clone();
+    events.register("quick-search", move |event| {
+        let state = quick_search_state.clone();
+        async move {
+            if let Err(e) = quick_search::apply(&*state.read().await, &event).await {
+                tracing::error!(?event, error = %e, "Failed to update the quick search index");
+            }
+        }
+    });
 
-    let state = Arc::new(RwLock::new(AppState { db, uploads_dir }));
+    // Maintenance: reap abandoned chunked uploads and rescore decks whose media changed
+    let maintenance_state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match uploads::reap_expired_sessions(&maintenance_state).await {
+                Ok(0) => {}
+                Ok(n) => tracing::info!(count = n, "Removed expired upload sessions"),
+                Err(e) => tracing::error!(error = %e, "Failed to remove expired upload sessions"),
+            }
+            // A clone, so a profile switch doesn't wait for the rescoring
+            let db = maintenance_state.read().await.db.clone();
+            if let Err(e) = db.refresh_health().await {
+                tracing::error!(error = %e, "Failed to refresh presentation health");
+            }
+        }
+    });
+
+    // Also check periodically, for changes made to the database by another process
+    let related_state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(RELATED_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = related::refresh(&related_state).await {
+                tracing::error!(error = %e, "Failed to refresh the related presentations index");
+            }
+        }
+    });
+
+    let addr = bind_addr()?;
+    if !addr.ip().is_loopback() {
+        tracing::warn!(
+            %addr,
+            "Binding a non-loopback address: the API has no authentication and is reachable from the network"
+        );
+    }
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!(%addr, error = %e, "Failed to bind. Is another instance running?");
+            return Err(e.into());
+        }
+    };
+    // The port actually bound, which differs from the requested one for port 0
+    let local_addr = listener.local_addr()?;
+    slides_desktop_lib::set_local_port(local_addr.port());
+    backend_addr.send_replace(Some(local_addr));
 
     // Create the API router
     let api_router = api::create_router(state.clone());
 
     // Create the MCP SSE router
-    let mcp_router = mcp::create_router(state.clone());
+    let mcp_router = mcp::create_router(state.clone(), &events);
 
     // Combine routers
     let app = axum::Router::new()
-        .nest("/api", api_router)
+        .merge(versioning::mount(api_router))
         .nest("/mcp", mcp_router)
-        .layer(
-            tower_http::cors::CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_methods(tower_http::cors::Any)
-                .allow_headers(tower_http::cors::Any),
-        );
+        .layer(cors_layer()?)
+        .layer(RequestIdLayer);
 
-    let listener = match tokio::net::TcpListener::bind("127.0.0.1:3332").await {
-        Ok(l) => {
-            tracing::info!("Backend server running on http://127.0.0.1:3332");
-            tracing::info!("MCP SSE endpoint available at http://127.0.0.1:3332/mcp/sse");
-            l
-        }
-        Err(e) => {
-            tracing::error!("Failed to bind to port 3332: {}. Is another instance running?", e);
-            return Err(e.into());
-        }
-    };
+    tracing::info!(url = %format!("http://{}", local_addr), "Backend server running");
+    tracing::info!(url = %format!("http://{}/mcp/sse", local_addr), "MCP SSE endpoint available");
 
-    axum::serve(listener, app).await?;
+    // Peer addresses let the AI rate limit count requests per client
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
 
     Ok(())
 }
+
+/// Address to listen on, from `SLIDES_BIND_ADDR` and `SLIDES_PORT`. Values that don't parse
+/// are an error rather than a silent fallback to the defaults.
+fn bind_addr() -> AppResult<SocketAddr> {
+    let ip = match std::env::var(BIND_ADDR_ENV) {
+        Ok(value) if !value.trim().is_empty() => value.trim().parse::<IpAddr>().map_err(|e| {
+            AppError::Internal(format!("Invalid {} {:?}: {}", BIND_ADDR_ENV, value, e))
+        })?,
+        _ => DEFAULT_BIND_ADDR,
+    };
+    let port = match std::env::var(PORT_ENV) {
+        Ok(value) if !value.trim().is_empty() => value.trim().parse::<u16>().map_err(|e| {
+            AppError::Internal(format!("Invalid {} {:?}: {}", PORT_ENV, value, e))
+        })?,
+        _ => DEFAULT_PORT,
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Period of the auto-snapshot task, or None when it's turned off.
+fn snapshot_interval() -> Option<std::time::Duration> {
+    let minutes = std::env::var(SNAPSHOT_INTERVAL_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_MINUTES);
+    (minutes > 0).then(|| std::time::Duration::from_secs(minutes * 60))
+}
+
+/// CORS for the API and MCP routes. Any origin is allowed unless `SLIDES_CORS_ORIGINS` lists
+/// the ones to accept, which is how the server should run outside development.
+fn cors_layer() -> AppResult<tower_http::cors::CorsLayer> {
+    let origins = match std::env::var(CORS_ORIGINS_ENV) {
+        Ok(value) if !value.trim().is_empty() => tower_http::cors::AllowOrigin::list(parse_cors_origins(&value)?),
+        _ => tower_http::cors::AllowOrigin::any(),
+    };
+
+    Ok(tower_http::cors::CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(tower_http::cors::Any)
+        .allow_headers(tower_http::cors::Any)
+        .expose_headers([axum::http::HeaderName::from_static(REQUEST_ID_HEADER)]))
+}
+
+fn parse_cors_origins(value: &str) -> AppResult<Vec<HeaderValue>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin).map_err(|e| {
+                AppError::Internal(format!("Invalid origin {:?} in {}: {}", origin, CORS_ORIGINS_ENV, e))
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,380 @@
+// Presentations as standalone markdown files, with a front-matter block carrying the
+// metadata that lives in database columns.
+use crate::db::content_hash;
+use crate::models::Presentation;
+use crate::uploads;
+
+/// A markdown file split into its front-matter fields and the slide content.
+#[derive(Debug, Default, PartialEq)]
+pub struct MarkdownFile {
+    /// The presentation the file was exported from
+    pub id: Option<String>,
+    /// Content hash of the body as exported, for telling whether the file was edited since
+    pub hash: Option<String>,
+    pub title: Option<String>,
+    pub theme: Option<String>,
+    pub favorite: bool,
+    pub content: String,
+}
+
+/// Origins the backend serves uploads from, as they may appear in absolute image URLs.
+fn local_origins() -> [String; 2] {
+    let port = crate::local_port();
+    [format!("http://localhost:{}", port), format!("http://127.0.0.1:{}", port)]
+}
+
+/// The presentation's markdown preceded by front matter. With `inline` false, upload URLs
+/// are rewritten to relative `uploads/...` paths so the file can sit next to its media. The
+/// front matter records the presentation's id and a hash of the body, so a re-import can
+/// update the presentation instead of creating a copy.
+pub fn export_markdown(presentation: &Presentation, inline: bool) -> String {
+    let content = if inline {
+        presentation.content.clone()
+    } else {
+        relative_upload_paths(&presentation.content)
+    };
+
+    // JSON strings are valid double-quoted YAML scalars, which keeps titles with colons or
+    // quotes intact
+    format!(
+        "---\nid: {}\nhash: {}\ntitle: {}\ntheme: {}\ncreated: {}\nupdated: {}\n{}---\n\n{}",
+        presentation.id,
+        content_hash(&content),
+        serde_json::Value::from(presentation.title.as_str()),
+        serde_json::Value::from(presentation.theme.as_str()),
+        presentation.created_at.to_rfc3339(),
+        presentation.updated_at.to_rfc3339(),
+        if presentation.is_favorite { "favorite: true\n" } else { "" },
+        content
+    )
+}
+
+/// Parses a markdown file, reading `id`, `hash`, `title`, `theme` and `favorite` from YAML
+/// front matter if present.
+/// Without a front-matter title, the first `# ` heading is used. Other front-matter keys
+/// (such as Marp's `marp: true`) are dropped.
+pub fn parse_markdown(text: &str) -> MarkdownFile {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n");
+
+    let mut file = match front_matter(&text) {
+        Some((fields, content)) => {
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.clone())
+                    .filter(|v| !v.is_empty())
+            };
+            MarkdownFile {
+                id: field("id"),
+                hash: field("hash"),
+                title: field("title"),
+                theme: field("theme"),
+                favorite: field("favorite").is_some_and(|v| v.eq_ignore_ascii_case("true")),
+                content: content.to_string(),
+            }
+        }
+        None => MarkdownFile {
+            content: text.clone(),
+            ..Default::default()
+        },
+    };
+
+    if file.title.is_none() {
+        file.title = file
+            .content
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("# "))
+            .map(|heading| heading.trim().to_string())
+            .filter(|heading| !heading.is_empty());
+    }
+
+    file
+}
+
+/// Top-level `key: value` pairs of a leading `---` block, and the text after it. A block with
+/// other top-level lines is a slide separator followed by a slide, not front matter.
+fn front_matter(text: &str) -> Option<(Vec<(&str, String)>, &str)> {
+    let rest = text.strip_prefix("---\n")?;
+    let end = rest.find("\n---\n").map(|i| (i, i + 5)).or_else(|| {
+        rest.strip_suffix("\n---")
+            .map(|block| (block.len(), rest.len()))
+    })?;
+    let block = &rest[..end.0];
+    let body = &rest[end.1..];
+
+    let mut fields = Vec::new();
+    for line in block.lines() {
+        // Nested values and list items belong to the previous key
+        if line.trim().is_empty() || line.starts_with([' ', '\t', '-', '#']) {
+            continue;
+        }
+        let (key, value) = line.split_once(':')?;
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return None;
+        }
+        fields.push((key, yaml_scalar(value.trim())));
+    }
+
+    if fields.is_empty() {
+        return None;
+    }
+    // Export puts a blank line between the front matter and the first slide
+    Some((fields, body.strip_prefix('\n').unwrap_or(body)))
+}
+
+/// Unquotes a YAML scalar; double-quoted values are read as JSON strings.
+fn yaml_scalar(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        if let Ok(unquoted) = serde_json::from_str::<String>(value) {
+            return unquoted;
+        }
+    }
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].replace("''", "'");
+    }
+    value.to_string()
+}
+
+/// Whether `content` is what an export with body hash `hash` held, with upload URLs kept or
+/// made relative; false means the presentation changed since that export.
+pub fn matches_export(content: &str, hash: &str) -> bool {
+    content_hash(content) == hash || content_hash(&relative_upload_paths(content)) == hash
+}
+
+/// A `.md` filename derived from the title, safe to put in a Content-Disposition header.
+pub fn export_filename(title: &str) -> String {
+    let mut name = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+
+    let name: String = name.trim_end_matches('-').chars().take(100).collect();
+    if name.is_empty() {
+        "presentation.md".to_string()
+    } else {
+        format!("{}.md", name)
+    }
+}
+
+/// Rewrites upload URLs (see `uploads::find_upload_prefixes`, optionally prefixed with the local
+/// origin) that start a link target or attribute value into `uploads/<file>`.
+fn relative_upload_paths(content: &str) -> String {
+    let origins = local_origins();
+    let mut out = String::with_capacity(content.len());
+    let mut copied = 0;
+
+    for (at, end) in uploads::find_upload_prefixes(content) {
+        let before = &content[..at];
+        let start = origins
+            .iter()
+            .find(|origin| before.ends_with(origin.as_str()))
+            .map_or(at, |origin| at - origin.len());
+
+        // Only whole URLs, not the path part of some other site's URL
+        if !starts_url(&content[..start]) {
+            continue;
+        }
+
+        out.push_str(&content[copied..start]);
+        out.push_str("uploads/");
+        copied = end;
+    }
+
+    out.push_str(&content[copied..]);
+    out
+}
+
+/// Reverses `relative_upload_paths`: `uploads/<file>` paths that start a link target or
+/// attribute value become upload URLs again.
+pub fn absolute_upload_paths(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut copied = 0;
+
+    for (at, relative) in content.match_indices("uploads/") {
+        if !starts_url(&content[..at]) {
+            continue;
+        }
+        out.push_str(&content[copied..at]);
+        out.push_str(&uploads::upload_url(""));
+        copied = at + relative.len();
+    }
+
+    out.push_str(&content[copied..]);
+    out
+}
+
+/// Whether a URL starting right after `before` is a whole URL rather than part of another.
+fn starts_url(before: &str) -> bool {
+    before
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '"' | '\'' | '=' | '<'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreatePresentation;
+    use crate::{AppState, SharedState};
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_export_filename() {
+        assert_eq!(export_filename("Q3 Review: Sales & Ops"), "Q3-Review-Sales-Ops.md");
+        assert_eq!(export_filename("../../etc/passwd"), "etc-passwd.md");
+        assert_eq!(export_filename("Über"), "ber.md");
+        assert_eq!(export_filename("???"), "presentation.md");
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        let marp = "---\nmarp: true\ntheme: 'gaia'\nstyle: |\n  section { color: red }\n---\n\n# Intro\n\n---\n\n# Next";
+        assert_eq!(
+            parse_markdown(marp),
+            MarkdownFile {
+                title: Some("Intro".to_string()),
+                theme: Some("gaia".to_string()),
+                content: "# Intro\n\n---\n\n# Next".to_string(),
+                ..Default::default()
+            }
+        );
+
+        // A leading separator is not front matter
+        let plain = "---\n\n# Only slide\n\n---\n\nMore";
+        assert_eq!(parse_markdown(plain).content, plain);
+        assert_eq!(parse_markdown("no heading").title, None);
+    }
+
+    #[test]
+    fn test_export_round_trips() {
+        let now = chrono::Utc::now();
+        let presentation = Presentation {
+            id: "p1".to_string(),
+            title: "Q3: \"Wins\"".to_string(),
+            content: "# One\n\n---\n\n# Two\n".to_string(),
+            theme: "dark".to_string(),
+            user_id: "local".to_string(),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            tags: Vec::new(),
+            slide_count: 2,
+            word_count: 2,
+            has_speaker_notes: false,
+            health_score: None,
+            archived: false,
+            last_opened_at: Some(now),
+            settings: Default::default(),
+            is_favorite: false,
+            locked: false,
+            has_draft: false,
+        };
+
+        let parsed = parse_markdown(&export_markdown(&presentation, true));
+        assert_eq!(parsed.id.as_deref(), Some("p1"));
+        assert_eq!(parsed.hash, Some(content_hash(&parsed.content)));
+        assert_eq!(parsed.title.as_deref(), Some("Q3: \"Wins\""));
+        assert_eq!(parsed.theme.as_deref(), Some("dark"));
+        assert!(!parsed.favorite);
+        assert_eq!(parsed.content, presentation.content);
+
+        let favorite = Presentation { is_favorite: true, ..presentation };
+        let parsed = parse_markdown(&export_markdown(&favorite, true));
+        assert!(parsed.favorite);
+        assert_eq!(parsed.content, favorite.content);
+
+        // The hash identifies the exported body whichever way uploads were written
+        let with_media = Presentation { content: "![a](/api/uploads/a.png)".to_string(), ..favorite };
+        let parsed = parse_markdown(&export_markdown(&with_media, false));
+        assert_eq!(parsed.content, "![a](uploads/a.png)");
+        assert!(matches_export(&with_media.content, parsed.hash.as_deref().unwrap()));
+        assert!(!matches_export("# Edited", parsed.hash.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn test_relative_upload_paths() {
+        let content = "![a](/api/uploads/a.png)\n<img src=\"http://localhost:3332/api/v1/uploads/b.png\">\n\
+                       [docs](https://example.com/api/uploads/c.png)";
+        assert_eq!(
+            relative_upload_paths(content),
+            "![a](uploads/a.png)\n<img src=\"uploads/b.png\">\n[docs](https://example.com/api/uploads/c.png)"
+        );
+        assert_eq!(
+            absolute_upload_paths(&relative_upload_paths(content)),
+            "![a](/api/v1/uploads/a.png)\n<img src=\"/api/v1/uploads/b.png\">\n[docs](https://example.com/api/uploads/c.png)"
+        );
+    }
+
+    async fn import(router: &Router, file: &str) -> (StatusCode, Value) {
+        let body = format!(
+            "--b\r\nContent-Disposition: form-data; name=\"file\"; filename=\"deck.md\"\r\n\
+             Content-Type: text/markdown\r\n\r\n{}\r\n--b--\r\n",
+            file
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/presentations/import?upsert=true")
+            .header("content-type", "multipart/form-data; boundary=b")
+            .body(Body::from(body))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_import() {
+        let state: SharedState = Arc::new(RwLock::new(AppState::for_tests().await));
+        let router = crate::api::create_router(state.clone());
+        let app_state = state.read().await;
+        let db = &app_state.db;
+        let deck = db
+            .create_presentation(CreatePresentation {
+                title: "Deck".to_string(),
+                content: Some("# Intro\n\n![a](/api/uploads/a.png)".to_string()),
+                theme: None,
+                if_not_exists: false,
+                on_conflict: None,
+            })
+            .await
+            .unwrap();
+        let exported = export_markdown(&deck, false);
+
+        // An unedited file changes nothing
+        let (status, body) = import(&router, &exported).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(db.get_presentation(&deck.id).await.unwrap().updated_at, deck.updated_at);
+        assert_eq!(body["content"], deck.content);
+
+        // An edited one updates the deck, with upload URLs made absolute again
+        let (status, body) = import(&router, &exported.replace("# Intro", "# Welcome")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], deck.id);
+        assert_eq!(body["content"], "# Welcome\n\n![a](/api/v1/uploads/a.png)");
+
+        // The deck changed since the export, so another edit of that export conflicts
+        let stored = db.get_presentation(&deck.id).await.unwrap();
+        let edited = exported.replace("# Intro", "# Hello");
+        let (status, body) = import(&router, &edited).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["exportedHash"], json!(parse_markdown(&exported).hash));
+        assert_eq!(body["fileHash"], content_hash(&parse_markdown(&edited).content));
+        assert_eq!(body["storedHash"], content_hash(&stored.content));
+        assert_eq!(db.get_presentation(&deck.id).await.unwrap().content, stored.content);
+    }
+}
@@ -1,21 +1,36 @@
 use axum::{
     extract::{Query, State},
     http::StatusCode,
+    middleware,
     response::sse::{Event, Sse},
     routing::{get, post},
     Json, Router,
 };
 use futures::stream::Stream;
-use serde::{Deserialize, Serialize};
+use schemars::{generate::SchemaSettings, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
 use uuid::Uuid;
 
-use crate::models::{CreatePresentation, UpdatePresentation};
+use crate::models::{
+    CreatePresentation, CreateTheme, DiffPresentationRequest, ListPresentationsQuery, McpToolSettings, Media, NameConflict,
+    ThemeFonts, TitleConflict, UpdatePresentation, UpdateTheme, DEFAULT_PER_PAGE,
+};
+use crate::db;
+use crate::diff;
+use crate::error::{AppError, AppResult, FieldError};
+use crate::events::{AppEvent, EventBus};
+use crate::lint;
+use crate::placeholders;
+use crate::profiles;
+use crate::related;
+use crate::slides;
+use crate::trace;
 use crate::SharedState;
 
 const SLIDE_FORMAT_GUIDE: &str = r#"
@@ -71,13 +86,100 @@ Best practices:
 - Let the automatic layouts do the work - just write natural markdown
 "#;
 
+// Error code returned when a client calls a tool disabled in the MCP tool settings
+const TOOL_DISABLED: i32 = -32001;
+
+// Tools that never modify data; the only ones available in read-only mode
+const READ_ONLY_TOOLS: &[&str] = &[
+    "list_presentations",
+    "search_presentations",
+    "find_related_presentations",
+    "get_presentation",
+    "get_presentation_stats",
+    "list_presentation_versions",
+    "list_slides",
+    "get_slide",
+    "list_placeholders",
+    "lint_presentation",
+    "diff_presentation",
+    "list_tags",
+    "list_themes",
+    "list_media",
+    "list_layout_rules",
+];
+
+/// Seconds a session may stay open before it is dropped, unless `SLIDES_MCP_SESSION_TTL_SECS`
+/// says otherwise. Clients reconnect when their stream ends.
+const SESSION_TTL_ENV: &str = "SLIDES_MCP_SESSION_TTL_SECS";
+const DEFAULT_SESSION_TTL_SECS: u64 = 60 * 60;
+// How often expired and disconnected sessions are removed
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+// Media downloads may be far larger than the shared client's 30-second deadline allows for
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A prompt template offered through `prompts/list`. `{argument}` in the template is
+/// replaced with the argument's value.
+struct Prompt {
+    name: &'static str,
+    description: &'static str,
+    argument: &'static str,
+    argument_description: &'static str,
+    template: &'static str,
+}
+
+const PROMPTS: &[Prompt] = &[
+    Prompt {
+        name: "create-presentation",
+        description: "Draft a new presentation on a topic",
+        argument: "topic",
+        argument_description: "What the presentation is about",
+        template: "Create a presentation about {topic}. Plan an outline of 6-10 slides first, then write \
+                   them with the create_presentation tool: open with a title slide, give each slide one idea, \
+                   keep bullet lists short and put details in speaker notes. Finish with lint_presentation \
+                   and fix anything it reports.",
+    },
+    Prompt {
+        name: "improve-slide",
+        description: "Rewrite a slide to be clearer and more concise",
+        argument: "content",
+        argument_description: "Markdown of the slide to improve",
+        template: "Improve this slide. Keep its meaning and any images, tighten the wording, cut it to the \
+                   points an audience can take in at a glance and move the rest to speaker notes. Reply \
+                   with the new slide markdown only; to save it into a presentation use replace_slide.\n\n\
+                   {content}",
+    },
+    Prompt {
+        name: "generate-theme",
+        description: "Design a custom theme from a description",
+        argument: "description",
+        argument_description: "The look the theme should have, e.g. colours, mood or brand",
+        template: "Design a slide theme that looks like this: {description}. Look at an existing theme with \
+                   list_themes for the structure, then save it with create_theme under a kebab-case name. \
+                   Scope every rule to [data-theme=\"<name>\"], set the --slide-bg, --slide-text, \
+                   --slide-heading and --slide-accent variables, and check that text contrasts with the \
+                   background.",
+    },
+];
+
+// Resource URIs of presentations, followed by the presentation id
+const PRESENTATION_URI_PREFIX: &str = "slides://presentations/";
+
 // Session state for MCP connections
-type Sessions = Arc<RwLock<HashMap<String, mpsc::Sender<String>>>>;
+type Sessions = Arc<RwLock<HashMap<String, Session>>>;
+
+struct Session {
+    sender: mpsc::Sender<String>,
+    // Profile the session was opened under; switching profiles invalidates it
+    profile: String,
+    created_at: Instant,
+}
 
 #[derive(Clone)]
 struct McpState {
     sessions: Sessions,
     app_state: SharedState,
+    /// Stream of the session the request being handled came from, for notifications
+    session: Option<mpsc::Sender<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -96,6 +198,14 @@ struct JsonRpcRequest {
     params: Value,
 }
 
+/// A message posted by a client: one request, or a batch of them sent together.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JsonRpcBatch {
+    Single(JsonRpcRequest),
+    Batch(Vec<JsonRpcRequest>),
+}
+
 #[derive(Debug, Serialize)]
 struct JsonRpcResponse {
     jsonrpc: String,
@@ -125,32 +235,122 @@ impl JsonRpcResponse {
         }
     }
 
-    fn error(id: Option<Value>, code: i32, message: String) -> Self {
+    fn error(id: Option<Value>, error: JsonRpcError) -> Self {
         Self {
             jsonrpc: "2.0".to_string(),
             id,
             result: None,
-            error: Some(JsonRpcError {
-                code,
-                message,
-                data: None,
-            }),
+            error: Some(error),
         }
     }
 }
 
-pub fn create_router(state: SharedState) -> Router {
+impl From<(i32, String)> for JsonRpcError {
+    fn from((code, message): (i32, String)) -> Self {
+        Self { code, message, data: None }
+    }
+}
+
+/// Tool handler errors from app errors: those the caller can fix by changing the arguments
+/// (including asking for something that may not be changed, like a built-in theme) are invalid
+/// params; the rest are tool failures.
+impl From<AppError> for (i32, String) {
+    fn from(e: AppError) -> Self {
+        let code = match e {
+            AppError::BadRequest(_) | AppError::Validation(_) | AppError::TooLarge(_) | AppError::Forbidden(_) => -32602,
+            _ => -32000,
+        };
+        (code, e.to_string())
+    }
+}
+
+/// A tool result as pretty-printed JSON text.
+fn to_json<T: Serialize>(value: &T) -> Result<String, (i32, String)> {
+    serde_json::to_string_pretty(value).map_err(|e| (-32000, e.to_string()))
+}
+
+pub fn create_router(state: SharedState, events: &EventBus) -> Router {
     let mcp_state = McpState {
         sessions: Arc::new(RwLock::new(HashMap::new())),
         app_state: state,
+        session: None,
     };
 
-    Router::new()
+    let sessions = mcp_state.sessions.clone();
+    events.register("mcp", move |event| notify_sessions(sessions.clone(), event));
+
+    // A client that goes away without its stream ending leaves its sender behind
+    let sessions = mcp_state.sessions.clone();
+    let ttl = session_ttl();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let removed = remove_stale_sessions(&sessions, ttl).await;
+            if removed > 0 {
+                tracing::debug!(removed, "Removed expired or disconnected MCP sessions");
+            }
+        }
+    });
+
+    let router = Router::new()
         .route("/sse", get(sse_handler))
-        .route("/message", post(message_handler))
+        .route("/message", post(message_handler));
+    #[cfg(debug_assertions)]
+    let router = router.route("/sessions", get(list_sessions));
+
+    router
+        .layer(middleware::from_fn_with_state(
+            mcp_state.app_state.clone(),
+            profiles::reject_while_switching,
+        ))
         .with_state(mcp_state)
 }
 
+/// How long a session may stay open, from `SLIDES_MCP_SESSION_TTL_SECS`.
+fn session_ttl() -> Duration {
+    let secs = std::env::var(SESSION_TTL_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_SESSION_TTL_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Drops sessions older than `ttl` or whose stream is gone, returning how many. Dropping a
+/// live session's sender ends its stream.
+async fn remove_stale_sessions(sessions: &Sessions, ttl: Duration) -> usize {
+    let mut sessions = sessions.write().await;
+    let before = sessions.len();
+    sessions.retain(|_, session| session.created_at.elapsed() < ttl && !session.sender.is_closed());
+    before - sessions.len()
+}
+
+/// Open sessions and their ages, for development. Session ids are left out since they are
+/// what clients post messages with.
+#[cfg(debug_assertions)]
+async fn list_sessions(State(state): State<McpState>) -> Json<Value> {
+    let sessions = state.sessions.read().await;
+    let mut ages: Vec<u64> = sessions.values().map(|session| session.created_at.elapsed().as_secs()).collect();
+    ages.sort_unstable();
+    Json(json!({ "count": ages.len(), "ageSeconds": ages }))
+}
+
+/// Tells connected clients to fetch the tool list again when the tool settings change.
+async fn notify_sessions(sessions: Sessions, event: AppEvent) {
+    if !matches!(&event, AppEvent::SettingsChanged { key } if key == db::MCP_TOOLS_SETTING) {
+        return;
+    }
+
+    let notification = json!({ "jsonrpc": "2.0", "method": "notifications/tools/list_changed" }).to_string();
+    for (session_id, session) in sessions.read().await.iter() {
+        // A client that stopped reading its stream misses the notification instead of
+        // holding up the others
+        if session.sender.try_send(notification.clone()).is_err() {
+            tracing::warn!(session_id = %session_id, "Failed to notify session of tool changes");
+        }
+    }
+}
+
 async fn sse_handler(
     State(state): State<McpState>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
@@ -159,17 +359,19 @@ async fn sse_handler(
 
     // Store the sender in sessions
     {
+        let profile = state.app_state.read().await.profile.clone();
         let mut sessions = state.sessions.write().await;
-        sessions.insert(session_id.clone(), tx);
+        sessions.insert(session_id.clone(), Session { sender: tx, profile, created_at: Instant::now() });
     }
 
     let session_id_clone = session_id.clone();
     let sessions_clone = state.sessions.clone();
+    // Relative, so clients resolve it against whatever address they reached the server on
+    let endpoint_url = format!("/mcp/message?sessionId={}", session_id);
 
     // Create the SSE stream
     let stream = async_stream::stream! {
         // Send the endpoint event first
-        let endpoint_url = format!("/mcp/message?sessionId={}", session_id_clone);
         yield Ok::<_, Infallible>(Event::default().event("endpoint").data(endpoint_url));
 
         // Forward messages from the channel
@@ -192,29 +394,36 @@ async fn sse_handler(
 async fn message_handler(
     State(state): State<McpState>,
     Query(params): Query<SessionParams>,
-    Json(request): Json<JsonRpcRequest>,
+    Json(message): Json<JsonRpcBatch>,
 ) -> StatusCode {
     let session_id = params.session_id;
 
     // Get the sender for this session
-    let sender = {
+    let session = {
         let sessions = state.sessions.read().await;
-        sessions.get(&session_id).cloned()
+        sessions
+            .get(&session_id)
+            .map(|session| (session.sender.clone(), session.profile.clone()))
     };
 
-    let Some(sender) = sender else {
-        tracing::error!("Session {} not found", session_id);
+    let Some((sender, profile)) = session else {
+        tracing::error!(session_id = %session_id, "Session not found");
         return StatusCode::NOT_FOUND;
     };
 
-    // Process the request
-    let response = process_request(&state, request).await;
+    // Dropping the session's sender ends its SSE stream, so the client reconnects
+    // against the newly active profile
+    if profile != state.app_state.read().await.profile {
+        tracing::info!(session_id = %session_id, profile = %profile, "Session belongs to another profile, closing it");
+        state.sessions.write().await.remove(&session_id);
+        return StatusCode::NOT_FOUND;
+    }
 
-    // Send response if there is one (notifications don't need responses)
-    if let Some(response) = response {
-        let response_json = serde_json::to_string(&response).unwrap_or_default();
+    // Send the reply if there is one (notifications don't need responses)
+    let state = McpState { session: Some(sender.clone()), ..state };
+    if let Some(response_json) = process_message(&state, message).await {
         if sender.send(response_json).await.is_err() {
-            tracing::error!("Failed to send response to session {}", session_id);
+            tracing::error!(session_id = %session_id, "Failed to send response to session");
             return StatusCode::INTERNAL_SERVER_ERROR;
         }
     }
@@ -222,6 +431,32 @@ async fn message_handler(
     StatusCode::ACCEPTED
 }
 
+/// The serialized reply to a posted message, if it needs one. A batch is answered with one
+/// array holding the responses to its requests, which run concurrently.
+async fn process_message(state: &McpState, message: JsonRpcBatch) -> Option<String> {
+    let reply = match message {
+        JsonRpcBatch::Single(request) => serde_json::to_value(process_request(state, request).await?),
+        JsonRpcBatch::Batch(requests) if requests.is_empty() => {
+            serde_json::to_value(JsonRpcResponse::error(None, (-32600, "Invalid Request: empty batch".to_string()).into()))
+        }
+        JsonRpcBatch::Batch(requests) => {
+            let responses: Vec<JsonRpcResponse> = futures::future::join_all(
+                requests.into_iter().map(|request| process_request(state, request)),
+            )
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+            // A batch of notifications gets no reply at all
+            if responses.is_empty() {
+                return None;
+            }
+            serde_json::to_value(responses)
+        }
+    };
+    reply.ok().map(|reply| reply.to_string())
+}
+
 async fn process_request(state: &McpState, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
     let id = request.id.clone();
 
@@ -230,24 +465,51 @@ async fn process_request(state: &McpState, request: JsonRpcRequest) -> Option<Js
         return None;
     }
 
+    let mut trace_id = None;
     let result = match request.method.as_str() {
-        "initialize" => handle_initialize(&request.params).await,
-        "tools/list" => handle_tools_list().await,
-        "tools/call" => handle_tools_call(state, &request.params).await,
-        _ => Err((-32601, format!("Method not found: {}", request.method))),
+        "initialize" => handle_initialize(&request.params).await.map_err(Into::into),
+        "tools/list" => handle_tools_list(state).await.map_err(Into::into),
+        "resources/list" => handle_resources_list(state).await.map_err(Into::into),
+        "resources/read" => handle_resources_read(state, &request.params).await.map_err(Into::into),
+        "prompts/list" => Ok(handle_prompts_list()),
+        "prompts/get" => handle_prompts_get(&request.params).map_err(Into::into),
+        "tools/call" => {
+            // Returned to the client so agent transcripts can be matched with server logs. Each
+            // call gets its own id unless the client sent one with the request.
+            let id = trace_id.insert(trace::current().unwrap_or_else(trace::new_id)).clone();
+            trace::scope(id, handle_tools_call(state, &request.params)).await
+        }
+        _ => Err((-32601, format!("Method not found: {}", request.method)).into()),
     };
 
-    Some(match result {
+    let mut response = match result {
         Ok(value) => JsonRpcResponse::success(id, value),
-        Err((code, message)) => JsonRpcResponse::error(id, code, message),
-    })
+        Err(error) => JsonRpcResponse::error(id, error),
+    };
+
+    if let Some(trace_id) = trace_id {
+        let meta = json!({ "traceId": trace_id });
+        match (&mut response.result, &mut response.error) {
+            (Some(result), _) => result["_meta"] = meta,
+            // Argument errors already carry data; the trace id is added alongside it
+            (_, Some(JsonRpcError { data: Some(Value::Object(data)), .. })) => {
+                data.insert("traceId".to_string(), json!(trace_id));
+            }
+            (_, Some(error)) => error.data = Some(meta),
+            _ => {}
+        }
+    }
+
+    Some(response)
 }
 
 async fn handle_initialize(_params: &Value) -> Result<Value, (i32, String)> {
     Ok(json!({
         "protocolVersion": "2024-11-05",
         "capabilities": {
-            "tools": {}
+            "tools": { "listChanged": true },
+            "resources": {},
+            "prompts": {}
         },
         "serverInfo": {
             "name": "slides",
@@ -256,194 +518,317 @@ async fn handle_initialize(_params: &Value) -> Result<Value, (i32, String)> {
     }))
 }
 
-async fn handle_tools_list() -> Result<Value, (i32, String)> {
-    let tools = vec![
-        json!({
-            "name": "list_presentations",
-            "description": "List all presentations for the authenticated user",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {},
-            }
-        }),
-        json!({
-            "name": "get_presentation",
-            "description": "Get a presentation by ID, including its full markdown content",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Presentation ID" }
-                },
-                "required": ["id"]
-            }
-        }),
-        json!({
-            "name": "create_presentation",
-            "description": format!("Create a new presentation. Content is Markdown with slides separated by \"---\". {}", SLIDE_FORMAT_GUIDE),
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "title": { "type": "string", "description": "Presentation title" },
-                    "content": { "type": "string", "description": "Markdown content with slides separated by ---. Supports headings, lists, code blocks, mermaid diagrams, <!-- columns -->/<!-- split --> for two-column layouts, and **Title:** description lists for card grids." },
-                    "theme": { "type": "string", "description": "Theme name (default: \"default\"). Use list_themes to see available themes." }
-                },
-                "required": ["title", "content"]
-            }
-        }),
-        json!({
-            "name": "update_presentation",
-            "description": "Update an existing presentation (title, content, or theme). Content follows the same Markdown slide format as create_presentation.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Presentation ID" },
-                    "title": { "type": "string", "description": "New title" },
-                    "content": { "type": "string", "description": "New full markdown content (replaces existing). Uses same format: slides separated by ---, supports layout directives." },
-                    "theme": { "type": "string", "description": "New theme name. Use list_themes to see available themes." }
-                },
-                "required": ["id"]
-            }
-        }),
-        json!({
-            "name": "delete_presentation",
-            "description": "Delete a presentation by ID",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Presentation ID" }
-                },
-                "required": ["id"]
-            }
-        }),
-        json!({
-            "name": "list_themes",
-            "description": "List all available presentation themes",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {},
-            }
-        }),
-        json!({
-            "name": "add_slides",
-            "description": "Append new slides to the end of an existing presentation. The slides are added after a --- separator.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Presentation ID" },
-                    "slides": { "type": "string", "description": "Markdown for the new slides to append. Multiple slides separated by ---. Supports all layout directives: <!-- columns -->/<!-- split -->, **Title:** card lists, ```mermaid diagrams, and <!-- notes -->." }
-                },
-                "required": ["id", "slides"]
-            }
-        }),
-        json!({
-            "name": "list_media",
-            "description": "List all media files in the media library. Returns an array of media items with id, filename, originalName, mimeType, size, url, and createdAt.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {},
-            }
-        }),
-        json!({
-            "name": "upload_media",
-            "description": "Upload a media file to the media library from a local file path or a URL. Returns the media metadata and a markdown image snippet for use in slides.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "source": { "type": "string", "description": "Local file path or URL (http/https) of the media file to upload" },
-                    "filename": { "type": "string", "description": "Optional custom filename override. If not provided, the original filename is used." }
-                },
-                "required": ["source"]
-            }
-        }),
-        json!({
-            "name": "delete_media",
-            "description": "Delete a media file from the media library by its ID",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Media file ID" }
-                },
-                "required": ["id"]
-            }
-        }),
-        json!({
-            "name": "list_layout_rules",
-            "description": "List all layout rules. Layout rules define how slide content is automatically arranged (e.g., hero layout, text+image split, image grid). Rules are checked in priority order; the first matching rule is applied.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {},
-            }
-        }),
-        json!({
-            "name": "create_layout_rule",
-            "description": "Create a custom layout rule. A rule has conditions (when to apply), a transform (how to rearrange HTML), and CSS (styling for the layout classes).",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "name": { "type": "string", "description": "Unique rule name (slug format, e.g. \"my-layout\")" },
-                    "displayName": { "type": "string", "description": "Human-readable name" },
-                    "description": { "type": "string", "description": "Description of what this rule does" },
-                    "priority": { "type": "number", "description": "Priority (lower = checked first, default: 100)" },
-                    "conditions": { "type": "string", "description": "JSON string of LayoutConditions object. Fields: hasHeading (bool), imageCount ({eq/gte/lte/gt: number}), figureCount, h3Count, textParagraphCount, hasCards (bool), hasList (bool), hasCodeBlock (bool), hasBlockquote (bool). All optional, AND logic." },
-                    "transform": { "type": "string", "description": "JSON string of LayoutTransform object. Type is one of: \"wrap\", \"split-two\", \"split-top-bottom\", \"group-by-heading\". Each type has specific options." },
-                    "cssContent": { "type": "string", "description": "CSS rules for the layout classes used by the transform" }
-                },
-                "required": ["name", "displayName", "conditions", "transform", "cssContent"]
+/// Presentations as resources: every deck outside the trash, its content as markdown.
+async fn handle_resources_list(state: &McpState) -> Result<Value, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentations = app_state.db.list_all_presentations().await?;
+    let resources: Vec<Value> = presentations
+        .iter()
+        .map(|p| json!({
+            "uri": format!("{}{}", PRESENTATION_URI_PREFIX, p.id),
+            "name": p.title,
+            "mimeType": "text/markdown",
+        }))
+        .collect();
+
+    Ok(json!({ "resources": resources }))
+}
+
+async fn handle_resources_read(state: &McpState, params: &Value) -> Result<Value, (i32, String)> {
+    let uri = params["uri"].as_str().ok_or((-32602, "Missing resource uri".to_string()))?;
+    let id = uri
+        .strip_prefix(PRESENTATION_URI_PREFIX)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| (-32602, format!("Unknown resource: {}", uri)))?;
+
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(id).await.map_err(|e| match e {
+        AppError::NotFound(_) => (-32002, format!("Resource not found: {}", uri)),
+        e => e.into(),
+    })?;
+
+    Ok(json!({
+        "contents": [{
+            "uri": uri,
+            "mimeType": "text/markdown",
+            "text": presentation.content,
+        }]
+    }))
+}
+
+fn handle_prompts_list() -> Value {
+    let prompts: Vec<Value> = PROMPTS
+        .iter()
+        .map(|prompt| json!({
+            "name": prompt.name,
+            "description": prompt.description,
+            "arguments": [{
+                "name": prompt.argument,
+                "description": prompt.argument_description,
+                "required": true,
+            }],
+        }))
+        .collect();
+    json!({ "prompts": prompts })
+}
+
+/// A prompt with its argument filled in, as a single user message.
+fn handle_prompts_get(params: &Value) -> Result<Value, (i32, String)> {
+    let name = params["name"].as_str().ok_or((-32602, "Missing prompt name".to_string()))?;
+    let prompt = PROMPTS
+        .iter()
+        .find(|prompt| prompt.name == name)
+        .ok_or_else(|| (-32602, format!("Unknown prompt: {}", name)))?;
+    let value = params["arguments"][prompt.argument]
+        .as_str()
+        .filter(|value| !value.trim().is_empty())
+        .ok_or_else(|| (-32602, format!("Missing argument: {}", prompt.argument)))?;
+
+    let text = prompt.template.replace(&format!("{{{}}}", prompt.argument), value);
+    Ok(json!({
+        "description": prompt.description,
+        "messages": [{
+            "role": "user",
+            "content": { "type": "text", "text": text },
+        }],
+    }))
+}
+
+/// Names of every tool exposed by the server.
+pub fn tool_names() -> Vec<String> {
+    tool_definitions()
+        .iter()
+        .filter_map(|tool| tool["name"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// Whether `settings` allow `tool` to be advertised and called.
+pub fn tool_enabled(settings: &McpToolSettings, tool: &str) -> bool {
+    if settings.read_only && !READ_ONLY_TOOLS.contains(&tool) {
+        return false;
+    }
+    if let Some(allow) = &settings.allow {
+        if !allow.iter().any(|name| name == tool) {
+            return false;
+        }
+    }
+    !settings.deny.iter().any(|name| name == tool)
+}
+
+async fn tool_settings(state: &McpState) -> Result<McpToolSettings, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    Ok(app_state.db.get_mcp_tool_settings().await?)
+}
+
+async fn handle_tools_list(state: &McpState) -> Result<Value, (i32, String)> {
+    // Settings are read on every request so changes apply to open sessions
+    let settings = tool_settings(state).await?;
+    let tools: Vec<Value> = tool_definitions()
+        .into_iter()
+        .filter(|tool| tool["name"].as_str().is_some_and(|name| tool_enabled(&settings, name)))
+        .collect();
+
+    Ok(json!({ "tools": tools }))
+}
+
+// Every tool with its arguments, handler and description. The advertised input schema is
+// generated from the same argument type calls are deserialized into, so the two can't drift.
+macro_rules! tools {
+    ($($name:literal => $handler:ident($args:ty) $description:expr;)*) => {
+        fn tool_definitions() -> Vec<Value> {
+            vec![$(json!({
+                "name": $name,
+                "description": $description,
+                "inputSchema": input_schema::<$args>(),
+            }),)*]
+        }
+
+        async fn call_tool(state: &McpState, name: &str, arguments: Value) -> Result<String, JsonRpcError> {
+            match name {
+                $($name => Ok($handler(state, parse_arguments::<$args>(arguments)?).await?),)*
+                _ => Err((-32602, format!("Unknown tool: {}", name)).into()),
             }
-        }),
-        json!({
-            "name": "delete_layout_rule",
-            "description": "Delete a custom layout rule by ID. Default (built-in) rules cannot be deleted.",
-            "inputSchema": {
-                "$schema": "http://json-schema.org/draft-07/schema#",
-                "type": "object",
-                "properties": {
-                    "id": { "type": "string", "description": "Layout rule ID" }
-                },
-                "required": ["id"]
+        }
+
+        #[cfg(test)]
+        fn check_arguments(name: &str, arguments: Value) -> Result<(), JsonRpcError> {
+            match name {
+                $($name => parse_arguments::<$args>(arguments).map(drop),)*
+                _ => Err((-32602, format!("Unknown tool: {}", name)).into()),
             }
-        }),
-    ];
+        }
+    };
+}
 
-    Ok(json!({ "tools": tools }))
+tools! {
+    "list_presentations" => tool_list_presentations(ListPresentationsArgs)
+        "List presentations for the authenticated user, most recently updated first. Results are paginated; the response includes items, total, page, and totalPages. Each item has a healthScore (0-100); low scores point to decks that need attention.";
+    "search_presentations" => tool_search_presentations(SearchPresentationsArgs)
+        "Full-text search across presentation titles and content. Results are ranked by relevance and paginated; the response includes items, total, page, and totalPages.";
+    "find_related_presentations" => tool_find_related_presentations(FindRelatedArgs)
+        "Find other presentations covering the same ground as a given one, useful for reusing prior material. Decks are compared by their most distinguishing terms; each result has a score (0-1) and the matchedTerms the decks share. Archived presentations are left out.";
+    "get_presentation" => tool_get_presentation(PresentationIdArgs)
+        "Get a presentation by ID, including its full markdown content";
+    "get_presentation_stats" => tool_get_presentation_stats(PresentationIdArgs)
+        "Get reading statistics for a presentation: slideCount, wordCount, estimatedReadingMinutes (at 130 words per minute), imageCount and codeBlockCount. Speaker notes and other HTML comments are not counted.";
+    "create_presentation" => tool_create_presentation(CreatePresentationArgs)
+        format!("Create a new presentation. Content is Markdown with slides separated by \"---\". {}", SLIDE_FORMAT_GUIDE);
+    "update_presentation" => tool_update_presentation(UpdatePresentationArgs)
+        "Update an existing presentation (title, content, or theme). Content follows the same Markdown slide format as create_presentation. The response has changed set to false when the presentation already had these values, in which case nothing was written.";
+    "delete_presentation" => tool_delete_presentation(PresentationIdArgs)
+        "Delete a presentation by ID. The presentation is moved to the trash and can be restored with undelete_presentation.";
+    "duplicate_presentation" => tool_duplicate_presentation(DuplicatePresentationArgs)
+        "Create a copy of a presentation with the same content and theme. The copy is titled \"Copy of <original title>\" unless newTitle is given.";
+    "undelete_presentation" => tool_undelete_presentation(PresentationIdArgs)
+        "Restore a previously deleted presentation from the trash";
+    "list_presentation_versions" => tool_list_presentation_versions(PresentationIdArgs)
+        "List saved versions of a presentation, newest first. A version is saved automatically whenever the title, content or theme changes, holding the state before the change. Older versions are pruned once the history limit (50 by default) is reached.";
+    "restore_presentation_version" => tool_restore_presentation_version(RestoreVersionArgs)
+        "Restore a presentation's content and theme from a saved version. The current state is saved as a new version first, so the restore can be undone.";
+    "list_tags" => tool_list_tags(NoArgs)
+        "List all tags used to organize presentations";
+    "add_tag" => tool_add_tag(TagArgs)
+        "Add a tag to a presentation. Tags are created on first use; names are case-insensitive.";
+    "remove_tag" => tool_remove_tag(TagArgs)
+        "Remove a tag from a presentation";
+    "list_themes" => tool_list_themes(NoArgs)
+        "List all available presentation themes";
+    "create_theme" => tool_create_theme(CreateThemeArgs)
+        "Create a custom theme. The CSS must scope its rules to [data-theme=\"<name>\"] so it only applies when the theme is selected; it is rejected, with a line number per problem, if its braces don't balance or it uses @import, javascript: URLs or position: fixed outside the theme's rules.";
+    "update_theme" => tool_update_theme(UpdateThemeArgs)
+        "Update a custom theme. Only provided fields are changed. Built-in themes cannot be modified.";
+    "delete_theme" => tool_delete_theme(DeleteThemeArgs)
+        "Delete a custom theme. Built-in themes cannot be deleted. A theme still used by presentations is only deleted with force set, which switches those presentations to the default theme.";
+    "add_slides" => tool_add_slides(AddSlidesArgs)
+        "Append new slides to the end of an existing presentation. The slides are added after a --- separator.";
+    "reorder_slides" => tool_reorder_slides(ReorderSlidesArgs)
+        "Reorder the slides of a presentation. Pass the current slide indices (0-based) in their new order, e.g. [2, 0, 1] moves the third slide to the front. Every index must appear exactly once.";
+    "list_placeholders" => tool_list_placeholders(PresentationIdArgs)
+        "List the {{name}} placeholders still unfilled in a presentation, with their type hints and the slides they appear on. Fill them with fill_placeholders.";
+    "lint_presentation" => tool_lint_presentation(LintPresentationArgs)
+        "Check presentation markdown for problems before presenting or saving: uploads that don't exist, slides with more than 4 cards, empty slides, unclosed <!-- notes --> blocks, and {{placeholders}} that have not been filled in. Pass id to lint a saved presentation, or content to check markdown you are about to save. Returns a list of { slideIndex, severity, rule, message }; an empty list means no issues.";
+    "diff_presentation" => tool_diff_presentation(DiffPresentationArgs)
+        "Show what changed in a presentation, slide by slide. Pass content to compare markdown you are about to save against the saved deck, or fromVersion (and optionally toVersion, defaulting to the saved deck) to compare versions from list_presentation_versions. Returns counts of added, removed, modified and unchanged slides, and each change with its old and new slide index and a unified diff of its lines. Slides that only moved are unchanged.";
+    "fill_placeholders" => tool_fill_placeholders(FillPlaceholdersArgs)
+        "Replace {{name}} placeholders throughout a presentation. Values are checked against type hints ({{when:date}} needs YYYY-MM-DD, {{count:number}} a number). Names that are already filled are ignored.";
+    "list_slides" => tool_list_slides(PresentationIdArgs)
+        "List the slides of a presentation in order, each with its 0-based index, markdown content (including any speaker notes block) and notes. Use the indices with get_slide, replace_slide, delete_slide and insert_slide.";
+    "get_slide" => tool_get_slide(SlideArgs)
+        "Get a single slide of a presentation by its 0-based index, with its speaker notes and the presentation's total slide count. Use this instead of get_presentation when you only need one slide.";
+    "replace_slide" => tool_replace_slide(ReplaceSlideArgs)
+        "Replace the markdown of a single slide, leaving the other slides untouched. Prefer this over update_presentation for small edits.";
+    "delete_slide" => tool_delete_slide(SlideArgs)
+        "Delete a single slide from a presentation";
+    "insert_slide" => tool_insert_slide(InsertSlideArgs)
+        "Insert a new slide before the slide at the given 0-based index, or append it when index is omitted";
+    "insert_slide_at" => tool_insert_slide_at(InsertSlideAtArgs)
+        "Same as insert_slide, with the index passed as position. Kept for older clients; prefer insert_slide.";
+    "list_media" => tool_list_media(NoArgs)
+        "List all media files in the media library. Returns an array of media items with id, filename, originalName, mimeType, size, url, and createdAt.";
+    "upload_media" => tool_upload_media(UploadMediaArgs)
+        "Upload a media file to the media library from a local file path or a URL. Returns the media metadata (including width and height for images and durationSeconds for audio and video, when they can be read) and a markdown image snippet for use in slides. A file already in the library is not stored twice: the existing entry is returned with deduplicated set to true.";
+    "delete_media" => tool_delete_media(MediaIdArgs)
+        "Delete a media file from the media library by its ID";
+    "list_layout_rules" => tool_list_layout_rules(NoArgs)
+        "List all layout rules. Layout rules define how slide content is automatically arranged (e.g., hero layout, text+image split, image grid). Rules are checked in priority order; the first matching rule is applied.";
+    "create_layout_rule" => tool_create_layout_rule(CreateLayoutRuleArgs)
+        "Create a custom layout rule. A rule has conditions (when to apply), a transform (how to rearrange HTML), and CSS (styling for the layout classes).";
+    "delete_layout_rule" => tool_delete_layout_rule(LayoutRuleIdArgs)
+        "Delete a custom layout rule by ID. Default (built-in) rules cannot be deleted.";
+    "run_pipeline" => tool_run_pipeline(RunPipelineArgs)
+        "Run a saved pipeline: a named sequence of operations executed in order, where each step can use the previous step's output. Returns per-step results. Stops at the first failing step unless that step is marked continueOnError. The built-in \"outline-to-deck\" pipeline takes input {provider, outline, title}.";
+}
+
+/// The JSON Schema advertised for a tool taking `T` as its arguments.
+fn input_schema<T: JsonSchema>() -> Value {
+    // Inlined, since not every client resolves $refs
+    let mut schema = SchemaSettings::draft07()
+        .with(|s| s.inline_subschemas = true)
+        .into_generator()
+        .into_root_schema_for::<T>();
+    // The title is the Rust type name, which means nothing to clients
+    schema.remove("title");
+    schema.to_value()
 }
 
-async fn handle_tools_call(state: &McpState, params: &Value) -> Result<Value, (i32, String)> {
+/// A struct argument that must be given as an object; serde alone would also take an array
+/// of its fields in order.
+fn object<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    Option::<serde_json::Map<String, Value>>::deserialize(deserializer)?
+        .map(|map| serde_json::from_value(Value::Object(map)).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Progress token chosen by the client, echoed in its progress notifications.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ProgressToken {
+    String(String),
+    Number(i64),
+}
+
+/// Reports progress of a tool call on the calling session's stream, as `notifications/progress`
+/// with a total of 1. Does nothing unless the client passed a progress token.
+struct Progress<'a> {
+    session: Option<&'a mpsc::Sender<String>>,
+    token: Option<ProgressToken>,
+}
+
+impl<'a> Progress<'a> {
+    fn new(state: &'a McpState, token: Option<ProgressToken>) -> Self {
+        Self { session: state.session.as_ref(), token }
+    }
+
+    async fn report(&self, progress: u32) {
+        let (Some(session), Some(token)) = (self.session, &self.token) else { return };
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": { "progressToken": token, "progress": progress, "total": 1 },
+        });
+        // Progress is advisory; a client that stopped reading misses it
+        if session.try_send(notification.to_string()).is_err() {
+            tracing::debug!("Failed to send progress notification");
+        }
+    }
+}
+
+/// Deserializes tool arguments. Errors name the offending field the way the REST API's
+/// validation errors do.
+fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T, JsonRpcError> {
+    serde_path_to_error::deserialize(arguments).map_err(|e| {
+        let path = e.path().to_string();
+        let message = e.inner().to_string();
+        JsonRpcError {
+            code: -32602,
+            message: format!("Invalid arguments: {} (at {})", message, path),
+            data: Some(json!({ "errors": [FieldError { field: path, message }] })),
+        }
+    })
+}
+
+async fn handle_tools_call(state: &McpState, params: &Value) -> Result<Value, JsonRpcError> {
     let name = params
         .get("name")
         .and_then(|v| v.as_str())
         .ok_or((-32602, "Missing tool name".to_string()))?;
 
-    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
-
-    let result = match name {
-        "list_presentations" => tool_list_presentations(state).await,
-        "get_presentation" => tool_get_presentation(state, &arguments).await,
-        "create_presentation" => tool_create_presentation(state, &arguments).await,
-        "update_presentation" => tool_update_presentation(state, &arguments).await,
-        "delete_presentation" => tool_delete_presentation(state, &arguments).await,
-        "list_themes" => tool_list_themes(state).await,
-        "add_slides" => tool_add_slides(state, &arguments).await,
-        "list_media" => tool_list_media(state).await,
-        "upload_media" => tool_upload_media(state, &arguments).await,
-        "delete_media" => tool_delete_media(state, &arguments).await,
-        "list_layout_rules" => tool_list_layout_rules(state).await,
-        "create_layout_rule" => tool_create_layout_rule(state, &arguments).await,
-        "delete_layout_rule" => tool_delete_layout_rule(state, &arguments).await,
-        _ => Err((-32602, format!("Unknown tool: {}", name))),
-    }?;
+    let arguments = params
+        .get("arguments")
+        .filter(|v| !v.is_null())
+        .cloned()
+        .unwrap_or(json!({}));
+
+    let settings = tool_settings(state).await?;
+    if !tool_enabled(&settings, name) {
+        return Err((TOOL_DISABLED, format!("Tool {} is disabled in the MCP tool settings", name)).into());
+    }
+
+    let result = call_tool(state, name, arguments).await?;
 
     Ok(json!({
         "content": [{
@@ -453,170 +838,581 @@ async fn handle_tools_call(state: &McpState, params: &Value) -> Result<Value, (i
     }))
 }
 
-// Tool implementations
+// Tool arguments and implementations. Unknown fields are rejected so a misspelled or
+// invented argument fails loudly instead of silently falling back to a default.
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct NoArgs {}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct PresentationIdArgs {
+    /// Presentation ID
+    id: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ListPresentationsArgs {
+    /// Page number, starting at 1 (default: 1)
+    page: Option<u32>,
+    /// Presentations per page (default: 20, max: 100)
+    per_page: Option<u32>,
+    /// Only include presentations carrying all of these tags
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Also list archived presentations (default: false)
+    #[serde(default)]
+    include_archived: bool,
+}
+
+async fn tool_list_presentations(state: &McpState, args: ListPresentationsArgs) -> Result<String, (i32, String)> {
+    let mut query = ListPresentationsQuery::default();
+    if let Some(page) = args.page {
+        query.page = page;
+    }
+    if let Some(per_page) = args.per_page {
+        query.per_page = per_page;
+    }
+    query.tags = args.tags;
+    query.include_archived = args.include_archived;
 
-async fn tool_list_presentations(state: &McpState) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    let presentations = app_state
-        .db
-        .list_presentations()
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&presentations).map_err(|e| (-32000, e.to_string()))
+    let presentations = app_state.db.list_presentations(query).await?;
+    to_json(&presentations)
 }
 
-async fn tool_get_presentation(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct SearchPresentationsArgs {
+    /// Search terms. All terms must match; each term also matches as a prefix.
+    query: String,
+    /// Page number, starting at 1 (default: 1)
+    page: Option<u32>,
+    /// Results per page (default: 20, max: 100)
+    per_page: Option<u32>,
+}
+
+async fn tool_search_presentations(state: &McpState, args: SearchPresentationsArgs) -> Result<String, (i32, String)> {
+    let page = args.page.unwrap_or(1);
+    let per_page = args.per_page.unwrap_or(DEFAULT_PER_PAGE);
 
     let app_state = state.app_state.read().await;
-    let presentation = app_state
-        .db
-        .get_presentation(id)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&presentation).map_err(|e| (-32000, e.to_string()))
+    // A blank query is a BadRequest, reported as invalid params
+    let results = app_state.db.search_presentations(&args.query, page, per_page).await?;
+    to_json(&results)
 }
 
-async fn tool_create_presentation(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let title = args
-        .get("title")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: title".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct FindRelatedArgs {
+    /// Presentation ID
+    id: String,
+    /// Maximum number of results (default: 5, max: 50)
+    limit: Option<usize>,
+}
+
+async fn tool_find_related_presentations(state: &McpState, args: FindRelatedArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let related = related::find(&app_state, &args.id, args.limit).await?;
+    to_json(&related)
+}
+
+async fn tool_get_presentation(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
+    to_json(&presentation)
+}
 
-    let content = args.get("content").and_then(|v| v.as_str()).map(String::from);
-    let theme = args.get("theme").and_then(|v| v.as_str()).map(String::from);
+async fn tool_get_presentation_stats(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
+    let stats = slides::presentation_stats(&presentation.content);
+    to_json(&stats)
+}
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CreatePresentationArgs {
+    /// Presentation title
+    title: String,
+    /// Markdown content with slides separated by ---. Supports headings, lists, code blocks, mermaid diagrams, <!-- columns -->/<!-- split --> for two-column layouts, and **Title:** description lists for card grids.
+    content: String,
+    /// Theme name. When omitted, the theme configured as the default for new presentations is used. Use list_themes to see available themes.
+    theme: Option<String>,
+    /// If a presentation with the same title (ignoring case and surrounding whitespace) exists, return it instead of creating a duplicate. Set this when retrying a create.
+    #[serde(default)]
+    if_not_exists: bool,
+    /// What to do when a presentation with the same title exists: return it, or fail. Overrides ifNotExists.
+    on_conflict: Option<TitleConflict>,
+    /// Token for notifications/progress messages about this call
+    #[serde(rename = "_progressToken")]
+    progress_token: Option<ProgressToken>,
+}
+
+async fn tool_create_presentation(state: &McpState, args: CreatePresentationArgs) -> Result<String, (i32, String)> {
     let data = CreatePresentation {
-        title: title.to_string(),
-        content,
-        theme,
+        title: args.title,
+        content: Some(args.content),
+        theme: args.theme,
+        if_not_exists: args.if_not_exists,
+        on_conflict: args.on_conflict,
     };
 
+    let progress = Progress::new(state, args.progress_token);
+    progress.report(0).await;
     let app_state = state.app_state.read().await;
-    let presentation = app_state
-        .db
-        .create_presentation(data)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&presentation).map_err(|e| (-32000, e.to_string()))
+    let presentation = app_state.db.create_presentation(data).await?;
+    progress.report(1).await;
+    to_json(&presentation)
 }
 
-async fn tool_update_presentation(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
-
-    let title = args.get("title").and_then(|v| v.as_str()).map(String::from);
-    let content = args.get("content").and_then(|v| v.as_str()).map(String::from);
-    let theme = args.get("theme").and_then(|v| v.as_str()).map(String::from);
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct UpdatePresentationArgs {
+    /// Presentation ID
+    id: String,
+    /// New title
+    title: Option<String>,
+    /// New full markdown content (replaces existing). Uses same format: slides separated by ---, supports layout directives.
+    content: Option<String>,
+    /// New theme name. Use list_themes to see available themes.
+    theme: Option<String>,
+    /// Token for notifications/progress messages about this call
+    #[serde(rename = "_progressToken")]
+    progress_token: Option<ProgressToken>,
+}
 
+async fn tool_update_presentation(state: &McpState, args: UpdatePresentationArgs) -> Result<String, (i32, String)> {
     let data = UpdatePresentation {
-        title,
-        content,
-        theme,
+        title: args.title,
+        content: args.content,
+        theme: args.theme,
+        settings: None,
     };
 
+    let progress = Progress::new(state, args.progress_token);
+    progress.report(0).await;
     let app_state = state.app_state.read().await;
-    let presentation = app_state
-        .db
-        .update_presentation(id, data)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&presentation).map_err(|e| (-32000, e.to_string()))
+    let presentation = app_state.db.update_presentation(&args.id, data).await?;
+    progress.report(1).await;
+    to_json(&presentation)
 }
 
-async fn tool_delete_presentation(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
+async fn tool_delete_presentation(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    app_state.db.delete_presentation(&args.id).await?;
+    Ok(format!("Presentation {} deleted successfully.", args.id))
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct DuplicatePresentationArgs {
+    /// ID of the presentation to copy
+    id: String,
+    /// Title for the copy (optional)
+    new_title: Option<String>,
+}
+
+async fn tool_duplicate_presentation(state: &McpState, args: DuplicatePresentationArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.duplicate_presentation(&args.id, args.new_title).await?;
+    to_json(&presentation)
+}
+
+async fn tool_undelete_presentation(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.restore_presentation(&args.id).await?;
+    to_json(&presentation)
+}
+
+async fn tool_list_presentation_versions(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let versions = app_state.db.list_versions(&args.id).await?;
+    to_json(&versions)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RestoreVersionArgs {
+    /// Presentation ID
+    id: String,
+    /// Version ID from list_presentation_versions
+    version_id: String,
+}
+
+async fn tool_restore_presentation_version(state: &McpState, args: RestoreVersionArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.restore_version(&args.id, &args.version_id).await?;
+    to_json(&presentation)
+}
+
+async fn tool_list_tags(state: &McpState, _: NoArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let tags = app_state.db.list_tags().await?;
+    to_json(&tags)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct TagArgs {
+    /// Presentation ID
+    id: String,
+    /// Tag name
+    tag: String,
+}
+
+async fn tool_add_tag(state: &McpState, args: TagArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.add_tag_to_presentation(&args.id, &args.tag).await?;
+    to_json(&presentation)
+}
 
+async fn tool_remove_tag(state: &McpState, args: TagArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    app_state
+    let presentation = app_state.db.remove_tag_from_presentation(&args.id, &args.tag).await?;
+    to_json(&presentation)
+}
+
+async fn tool_list_themes(state: &McpState, _: NoArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let mut themes = app_state.db.list_themes().await?;
+    if app_state.safe_mode {
+        themes = crate::safe_mode::themes(themes, false);
+    }
+    to_json(&themes)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CreateThemeArgs {
+    /// Unique theme identifier (kebab-case)
+    name: String,
+    /// Human-readable theme name
+    display_name: String,
+    /// Theme CSS
+    css_content: String,
+    /// Vertically center slide content (default: true)
+    center_content: Option<bool>,
+    /// Font stacks the CSS uses, so the app can load them (default: none)
+    #[serde(default, deserialize_with = "object")]
+    fonts: Option<ThemeFonts>,
+}
+
+async fn tool_create_theme(state: &McpState, args: CreateThemeArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let theme = app_state
         .db
-        .delete_presentation(id)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    Ok(format!("Presentation {} deleted successfully.", id))
+        .create_theme(
+            CreateTheme {
+                name: args.name,
+                display_name: args.display_name,
+                css_content: args.css_content,
+                center_content: args.center_content,
+                fonts: args.fonts.unwrap_or_default(),
+            },
+            NameConflict::Error,
+        )
+        .await?;
+    to_json(&theme)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct UpdateThemeArgs {
+    /// Theme ID
+    id: String,
+    /// New display name
+    display_name: Option<String>,
+    /// New theme CSS
+    css_content: Option<String>,
+    /// Vertically center slide content
+    center_content: Option<bool>,
+    /// New fonts, replacing all of the current ones
+    #[serde(default, deserialize_with = "object")]
+    fonts: Option<ThemeFonts>,
 }
 
-async fn tool_list_themes(state: &McpState) -> Result<String, (i32, String)> {
+async fn tool_update_theme(state: &McpState, args: UpdateThemeArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    let themes = app_state
+    let theme = app_state
         .db
-        .list_themes()
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&themes).map_err(|e| (-32000, e.to_string()))
+        .update_theme(&args.id, UpdateTheme {
+            display_name: args.display_name,
+            css_content: args.css_content,
+            center_content: args.center_content,
+            fonts: args.fonts,
+        })
+        .await?;
+    to_json(&theme)
 }
 
-async fn tool_add_slides(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct DeleteThemeArgs {
+    /// Theme ID
+    id: String,
+    /// Switch presentations still using the theme to the default theme (default: false, which refuses to delete a theme in use)
+    #[serde(default)]
+    force: bool,
+}
 
-    let slides = args
-        .get("slides")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: slides".to_string()))?;
+async fn tool_delete_theme(state: &McpState, args: DeleteThemeArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    app_state.db.delete_theme(&args.id, args.force).await?;
+    Ok(format!("Theme {} deleted successfully.", args.id))
+}
 
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct AddSlidesArgs {
+    /// Presentation ID
+    id: String,
+    /// Markdown for the new slides to append. Multiple slides separated by ---. Supports all layout directives: <!-- columns -->/<!-- split -->, **Title:** card lists, ```mermaid diagrams, and <!-- notes -->.
+    slides: String,
+}
+
+async fn tool_add_slides(state: &McpState, args: AddSlidesArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
 
     // Get existing presentation
-    let presentation = app_state
-        .db
-        .get_presentation(id)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
 
     // Append new slides
-    let new_content = format!("{}\n\n---\n\n{}", presentation.content.trim_end(), slides);
+    let new_content = format!("{}\n\n---\n\n{}", presentation.content.trim_end(), args.slides);
 
     let data = UpdatePresentation {
         title: None,
         content: Some(new_content),
         theme: None,
+        settings: None,
     };
 
-    let updated = app_state
-        .db
-        .update_presentation(id, data)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&updated).map_err(|e| (-32000, e.to_string()))
+    let updated = app_state.db.update_presentation(&args.id, data).await?;
+    to_json(&updated.presentation)
 }
 
-async fn tool_list_media(state: &McpState) -> Result<String, (i32, String)> {
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ReorderSlidesArgs {
+    /// Presentation ID
+    id: String,
+    /// Current slide indices in their new order
+    order: Vec<usize>,
+}
+
+async fn tool_reorder_slides(state: &McpState, args: ReorderSlidesArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    let media = app_state
-        .db
-        .list_media()
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    serde_json::to_string_pretty(&media).map_err(|e| (-32000, e.to_string()))
+    let presentation = app_state.db.reorder_slides(&args.id, &args.order).await?;
+    to_json(&presentation)
 }
 
-async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let source = args
-        .get("source")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: source".to_string()))?;
+async fn tool_list_placeholders(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
+
+    let found = placeholders::find_placeholders(&presentation.content);
+    to_json(&found)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct LintPresentationArgs {
+    /// Presentation ID
+    id: Option<String>,
+    /// Markdown to lint instead of a saved presentation
+    content: Option<String>,
+}
+
+async fn tool_lint_presentation(state: &McpState, args: LintPresentationArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let content = match (args.content, args.id) {
+        (Some(content), _) => content,
+        (None, Some(id)) => {
+            app_state
+                .db
+                .get_presentation(&id)
+                .await?
+                .content
+        }
+        (None, None) => return Err((-32602, "Provide either id or content".to_string())),
+    };
+
+    let issues = lint::lint_content(&app_state, &content).await?;
+    to_json(&issues)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct DiffPresentationArgs {
+    /// Presentation ID
+    id: String,
+    /// Candidate markdown to compare against the saved deck
+    content: Option<String>,
+    /// Version ID to compare from
+    from_version: Option<String>,
+    /// Version ID to compare to; the saved deck when omitted
+    to_version: Option<String>,
+}
+
+async fn tool_diff_presentation(state: &McpState, args: DiffPresentationArgs) -> Result<String, (i32, String)> {
+    let request = DiffPresentationRequest {
+        from_version: args.from_version,
+        to_version: args.to_version,
+        content: args.content,
+    };
+
+    let app_state = state.app_state.read().await;
+    let diff = diff::diff_presentation(&app_state, &args.id, &request).await?;
+    to_json(&diff)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct FillPlaceholdersArgs {
+    /// Presentation ID
+    id: String,
+    /// Placeholder names mapped to their values
+    values: HashMap<String, PlaceholderValue>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum PlaceholderValue {
+    Text(String),
+    Number(serde_json::Number),
+    Flag(bool),
+}
+
+impl From<PlaceholderValue> for Value {
+    fn from(value: PlaceholderValue) -> Self {
+        match value {
+            PlaceholderValue::Text(text) => Value::String(text),
+            PlaceholderValue::Number(number) => Value::Number(number),
+            PlaceholderValue::Flag(flag) => Value::Bool(flag),
+        }
+    }
+}
+
+async fn tool_fill_placeholders(state: &McpState, args: FillPlaceholdersArgs) -> Result<String, (i32, String)> {
+    let values: HashMap<String, Value> = args.values.into_iter().map(|(k, v)| (k, v.into())).collect();
+
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.fill_placeholders(&args.id, &values).await?;
+    to_json(&presentation)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct SlideArgs {
+    /// Presentation ID
+    id: String,
+    /// Slide index (0-based)
+    index: usize,
+}
+
+async fn tool_list_slides(state: &McpState, args: PresentationIdArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
+    to_json(&slides::slide_items(&presentation.content))
+}
 
-    let custom_filename = args.get("filename").and_then(|v| v.as_str());
+async fn tool_get_slide(state: &McpState, args: SlideArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.get_presentation(&args.id).await?;
+
+    let slide = slides::get_slide(&presentation.content, args.index).map_err(|e| (-32602, e.to_string()))?;
+    to_json(&slide)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct ReplaceSlideArgs {
+    /// Presentation ID
+    id: String,
+    /// Slide index (0-based)
+    index: usize,
+    /// New slide markdown, without --- separators
+    content: String,
+}
+
+async fn tool_replace_slide(state: &McpState, args: ReplaceSlideArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.replace_slide(&args.id, args.index, &args.content).await?;
+    to_json(&presentation)
+}
+
+async fn tool_delete_slide(state: &McpState, args: SlideArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.delete_slide(&args.id, args.index).await?;
+    to_json(&presentation)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct InsertSlideArgs {
+    /// Presentation ID
+    id: String,
+    /// Slide markdown, without --- separators
+    content: String,
+    /// 0-based index of the slide to insert before (optional)
+    index: Option<usize>,
+}
+
+async fn tool_insert_slide(state: &McpState, args: InsertSlideArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let presentation = app_state.db.insert_slide(&args.id, args.index, &args.content).await?;
+    to_json(&presentation)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct InsertSlideAtArgs {
+    /// Presentation ID
+    id: String,
+    /// Slide markdown, without --- separators
+    content: String,
+    /// Index to insert before (optional)
+    position: Option<usize>,
+}
+
+async fn tool_insert_slide_at(state: &McpState, args: InsertSlideAtArgs) -> Result<String, (i32, String)> {
+    let InsertSlideAtArgs { id, content, position } = args;
+    tool_insert_slide(state, InsertSlideArgs { id, content, index: position }).await
+}
+
+async fn tool_list_media(state: &McpState, _: NoArgs) -> Result<String, (i32, String)> {
+    let app_state = state.app_state.read().await;
+    let media = app_state.db.list_media().await?;
+    to_json(&media)
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct UploadMediaArgs {
+    /// Local file path or URL (http/https) of the media file to upload
+    source: String,
+    /// Optional custom filename override. If not provided, the original filename is used.
+    filename: Option<String>,
+}
+
+async fn tool_upload_media(state: &McpState, args: UploadMediaArgs) -> Result<String, (i32, String)> {
+    media_upload_response(upload_media(state, args).await?)
+}
+
+async fn upload_media(state: &McpState, args: UploadMediaArgs) -> AppResult<Media> {
+    let source = args.source.as_str();
+    let custom_filename = args.filename.as_deref();
 
     let (data, filename, mime_type) = if source.starts_with("http://") || source.starts_with("https://") {
         // Download from URL
-        let response = reqwest::get(source)
-            .await
-            .map_err(|e| (-32000, format!("Failed to download: {}", e)))?;
+        let client = state.app_state.read().await.http_client.clone();
+        let mut response = client.get(source).timeout(DOWNLOAD_TIMEOUT).send().await?.error_for_status()?;
 
-        if !response.status().is_success() {
-            return Err((-32000, format!("Failed to download: {}", response.status())));
+        // Turn oversized downloads away before buffering them
+        if let Some(len) = response.content_length() {
+            crate::uploads::check_upload_size(len as usize)?;
         }
 
         let content_type = response
@@ -633,18 +1429,23 @@ async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i3
 
         let name = custom_filename.map(String::from).unwrap_or(url_path);
 
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| (-32000, format!("Failed to read response: {}", e)))?;
+        // The declared length can be missing or wrong, so the cap is enforced while reading
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            crate::uploads::check_upload_size(data.len() + chunk.len())?;
+            data.extend_from_slice(&chunk);
+        }
 
-        (bytes.to_vec(), name, content_type)
+        (data, name, content_type)
     } else {
         // Read from local file
         let path = std::path::Path::new(source);
+        if let Ok(metadata) = tokio::fs::metadata(path).await {
+            crate::uploads::check_upload_size(metadata.len() as usize)?;
+        }
         let data = tokio::fs::read(path)
             .await
-            .map_err(|e| (-32000, format!("Failed to read file: {}", e)))?;
+            .map_err(|e| AppError::Internal(format!("Failed to read file: {}", e)))?;
 
         let name = custom_filename
             .map(String::from)
@@ -665,12 +1466,23 @@ async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i3
         && !mime_type.starts_with("video/")
         && !mime_type.starts_with("audio/")
     {
-        return Err((-32602, "Only image, video, and audio files are allowed".to_string()));
+        return Err(AppError::BadRequest("Only image, video, and audio files are allowed".to_string()));
+    }
+    if !crate::media::validate_file_magic(&data, &mime_type) {
+        return Err(AppError::BadRequest("File content does not match declared MIME type".to_string()));
     }
 
+    let data = axum::body::Bytes::from(data);
+    let content_hash = crate::uploads::sha256_bytes(data.clone()).await?;
+
     let app_state = state.app_state.read().await;
     let uploads_dir = app_state.uploads_dir.clone();
 
+    // A file already in the library isn't stored again
+    if let Some(media) = app_state.db.find_media_by_hash(&content_hash).await? {
+        return Ok(media);
+    }
+
     // Generate unique filename
     let ext = std::path::Path::new(&filename)
         .extension()
@@ -687,10 +1499,12 @@ async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i3
     let file_path = uploads_dir.join(&unique_name);
     tokio::fs::write(&file_path, &data)
         .await
-        .map_err(|e| (-32000, format!("Failed to write file: {}", e)))?;
+        .map_err(|e| AppError::Internal(format!("Failed to write file: {}", e)))?;
+
+    let metadata = crate::media::read_metadata(file_path.clone(), &mime_type).await;
 
     // Create database record
-    let url = format!("/api/uploads/{}", unique_name);
+    let url = crate::uploads::upload_url(&unique_name);
     let media = app_state
         .db
         .create_media(
@@ -699,10 +1513,19 @@ async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i3
             mime_type,
             data.len() as i64,
             url.clone(),
+            metadata,
+            content_hash,
         )
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
+        .await?;
+    if media.deduplicated {
+        let _ = tokio::fs::remove_file(&file_path).await;
+    }
 
+    Ok(media)
+}
+
+/// The upload_media result: the media entry plus a markdown snippet for use in slides.
+fn media_upload_response(media: Media) -> Result<String, (i32, String)> {
     // Add markdown snippet to response
     let markdown_snippet = format!("![{}]({})", media.original_name, media.url);
     let response = json!({
@@ -713,26 +1536,29 @@ async fn tool_upload_media(state: &McpState, args: &Value) -> Result<String, (i3
         "size": media.size,
         "url": media.url,
         "createdAt": media.created_at,
+        "width": media.width,
+        "height": media.height,
+        "durationSeconds": media.duration_seconds,
+        "contentHash": media.content_hash,
+        "deduplicated": media.deduplicated,
         "markdownSnippet": markdown_snippet
     });
 
-    serde_json::to_string_pretty(&response).map_err(|e| (-32000, e.to_string()))
+    to_json(&response)
 }
 
-async fn tool_delete_media(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct MediaIdArgs {
+    /// Media file ID
+    id: String,
+}
 
+async fn tool_delete_media(state: &McpState, args: MediaIdArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
     let uploads_dir = app_state.uploads_dir.clone();
 
-    let media = app_state
-        .db
-        .delete_media(id)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
+    let media = app_state.db.delete_media(&args.id).await?;
 
     if let Some(media) = media {
         // Delete file from disk
@@ -740,93 +1566,102 @@ async fn tool_delete_media(state: &McpState, args: &Value) -> Result<String, (i3
         if file_path.exists() {
             let _ = tokio::fs::remove_file(file_path).await;
         }
-        Ok(format!("Media {} deleted successfully.", id))
+        Ok(format!("Media {} deleted successfully.", args.id))
     } else {
         Err((-32000, "Media not found".to_string()))
     }
 }
 
-async fn tool_list_layout_rules(state: &McpState) -> Result<String, (i32, String)> {
+async fn tool_list_layout_rules(state: &McpState, _: NoArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    let rules = app_state
-        .db
-        .list_layout_rules()
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
+    let rules = app_state.db.list_layout_rules().await?;
 
     // Convert to response format with parsed JSON fields
-    let responses: Vec<crate::models::LayoutRuleResponse> =
+    let mut responses: Vec<crate::models::LayoutRuleResponse> =
         rules.into_iter().map(Into::into).collect();
-    serde_json::to_string_pretty(&responses).map_err(|e| (-32000, e.to_string()))
+    if app_state.safe_mode {
+        responses = crate::safe_mode::layout_rules(responses, false);
+    }
+    to_json(&responses)
 }
 
-async fn tool_create_layout_rule(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let name = args
-        .get("name")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: name".to_string()))?;
-
-    let display_name = args
-        .get("displayName")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: displayName".to_string()))?;
-
-    let description = args.get("description").and_then(|v| v.as_str());
-    let priority = args.get("priority").and_then(|v| v.as_i64()).unwrap_or(100) as i32;
-
-    let conditions = args
-        .get("conditions")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: conditions".to_string()))?;
-
-    let transform = args
-        .get("transform")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: transform".to_string()))?;
-
-    let css_content = args
-        .get("cssContent")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: cssContent".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CreateLayoutRuleArgs {
+    /// Unique rule name (slug format, e.g. "my-layout")
+    name: String,
+    /// Human-readable name
+    display_name: String,
+    /// Description of what this rule does
+    description: Option<String>,
+    /// Priority (lower = checked first, default: 100)
+    priority: Option<i32>,
+    /// JSON string of LayoutConditions object. Fields: hasHeading (bool), imageCount ({eq/gte/lte/gt: number}), figureCount, h3Count, textParagraphCount, hasCards (bool), hasList (bool), hasCodeBlock (bool), hasBlockquote (bool). All optional, AND logic.
+    conditions: String,
+    /// JSON string of LayoutTransform object. Type is one of: "wrap", "split-two", "split-top-bottom", "group-by-heading". Each type has specific options.
+    transform: String,
+    /// CSS rules for the layout classes used by the transform
+    css_content: String,
+}
 
+async fn tool_create_layout_rule(state: &McpState, args: CreateLayoutRuleArgs) -> Result<String, (i32, String)> {
     // Validate JSON strings
-    serde_json::from_str::<Value>(conditions)
+    serde_json::from_str::<Value>(&args.conditions)
         .map_err(|e| (-32602, format!("Invalid conditions JSON: {}", e)))?;
-    serde_json::from_str::<Value>(transform)
+    serde_json::from_str::<Value>(&args.transform)
         .map_err(|e| (-32602, format!("Invalid transform JSON: {}", e)))?;
 
     let app_state = state.app_state.read().await;
     let rule = app_state
         .db
         .create_layout_rule(
-            name.to_string(),
-            display_name.to_string(),
-            description.map(String::from),
-            priority,
-            conditions.to_string(),
-            transform.to_string(),
-            css_content.to_string(),
+            args.name,
+            args.display_name,
+            args.description,
+            args.priority.unwrap_or(100),
+            args.conditions,
+            args.transform,
+            args.css_content,
         )
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
+        .await?;
 
     let response: crate::models::LayoutRuleResponse = rule.into();
-    serde_json::to_string_pretty(&response).map_err(|e| (-32000, e.to_string()))
+    to_json(&response)
 }
 
-async fn tool_delete_layout_rule(state: &McpState, args: &Value) -> Result<String, (i32, String)> {
-    let id = args
-        .get("id")
-        .and_then(|v| v.as_str())
-        .ok_or((-32602, "Missing required parameter: id".to_string()))?;
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct LayoutRuleIdArgs {
+    /// Layout rule ID
+    id: String,
+}
 
+async fn tool_delete_layout_rule(state: &McpState, args: LayoutRuleIdArgs) -> Result<String, (i32, String)> {
     let app_state = state.app_state.read().await;
-    app_state
-        .db
-        .delete_layout_rule(id)
-        .await
-        .map_err(|e| (-32000, e.to_string()))?;
-    Ok(format!("Layout rule {} deleted successfully.", id))
+    app_state.db.delete_layout_rule(&args.id).await?;
+    Ok(format!("Layout rule {} deleted successfully.", args.id))
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct RunPipelineArgs {
+    /// Pipeline ID or name
+    id: String,
+    /// Input values, referenced by steps as {{input.field}}
+    input: Option<serde_json::Map<String, Value>>,
+    /// Token for notifications/progress messages about this call; AI steps can take a while
+    #[serde(rename = "_progressToken")]
+    progress_token: Option<ProgressToken>,
+}
+
+async fn tool_run_pipeline(state: &McpState, args: RunPipelineArgs) -> Result<String, (i32, String)> {
+    let input = args.input.map(Value::Object).unwrap_or(Value::Null);
+
+    let progress = Progress::new(state, args.progress_token);
+    progress.report(0).await;
+    let result = crate::pipeline::run_pipeline(&state.app_state, &args.id, input).await?;
+    progress.report(1).await;
+    to_json(&result)
 }
 
 fn get_mime_type(filename: &str) -> String {
@@ -859,3 +1694,523 @@ fn get_mime_type(filename: &str) -> String {
     }
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::Presentation, AppState};
+
+    async fn test_state() -> McpState {
+        McpState {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            session: None,
+            app_state: Arc::new(RwLock::new(AppState::for_tests().await)),
+        }
+    }
+
+    /// Creates a presentation directly in the database, bypassing the tools under test.
+    async fn create_deck(state: &McpState, title: &str, content: &str) -> Presentation {
+        state
+            .app_state
+            .read()
+            .await
+            .db
+            .create_presentation(CreatePresentation {
+                title: title.to_string(),
+                content: Some(content.to_string()),
+                theme: None,
+                if_not_exists: false,
+                on_conflict: None,
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn advertised(state: &McpState) -> Vec<String> {
+        let list = handle_tools_list(state).await.unwrap();
+        list["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    async fn call_is_disabled(state: &McpState, tool: &str) -> bool {
+        // Empty arguments: enabled tools fail parameter validation instead
+        let params = json!({ "name": tool, "arguments": {} });
+        matches!(handle_tools_call(state, &params).await, Err(JsonRpcError { code: TOOL_DISABLED, .. }))
+    }
+
+    #[tokio::test]
+    async fn test_advertised_tools_match_enforcement() {
+        let state = test_state().await;
+        let settings = [
+            McpToolSettings::default(),
+            McpToolSettings {
+                deny: vec!["delete_presentation".to_string(), "upload_media".to_string()],
+                ..Default::default()
+            },
+            McpToolSettings {
+                allow: Some(vec!["list_presentations".to_string(), "create_presentation".to_string()]),
+                ..Default::default()
+            },
+        ];
+
+        for settings in settings {
+            state.app_state.read().await.db.set_mcp_tool_settings(&settings).await.unwrap();
+            let advertised = advertised(&state).await;
+
+            for tool in tool_names() {
+                let disabled = call_is_disabled(&state, &tool).await;
+                assert_eq!(advertised.contains(&tool), !disabled, "{} with {:?}", tool, settings);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_only_blocks_mutating_tools() {
+        let state = test_state().await;
+        let settings = McpToolSettings {
+            read_only: true,
+            allow: Some(tool_names()),
+            ..Default::default()
+        };
+        state.app_state.read().await.db.set_mcp_tool_settings(&settings).await.unwrap();
+
+        let mut advertised = advertised(&state).await;
+        advertised.sort();
+        let mut read_only: Vec<String> = READ_ONLY_TOOLS.iter().map(|t| t.to_string()).collect();
+        read_only.sort();
+        assert_eq!(advertised, read_only);
+
+        for tool in tool_names().iter().filter(|t| !READ_ONLY_TOOLS.contains(&t.as_str())) {
+            assert!(call_is_disabled(&state, tool).await, "{} should be blocked", tool);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_return_trace_id() {
+        let state = test_state().await;
+        let call = |params: Value| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: "tools/call".to_string(),
+            params,
+        };
+
+        let ok = process_request(&state, call(json!({ "name": "list_tags" }))).await.unwrap();
+        let trace_id = ok.result.unwrap()["_meta"]["traceId"].as_str().unwrap().to_string();
+
+        let err = process_request(&state, call(json!({ "name": "get_presentation" }))).await.unwrap();
+        let other = err.error.unwrap().data.unwrap()["traceId"].as_str().unwrap().to_string();
+        assert_ne!(trace_id, other);
+    }
+
+    /// A value `schema` accepts, with every optional property filled in.
+    fn example(schema: &Value) -> Value {
+        if let Some(value) = schema.get("const").or_else(|| schema.pointer("/enum/0")) {
+            return value.clone();
+        }
+        if let Some(first) = schema.pointer("/anyOf/0").or_else(|| schema.pointer("/oneOf/0")) {
+            return example(first);
+        }
+        let kind = schema["type"].as_array().map_or(&schema["type"], |types| &types[0]);
+        match kind.as_str().unwrap() {
+            "string" => json!("text"),
+            "integer" => json!(1),
+            "number" => json!(1.5),
+            "boolean" => json!(true),
+            "array" => json!([example(&schema["items"])]),
+            "object" => match schema.get("properties").and_then(Value::as_object) {
+                Some(properties) => properties.iter().map(|(k, v)| (k.clone(), example(v))).collect(),
+                None => json!({}),
+            },
+            other => panic!("no example for {}", other),
+        }
+    }
+
+    #[test]
+    fn test_arguments_match_advertised_schema() {
+        for tool in tool_definitions() {
+            let name = tool["name"].as_str().unwrap();
+            let schema = &tool["inputSchema"];
+            assert_eq!(schema["type"], "object", "{}", name);
+            assert_eq!(schema["additionalProperties"], false, "{}", name);
+
+            let full = example(schema);
+            let properties = full.as_object().unwrap();
+            let required: Vec<&str> = schema["required"]
+                .as_array()
+                .map_or(vec![], |r| r.iter().map(|k| k.as_str().unwrap()).collect());
+            let minimal: serde_json::Map<String, Value> = properties
+                .iter()
+                .filter(|(k, _)| required.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+
+            // Everything the schema describes is accepted, with or without the optional fields
+            assert!(check_arguments(name, full.clone()).is_ok(), "{} rejected {}", name, full);
+            assert!(check_arguments(name, Value::Object(minimal.clone())).is_ok(), "{} rejected {:?}", name, minimal);
+
+            // ...and nothing it doesn't, with the offending field named in the error data
+            let mut extra = full.clone();
+            extra["unexpected"] = json!(1);
+            let error = check_arguments(name, extra).unwrap_err();
+            assert_eq!(error.data.unwrap()["errors"][0]["field"], "unexpected", "{}", name);
+
+            for field in &required {
+                let mut missing = full.clone();
+                missing.as_object_mut().unwrap().remove(*field);
+                assert!(check_arguments(name, missing).is_err(), "{} accepted missing {}", name, field);
+            }
+
+            for (field, property) in schema["properties"].as_object().into_iter().flatten() {
+                let takes_object = property.to_string().contains("\"object\"");
+                let mut mistyped = full.clone();
+                mistyped[field] = if takes_object { json!(["wrong"]) } else { json!({ "wrong": true }) };
+                let error = check_arguments(name, mistyped).unwrap_err();
+                assert_eq!(error.data.unwrap()["errors"][0]["field"], field.as_str(), "{} accepted a mistyped {}", name, field);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_report_path() {
+        let state = test_state().await;
+        let params = json!({
+            "name": "create_layout_rule",
+            "arguments": {
+                "name": "my-layout",
+                "displayName": "My layout",
+                "priority": "high",
+                "conditions": "{}",
+                "transform": "{}",
+                "cssContent": ""
+            }
+        });
+
+        let error = handle_tools_call(&state, &params).await.unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert_eq!(error.data.unwrap()["errors"][0]["field"], "priority");
+
+        let params = json!({ "name": "reorder_slides", "arguments": { "id": "x", "order": [0, -1] } });
+        let error = handle_tools_call(&state, &params).await.unwrap_err();
+        assert_eq!(error.data.unwrap()["errors"][0]["field"], "order[1]");
+    }
+
+    #[tokio::test]
+    async fn test_search_presentations_tool() {
+        let state = test_state().await;
+        for title in ["Kubernetes intro", "Kubernetes deep dive", "Quarterly review"] {
+            create_deck(&state, title, &format!("# {}", title)).await;
+        }
+
+        let params = json!({ "name": "search_presentations", "arguments": { "query": "kubernetes", "perPage": 1 } });
+        let result = handle_tools_call(&state, &params).await.unwrap();
+        let page: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!((page["total"].as_i64(), page["totalPages"].as_i64()), (Some(2), Some(2)));
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+
+        let params = json!({ "name": "search_presentations", "arguments": { "query": "  " } });
+        assert_eq!(handle_tools_call(&state, &params).await.unwrap_err().code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_presentation_tool() {
+        let state = test_state().await;
+        let original = create_deck(&state, "Roadmap", "# 2025").await;
+
+        let list = handle_tools_list(&state).await.unwrap();
+        let tool = list["tools"].as_array().unwrap().iter().find(|t| t["name"] == "duplicate_presentation").unwrap();
+        assert_eq!(tool["inputSchema"]["required"], json!(["id"]));
+        assert!(tool["inputSchema"]["properties"]["newTitle"].is_object());
+
+        for (arguments, title) in [
+            (json!({ "id": original.id }), "Copy of Roadmap"),
+            (json!({ "id": original.id, "newTitle": "Roadmap v2" }), "Roadmap v2"),
+        ] {
+            let params = json!({ "name": "duplicate_presentation", "arguments": arguments });
+            let result = handle_tools_call(&state, &params).await.unwrap();
+            let copy: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+            assert_ne!(copy["id"], json!(original.id));
+            assert_eq!((copy["title"].as_str(), copy["content"].as_str()), (Some(title), Some("# 2025")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_theme_tools() {
+        let state = test_state().await;
+        let call = |name: &str, arguments: Value| json!({ "name": name, "arguments": arguments });
+
+        let params = call("create_theme", json!({ "name": "agent-made", "displayName": "Agent made", "cssContent": "[data-theme=\"agent-made\"] h1 { color: red; }" }));
+        let result = handle_tools_call(&state, &params).await.unwrap();
+        let theme: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        let id = theme["id"].as_str().unwrap();
+
+        let params = call("update_theme", json!({ "id": id, "centerContent": false }));
+        assert!(handle_tools_call(&state, &params).await.is_ok());
+        let params = call("update_theme", json!({ "id": id, "cssContent": "body { position: fixed; " }));
+        let error = handle_tools_call(&state, &params).await.unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert!(error.message.contains("cssContent: line 1: position: fixed"), "{}", error.message);
+        let params = call("create_theme", json!({ "name": "Not Kebab", "displayName": "x", "cssContent": "" }));
+        assert_eq!(handle_tools_call(&state, &params).await.unwrap_err().code, -32602);
+
+        let builtin = state.app_state.read().await.db.get_theme_by_name("default").await.unwrap();
+        for params in [
+            call("update_theme", json!({ "id": builtin.id, "cssContent": "" })),
+            call("delete_theme", json!({ "id": builtin.id })),
+        ] {
+            let error = handle_tools_call(&state, &params).await.unwrap_err();
+            assert_eq!(error.code, -32602);
+            assert!(error.message.contains("'default' is built in"), "{}", error.message);
+        }
+
+        let params = call("delete_theme", json!({ "id": id }));
+        assert!(handle_tools_call(&state, &params).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reorder_slides_tool() {
+        let state = test_state().await;
+        let id = create_deck(&state, "Deck", "# A\n\n---\n\n# B\n\n---\n\n# C").await.id;
+
+        let params = json!({ "name": "reorder_slides", "arguments": { "id": id, "order": [2, 0, 1] } });
+        let result = handle_tools_call(&state, &params).await.unwrap();
+        let presentation: Value = serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(presentation["content"], "# C\n\n---\n\n# A\n\n---\n\n# B");
+
+        for order in [json!([0, 1]), json!([0, 1, 1]), json!([0, 1, 3])] {
+            let params = json!({ "name": "reorder_slides", "arguments": { "id": id, "order": order } });
+            let error = handle_tools_call(&state, &params).await.unwrap_err();
+            assert_eq!(error.code, -32602, "{}", order);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slide_tools() {
+        let state = test_state().await;
+        let id = create_deck(&state, "Deck", "# One\n\n---\n\n# Two\n\n<!-- notes -->\nSay hi\n<!-- /notes -->").await.id;
+        let call = |name: &str, arguments: Value| json!({ "name": name, "arguments": arguments });
+        let slides = |result: Value| -> Value { serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap() };
+
+        let listed = slides(handle_tools_call(&state, &call("list_slides", json!({ "id": id }))).await.unwrap());
+        assert_eq!(listed.as_array().unwrap().len(), 2);
+        assert_eq!((listed[1]["index"].as_u64(), listed[1]["notes"].as_str()), (Some(1), Some("Say hi")));
+
+        handle_tools_call(&state, &call("insert_slide", json!({ "id": id, "index": 1, "content": "# Middle" })))
+            .await
+            .unwrap();
+        handle_tools_call(&state, &call("insert_slide_at", json!({ "id": id, "position": 0, "content": "# Cover" })))
+            .await
+            .unwrap();
+        handle_tools_call(&state, &call("insert_slide", json!({ "id": id, "content": "# End" }))).await.unwrap();
+        handle_tools_call(&state, &call("replace_slide", json!({ "id": id, "index": 1, "content": "# First" })))
+            .await
+            .unwrap();
+        handle_tools_call(&state, &call("delete_slide", json!({ "id": id, "index": 3 }))).await.unwrap();
+
+        let listed = slides(handle_tools_call(&state, &call("list_slides", json!({ "id": id }))).await.unwrap());
+        let contents: Vec<&str> = listed.as_array().unwrap().iter().map(|s| s["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, ["# Cover", "# First", "# Middle", "# End"]);
+
+        // Each tool takes only its own name for the index
+        let error = handle_tools_call(&state, &call("insert_slide", json!({ "id": id, "position": 0, "content": "x" })))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, -32602);
+
+        let error = handle_tools_call(&state, &call("get_slide", json!({ "id": id, "index": 5 }))).await.unwrap_err();
+        assert_eq!(error.code, -32602);
+    }
+
+    #[tokio::test]
+    async fn test_locked_presentation_rejects_tool_edits() {
+        let state = test_state().await;
+        let id = create_deck(&state, "Delivered", "# Intro\n\n---\n\n# Outro").await.id;
+        state.app_state.read().await.db.set_locked(&id, true).await.unwrap();
+
+        for (tool, arguments) in [
+            ("update_presentation", json!({ "id": id, "title": "Renamed" })),
+            ("add_slides", json!({ "id": id, "slides": "# More" })),
+            ("replace_slide", json!({ "id": id, "index": 0, "content": "# Hi" })),
+            ("delete_slide", json!({ "id": id, "index": 1 })),
+            ("insert_slide", json!({ "id": id, "index": 1, "content": "# Hi" })),
+            ("delete_presentation", json!({ "id": id })),
+        ] {
+            let params = json!({ "name": tool, "arguments": arguments });
+            let error = handle_tools_call(&state, &params).await.unwrap_err();
+            assert!(error.message.contains("locked"), "{}: {}", tool, error.message);
+        }
+
+        let params = json!({ "name": "get_presentation", "arguments": { "id": id } });
+        let result = handle_tools_call(&state, &params).await.unwrap();
+        assert!(result.to_string().contains(r#"\"locked\": true"#));
+    }
+
+    #[tokio::test]
+    async fn test_presentation_resources() {
+        let state = test_state().await;
+        let deck = create_deck(&state, "Roadmap", "# 2025").await;
+        let request = |method: &str, params: Value| JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params,
+        };
+
+        let response = process_request(&state, request("initialize", json!({}))).await.unwrap();
+        assert_eq!(response.result.unwrap()["capabilities"]["resources"], json!({}));
+
+        let uri = format!("slides://presentations/{}", deck.id);
+        let response = process_request(&state, request("resources/list", json!({}))).await.unwrap();
+        assert_eq!(
+            response.result.unwrap()["resources"],
+            json!([{ "uri": uri, "name": "Roadmap", "mimeType": "text/markdown" }])
+        );
+
+        let response = process_request(&state, request("resources/read", json!({ "uri": uri }))).await.unwrap();
+        assert_eq!(response.result.unwrap()["contents"][0]["text"], "# 2025");
+
+        for (uri, code) in [("slides://presentations/missing", -32002), ("file:///etc/passwd", -32602)] {
+            let response = process_request(&state, request("resources/read", json!({ "uri": uri }))).await.unwrap();
+            assert_eq!(response.error.unwrap().code, code);
+        }
+    }
+
+    #[test]
+    fn test_prompts() {
+        let prompts = handle_prompts_list();
+        let names: Vec<&str> = prompts["prompts"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["create-presentation", "improve-slide", "generate-theme"]);
+        assert_eq!(prompts["prompts"][0]["arguments"][0]["name"], "topic");
+
+        // Every template uses its argument
+        for prompt in PROMPTS {
+            assert!(prompt.template.contains(&format!("{{{}}}", prompt.argument)), "{}", prompt.name);
+        }
+
+        let filled = handle_prompts_get(&json!({ "name": "improve-slide", "arguments": { "content": "# Wordy {topic}" } })).unwrap();
+        let text = filled["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.ends_with("\n\n# Wordy {topic}"));
+
+        for params in [json!({ "name": "create-presentation" }), json!({ "name": "nope", "arguments": {} })] {
+            assert_eq!(handle_prompts_get(&params).unwrap_err().0, -32602);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_notifications() {
+        let (sender, mut rx) = mpsc::channel(10);
+        let state = McpState { session: Some(sender), ..test_state().await };
+
+        let params = json!({ "name": "create_presentation", "arguments": { "title": "Deck", "content": "# Hi", "_progressToken": "tok" } });
+        let result = handle_tools_call(&state, &params).await.unwrap();
+        let id = serde_json::from_str::<Value>(result["content"][0]["text"].as_str().unwrap()).unwrap()["id"].clone();
+        for progress in [0, 1] {
+            let message: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+            assert_eq!(message["method"], "notifications/progress");
+            assert!(message.get("id").is_none());
+            assert_eq!(message["params"], json!({ "progressToken": "tok", "progress": progress, "total": 1 }));
+        }
+
+        let params = json!({ "name": "update_presentation", "arguments": { "id": id, "title": "Renamed", "_progressToken": 7 } });
+        handle_tools_call(&state, &params).await.unwrap();
+        let message: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+        assert_eq!(message["params"]["progressToken"], 7);
+        rx.try_recv().unwrap();
+
+        // Without a token nothing is sent
+        let params = json!({ "name": "update_presentation", "arguments": { "id": id, "title": "Again" } });
+        handle_tools_call(&state, &params).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_requests() {
+        let state = test_state().await;
+        let message = |body: Value| -> JsonRpcBatch { serde_json::from_value(body).unwrap() };
+        let reply = |text: Option<String>| -> Value { serde_json::from_str(&text.unwrap()).unwrap() };
+
+        let single = message(json!({ "jsonrpc": "2.0", "id": 1, "method": "prompts/list" }));
+        assert_eq!(reply(process_message(&state, single).await)["id"], 1);
+
+        let batch = message(json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "tools/list" },
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "id": "b", "method": "nope" },
+        ]));
+        let replies = reply(process_message(&state, batch).await);
+        let replies = replies.as_array().unwrap();
+        assert_eq!(replies.len(), 2);
+        assert!(replies[0]["result"]["tools"].is_array());
+        assert_eq!((replies[1]["id"].as_str(), replies[1]["error"]["code"].as_i64()), (Some("b"), Some(-32601)));
+
+        let notifications = message(json!([{ "jsonrpc": "2.0", "method": "notifications/initialized" }]));
+        assert_eq!(process_message(&state, notifications).await, None);
+        assert_eq!(reply(process_message(&state, message(json!([]))).await)["error"]["code"], -32600);
+    }
+
+    #[tokio::test]
+    async fn test_stale_sessions_are_removed() {
+        let state = test_state().await;
+        let (open, _rx) = mpsc::channel(1);
+        let (closed, _) = mpsc::channel(1);
+        let profile = crate::profiles::DEFAULT_PROFILE.to_string();
+        {
+            let mut sessions = state.sessions.write().await;
+            sessions.insert("open".to_string(), Session { sender: open.clone(), profile: profile.clone(), created_at: Instant::now() });
+            sessions.insert("closed".to_string(), Session { sender: closed, profile: profile.clone(), created_at: Instant::now() });
+            let old = Instant::now() - Duration::from_secs(120);
+            sessions.insert("expired".to_string(), Session { sender: open, profile, created_at: old });
+        }
+
+        assert_eq!(remove_stale_sessions(&state.sessions, Duration::from_secs(60)).await, 2);
+        assert_eq!(state.sessions.read().await.keys().collect::<Vec<_>>(), ["open"]);
+        assert_eq!(remove_stale_sessions(&state.sessions, Duration::from_secs(60)).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_tool_setting_changes_notify_sessions() {
+        let state = test_state().await;
+        let (sender, mut rx) = mpsc::channel(1);
+        state.sessions.write().await.insert(
+            "session".to_string(),
+            Session { sender, profile: crate::profiles::DEFAULT_PROFILE.to_string(), created_at: Instant::now() },
+        );
+        let sessions = state.sessions.clone();
+        let app_state = state.app_state.read().await;
+        app_state.db.events().register("mcp", move |event| notify_sessions(sessions.clone(), event));
+
+        app_state.db.set_health_weights(&Default::default()).await.unwrap();
+        app_state.db.set_mcp_tool_settings(&McpToolSettings { read_only: true, ..Default::default() }).await.unwrap();
+
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+        let message: Value = serde_json::from_str(&message).unwrap();
+        assert_eq!(message["method"], "notifications/tools/list_changed");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_media_caps_downloads_without_length() {
+        // A chunked response declares no length, so only the bytes read can be counted
+        let chunk = vec![0u8; 1024 * 1024];
+        let app = axum::Router::new().route(
+            "/huge.png",
+            axum::routing::get(move || async move {
+                let chunks = futures::stream::iter(std::iter::repeat_n(Ok::<_, Infallible>(chunk), 60));
+                ([("content-type", "image/png")], axum::body::Body::from_stream(chunks))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let state = test_state().await;
+        let params = json!({ "name": "upload_media", "arguments": { "source": format!("http://{}/huge.png", addr) } });
+        let error = handle_tools_call(&state, &params).await.unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert!(error.message.contains("File exceeds 50 MB limit"), "{}", error.message);
+    }
+}
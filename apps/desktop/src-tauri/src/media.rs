@@ -0,0 +1,320 @@
+// Content checks and metadata for uploaded media. The declared MIME type comes from the
+// client (a multipart header, a download's Content-Type or a file extension), so the file's
+// leading bytes are checked against it before anything is written to the library. Once a file
+// is on disk, its dimensions or duration are read from its header for the media listing.
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Bytes needed from the start of a file to check its type.
+pub const MAGIC_LEN: usize = 512;
+
+/// Whether `data` starts with the signature of `claimed_mime`. Types without a known
+/// signature are rejected.
+pub fn validate_file_magic(data: &[u8], claimed_mime: &str) -> bool {
+    let mime = claimed_mime.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+    let riff = |format: &[u8]| at(0, b"RIFF") && at(8, format);
+
+    match mime.as_str() {
+        "image/png" => at(0, b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" | "image/jpg" => at(0, b"\xFF\xD8\xFF"),
+        "image/gif" => at(0, b"GIF87a") || at(0, b"GIF89a"),
+        "image/webp" => riff(b"WEBP"),
+        "image/bmp" => at(0, b"BM"),
+        "image/x-icon" | "image/vnd.microsoft.icon" => at(0, b"\x00\x00\x01\x00"),
+        "image/tiff" => at(0, b"II*\x00") || at(0, b"MM\x00*"),
+        "image/avif" | "image/heic" | "image/heif" => at(4, b"ftyp"),
+        "image/svg+xml" => is_svg(data),
+        "video/mp4" | "video/quicktime" | "video/x-m4v" | "audio/mp4" | "audio/x-m4a" => at(4, b"ftyp"),
+        "video/webm" | "audio/webm" | "video/x-matroska" => at(0, b"\x1A\x45\xDF\xA3"),
+        "video/ogg" | "audio/ogg" => at(0, b"OggS"),
+        "video/x-msvideo" => riff(b"AVI "),
+        "audio/wav" | "audio/x-wav" | "audio/wave" => riff(b"WAVE"),
+        "audio/flac" => at(0, b"fLaC"),
+        "audio/mpeg" => at(0, b"ID3") || matches!(data, [0xFF, b, ..] if b & 0xE0 == 0xE0),
+        "audio/aac" => at(0, b"ADIF") || matches!(data, [0xFF, b, ..] if b & 0xF6 == 0xF0),
+        _ => false,
+    }
+}
+
+/// SVG is text, so look for an `<svg` root after any XML prolog, comments or doctype.
+fn is_svg(data: &[u8]) -> bool {
+    let head = &data[..data.len().min(MAGIC_LEN)];
+    let Ok(text) = std::str::from_utf8(head).or_else(|e| std::str::from_utf8(&head[..e.valid_up_to()])) else {
+        return false;
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    (text.starts_with("<?xml") || text.starts_with("<!--") || text.starts_with("<!DOCTYPE") || text.starts_with("<svg"))
+        && text.contains("<svg")
+}
+
+/// Dimensions of an image, duration of audio or video; None where the format isn't
+/// supported or the header couldn't be read.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MediaMetadata {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration_seconds: Option<f64>,
+}
+
+// Most of a file read looking for a WebM duration, which sits near the start
+const WEBM_HEADER_LEN: u64 = 1024 * 1024;
+// Largest `moov` box read looking for an MP4 duration
+const MAX_MOOV_LEN: u64 = 64 * 1024 * 1024;
+
+/// Reads the metadata of an uploaded file, on a blocking thread. Unreadable headers are
+/// logged and leave the fields empty rather than failing the upload.
+pub async fn read_metadata(path: PathBuf, mime_type: &str) -> MediaMetadata {
+    let mime = mime_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let result = tokio::task::spawn_blocking(move || -> std::io::Result<MediaMetadata> {
+        if mime.starts_with("image/") {
+            let (width, height) = image::ImageReader::open(&path)?
+                .with_guessed_format()?
+                .into_dimensions()
+                .map_err(std::io::Error::other)?;
+            return Ok(MediaMetadata {
+                width: i32::try_from(width).ok(),
+                height: i32::try_from(height).ok(),
+                duration_seconds: None,
+            });
+        }
+
+        let duration_seconds = match mime.as_str() {
+            "video/mp4" | "video/quicktime" | "video/x-m4v" | "audio/mp4" | "audio/x-m4a" => mp4_duration(&path)?,
+            "video/webm" | "audio/webm" | "video/x-matroska" => webm_duration(&path)?,
+            _ => None,
+        };
+        Ok(MediaMetadata { duration_seconds, ..Default::default() })
+    })
+    .await;
+
+    match result {
+        Ok(Ok(metadata)) => metadata,
+        Ok(Err(e)) => {
+            tracing::debug!("Could not read media metadata: {}", e);
+            MediaMetadata::default()
+        }
+        Err(e) => {
+            tracing::warn!("Media metadata task failed: {}", e);
+            MediaMetadata::default()
+        }
+    }
+}
+
+/// Duration from the `mvhd` box inside the top-level `moov` box, which may come after the
+/// media data.
+fn mp4_duration(path: &Path) -> std::io::Result<Option<f64>> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut offset = 0;
+
+    while offset + 8 <= file_len {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            file.read_exact(&mut header[8..])?;
+            size = u64::from_be_bytes(header[8..16].try_into().unwrap());
+            header_len = 16;
+        } else if size == 0 {
+            size = file_len - offset;
+        }
+        if size < header_len {
+            return Ok(None);
+        }
+
+        if &header[4..8] == b"moov" {
+            let body_len = size - header_len;
+            if body_len > MAX_MOOV_LEN {
+                return Ok(None);
+            }
+            let mut body = vec![0; body_len as usize];
+            file.read_exact(&mut body)?;
+            return Ok(mvhd_duration(&body));
+        }
+        // A 64-bit size can claim more than fits after `offset`
+        match offset.checked_add(size) {
+            Some(next) => offset = next,
+            None => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+fn mvhd_duration(moov: &[u8]) -> Option<f64> {
+    let mut offset = 0;
+    while offset + 8 <= moov.len() {
+        let size = u32::from_be_bytes(moov[offset..offset + 4].try_into().ok()?) as usize;
+        if size < 8 {
+            return None;
+        }
+        if &moov[offset + 4..offset + 8] == b"mvhd" {
+            let body = moov.get(offset + 8..offset + size)?;
+            let be32 = |at: usize| body.get(at..at + 4).map(|b| u32::from_be_bytes(b.try_into().unwrap()) as u64);
+            let be64 = |at: usize| body.get(at..at + 8).map(|b| u64::from_be_bytes(b.try_into().unwrap()));
+            // version/flags, then creation and modification times, timescale and duration;
+            // version 1 widens the times and duration to 64 bits
+            let (timescale, duration) = match body.first()? {
+                0 => (be32(12)?, be32(16)?),
+                1 => (be32(20)?, be64(24)?),
+                _ => return None,
+            };
+            return (timescale > 0).then(|| duration as f64 / timescale as f64);
+        }
+        offset = offset.checked_add(size)?;
+    }
+    None
+}
+
+// EBML element IDs
+const EBML_SEGMENT: u32 = 0x1853_8067;
+const EBML_INFO: u32 = 0x1549_A966;
+const EBML_TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const EBML_DURATION: u32 = 0x4489;
+const EBML_CLUSTER: u32 = 0x1F43_B675;
+// Nanoseconds per timecode unit unless the file says otherwise
+const DEFAULT_TIMECODE_SCALE: u64 = 1_000_000;
+
+/// Duration from the Segment's Info element, which comes before the first Cluster.
+fn webm_duration(path: &Path) -> std::io::Result<Option<f64>> {
+    let mut data = Vec::new();
+    File::open(path)?.take(WEBM_HEADER_LEN).read_to_end(&mut data)?;
+    Ok(ebml_duration(&data))
+}
+
+fn ebml_duration(data: &[u8]) -> Option<f64> {
+    let mut offset = 0;
+    while let Some((id, size, header_len)) = ebml_element(data, offset) {
+        let body = offset + header_len;
+        match id {
+            // Step into the segment; its size is often unknown while recording
+            EBML_SEGMENT => offset = body,
+            EBML_INFO => {
+                let end = size.map_or(data.len(), |size| (body + size).min(data.len()));
+                return info_duration(&data[body..end]);
+            }
+            EBML_CLUSTER => return None,
+            _ => offset = body + size?,
+        }
+    }
+    None
+}
+
+fn info_duration(info: &[u8]) -> Option<f64> {
+    let mut scale = DEFAULT_TIMECODE_SCALE;
+    let mut duration = None;
+    let mut offset = 0;
+    while let Some((id, size, header_len)) = ebml_element(info, offset) {
+        let size = size?;
+        let value = info.get(offset + header_len..offset + header_len + size)?;
+        match id {
+            EBML_TIMECODE_SCALE => scale = value.iter().fold(0u64, |n, &b| n << 8 | b as u64),
+            EBML_DURATION => {
+                duration = match value.len() {
+                    4 => Some(f32::from_be_bytes(value.try_into().ok()?) as f64),
+                    8 => Some(f64::from_be_bytes(value.try_into().ok()?)),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+        offset += header_len + size;
+    }
+    duration.map(|d| d * scale as f64 / 1e9)
+}
+
+/// ID, size (None when unknown) and header length of the EBML element at `offset`.
+fn ebml_element(data: &[u8], offset: usize) -> Option<(u32, Option<usize>, usize)> {
+    let (id_len, id) = ebml_vint(data.get(offset..)?, true)?;
+    let (size_len, size) = ebml_vint(data.get(offset + id_len..)?, false)?;
+    let unknown = size == (1u64 << (7 * size_len)) - 1;
+    let size = if unknown { None } else { Some(usize::try_from(size).ok()?) };
+    Some((id as u32, size, id_len + size_len))
+}
+
+/// A variable-length integer; IDs keep their length marker bit, sizes drop it.
+fn ebml_vint(data: &[u8], keep_marker: bool) -> Option<(usize, u64)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return None;
+    }
+    let first = if keep_marker { first as u64 } else { (first as u64) & (0xFF >> len) };
+    let value = data[1..len].iter().fold(first, |n, &b| n << 8 | b as u64);
+    Some((len, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_file_magic() {
+        assert!(validate_file_magic(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR", "image/png"));
+        assert!(validate_file_magic(b"\xFF\xD8\xFF\xE0\x00\x10JFIF", "image/jpeg"));
+        assert!(validate_file_magic(b"RIFF\x24\x00\x00\x00WEBPVP8 ", "image/webp"));
+        assert!(validate_file_magic(b"\x00\x00\x00\x20ftypisom", "video/mp4"));
+        assert!(validate_file_magic(b"ID3\x04\x00", "audio/mpeg"));
+        assert!(validate_file_magic(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>", "image/svg+xml"));
+
+        // HTML claiming to be an image, and formats mixed up
+        assert!(!validate_file_magic(b"<!DOCTYPE html><html><script>", "image/png"));
+        assert!(!validate_file_magic(b"<!DOCTYPE html><html></html>", "image/svg+xml"));
+        assert!(!validate_file_magic(b"RIFF\x24\x00\x00\x00WAVEfmt ", "image/webp"));
+        assert!(!validate_file_magic(b"\x89PN", "image/png"));
+        assert!(!validate_file_magic(b"\x89PNG\r\n\x1a\n", "image/x-unknown"));
+    }
+
+    #[tokio::test]
+    async fn test_read_metadata() {
+        let dir = std::env::temp_dir().join(format!("slides-media-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 3x2 RGB PNG
+        let png = dir.join("pixels.png");
+        let data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR\x00\x00\x00\x03\x00\x00\x00\x02\x08\x02\x00\x00\x00\x12\x16\xf1\x4d\
+                     \x00\x00\x00\x0bIDAT\x78\x9c\x63\x60\xc0\x04\x00\x00\x14\x00\x01\x7d\xfe\x1e\xee\
+                     \x00\x00\x00\x00IEND\xae\x42\x60\x82";
+        std::fs::write(&png, data).unwrap();
+        let metadata = read_metadata(png, "image/png").await;
+        assert_eq!((metadata.width, metadata.height, metadata.duration_seconds), (Some(3), Some(2), None));
+
+        // ftyp, mdat, then moov with a version 0 mvhd: 2500 units at 1000 per second
+        let mp4 = dir.join("clip.mp4");
+        let mut mvhd = vec![0u8; 20];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&2500u32.to_be_bytes());
+        let mut data = b"\x00\x00\x00\x10ftypisom\x00\x00\x00\x00\x00\x00\x00\x0cmdat\x01\x02\x03\x04".to_vec();
+        data.extend_from_slice(&(8 + 8 + mvhd.len() as u32).to_be_bytes());
+        data.extend_from_slice(b"moov");
+        data.extend_from_slice(&(8 + mvhd.len() as u32).to_be_bytes());
+        data.extend_from_slice(b"mvhd");
+        data.extend_from_slice(&mvhd);
+        std::fs::write(&mp4, data).unwrap();
+        assert_eq!(read_metadata(mp4, "video/mp4").await.duration_seconds, Some(2.5));
+
+        // A 64-bit box size running past the end of the offsets
+        let huge = dir.join("huge.mp4");
+        let mut data = b"\x00\x00\x00\x10ftypisom\x00\x00\x00\x00\x00\x00\x00\x01free".to_vec();
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        std::fs::write(&huge, data).unwrap();
+        assert_eq!(mp4_duration(&huge).unwrap(), None);
+
+        // EBML header, segment of unknown size, Info with a 1.5s float duration
+        let webm = dir.join("clip.webm");
+        let mut data = b"\x1A\x45\xDF\xA3\x80\x18\x53\x80\x67\x01\xFF\xFF\xFF\xFF\xFF\xFF\xFF".to_vec();
+        data.extend_from_slice(b"\x15\x49\xA9\x66\x8B\x44\x89\x88");
+        data.extend_from_slice(&1500.0f64.to_be_bytes());
+        std::fs::write(&webm, data).unwrap();
+        assert_eq!(read_metadata(webm, "video/webm").await.duration_seconds, Some(1.5));
+
+        // Unreadable headers leave the fields empty
+        let broken = dir.join("broken.png");
+        std::fs::write(&broken, b"not a png").unwrap();
+        assert_eq!(read_metadata(broken, "image/png").await, MediaMetadata::default());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,260 @@
+// Tower layers shared by the API and MCP routes.
+//
+// `RequestIdLayer` gives every request an id (see `trace`) and echoes it in `X-Request-ID`.
+//
+// `RateLimitLayer` guards the AI routes. Every AI request costs money at the provider, and an
+// MCP client or a frontend bug calling them in a loop can run up a large bill before anyone
+// notices. Each client IP may make `SLIDES_AI_RATE_LIMIT_RPM` requests (20 by default) in any
+// sliding minute; past that the request is answered with 429 and the seconds until the
+// oldest counted request leaves the window.
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::{self, BoxFuture, Either, Ready};
+use serde_json::json;
+use tower::{Layer, Service};
+
+use crate::trace;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+pub const AI_RATE_LIMIT_ENV: &str = "SLIDES_AI_RATE_LIMIT_RPM";
+const DEFAULT_AI_REQUESTS_PER_MINUTE: u32 = 20;
+
+const WINDOW: Duration = Duration::from_secs(60);
+// Clients tracked before idle ones are forgotten
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Runs each request under the caller's `X-Request-ID` (or `traceparent`) id, or a fresh one,
+/// and returns the id in the response's `X-Request-ID`.
+#[derive(Clone, Copy, Default)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestId<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestId { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestId<S> {
+    inner: S,
+}
+
+impl<S> Service<Request> for RequestId<S>
+where
+    S: Service<Request, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let id = trace::from_headers(request.headers()).unwrap_or_else(trace::new_id);
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = trace::scope(id.clone(), response).await?;
+            // Ids come from validated headers or are generated, so they're valid header values
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Requests per minute allowed on the AI routes, from `SLIDES_AI_RATE_LIMIT_RPM`. 0 turns the
+/// limit off.
+pub fn ai_requests_per_minute() -> u32 {
+    std::env::var(AI_RATE_LIMIT_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_AI_REQUESTS_PER_MINUTE)
+}
+
+/// Limits each client IP to a number of requests per sliding minute across the routes it
+/// wraps. Clones, and the services they wrap, share one set of counters. Requests without a
+/// peer address (the server wasn't started with connect info) share a single budget.
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    /// Allows `requests_per_minute` per client; 0 allows any number.
+    pub fn new(requests_per_minute: u32) -> Self {
+        Self {
+            limiter: Arc::new(Limiter {
+                limit: requests_per_minute as usize,
+                window: WINDOW,
+                clients: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+    S: Service<Request, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let client = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        match self.limiter.check(client, Instant::now()) {
+            Ok(()) => Either::Right(self.inner.call(request)),
+            Err(retry_after) => {
+                tracing::warn!(client = ?client, path = %request.uri().path(), "Rate limit exceeded");
+                Either::Left(future::ready(Ok(too_many_requests(retry_after))))
+            }
+        }
+    }
+}
+
+struct Limiter {
+    limit: usize,
+    window: Duration,
+    /// When each client's requests in the current window were let through, oldest first
+    clients: Mutex<HashMap<Option<IpAddr>, VecDeque<Instant>>>,
+}
+
+impl Limiter {
+    /// Counts a request from `client` at `now`, or returns how long until it would be allowed.
+    fn check(&self, client: Option<IpAddr>, now: Instant) -> Result<(), Duration> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let mut clients = self.clients.lock().unwrap();
+        if clients.len() >= MAX_TRACKED_CLIENTS {
+            clients.retain(|_, hits| hits.back().is_some_and(|hit| now.duration_since(*hit) < self.window));
+        }
+
+        let hits = clients.entry(client).or_default();
+        while hits.front().is_some_and(|hit| now.duration_since(*hit) >= self.window) {
+            hits.pop_front();
+        }
+
+        match hits.front() {
+            Some(oldest) if hits.len() >= self.limit => Err(self.window - now.duration_since(*oldest)),
+            _ => {
+                hits.push_back(now);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // Whole seconds, rounded up so a client that waits exactly this long gets through
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let seconds = seconds.max(1);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, seconds.to_string())],
+        Json(json!({ "error": "Rate limit exceeded", "retryAfter": seconds })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_sliding_window() {
+        let limiter = RateLimitLayer::new(2).limiter;
+        let client = Some(IpAddr::from([10, 0, 0, 1]));
+        let start = Instant::now();
+
+        assert_eq!(limiter.check(client, start), Ok(()));
+        assert_eq!(limiter.check(client, start + Duration::from_secs(30)), Ok(()));
+        assert_eq!(limiter.check(client, start + Duration::from_secs(45)), Err(Duration::from_secs(15)));
+        // Other clients have their own budget
+        assert_eq!(limiter.check(Some(IpAddr::from([10, 0, 0, 2])), start + Duration::from_secs(45)), Ok(()));
+        // The first request has left the window, the second hasn't
+        assert_eq!(limiter.check(client, start + Duration::from_secs(60)), Ok(()));
+        assert_eq!(limiter.check(client, start + Duration::from_secs(61)), Err(Duration::from_secs(29)));
+
+        let unlimited = RateLimitLayer::new(0).limiter;
+        assert!((0..100).all(|_| unlimited.check(client, start).is_ok()));
+    }
+
+    #[tokio::test]
+    async fn test_layer_rejects_with_retry_after() {
+        let router = Router::new()
+            .route("/ai/generate", get(|| async { "ok" }))
+            .layer(RateLimitLayer::new(2))
+            .route("/themes", get(|| async { "ok" }));
+
+        let request = |uri: &str, ip: [u8; 4]| {
+            let mut request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(SocketAddr::from((ip, 50000))));
+            request
+        };
+
+        for _ in 0..2 {
+            let response = router.clone().oneshot(request("/ai/generate", [127, 0, 0, 1])).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = router.clone().oneshot(request("/ai/generate", [127, 0, 0, 1])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+        assert!((1..=60).contains(&retry_after));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, json!({ "error": "Rate limit exceeded", "retryAfter": retry_after }));
+
+        // Routes outside the layer and other clients are unaffected
+        let response = router.clone().oneshot(request("/themes", [127, 0, 0, 1])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router.oneshot(request("/ai/generate", [127, 0, 0, 2])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -1,6 +1,9 @@
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::ai::ConversationMessage;
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
 pub struct Presentation {
@@ -11,6 +14,72 @@ pub struct Presentation {
     pub user_id: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[sqlx(json)]
+    pub tags: Vec<String>,
+    pub slide_count: i64,
+    pub word_count: i64,
+    pub has_speaker_notes: bool,
+    pub health_score: Option<i64>,
+    /// Hidden from listings unless asked for, but otherwise a normal presentation
+    pub archived: bool,
+    /// When the deck was last opened in the editor; opening doesn't change `updated_at`
+    pub last_opened_at: Option<DateTime<Utc>>,
+    #[sqlx(json)]
+    pub settings: PresentationSettings,
+    /// Starred by the user; listings can put these first
+    pub is_favorite: bool,
+    /// Frozen against edits and deletion until unlocked
+    pub locked: bool,
+    /// Autosaved editor content is waiting to be committed or discarded
+    pub has_draft: bool,
+}
+
+/// Per-deck display options, stored as JSON. Missing keys take their defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct PresentationSettings {
+    pub aspect_ratio: AspectRatio,
+    pub transition: SlideTransition,
+    /// Shown at the bottom of every slide
+    pub footer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AspectRatio {
+    #[default]
+    #[serde(rename = "16:9")]
+    Widescreen,
+    #[serde(rename = "4:3")]
+    Standard,
+}
+
+/// Transitions the presenter can play between slides, named as in its transition picker
+/// (`TransitionType` in presenter.component.ts). The aliases are the WebGL effect names
+/// earlier versions stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SlideTransition {
+    #[default]
+    None,
+    Fade,
+    Slide,
+    Zoom,
+    Flip,
+    Cube,
+    Swap,
+    Fall,
+    Glitch,
+    #[serde(alias = "disintegrate")]
+    Dissolve,
+    Morph,
+    #[serde(rename = "waveGL", alias = "wave")]
+    WaveGl,
+    Pixelate,
+    #[serde(alias = "directionalWipe")]
+    Wipe,
+    Noise,
+    Circle,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,14 +88,592 @@ pub struct CreatePresentation {
     pub title: String,
     pub content: Option<String>,
     pub theme: Option<String>,
+    /// Return an existing presentation with the same title instead of creating a duplicate;
+    /// shorthand for `onConflict: "return"`
+    #[serde(default)]
+    pub if_not_exists: bool,
+    #[serde(default)]
+    pub on_conflict: Option<TitleConflict>,
 }
 
-#[derive(Debug, Deserialize)]
+/// What creating a presentation does when one with the same title (ignoring case and
+/// surrounding whitespace) already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleConflict {
+    /// Return the existing presentation
+    Return,
+    /// Fail with 409 Conflict
+    Error,
+}
+
+impl CreatePresentation {
+    pub fn title_conflict(&self) -> Option<TitleConflict> {
+        self.on_conflict.or(self.if_not_exists.then_some(TitleConflict::Return))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdatePresentation {
     pub title: Option<String>,
     pub content: Option<String>,
     pub theme: Option<String>,
+    /// Settings keys to change; keys left out keep their value and `null` resets one
+    #[serde(default)]
+    pub settings: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// Content autosaved by the editor, kept apart from the saved content until it's committed.
+/// Saving a draft doesn't touch `updatedAt` or the history.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationDraft {
+    pub presentation_id: String,
+    pub content: String,
+    pub saved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveDraft {
+    pub content: String,
+}
+
+/// The presentation after an update, and whether the update changed anything. Saving the
+/// values a deck already has is a no-op that leaves `updatedAt` and the history alone.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationUpdate {
+    #[serde(flatten)]
+    pub presentation: Presentation,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Delete,
+    Archive,
+    SetTheme,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkPresentations {
+    pub action: BulkAction,
+    pub ids: Vec<String>,
+    /// Required for `set_theme`
+    pub theme: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePresentations {
+    /// Decks to merge, in slide order
+    pub source_ids: Vec<String>,
+    pub title: String,
+    /// Defaults to the first source's theme
+    pub theme: Option<String>,
+    /// Move the sources to the trash once merged
+    #[serde(default)]
+    pub delete_sources: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceRequest {
+    pub search: String,
+    /// With `regex`, `$1` or `${name}` insert capture groups
+    pub replace: String,
+    /// Presentations to change; every presentation outside the trash when absent
+    pub ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub regex: bool,
+    /// Report matches without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Also replace inside fenced code blocks
+    #[serde(default)]
+    pub include_code: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceResult {
+    pub dry_run: bool,
+    pub total_matches: usize,
+    /// Presentations with at least one match
+    pub presentations: Vec<PresentationMatches>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationMatches {
+    pub id: String,
+    pub title: String,
+    pub matches: usize,
+    pub slides: Vec<SlideMatches>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlideMatches {
+    pub index: usize,
+    pub matches: usize,
+    /// The line around each of the first few matches
+    pub snippets: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkItemResult {
+    pub id: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkResult {
+    /// False when the batch was rolled back; nothing was changed
+    pub committed: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicatePresentation {
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SlideContent {
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InsertSlide {
+    pub content: String,
+    /// Index to insert before; appends when omitted
+    pub position: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlideItem {
+    pub index: usize,
+    /// The slide's markdown, including any speaker notes block
+    pub content: String,
+    pub notes: Option<String>,
+}
+
+/// One slide pulled out of a deck, with the deck's slide count.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlideDetail {
+    #[serde(flatten)]
+    pub slide: SlideItem,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Placeholder {
+    pub name: String,
+    pub type_hint: Option<String>,
+    /// Indices of the slides the placeholder appears on
+    pub slides: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HealthFindingKind {
+    EmptySlide,
+    MissingNotes,
+    LongSlide,
+    MissingAltText,
+    OversizedMedia,
+    UnfilledPlaceholder,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthFinding {
+    pub kind: HealthFindingKind,
+    /// Index of the slide the finding is on
+    pub slide: usize,
+    pub message: String,
+}
+
+/// Reading statistics for a presentation, computed from its content on request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationStats {
+    pub slide_count: usize,
+    /// Words outside speaker notes and other HTML comments
+    pub word_count: usize,
+    pub estimated_reading_minutes: f32,
+    /// Markdown images and `<img>` tags outside code blocks
+    pub image_count: usize,
+    pub code_block_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckHealth {
+    /// 0-100, where 100 means no findings
+    pub score: i64,
+    pub findings: Vec<HealthFinding>,
+}
+
+/// Points deducted from the health score for each finding of a kind.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HealthWeights {
+    pub empty_slide: f64,
+    pub missing_notes: f64,
+    pub long_slide: f64,
+    pub missing_alt_text: f64,
+    pub oversized_media: f64,
+    pub unfilled_placeholder: f64,
+}
+
+impl HealthWeights {
+    pub fn weight(&self, kind: HealthFindingKind) -> f64 {
+        match kind {
+            HealthFindingKind::EmptySlide => self.empty_slide,
+            HealthFindingKind::MissingNotes => self.missing_notes,
+            HealthFindingKind::LongSlide => self.long_slide,
+            HealthFindingKind::MissingAltText => self.missing_alt_text,
+            HealthFindingKind::OversizedMedia => self.oversized_media,
+            HealthFindingKind::UnfilledPlaceholder => self.unfilled_placeholder,
+        }
+    }
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            empty_slide: 5.0,
+            missing_notes: 2.0,
+            long_slide: 5.0,
+            missing_alt_text: 3.0,
+            oversized_media: 5.0,
+            unfilled_placeholder: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintSeverity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintRule {
+    BrokenImage,
+    TooManyCards,
+    EmptySlide,
+    UnclosedNotes,
+    UnfilledPlaceholder,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintIssue {
+    pub slide_index: usize,
+    pub severity: LintSeverity,
+    pub rule: LintRule,
+    pub message: String,
+}
+
+/// What to compare in `POST /presentations/{id}/diff`: a candidate `content` against the
+/// saved deck, or `from_version` against `to_version` (the saved deck when omitted).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffPresentationRequest {
+    pub from_version: Option<String>,
+    pub to_version: Option<String>,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SlideChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlideChange {
+    pub kind: SlideChangeKind,
+    /// Index in the old deck; None for added slides
+    pub old_index: Option<usize>,
+    /// Index in the new deck; None for removed slides
+    pub new_index: Option<usize>,
+    /// The slide's first heading, from the new deck unless the slide was removed
+    pub heading: Option<String>,
+    /// Unified diff of the slide's lines
+    pub diff: String,
+}
+
+/// Slide-level changes between two versions of a deck, in new-deck order with removed
+/// slides where they used to be. Slides that only moved count as unchanged.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub modified: usize,
+    pub unchanged: usize,
+    pub changes: Vec<SlideChange>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FillPlaceholders {
+    pub values: std::collections::HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationVersion {
+    pub id: String,
+    pub presentation_id: String,
+    pub title: Option<String>,
+    pub content: String,
+    pub theme: String,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+}
+
+/// Copy of a presentation taken by the periodic auto-snapshot when its content changed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationSnapshot {
+    pub id: String,
+    pub presentation_id: String,
+    pub title: String,
+    pub content: String,
+    pub theme: String,
+    pub content_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresentationSortField {
+    Title,
+    CreatedAt,
+    #[default]
+    UpdatedAt,
+    #[serde(alias = "last_opened_at")]
+    LastOpenedAt,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortDir {
+    Asc,
+    #[default]
+    Desc,
+}
+
+pub const DEFAULT_PER_PAGE: u32 = 20;
+pub const MAX_PER_PAGE: u32 = 100;
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_per_page() -> u32 {
+    DEFAULT_PER_PAGE
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPresentationsQuery {
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_per_page", alias = "per_page")]
+    pub per_page: u32,
+    #[serde(default, alias = "sort_by", alias = "sort")]
+    pub sort_by: PresentationSortField,
+    #[serde(default, alias = "sort_dir")]
+    pub sort_dir: SortDir,
+    /// Only presentations carrying all of these tags; comma-separated in query strings
+    #[serde(default, deserialize_with = "comma_separated")]
+    pub tags: Vec<String>,
+    #[serde(default, alias = "include_archived")]
+    pub include_archived: bool,
+    /// List favorites before the rest, each group in the requested order
+    #[serde(default, alias = "favorites_first")]
+    pub favorites_first: bool,
+}
+
+fn comma_separated<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let raw = String::deserialize(deserializer)?;
+    Ok(raw
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+impl Default for ListPresentationsQuery {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            per_page: default_per_page(),
+            sort_by: PresentationSortField::default(),
+            sort_dir: SortDir::default(),
+            tags: Vec::new(),
+            include_archived: false,
+            favorites_first: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    #[default]
+    Markdown,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportPresentationQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Keep `/api/v1/uploads/...` image URLs; false rewrites them to relative `uploads/...` paths
+    #[serde(default = "default_inline")]
+    pub inline: bool,
+}
+
+fn default_inline() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPresentationQuery {
+    /// Update the presentation named by the file's front-matter `id` instead of creating one
+    #[serde(default)]
+    pub upsert: bool,
+}
+
+/// Body of the 409 returned when an upsert import and the stored presentation both changed
+/// since the export.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConflict {
+    pub error: String,
+    pub id: String,
+    /// Hash recorded in the file's front matter at export
+    pub exported_hash: Option<String>,
+    /// Hash of the file's content as imported
+    pub file_hash: String,
+    /// Hash of the presentation's current content
+    pub stored_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPresentationsQuery {
+    pub q: String,
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_per_page", alias = "per_page")]
+    pub per_page: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedPresentationsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedPresentation {
+    pub id: String,
+    pub title: String,
+    /// Cosine similarity of the two decks' distinguishing terms, 0-1
+    pub score: f64,
+    /// Shared distinguishing terms, strongest first
+    pub matched_terms: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuickSearchQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickSearchKind {
+    Presentation,
+    Slide,
+    Theme,
+    Media,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickSearchResult {
+    #[serde(rename = "type")]
+    pub kind: QuickSearchKind,
+    /// ID of the presentation, theme or media file; slides carry their presentation's
+    pub id: String,
+    /// Title, slide heading, theme display name or media file name
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slide_index: Option<usize>,
+    /// Where the UI should go when the result is picked
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PaginatedResult<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: u32,
+    pub total_pages: u32,
+}
+
+impl<T> PaginatedResult<T> {
+    pub fn new(items: Vec<T>, total: i64, page: u32, per_page: u32) -> Self {
+        let total_pages = (total.max(0) as u64).div_ceil(per_page.max(1) as u64) as u32;
+        Self {
+            items,
+            total,
+            page,
+            total_pages,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddTagRequest {
+    pub tag: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -38,9 +685,76 @@ pub struct Theme {
     pub css_content: String,
     pub is_default: bool,
     pub center_content: bool,
+    #[sqlx(json)]
+    pub fonts: ThemeFonts,
+    /// Presentations using the theme, trash included; only filled in by theme listings
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage_count: Option<i64>,
     pub user_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Custom theme listed while safe mode is on; it is not applied until a normal start
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub disabled_by_safe_mode: bool,
+}
+
+/// Presentations using a theme, trash included, most recently updated first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeUsage {
+    pub count: usize,
+    pub presentations: Vec<ThemeUser>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeUser {
+    pub id: String,
+    pub title: String,
+    pub updated_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub locked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthQuery {
+    /// Also run SQLite's integrity check, which reads the whole database
+    #[serde(default)]
+    pub integrity: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeModeQuery {
+    /// In safe mode, also list custom themes or layout rules (marked `disabledBySafeMode`)
+    #[serde(default)]
+    pub include_disabled_by_safe_mode: bool,
+}
+
+/// Fonts a theme's CSS uses, stored as JSON so the frontend can load them before rendering.
+/// Each is a CSS font stack such as `'Inter', sans-serif`; unset ones inherit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct ThemeFonts {
+    pub heading: Option<String>,
+    pub body: Option<String>,
+    pub mono: Option<String>,
+    pub source: FontSource,
+}
+
+/// Where the frontend loads a theme's fonts from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum FontSource {
+    /// Installed on the machine; nothing to load
+    #[default]
+    System,
+    /// Google Fonts, by the first family of each stack
+    Google,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,6 +764,79 @@ pub struct CreateTheme {
     pub display_name: String,
     pub css_content: String,
     pub center_content: Option<bool>,
+    #[serde(default)]
+    pub fonts: ThemeFonts,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateTheme {
+    /// Name of the copy; defaults to the original's name with a `-copy` suffix
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub on_conflict: NameConflict,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateThemeQuery {
+    #[serde(default)]
+    pub on_conflict: NameConflict,
+}
+
+/// What creating a theme does when its name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameConflict {
+    /// Fail with 409 Conflict
+    #[default]
+    Error,
+    /// Use the first free `name-2`, `name-3`, ... instead
+    Rename,
+}
+
+/// Version of the theme export format written by this build. Version 2 added `fonts`.
+pub const THEME_FORMAT_VERSION: u32 = 2;
+
+/// A theme as a portable JSON file. `checksum` covers every other field, so an edited file is
+/// caught on import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThemeExport {
+    pub format_version: u32,
+    pub name: String,
+    pub display_name: String,
+    pub css_content: String,
+    pub center_content: bool,
+    #[serde(default)]
+    pub fonts: ThemeFonts,
+    pub checksum: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportThemeQuery {
+    #[serde(default)]
+    pub on_conflict: ImportThemeConflict,
+}
+
+/// What importing a theme does when its name is already taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportThemeConflict {
+    /// Import under the first free `name-2`, `name-3`, ...
+    #[default]
+    Rename,
+    /// Replace the custom theme of that name, keeping its id so presentations keep using it
+    Overwrite,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DeleteThemeQuery {
+    /// Switch presentations still using the theme to the default theme instead of refusing
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,6 +845,7 @@ pub struct UpdateTheme {
     pub display_name: Option<String>,
     pub css_content: Option<String>,
     pub center_content: Option<bool>,
+    pub fonts: Option<ThemeFonts>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -71,6 +859,48 @@ pub struct Media {
     pub url: String,
     pub user_id: String,
     pub created_at: DateTime<Utc>,
+    /// Pixel dimensions of images
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Length of audio and video
+    pub duration_seconds: Option<f64>,
+    /// Hex SHA-256 of the file; missing for files uploaded before it was recorded
+    pub content_hash: Option<String>,
+    /// Set on upload responses when the file was already in the library and the existing
+    /// entry was returned instead of storing a second copy
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub deduplicated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadSession {
+    pub filename: String,
+    pub mime_type: String,
+    /// Total size in bytes
+    pub size: i64,
+    /// Hex SHA-256 of the whole file, checked on completion
+    pub sha256: String,
+    /// Bytes per chunk; every chunk but the last must be exactly this long
+    pub chunk_size: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadSession {
+    pub id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub chunk_size: i64,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub chunk_count: i64,
+    #[sqlx(skip)]
+    pub received_chunks: Vec<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -107,6 +937,8 @@ pub struct LayoutRuleResponse {
     pub css_content: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Custom rule listed while safe mode is on; it is not applied until a normal start
+    pub disabled_by_safe_mode: bool,
 }
 
 impl From<LayoutRule> for LayoutRuleResponse {
@@ -125,10 +957,210 @@ impl From<LayoutRule> for LayoutRuleResponse {
             css_content: rule.css_content,
             created_at: rule.created_at,
             updated_at: rule.updated_at,
+            disabled_by_safe_mode: false,
         }
     }
 }
 
+// Pipelines
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Pipeline {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: String, // JSON string
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStep {
+    pub op: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineResponse {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<PipelineStep>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Pipeline> for PipelineResponse {
+    fn from(pipeline: Pipeline) -> Self {
+        Self {
+            id: pipeline.id,
+            name: pipeline.name,
+            description: pipeline.description,
+            steps: serde_json::from_str(&pipeline.steps).unwrap_or_default(),
+            created_at: pipeline.created_at,
+            updated_at: pipeline.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePipeline {
+    pub name: String,
+    pub description: Option<String>,
+    pub steps: Vec<PipelineStep>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatePipeline {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub steps: Option<Vec<PipelineStep>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunPipelineRequest {
+    #[serde(default)]
+    pub input: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PipelineStepStatus {
+    Ok,
+    Error,
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStepResult {
+    pub index: usize,
+    pub op: String,
+    pub status: PipelineStepStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRunResult {
+    pub pipeline_id: String,
+    pub success: bool,
+    pub steps: Vec<PipelineStepResult>,
+}
+
+// Watchlists
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchlistSource {
+    pub id: String,
+    pub presentation_id: String,
+    pub url: String,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_refreshed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateWatchlistSource {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateWatchlistSource {
+    pub url: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshWatchlistRequest {
+    /// AI provider for item summaries; defaults to the first configured one
+    pub provider: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchlistSourceResult {
+    pub source_id: String,
+    pub url: String,
+    pub imported: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchlistRefreshResult {
+    pub presentation: Presentation,
+    pub imported: usize,
+    pub sources: Vec<WatchlistSourceResult>,
+}
+
+// Settings
+/// Workspace-wide preferences. Keys left out of a stored value take their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// Theme for new presentations that don't name one
+    pub default_theme: String,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            default_theme: "default".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolSettings {
+    /// Only expose tools that don't modify data
+    #[serde(default)]
+    pub read_only: bool,
+    /// When set, only these tools are exposed
+    #[serde(default)]
+    pub allow: Option<Vec<String>>,
+    /// Tools that are never exposed
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+// Profiles
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateProfile {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteProfile {
+    pub confirm: String,
+}
+
 // AI Provider Config
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 #[serde(rename_all = "camelCase")]
@@ -182,6 +1214,49 @@ pub struct UpdateAiProviderConfig {
     pub base_url: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateKeyRequest {
+    pub old_key: String,
+    pub new_key: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRotation {
+    /// API keys re-encrypted across all profiles
+    pub rotated: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupFile {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ModelCacheEntry {
+    pub provider_name: String,
+    pub base_url: Option<String>,
+    pub models: String, // JSON string
+    pub fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModelListQuery {
+    pub provider: String,
+}
+
 // AI Request DTOs
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -191,6 +1266,23 @@ pub struct AiGenerateRequest {
     pub context: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiStreamRequest {
+    pub prompt: String,
+    pub provider: String,
+    pub context: Option<String>,
+}
+
+/// A chat with a provider; `messages` is the whole history, ending with the user's turn.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiConversationRequest {
+    pub provider: String,
+    pub messages: Vec<ConversationMessage>,
+    pub system_prompt: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AiImproveRequest {
@@ -199,6 +1291,59 @@ pub struct AiImproveRequest {
     pub instruction: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiSuggestionsRequest {
+    pub presentation_id: String,
+    pub slide_index: usize,
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionType {
+    DuplicateSlide,
+    SplitSlide,
+    TableToChart,
+    ListToDiagram,
+    Improve,
+}
+
+impl SuggestionType {
+    /// Higher ranks are listed first.
+    pub fn rank(self) -> u8 {
+        match self {
+            SuggestionType::DuplicateSlide => 5,
+            SuggestionType::SplitSlide => 4,
+            SuggestionType::TableToChart => 3,
+            SuggestionType::ListToDiagram => 2,
+            SuggestionType::Improve => 1,
+        }
+    }
+}
+
+/// A suggestion the UI can offer as a one-click action: calling `action_endpoint` with
+/// `action_payload` applies it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Suggestion {
+    #[serde(rename = "type")]
+    pub suggestion_type: SuggestionType,
+    pub message: String,
+    pub action_method: String,
+    pub action_endpoint: String,
+    pub action_payload: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiSuggestions {
+    pub suggestions: Vec<Suggestion>,
+    /// False when the AI provider was unavailable or too slow and only rule-based
+    /// suggestions are included
+    pub ai_included: bool,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AiSuggestStyleRequest {
@@ -0,0 +1,448 @@
+// Brand palettes from images, for themes that match a logo.
+//
+// `extract` downsamples the image and runs k-means over its opaque pixels. Starting centres are
+// picked k-means++ style from a generator seeded by the caller, and pixels are visited in a
+// fixed order, so the same image, cluster count and seed always give the same palette.
+// `assign_roles` turns a palette into the four theme variables (`--slide-bg`, `--slide-text`,
+// `--slide-heading`, `--slide-accent`), moving colours towards black or white until they reach
+// WCAG AA contrast against the background, and `variant_css` writes them over a base theme.
+use std::collections::HashMap;
+
+use image::{imageops::FilterType, DynamicImage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::models::Theme;
+
+/// Longest side images are downsampled to before clustering.
+pub const MAX_SIDE: u32 = 64;
+/// Clusters extracted when the caller doesn't ask for a number.
+pub const DEFAULT_COLORS: usize = 6;
+pub const MAX_COLORS: usize = 12;
+
+const MAX_ITERATIONS: usize = 32;
+// Pixels more transparent than this are background, not logo
+const MIN_ALPHA: u8 = 128;
+// Colours with less chroma than this count as greys for heading and accent
+const MIN_CHROMA: f64 = 0.15;
+
+// WCAG 2 AA: body text and links, and large text (headings)
+pub const AA_NORMAL: f64 = 4.5;
+pub const AA_LARGE: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rgb(pub [u8; 3]);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb([0, 0, 0]);
+    pub const WHITE: Rgb = Rgb([255, 255, 255]);
+
+    pub fn hex(self) -> String {
+        let [r, g, b] = self.0;
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+
+    /// WCAG relative luminance, 0 for black to 1 for white.
+    pub fn luminance(self) -> f64 {
+        let channel = |c: u8| {
+            let c = c as f64 / 255.0;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let [r, g, b] = self.0;
+        0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+    }
+
+    /// Spread between the strongest and weakest channel, 0 for greys to 1 for pure hues.
+    pub fn chroma(self) -> f64 {
+        let max = self.0.iter().max().copied().unwrap_or(0);
+        let min = self.0.iter().min().copied().unwrap_or(0);
+        (max - min) as f64 / 255.0
+    }
+
+    /// `self` moved a fraction `t` of the way to `other`.
+    fn mix(self, other: Rgb, t: f64) -> Rgb {
+        let channel = |i: usize| {
+            let (a, b) = (self.0[i] as f64, other.0[i] as f64);
+            (a + (b - a) * t).round().clamp(0.0, 255.0) as u8
+        };
+        Rgb([channel(0), channel(1), channel(2)])
+    }
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.hex())
+    }
+}
+
+/// WCAG contrast ratio between two colours, from 1 (same luminance) to 21.
+pub fn contrast_ratio(a: Rgb, b: Rgb) -> f64 {
+    let (la, lb) = (a.luminance(), b.luminance());
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// `fg` if it already reaches `min_ratio` against `bg`, otherwise the smallest step from `fg`
+/// towards black or white (whichever contrasts more with `bg`) that does.
+pub fn ensure_contrast(fg: Rgb, bg: Rgb, min_ratio: f64) -> Rgb {
+    if contrast_ratio(fg, bg) >= min_ratio {
+        return fg;
+    }
+    let target = if contrast_ratio(Rgb::BLACK, bg) >= contrast_ratio(Rgb::WHITE, bg) {
+        Rgb::BLACK
+    } else {
+        Rgb::WHITE
+    };
+
+    // Contrast only grows on the way to the target, so bisect for the closest passing mix
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..16 {
+        let mid = (lo + hi) / 2.0;
+        if contrast_ratio(fg.mix(target, mid), bg) >= min_ratio {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    fg.mix(target, hi)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Swatch {
+    pub color: Rgb,
+    /// Fraction of the image's opaque pixels in this cluster
+    pub share: f64,
+}
+
+/// Up to `k` dominant colours of `image`, most common first. Images with no more than `k`
+/// distinct opaque colours give those colours exactly.
+pub fn extract(image: &DynamicImage, k: usize, seed: u64) -> Vec<Swatch> {
+    let image = if image.width() > MAX_SIDE || image.height() > MAX_SIDE {
+        // Nearest keeps real pixel colours; smoothing filters invent blends at edges
+        image.resize(MAX_SIDE, MAX_SIDE, FilterType::Nearest)
+    } else {
+        image.clone()
+    };
+
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in image.to_rgba8().pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a >= MIN_ALPHA {
+            *counts.entry([r, g, b]).or_default() += 1;
+        }
+    }
+    // Sorted so the result doesn't depend on hash order
+    let mut colors: Vec<([f64; 3], f64)> = counts
+        .into_iter()
+        .map(|(c, n)| ([c[0] as f64, c[1] as f64, c[2] as f64], n as f64))
+        .collect();
+    colors.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let total: f64 = colors.iter().map(|(_, n)| n).sum();
+    if k == 0 || colors.is_empty() {
+        return Vec::new();
+    }
+
+    let clusters = if colors.len() <= k {
+        colors
+    } else {
+        kmeans(&colors, k, seed)
+    };
+
+    let mut palette: Vec<Swatch> = clusters
+        .into_iter()
+        .filter(|(_, n)| *n > 0.0)
+        .map(|(c, n)| Swatch {
+            color: Rgb([c[0].round() as u8, c[1].round() as u8, c[2].round() as u8]),
+            share: n / total,
+        })
+        .collect();
+    palette.sort_by(|a, b| b.share.total_cmp(&a.share).then(a.color.cmp(&b.color)));
+    palette
+}
+
+fn distance(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    (0..3).map(|i| (a[i] - b[i]).powi(2)).sum()
+}
+
+fn nearest(centres: &[[f64; 3]], color: &[f64; 3]) -> usize {
+    (0..centres.len())
+        .min_by(|&a, &b| distance(&centres[a], color).total_cmp(&distance(&centres[b], color)))
+        .unwrap_or(0)
+}
+
+/// Weighted k-means over distinct colours and their pixel counts. Returns each centre with the
+/// number of pixels assigned to it.
+fn kmeans(colors: &[([f64; 3], f64)], k: usize, seed: u64) -> Vec<([f64; 3], f64)> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // k-means++: each further centre is picked with probability proportional to its pixels'
+    // squared distance from the nearest centre so far
+    let pick = |rng: &mut StdRng, weights: &[f64]| {
+        let mut target = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (i, w) in weights.iter().enumerate() {
+            if target < *w {
+                return i;
+            }
+            target -= w;
+        }
+        weights.len() - 1
+    };
+    let counts: Vec<f64> = colors.iter().map(|(_, n)| *n).collect();
+    let mut centres = vec![colors[pick(&mut rng, &counts)].0];
+    while centres.len() < k {
+        let weights: Vec<f64> = colors
+            .iter()
+            .map(|(c, n)| n * distance(&centres[nearest(&centres, c)], c))
+            .collect();
+        if weights.iter().all(|w| *w == 0.0) {
+            break;
+        }
+        centres.push(colors[pick(&mut rng, &weights)].0);
+    }
+
+    let mut assignment = vec![usize::MAX; colors.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (i, (c, _)) in colors.iter().enumerate() {
+            let cluster = nearest(&centres, c);
+            if assignment[i] != cluster {
+                assignment[i] = cluster;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        let mut sums = vec![([0.0; 3], 0.0); centres.len()];
+        for ((c, n), &cluster) in colors.iter().zip(&assignment) {
+            let (sum, weight) = &mut sums[cluster];
+            (0..3).for_each(|i| sum[i] += c[i] * n);
+            *weight += n;
+        }
+        for (centre, (sum, weight)) in centres.iter_mut().zip(&sums) {
+            // A centre nothing is nearest to stays put
+            if *weight > 0.0 {
+                *centre = [sum[0] / weight, sum[1] / weight, sum[2] / weight];
+            }
+        }
+    }
+
+    let mut weights = vec![0.0; centres.len()];
+    for ((_, n), &cluster) in colors.iter().zip(&assignment) {
+        weights[cluster] += n;
+    }
+    centres.into_iter().zip(weights).collect()
+}
+
+/// Whether the generated theme has a light or a dark background.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// A theme generated from an image, with the palette it was built from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageTheme {
+    pub name: String,
+    pub display_name: String,
+    pub css_content: String,
+    pub center_content: bool,
+    /// The stored theme, when saving was asked for
+    pub theme: Option<Theme>,
+    pub palette: Vec<Swatch>,
+    pub colors: ThemeColors,
+}
+
+/// The theme variables a palette maps to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ThemeColors {
+    pub bg: Rgb,
+    pub text: Rgb,
+    pub heading: Rgb,
+    pub accent: Rgb,
+}
+
+/// Maps a palette to theme variables. The background is the lightest cluster in light mode
+/// and the darkest in dark mode, lightened or darkened if the logo has no colour light or dark
+/// enough; text is the opposite extreme; heading and accent are the most saturated clusters.
+/// Text and accent are adjusted to AA contrast for normal text, headings to AA for large text.
+pub fn assign_roles(palette: &[Swatch], mode: ThemeMode) -> ThemeColors {
+    let by_luminance = |a: &&Swatch, b: &&Swatch| a.color.luminance().total_cmp(&b.color.luminance());
+    let lightest = palette.iter().max_by(by_luminance).map_or(Rgb::WHITE, |s| s.color);
+    let darkest = palette.iter().min_by(by_luminance).map_or(Rgb::BLACK, |s| s.color);
+
+    let (bg, text) = match mode {
+        ThemeMode::Light => (towards(lightest, Rgb::WHITE, |c| c.luminance() >= 0.8), darkest),
+        ThemeMode::Dark => (towards(darkest, Rgb::BLACK, |c| c.luminance() <= 0.03), lightest),
+    };
+
+    let mut saturated: Vec<&Swatch> = palette
+        .iter()
+        .filter(|s| s.color.chroma() >= MIN_CHROMA && s.color != bg)
+        .collect();
+    saturated.sort_by(|a, b| {
+        b.color
+            .chroma()
+            .total_cmp(&a.color.chroma())
+            .then(b.share.total_cmp(&a.share))
+            .then(a.color.cmp(&b.color))
+    });
+    let heading = saturated.first().map_or(text, |s| s.color);
+    let accent = saturated.get(1).map_or(heading, |s| s.color);
+
+    ThemeColors {
+        bg,
+        text: ensure_contrast(text, bg, AA_NORMAL),
+        heading: ensure_contrast(heading, bg, AA_LARGE),
+        accent: ensure_contrast(accent, bg, AA_NORMAL),
+    }
+}
+
+/// The closest mix of `color` towards `target` that satisfies `done`.
+fn towards(color: Rgb, target: Rgb, done: impl Fn(Rgb) -> bool) -> Rgb {
+    (0..=20)
+        .map(|step| color.mix(target, step as f64 / 20.0))
+        .find(|c| done(*c))
+        .unwrap_or(target)
+}
+
+/// CSS for a theme `name` that extends `base_css` (the CSS of theme `base_name`) with its
+/// variables set to `colors`. The base rules are copied under the new name, then the
+/// variables are overridden; the background is reset to the variable since bases may paint
+/// gradients over it.
+pub fn variant_css(base_css: &str, base_name: &str, name: &str, colors: &ThemeColors) -> String {
+    let base = base_css.replace(
+        &format!("[data-theme=\"{}\"]", base_name),
+        &format!("[data-theme=\"{}\"]", name),
+    );
+    format!(
+        "{}\n.slide-content[data-theme=\"{name}\"], [data-theme=\"{name}\"] .slide-content, [data-theme=\"{name}\"] .slide {{\n  \
+         --slide-bg: {}; --slide-text: {}; --slide-heading: {}; --slide-accent: {};\n  \
+         background: var(--slide-bg);\n}}\n",
+        base.trim_end(),
+        colors.bg.hex(),
+        colors.text.hex(),
+        colors.heading.hex(),
+        colors.accent.hex(),
+        name = name,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    /// A 100x100 image of horizontal bands, each `(color, rows)`.
+    fn bands(bands: &[([u8; 3], u32)]) -> DynamicImage {
+        let mut image = RgbaImage::new(100, bands.iter().map(|(_, rows)| rows).sum());
+        let mut y = 0;
+        for ([r, g, b], rows) in bands {
+            for row in y..y + rows {
+                for x in 0..100 {
+                    image.put_pixel(x, row, Rgba([*r, *g, *b, 255]));
+                }
+            }
+            y += rows;
+        }
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn test_extracts_known_palette() {
+        let navy = [0x1a, 0x23, 0x7e];
+        let orange = [0xff, 0x6f, 0x00];
+        let white = [0xff, 0xff, 0xff];
+        let image = bands(&[(white, 50), (navy, 30), (orange, 20)]);
+
+        let palette = extract(&image, 3, 7);
+        let colors: Vec<Rgb> = palette.iter().map(|s| s.color).collect();
+        assert_eq!(colors, vec![Rgb(white), Rgb(navy), Rgb(orange)]);
+        let shares: Vec<f64> = palette.iter().map(|s| (s.share * 100.0).round()).collect();
+        assert_eq!(shares, vec![50.0, 30.0, 20.0]);
+
+        // Transparent pixels are ignored
+        let mut transparent = image.to_rgba8();
+        for x in 0..100 {
+            transparent.put_pixel(x, 0, Rgba([0, 255, 0, 0]));
+        }
+        let palette = extract(&DynamicImage::ImageRgba8(transparent), 3, 7);
+        assert!(palette.iter().all(|s| s.color != Rgb([0, 255, 0])));
+    }
+
+    #[test]
+    fn test_clusters_are_deterministic() {
+        // Shades around three colours; more distinct colours than clusters
+        let mut image = RgbaImage::new(120, 120);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let shade = ((x + y) % 9) as u8;
+            *pixel = match x / 40 {
+                0 => Rgba([200 + shade, 30, 40, 255]),
+                1 => Rgba([20, 140 + shade, 60, 255]),
+                _ => Rgba([240, 240, 230 + shade, 255]),
+            };
+        }
+        let image = DynamicImage::ImageRgba8(image);
+
+        let palette = extract(&image, 3, 42);
+        assert_eq!(palette, extract(&image, 3, 42));
+        assert_eq!(palette.len(), 3);
+        for expected in [Rgb([204, 30, 40]), Rgb([20, 144, 60]), Rgb([240, 240, 234])] {
+            assert!(
+                palette.iter().any(|s| (0..3).all(|i| s.color.0[i].abs_diff(expected.0[i]) <= 3)),
+                "{} missing from {:?}",
+                expected.hex(),
+                palette
+            );
+        }
+        assert!(palette.iter().all(|s| (s.share - 1.0 / 3.0).abs() < 0.05));
+    }
+
+    #[test]
+    fn test_roles_meet_aa_contrast() {
+        let palette = extract(&bands(&[([0xff, 0xff, 0xff], 50), ([0xff, 0xd5, 0x4f], 30), ([0x29, 0xb6, 0xf6], 20)]), 3, 1);
+
+        let light = assign_roles(&palette, ThemeMode::Light);
+        assert_eq!(light.bg, Rgb::WHITE);
+        assert!(contrast_ratio(light.text, light.bg) >= AA_NORMAL);
+        assert!(contrast_ratio(light.heading, light.bg) >= AA_LARGE);
+        assert!(contrast_ratio(light.accent, light.bg) >= AA_NORMAL);
+        // Light blue and yellow are too pale on white, so they're darkened
+        assert_ne!(light.heading, Rgb([0xff, 0xd5, 0x4f]));
+        assert_ne!(light.accent, Rgb([0x29, 0xb6, 0xf6]));
+
+        // Nothing is dark enough for a dark background, so one is made from the darkest colour
+        let dark = assign_roles(&palette, ThemeMode::Dark);
+        assert!(dark.bg.luminance() <= 0.03);
+        assert!(contrast_ratio(dark.text, dark.bg) >= AA_NORMAL);
+        assert!(contrast_ratio(dark.heading, dark.bg) >= AA_LARGE);
+        assert!(contrast_ratio(dark.accent, dark.bg) >= AA_NORMAL);
+
+        assert!((contrast_ratio(Rgb::BLACK, Rgb::WHITE) - 21.0).abs() < 1e-9);
+        assert_eq!(ensure_contrast(Rgb::BLACK, Rgb::WHITE, AA_NORMAL), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_variant_css_extends_base() {
+        let base = "[data-theme=\"dark\"] h1 { color: var(--slide-heading); }\n";
+        let colors = ThemeColors {
+            bg: Rgb([0x10, 0x10, 0x20]),
+            text: Rgb::WHITE,
+            heading: Rgb([0xff, 0x6f, 0x00]),
+            accent: Rgb([0x29, 0xb6, 0xf6]),
+        };
+        let css = variant_css(base, "dark", "acme", &colors);
+        assert!(css.starts_with("[data-theme=\"acme\"] h1 { color: var(--slide-heading); }"));
+        assert!(!css.contains("\"dark\""));
+        assert!(css.contains("--slide-bg: #101020; --slide-text: #ffffff; --slide-heading: #ff6f00; --slide-accent: #29b6f6;"));
+    }
+}
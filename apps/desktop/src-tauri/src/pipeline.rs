@@ -0,0 +1,341 @@
+// Named pipelines: a stored list of steps, each calling an existing internal operation.
+// Step params may reference the run input (`{{input.field}}`) and the output of the
+// most recent successful step (`{{previous.field}}`).
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::events::AppEvent;
+use crate::models::*;
+use crate::SharedState;
+
+/// Operations that pipeline steps can reference.
+pub const OPERATIONS: &[&str] = &[
+    "create_presentation",
+    "get_presentation",
+    "update_presentation",
+    "add_slides",
+    "search_presentations",
+    "outline_to_slides",
+];
+
+pub fn validate_steps(steps: &[PipelineStep]) -> AppResult<()> {
+    if steps.is_empty() {
+        return Err(AppError::BadRequest("A pipeline needs at least one step".to_string()));
+    }
+
+    for (index, step) in steps.iter().enumerate() {
+        if !OPERATIONS.contains(&step.op.as_str()) {
+            return Err(AppError::BadRequest(format!(
+                "Step {}: unknown operation '{}'. Available operations: {}",
+                index,
+                step.op,
+                OPERATIONS.join(", ")
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run_pipeline(state: &SharedState, id_or_name: &str, input: Value) -> AppResult<PipelineRunResult> {
+    let pipeline = {
+        let state = state.read().await;
+        state.db.get_pipeline(id_or_name).await?
+    };
+
+    let steps: Vec<PipelineStep> = serde_json::from_str(&pipeline.steps)
+        .map_err(|e| AppError::Internal(format!("Pipeline {} has invalid steps: {}", pipeline.id, e)))?;
+
+    Ok(run_steps(state, &pipeline.id, &steps, input).await)
+}
+
+async fn run_steps(state: &SharedState, pipeline_id: &str, steps: &[PipelineStep], input: Value) -> PipelineRunResult {
+    let mut results = Vec::with_capacity(steps.len());
+    let mut previous = Value::Null;
+    let mut halted = false;
+
+    for (index, step) in steps.iter().enumerate() {
+        if halted {
+            results.push(PipelineStepResult {
+                index,
+                op: step.op.clone(),
+                status: PipelineStepStatus::Skipped,
+                output: None,
+                error: None,
+            });
+            continue;
+        }
+
+        let scope = json!({ "input": input, "previous": previous });
+        let outcome = match substitute(&step.params, &scope) {
+            Ok(params) => execute_step(state, &step.op, params).await,
+            Err(e) => Err(e),
+        };
+
+        match outcome {
+            Ok(output) => {
+                previous = output.clone();
+                results.push(PipelineStepResult {
+                    index,
+                    op: step.op.clone(),
+                    status: PipelineStepStatus::Ok,
+                    output: Some(output),
+                    error: None,
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Pipeline {} step {} ({}) failed: {}", pipeline_id, index, step.op, e);
+                results.push(PipelineStepResult {
+                    index,
+                    op: step.op.clone(),
+                    status: PipelineStepStatus::Error,
+                    output: None,
+                    error: Some(e.to_string()),
+                });
+                if !step.continue_on_error {
+                    halted = true;
+                }
+            }
+        }
+
+        state.read().await.db.events().publish(AppEvent::JobProgress {
+            job: format!("pipeline:{}", pipeline_id),
+            completed: index + 1,
+            total: steps.len(),
+        });
+    }
+
+    PipelineRunResult {
+        pipeline_id: pipeline_id.to_string(),
+        success: !halted,
+        steps: results,
+    }
+}
+
+/// Replaces `{{path}}` references in every string of `value`. A string that consists of a
+/// single reference takes the referenced value as-is (keeping its JSON type); references
+/// embedded in longer strings are interpolated as text.
+fn substitute(value: &Value, scope: &Value) -> AppResult<Value> {
+    match value {
+        Value::String(s) => substitute_str(s, scope),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| substitute(item, scope))
+            .collect::<AppResult<Vec<_>>>()
+            .map(Value::Array),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, item)| Ok((key.clone(), substitute(item, scope)?)))
+            .collect::<AppResult<serde_json::Map<_, _>>>()
+            .map(Value::Object),
+        other => Ok(other.clone()),
+    }
+}
+
+fn substitute_str(s: &str, scope: &Value) -> AppResult<Value> {
+    let trimmed = s.trim();
+    if let Some(path) = trimmed.strip_prefix("{{").and_then(|rest| rest.strip_suffix("}}")) {
+        if !path.contains("{{") && !path.contains("}}") {
+            return resolve(path.trim(), scope).cloned();
+        }
+    }
+
+    let mut output = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let resolved = resolve(rest[start + 2..start + len].trim(), scope)?;
+        match resolved {
+            Value::String(text) => output.push_str(text),
+            other => output.push_str(&other.to_string()),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(Value::String(output))
+}
+
+fn resolve<'a>(path: &str, scope: &'a Value) -> AppResult<&'a Value> {
+    let mut segments = path.split('.');
+    let root = segments.next().unwrap_or_default();
+    if root != "input" && root != "previous" {
+        return Err(AppError::BadRequest(format!(
+            "Unknown reference '{{{{{}}}}}': references must start with 'input' or 'previous'",
+            path
+        )));
+    }
+
+    let mut current = &scope[root];
+    for segment in segments {
+        let next = match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        current = next.ok_or_else(|| AppError::BadRequest(format!("Unresolved reference '{{{{{}}}}}'", path)))?;
+    }
+
+    if current.is_null() {
+        return Err(AppError::BadRequest(format!("Unresolved reference '{{{{{}}}}}'", path)));
+    }
+
+    Ok(current)
+}
+
+async fn execute_step(state: &SharedState, op: &str, params: Value) -> AppResult<Value> {
+    match op {
+        "create_presentation" => {
+            let data: CreatePresentation = parse_params(params)?;
+            let state = state.read().await;
+            to_output(&state.db.create_presentation(data).await?)
+        }
+        "get_presentation" => {
+            let id = required_str(&params, "id")?;
+            let state = state.read().await;
+            to_output(&state.db.get_presentation(id).await?)
+        }
+        "update_presentation" => {
+            let id = required_str(&params, "id")?.to_string();
+            let data: UpdatePresentation = parse_params(params)?;
+            let state = state.read().await;
+            to_output(&state.db.update_presentation(&id, data).await?)
+        }
+        "add_slides" => {
+            let id = required_str(&params, "id")?;
+            let slides = required_str(&params, "slides")?;
+            let state = state.read().await;
+            let presentation = state.db.get_presentation(id).await?;
+            let content = format!("{}\n\n---\n\n{}", presentation.content.trim_end(), slides);
+            let updated = state
+                .db
+                .update_presentation(id, UpdatePresentation {
+                    title: None,
+                    content: Some(content),
+                    theme: None,
+                    settings: None,
+                })
+                .await?;
+            to_output(&updated.presentation)
+        }
+        "search_presentations" => {
+            let query = required_str(&params, "query")?;
+            let page = params.get("page").and_then(|v| v.as_u64()).unwrap_or(1) as u32;
+            let per_page = params
+                .get("perPage")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DEFAULT_PER_PAGE as u64) as u32;
+            let state = state.read().await;
+            to_output(&state.db.search_presentations(query, page, per_page).await?)
+        }
+        "outline_to_slides" => {
+            let provider = required_str(&params, "provider")?;
+            let outline = required_str(&params, "outline")?;
+            let content = crate::api::outline_to_slides(state, provider, outline).await?;
+            Ok(json!({ "content": content }))
+        }
+        _ => Err(AppError::BadRequest(format!("Unknown pipeline operation: {}", op))),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> AppResult<T> {
+    serde_json::from_value(params).map_err(|e| AppError::BadRequest(format!("Invalid step params: {}", e)))
+}
+
+fn required_str<'a>(params: &'a Value, key: &str) -> AppResult<&'a str> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| AppError::BadRequest(format!("Missing required param: {}", key)))
+}
+
+fn to_output<T: Serialize>(value: &T) -> AppResult<Value> {
+    serde_json::to_value(value).map_err(|e| AppError::Internal(format!("Failed to serialize step output: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    async fn test_state() -> SharedState {
+        Arc::new(RwLock::new(AppState::for_tests().await))
+    }
+
+    fn step(op: &str, params: Value, continue_on_error: bool) -> PipelineStep {
+        PipelineStep {
+            op: op.to_string(),
+            params,
+            continue_on_error,
+        }
+    }
+
+    #[test]
+    fn test_substitute_references() {
+        let scope = json!({
+            "input": { "title": "Launch", "count": 3 },
+            "previous": { "id": "abc", "tags": ["x", "y"] }
+        });
+
+        let params = json!({
+            "id": "{{previous.id}}",
+            "title": "{{ input.title }} v{{input.count}}",
+            "count": "{{input.count}}",
+            "nested": ["{{previous.tags.1}}"]
+        });
+
+        let resolved = substitute(&params, &scope).unwrap();
+        assert_eq!(resolved["id"], "abc");
+        assert_eq!(resolved["title"], "Launch v3");
+        assert_eq!(resolved["count"], 3);
+        assert_eq!(resolved["nested"][0], "y");
+
+        assert!(substitute(&json!("{{previous.missing}}"), &scope).is_err());
+        assert!(substitute(&json!("{{other.id}}"), &scope).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_step_outputs_feed_later_steps() {
+        let state = test_state().await;
+        let steps = vec![
+            step("create_presentation", json!({ "title": "{{input.title}}", "content": "# One" }), false),
+            step("add_slides", json!({ "id": "{{previous.id}}", "slides": "# Two" }), false),
+        ];
+
+        let result = run_steps(&state, "test", &steps, json!({ "title": "Piped" })).await;
+        assert!(result.success);
+        assert_eq!(result.steps[1].status, PipelineStepStatus::Ok);
+
+        let output = result.steps[1].output.as_ref().unwrap();
+        assert_eq!(output["title"], "Piped");
+        assert_eq!(output["content"], "# One\n\n---\n\n# Two");
+    }
+
+    #[tokio::test]
+    async fn test_failure_stops_pipeline_unless_continue_on_error() {
+        let state = test_state().await;
+        let failing = step("get_presentation", json!({ "id": "missing" }), false);
+        let create = step("create_presentation", json!({ "title": "After" }), false);
+
+        let result = run_steps(&state, "test", &[failing.clone(), create.clone()], Value::Null).await;
+        assert!(!result.success);
+        assert_eq!(result.steps[0].status, PipelineStepStatus::Error);
+        assert!(result.steps[0].error.is_some());
+        assert_eq!(result.steps[1].status, PipelineStepStatus::Skipped);
+
+        let tolerant = PipelineStep {
+            continue_on_error: true,
+            ..failing
+        };
+        let result = run_steps(&state, "test", &[tolerant, create], Value::Null).await;
+        assert!(result.success);
+        assert_eq!(result.steps[0].status, PipelineStepStatus::Error);
+        assert_eq!(result.steps[1].status, PipelineStepStatus::Ok);
+    }
+}
@@ -0,0 +1,184 @@
+// `{{name}}` placeholders left in decks by templates and scaffolds. A placeholder may carry a
+// type hint, as in `{{date:date}}` or `{{count:number}}`, which fill values are checked against.
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+use crate::models::Placeholder;
+use crate::slides;
+
+struct Token<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+    type_hint: Option<&'a str>,
+}
+
+/// Unfilled placeholders in order of first appearance, with the slides each appears on.
+pub fn find_placeholders(content: &str) -> Vec<Placeholder> {
+    let mut found: Vec<Placeholder> = Vec::new();
+
+    for (index, slide) in slides::split_slides(content).iter().enumerate() {
+        for token in tokens(slide) {
+            match found.iter_mut().find(|p| p.name == token.name) {
+                Some(placeholder) => {
+                    if placeholder.slides.last() != Some(&index) {
+                        placeholder.slides.push(index);
+                    }
+                    if placeholder.type_hint.is_none() {
+                        placeholder.type_hint = token.type_hint.map(str::to_string);
+                    }
+                }
+                None => found.push(Placeholder {
+                    name: token.name.to_string(),
+                    type_hint: token.type_hint.map(str::to_string),
+                    slides: vec![index],
+                }),
+            }
+        }
+    }
+
+    found
+}
+
+/// Substitutes `values` for every occurrence of the named placeholders. Values are checked
+/// against each type hint their placeholder carries; names not in the deck are ignored, so
+/// filling twice is a no-op.
+pub fn fill_placeholders(content: &str, values: &HashMap<String, Value>) -> AppResult<String> {
+    let mut texts = HashMap::new();
+    for (name, value) in values {
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "Value for placeholder '{}' must be a string, number or boolean",
+                    name
+                )))
+            }
+        };
+        texts.insert(name.as_str(), text);
+    }
+
+    let tokens = tokens(content);
+    for token in &tokens {
+        if let (Some(text), Some(hint)) = (texts.get(token.name), token.type_hint) {
+            check_type(token.name, hint, text)?;
+        }
+    }
+
+    let mut filled = String::with_capacity(content.len());
+    let mut copied = 0;
+    for token in &tokens {
+        if let Some(text) = texts.get(token.name) {
+            filled.push_str(&content[copied..token.start]);
+            filled.push_str(text);
+            copied = token.end;
+        }
+    }
+    filled.push_str(&content[copied..]);
+    Ok(filled)
+}
+
+fn check_type(name: &str, hint: &str, value: &str) -> AppResult<()> {
+    let valid = match hint {
+        "number" => value.trim().parse::<f64>().is_ok_and(f64::is_finite),
+        "date" => NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").is_ok(),
+        // `text` and hints we don't know accept anything
+        _ => true,
+    };
+
+    if !valid {
+        let expected = if hint == "date" { "a YYYY-MM-DD date" } else { "a number" };
+        return Err(AppError::BadRequest(format!(
+            "Value '{}' for placeholder '{}' is not {}",
+            value, name, expected
+        )));
+    }
+    Ok(())
+}
+
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+
+    while let Some(open) = text[offset..].find("{{") {
+        let start = offset + open;
+        let Some(close) = text[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+        let inner = text[start + 2..end - 2].trim();
+
+        let (name, type_hint) = match inner.split_once(':') {
+            Some((name, hint)) => (name.trim(), Some(hint.trim())),
+            None => (inner, None),
+        };
+
+        if is_name(name) && type_hint.is_none_or(is_name) {
+            tokens.push(Token {
+                start,
+                end,
+                name,
+                type_hint,
+            });
+            offset = end;
+        } else {
+            // Not a placeholder (e.g. `{{ a + b }}` in a code sample); keep scanning after it
+            offset = start + 2;
+        }
+    }
+
+    tokens
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DECK: &str = "# {{title}}\n\n{{ date:date }}\n\n---\n\n{{count:number}} attendees, {{ a + b }}\n\n---\n\n# {{title}}";
+
+    #[test]
+    fn test_find_placeholders() {
+        let found = find_placeholders(DECK);
+        let summary: Vec<_> = found
+            .iter()
+            .map(|p| (p.name.as_str(), p.type_hint.as_deref(), p.slides.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("title", None, vec![0, 2]),
+                ("date", Some("date"), vec![0]),
+                ("count", Some("number"), vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_placeholders() {
+        let values: HashMap<String, Value> = serde_json::from_value(json!({
+            "title": "Q3 Review",
+            "count": 42,
+        }))
+        .unwrap();
+        let filled = fill_placeholders(DECK, &values).unwrap();
+        assert_eq!(filled, "# Q3 Review\n\n{{ date:date }}\n\n---\n\n42 attendees, {{ a + b }}\n\n---\n\n# Q3 Review");
+        assert_eq!(fill_placeholders(&filled, &values).unwrap(), filled);
+
+        let bad_date = HashMap::from([("date".to_string(), json!("next week"))]);
+        assert!(fill_placeholders(DECK, &bad_date).is_err());
+        let bad_number = HashMap::from([("count".to_string(), json!("many"))]);
+        assert!(fill_placeholders(DECK, &bad_number).is_err());
+    }
+}
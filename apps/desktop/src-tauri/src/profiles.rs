@@ -0,0 +1,560 @@
+// Named workspace profiles. Each profile has its own database and uploads directory under
+// `profiles/<name>/`; the `default` profile keeps the original locations directly in the
+// app data directory so existing installs carry on unchanged.
+use std::path::{Path, PathBuf};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::encryption;
+use crate::events::AppEvent;
+use crate::models::{KeyRotation, Profile};
+use crate::SharedState;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+const REGISTRY_FILE: &str = "profiles.json";
+const MAX_NAME_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileEntry {
+    name: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RegistryFile {
+    active: String,
+    profiles: Vec<ProfileEntry>,
+}
+
+pub struct ProfilePaths {
+    pub db_path: PathBuf,
+    pub uploads_dir: PathBuf,
+}
+
+impl ProfilePaths {
+    pub fn database_url(&self) -> String {
+        format!("sqlite:{}?mode=rwc", self.db_path.display())
+    }
+}
+
+/// The profile registry stored as `profiles.json` in the app data directory.
+pub struct ProfileRegistry {
+    root: PathBuf,
+    file: RegistryFile,
+}
+
+impl ProfileRegistry {
+    /// Loads the registry, or just the default profile if none has been saved yet. Changes
+    /// should be made under `lock`, so they start from the latest saved registry.
+    pub fn load(root: &Path) -> AppResult<Self> {
+        let path = root.join(REGISTRY_FILE);
+        let file = match std::fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map_err(|e| AppError::Internal(format!("Invalid profile registry {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RegistryFile {
+                active: DEFAULT_PROFILE.to_string(),
+                profiles: vec![ProfileEntry {
+                    name: DEFAULT_PROFILE.to_string(),
+                    created_at: Utc::now(),
+                }],
+            },
+            Err(e) => return Err(io_error("Failed to read profile registry", e)),
+        };
+
+        let mut registry = Self {
+            root: root.to_path_buf(),
+            file,
+        };
+
+        if !registry.contains(&registry.file.active) {
+            tracing::warn!("Active profile '{}' is not registered, using default", registry.file.active);
+            registry.file.active = DEFAULT_PROFILE.to_string();
+        }
+
+        Ok(registry)
+    }
+
+    pub fn active(&self) -> &str {
+        &self.file.active
+    }
+
+    pub fn list(&self) -> Vec<Profile> {
+        self.file.profiles.iter().map(|entry| self.to_profile(entry)).collect()
+    }
+
+    pub fn get(&self, name: &str) -> AppResult<Profile> {
+        self.file
+            .profiles
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| self.to_profile(entry))
+            .ok_or_else(|| AppError::NotFound(format!("Profile {} not found", name)))
+    }
+
+    pub fn paths(&self, name: &str) -> ProfilePaths {
+        let dir = if name == DEFAULT_PROFILE {
+            self.root.clone()
+        } else {
+            self.root.join("profiles").join(name)
+        };
+
+        ProfilePaths {
+            db_path: dir.join("slides.db"),
+            uploads_dir: dir.join("uploads"),
+        }
+    }
+
+    pub fn create(&mut self, name: &str) -> AppResult<Profile> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(AppError::BadRequest(format!("Profile {} already exists", name)));
+        }
+
+        let paths = self.paths(name);
+        std::fs::create_dir_all(&paths.uploads_dir).map_err(|e| io_error("Failed to create profile directory", e))?;
+
+        let entry = ProfileEntry {
+            name: name.to_string(),
+            created_at: Utc::now(),
+        };
+        let profile = self.to_profile(&entry);
+        self.file.profiles.push(entry);
+        self.save()?;
+
+        Ok(profile)
+    }
+
+    pub fn set_active(&mut self, name: &str) -> AppResult<()> {
+        self.get(name)?;
+        self.file.active = name.to_string();
+        self.save()
+    }
+
+    /// Unregisters a profile and moves its directory to `trash/` in the app data directory.
+    /// `confirm` must repeat the profile name.
+    pub fn remove(&mut self, name: &str, confirm: &str) -> AppResult<()> {
+        self.get(name)?;
+        if confirm != name {
+            return Err(AppError::BadRequest(format!(
+                "Type the profile name ({}) to confirm deletion",
+                name
+            )));
+        }
+        if name == DEFAULT_PROFILE {
+            return Err(AppError::Forbidden("The default profile cannot be deleted".to_string()));
+        }
+        if name == self.file.active {
+            return Err(AppError::BadRequest(
+                "Cannot delete the active profile. Switch to another profile first".to_string(),
+            ));
+        }
+
+        let dir = self.root.join("profiles").join(name);
+        if dir.exists() {
+            let trash_dir = self.root.join("trash");
+            std::fs::create_dir_all(&trash_dir).map_err(|e| io_error("Failed to create trash directory", e))?;
+            let target = trash_dir.join(format!("{}-{}", name, Utc::now().format("%Y%m%d%H%M%S")));
+            std::fs::rename(&dir, &target).map_err(|e| io_error("Failed to move profile to trash", e))?;
+            tracing::info!("Moved profile {} to {}", name, target.display());
+        }
+
+        self.file.profiles.retain(|entry| entry.name != name);
+        self.save()
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.file.profiles.iter().any(|entry| entry.name == name)
+    }
+
+    fn to_profile(&self, entry: &ProfileEntry) -> Profile {
+        Profile {
+            name: entry.name.clone(),
+            active: entry.name == self.file.active,
+            created_at: entry.created_at,
+        }
+    }
+
+    fn save(&self) -> AppResult<()> {
+        let json = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize profile registry: {}", e)))?;
+
+        // Write to a temporary file first so a crash never leaves a truncated registry
+        let path = self.root.join(REGISTRY_FILE);
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, json).map_err(|e| io_error("Failed to write profile registry", e))?;
+        std::fs::rename(&tmp, &path).map_err(|e| io_error("Failed to write profile registry", e))
+    }
+}
+
+fn validate_name(name: &str) -> AppResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(AppError::BadRequest(format!(
+            "Invalid profile name '{}': use up to {} lowercase letters, digits, '-' or '_'",
+            name, MAX_NAME_LENGTH
+        )));
+    }
+
+    Ok(())
+}
+
+fn io_error(context: &str, e: std::io::Error) -> AppError {
+    AppError::Internal(format!("{}: {}", context, e))
+}
+
+/// Takes `AppState::profile_registry` and returns the app data directory holding the registry.
+/// The state lock is released before waiting, since `activate` holds the registry lock while
+/// it waits for the state write lock.
+async fn lock(state: &SharedState) -> (OwnedMutexGuard<()>, PathBuf) {
+    let (registry_lock, root) = {
+        let state = state.read().await;
+        (state.profile_registry.clone(), state.app_data_dir.clone())
+    };
+    (registry_lock.lock_owned().await, root)
+}
+
+/// Registers a new profile, see `ProfileRegistry::create`.
+pub async fn create(state: &SharedState, name: &str) -> AppResult<Profile> {
+    let (_registry_lock, root) = lock(state).await;
+    ProfileRegistry::load(&root)?.create(name)
+}
+
+/// Deletes a profile, see `ProfileRegistry::remove`.
+pub async fn remove(state: &SharedState, name: &str, confirm: &str) -> AppResult<()> {
+    let (_registry_lock, root) = lock(state).await;
+    ProfileRegistry::load(&root)?.remove(name, confirm)
+}
+
+/// Switches the backend to another profile: opens and migrates the profile's database,
+/// swaps it into the shared state and closes the previous pool. Listeners on
+/// `AppState::profile_changes` and event subscribers are notified once the swap is done.
+pub async fn activate(state: &SharedState, name: &str) -> AppResult<Profile> {
+    // Held throughout, so switches run one at a time and no registry change lands in between
+    let (_registry_lock, root) = lock(state).await;
+    let current = state.read().await.profile.clone();
+
+    let mut registry = ProfileRegistry::load(&root)?;
+    registry.get(name)?;
+    if current == name {
+        return registry.get(name);
+    }
+
+    let paths = registry.paths(name);
+    std::fs::create_dir_all(&paths.uploads_dir).map_err(|e| io_error("Failed to create profile directory", e))?;
+    let events = state.read().await.db.events().clone();
+    let db = Database::new_with_url(&paths.database_url()).await?.with_events(events.clone());
+    db.migrate().await?;
+
+    // Waits for in-flight requests to release their read locks. Requests arriving in the
+    // meantime are turned away by `reject_while_switching` instead of queueing.
+    let previous = {
+        let mut state = state.write().await;
+        state.uploads_dir = paths.uploads_dir;
+        state.profile = name.to_string();
+        state.profile_changes.send_replace(name.to_string());
+        std::mem::replace(&mut state.db, db)
+    };
+    previous.close().await;
+    events.publish(AppEvent::ProfileChanged { profile: name.to_string() });
+
+    registry.set_active(name)?;
+    tracing::info!("Switched to profile {}", name);
+
+    registry.get(name)
+}
+
+/// Re-encrypts stored API keys in every profile's database and switches to `new_key`. The
+/// active profile goes first, so a bad `old_key` fails before anything changes. If another
+/// profile fails, the profiles already rotated are changed back and the old key stays.
+pub async fn rotate_encryption_key(state: &SharedState, old_key: &str, new_key: &str) -> AppResult<KeyRotation> {
+    let _rotating = encryption::KEY_ROTATION.write().await;
+    // A read lock is enough to keep the profile from switching underneath
+    let state = state.read().await;
+    let mut rotated = state.db.rotate_encryption_key(old_key, new_key).await?;
+    let mut done = Vec::new();
+    let mut failures = Vec::new();
+
+    let registry = ProfileRegistry::load(&state.app_data_dir)?;
+    for profile in registry.list() {
+        let paths = registry.paths(&profile.name);
+        if profile.name == state.profile || !paths.db_path.exists() {
+            continue;
+        }
+
+        match rotate_profile(&paths.database_url(), old_key, new_key).await {
+            Ok(count) => {
+                rotated += count;
+                done.push(profile.name);
+            }
+            Err(e) => failures.push(format!("{}: {}", profile.name, e)),
+        }
+    }
+
+    if !failures.is_empty() {
+        if let Err(e) = state.db.rotate_encryption_key(new_key, old_key).await {
+            tracing::error!(profile = %state.profile, error = %e, "Failed to restore API keys after a failed key rotation");
+        }
+        for name in done {
+            if let Err(e) = rotate_profile(&registry.paths(&name).database_url(), new_key, old_key).await {
+                tracing::error!(profile = %name, error = %e, "Failed to restore API keys after a failed key rotation");
+            }
+        }
+        return Err(AppError::Conflict(format!(
+            "Encryption key not rotated, keys in other profiles could not be re-encrypted: {}",
+            failures.join("; ")
+        )));
+    }
+
+    encryption::set_current_key(new_key);
+    tracing::warn!("Encryption key rotated; set SLIDES_ENCRYPTION_KEY to the new key before the next start");
+
+    Ok(KeyRotation { rotated })
+}
+
+/// Rotates the API keys of a profile that isn't active.
+async fn rotate_profile(database_url: &str, old_key: &str, new_key: &str) -> AppResult<usize> {
+    let db = Database::new_with_url(database_url).await?;
+    let result = db.rotate_encryption_key(old_key, new_key).await;
+    db.close().await;
+    result
+}
+
+/// Middleware that fails fast with 503 while a profile switch holds (or waits for) the
+/// state write lock.
+pub async fn reject_while_switching(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    if state.try_read().is_err() {
+        return AppError::Unavailable("Profile switching in progress, retry shortly".to_string()).into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreateAiProviderConfig, CreatePresentation};
+    use crate::AppState;
+    use axum::{body::Body, http::StatusCode};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{watch, RwLock};
+    use tower::ServiceExt;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("slides-profiles-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    async fn test_state(root: &Path) -> SharedState {
+        let registry = ProfileRegistry::load(root).unwrap();
+        let paths = registry.paths(registry.active());
+        std::fs::create_dir_all(&paths.uploads_dir).unwrap();
+        let db = Database::new_with_url(&paths.database_url()).await.unwrap();
+        db.migrate().await.unwrap();
+
+        Arc::new(RwLock::new(AppState {
+            uploads_dir: paths.uploads_dir,
+            app_data_dir: root.to_path_buf(),
+            profile: registry.active().to_string(),
+            profile_changes: watch::channel(registry.active().to_string()).0,
+            ..AppState::for_tests_with(db)
+        }))
+    }
+
+    #[test]
+    fn test_registry_create_and_remove() {
+        let root = temp_root();
+        let mut registry = ProfileRegistry::load(&root).unwrap();
+        assert_eq!(registry.active(), DEFAULT_PROFILE);
+        // Reading alone writes nothing
+        assert!(!root.join(REGISTRY_FILE).exists());
+
+        registry.create("client-a").unwrap();
+        assert!(root.join("profiles/client-a/uploads").is_dir());
+        assert!(registry.create("client-a").is_err());
+        assert!(registry.create("Client A").is_err());
+        assert!(registry.create("../escape").is_err());
+
+        // Reloading sees the persisted profile
+        let mut registry = ProfileRegistry::load(&root).unwrap();
+        assert_eq!(registry.list().len(), 2);
+
+        assert!(registry.remove("client-a", "client-b").is_err());
+        assert!(registry.remove(DEFAULT_PROFILE, DEFAULT_PROFILE).is_err());
+        registry.remove("client-a", "client-a").unwrap();
+        assert!(!root.join("profiles/client-a").exists());
+        assert_eq!(std::fs::read_dir(root.join("trash")).unwrap().count(), 1);
+        assert_eq!(registry.list().len(), 1);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_activate_switches_database() {
+        let root = temp_root();
+        let state = test_state(&root).await;
+        ProfileRegistry::load(&root).unwrap().create("client-a").unwrap();
+
+        state
+            .read()
+            .await
+            .db
+            .create_presentation(CreatePresentation {
+                title: "Personal".to_string(),
+                content: None,
+                theme: None,
+                if_not_exists: false,
+                on_conflict: None,
+            })
+            .await
+            .unwrap();
+
+        let mut changes = state.read().await.profile_changes.subscribe();
+        activate(&state, "client-a").await.unwrap();
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(*changes.borrow_and_update(), "client-a");
+        assert_eq!(ProfileRegistry::load(&root).unwrap().active(), "client-a");
+        {
+            let state = state.read().await;
+            assert_eq!(state.profile, "client-a");
+            assert_eq!(state.db.list_presentations(Default::default()).await.unwrap().total, 0);
+        }
+
+        activate(&state, DEFAULT_PROFILE).await.unwrap();
+        assert_eq!(
+            state.read().await.db.list_presentations(Default::default()).await.unwrap().total,
+            1
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    async fn save_api_key(db: &Database, key: &str) {
+        let config = CreateAiProviderConfig {
+            provider_name: "openai".to_string(),
+            api_key: None,
+            model: None,
+            base_url: None,
+        };
+        let encrypted = encryption::encrypt_with(&encryption::derive_key(key), "sk-test").unwrap();
+        db.upsert_ai_provider_config(config, encrypted).await.unwrap();
+    }
+
+    async fn api_key_opens_with(db: &Database, key: &str) -> bool {
+        let configs = db.list_ai_provider_configs().await.unwrap();
+        encryption::decrypt_with(&encryption::derive_key(key), &configs[0].api_key_encrypted).is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_failed_key_rotation_changes_nothing() {
+        let root = temp_root();
+        let state = test_state(&root).await;
+        save_api_key(&state.read().await.db, "old-key").await;
+
+        // client-a rotates fine; client-b holds a key the old key can't open
+        let mut registry = ProfileRegistry::load(&root).unwrap();
+        for (name, key) in [("client-a", "old-key"), ("client-b", "another-key")] {
+            registry.create(name).unwrap();
+            let db = Database::new_with_url(&registry.paths(name).database_url()).await.unwrap();
+            db.migrate().await.unwrap();
+            save_api_key(&db, key).await;
+            db.close().await;
+        }
+
+        let err = rotate_encryption_key(&state, "old-key", "new-key").await.unwrap_err();
+        assert!(matches!(&err, AppError::Conflict(msg) if msg.contains("client-b")));
+        assert!(api_key_opens_with(&state.read().await.db, "old-key").await);
+        let client_a = Database::new_with_url(&registry.paths("client-a").database_url()).await.unwrap();
+        assert!(api_key_opens_with(&client_a, "old-key").await);
+        client_a.close().await;
+        assert!(!encryption::is_current_key("new-key"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_requests_rejected_while_switching() {
+        let root = temp_root();
+        let state = test_state(&root).await;
+        ProfileRegistry::load(&root).unwrap().create("client-a").unwrap();
+        let router = crate::api::create_router(state.clone());
+
+        // An in-flight request keeps the switch waiting for the write lock
+        let in_flight = state.clone().read_owned().await;
+        let switch = tokio::spawn({
+            let state = state.clone();
+            async move { activate(&state, "client-a").await }
+        });
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while state.try_read().is_ok() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let request = Request::builder().uri("/presentations").body(Body::empty()).unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Profile switching"));
+
+        drop(in_flight);
+        switch.await.unwrap().unwrap();
+
+        let request = Request::builder().uri("/presentations").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+    #[tokio::test]
+    async fn test_switch_keeps_profiles_created_meanwhile() {
+        let root = temp_root();
+        let state = test_state(&root).await;
+        create(&state, "client-a").await.unwrap();
+
+        // A profile being created holds the registry; the switch waits for it and then reloads
+        let creating = state.read().await.profile_registry.clone().lock_owned().await;
+        let switch = tokio::spawn({
+            let state = state.clone();
+            async move { activate(&state, "client-a").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!switch.is_finished());
+        ProfileRegistry::load(&root).unwrap().create("client-b").unwrap();
+        drop(creating);
+        switch.await.unwrap().unwrap();
+
+        let registry = ProfileRegistry::load(&root).unwrap();
+        assert_eq!(registry.active(), "client-a");
+        assert!(registry.get("client-b").is_ok());
+
+        // Two switches at once run one after the other
+        let (a, b) = tokio::join!(activate(&state, "client-b"), activate(&state, DEFAULT_PROFILE));
+        a.unwrap();
+        b.unwrap();
+        let active = ProfileRegistry::load(&root).unwrap().active().to_string();
+        assert_eq!(state.read().await.profile, active);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,427 @@
+// Search-as-you-type for the command palette. The short names in the workspace (presentation
+// titles and tags, slide headings, theme and media names) are kept in memory, so single-word
+// prefix queries never touch the database. The index is built at startup and kept current
+// from the event bus; queries of several words go to full-text search instead.
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::error::{AppError, AppResult};
+use crate::events::AppEvent;
+use crate::models::{Media, Presentation, QuickSearchKind, QuickSearchResult, Theme};
+use crate::safe_mode;
+use crate::slides;
+use crate::AppState;
+
+pub const DEFAULT_LIMIT: usize = 20;
+pub const MAX_LIMIT: usize = 50;
+
+// Labels are cut to this length so the index holds ids and short strings only
+const MAX_LABEL_CHARS: usize = 100;
+
+/// Index entries grouped by the presentation, theme or media file they came from, so one
+/// source can be replaced without rebuilding the rest.
+#[derive(Default)]
+pub struct QuickSearchIndex(RwLock<HashMap<Source, Vec<Entry>>>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Source {
+    Presentation(String),
+    Theme(String),
+    Media(String),
+}
+
+struct Entry {
+    result: QuickSearchResult,
+    /// Lowercased label followed by its words and, for presentations, tags
+    keys: Vec<String>,
+}
+
+impl Entry {
+    fn new(result: QuickSearchResult, extra_keys: &[String]) -> Self {
+        let label = result.label.to_lowercase();
+        let mut keys: Vec<String> = label
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty() && *word != label)
+            .map(str::to_string)
+            .collect();
+        keys.insert(0, label);
+        keys.extend(extra_keys.iter().map(|key| key.to_lowercase()));
+        Self { result, keys }
+    }
+
+    /// 0 when the label starts with `prefix`, 1 when one of its words or tags does.
+    fn rank(&self, prefix: &str) -> Option<u8> {
+        let mut keys = self.keys.iter();
+        if keys.next().is_some_and(|label| label.starts_with(prefix)) {
+            return Some(0);
+        }
+        keys.any(|key| key.starts_with(prefix)).then_some(1)
+    }
+}
+
+impl QuickSearchIndex {
+    /// Entries whose label, or one of its words or tags, starts with `prefix`. Whole-label
+    /// matches come first, then presentations before slides, themes and media.
+    pub fn search(&self, prefix: &str, limit: usize) -> Vec<QuickSearchResult> {
+        let prefix = prefix.trim().to_lowercase();
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+
+        let index = self.0.read().unwrap();
+        let mut matches: Vec<(u8, &QuickSearchResult)> = index
+            .values()
+            .flatten()
+            .filter_map(|entry| entry.rank(&prefix).map(|rank| (rank, &entry.result)))
+            .collect();
+        matches.sort_by(|(a_rank, a), (b_rank, b)| {
+            a_rank
+                .cmp(b_rank)
+                .then_with(|| a.kind.cmp(&b.kind))
+                .then_with(|| a.label.len().cmp(&b.label.len()))
+                .then_with(|| a.label.cmp(&b.label))
+                .then_with(|| a.slide_index.cmp(&b.slide_index))
+        });
+        matches.into_iter().take(limit).map(|(_, result)| result.clone()).collect()
+    }
+
+    /// Number of indexed entries.
+    pub fn len(&self) -> usize {
+        self.0.read().unwrap().values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn replace(&self, source: Source, entries: Vec<Entry>) {
+        let mut index = self.0.write().unwrap();
+        if entries.is_empty() {
+            index.remove(&source);
+        } else {
+            index.insert(source, entries);
+        }
+    }
+}
+
+/// Results for the palette: single words from the in-memory index, anything longer from
+/// full-text search over presentations.
+pub async fn search(state: &AppState, query: &str, limit: Option<usize>) -> AppResult<Vec<QuickSearchResult>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let query = query.trim();
+    if query.is_empty() {
+        return Err(AppError::BadRequest("Search query must not be empty".to_string()));
+    }
+
+    if !query.contains(char::is_whitespace) {
+        return Ok(state.quick_search.search(query, limit));
+    }
+
+    let found = state.db.search_presentations(query, 1, limit as u32).await?;
+    Ok(found.items.iter().map(presentation_result).collect())
+}
+
+/// Rebuilds the whole index from the database, e.g. at startup or after a profile switch.
+pub async fn rebuild(state: &AppState) -> AppResult<()> {
+    let presentations = state.db.list_all_presentations().await?;
+    let themes = state.db.list_themes().await?;
+    let media = state.db.list_media().await?;
+
+    let mut index = HashMap::new();
+    for presentation in presentations.iter().filter(|p| !p.archived) {
+        index.insert(Source::Presentation(presentation.id.clone()), presentation_entries(presentation));
+    }
+    for theme in themes.iter().filter(|theme| theme_served(state, theme)) {
+        index.insert(Source::Theme(theme.id.clone()), vec![theme_entry(theme)]);
+    }
+    for media in &media {
+        index.insert(Source::Media(media.id.clone()), vec![media_entry(media)]);
+    }
+
+    *state.quick_search.0.write().unwrap() = index;
+    Ok(())
+}
+
+/// Brings the index up to date with one change.
+pub async fn apply(state: &AppState, event: &AppEvent) -> AppResult<()> {
+    let index = &state.quick_search;
+    match event {
+        AppEvent::PresentationCreated { id } | AppEvent::PresentationUpdated { id } | AppEvent::PresentationRestored { id } => {
+            // Archived decks are left out, as they are from listings
+            let entries = match found(state.db.get_presentation(id).await)? {
+                Some(presentation) if !presentation.archived => presentation_entries(&presentation),
+                _ => Vec::new(),
+            };
+            index.replace(Source::Presentation(id.clone()), entries);
+        }
+        AppEvent::PresentationDeleted { id, .. } => index.replace(Source::Presentation(id.clone()), Vec::new()),
+        AppEvent::ThemeChanged { id } => {
+            let entries = match found(state.db.get_theme_by_id(id).await)? {
+                Some(theme) if theme_served(state, &theme) => vec![theme_entry(&theme)],
+                _ => Vec::new(),
+            };
+            index.replace(Source::Theme(id.clone()), entries);
+        }
+        AppEvent::MediaAdded { id } => {
+            let entries = state.db.get_media(id).await?.iter().map(media_entry).collect();
+            index.replace(Source::Media(id.clone()), entries);
+        }
+        AppEvent::MediaDeleted { id } => index.replace(Source::Media(id.clone()), Vec::new()),
+        AppEvent::ProfileChanged { .. } | AppEvent::EventsMissed { .. } => rebuild(state).await?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn found<T>(result: AppResult<T>) -> AppResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(AppError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// Custom themes aren't served in safe mode, so they aren't offered either
+fn theme_served(state: &AppState, theme: &Theme) -> bool {
+    !state.safe_mode || safe_mode::serves_theme(theme)
+}
+
+fn presentation_result(presentation: &Presentation) -> QuickSearchResult {
+    QuickSearchResult {
+        kind: QuickSearchKind::Presentation,
+        id: presentation.id.clone(),
+        label: label(&presentation.title),
+        slide_index: None,
+        target: format!("/editor/{}", presentation.id),
+    }
+}
+
+fn presentation_entries(presentation: &Presentation) -> Vec<Entry> {
+    let mut entries = vec![Entry::new(presentation_result(presentation), &presentation.tags)];
+    for (index, slide) in slides::split_slides(&presentation.content).iter().enumerate() {
+        if let Some(heading) = slides::heading(slide) {
+            let result = QuickSearchResult {
+                kind: QuickSearchKind::Slide,
+                id: presentation.id.clone(),
+                label: label(&heading),
+                slide_index: Some(index),
+                target: format!("/editor/{}?slide={}", presentation.id, index),
+            };
+            entries.push(Entry::new(result, &[]));
+        }
+    }
+    entries
+}
+
+fn theme_entry(theme: &Theme) -> Entry {
+    let result = QuickSearchResult {
+        kind: QuickSearchKind::Theme,
+        id: theme.id.clone(),
+        label: label(&theme.display_name),
+        slide_index: None,
+        target: format!("/settings?theme={}", theme.name),
+    };
+    Entry::new(result, std::slice::from_ref(&theme.name))
+}
+
+fn media_entry(media: &Media) -> Entry {
+    let result = QuickSearchResult {
+        kind: QuickSearchKind::Media,
+        id: media.id.clone(),
+        label: label(&media.original_name),
+        slide_index: None,
+        target: media.url.clone(),
+    };
+    Entry::new(result, &[])
+}
+
+fn label(text: &str) -> String {
+    text.chars().take(MAX_LABEL_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::MediaMetadata;
+    use crate::models::{CreatePresentation, CreateTheme, NameConflict, UpdatePresentation, UpdateTheme};
+
+    async fn state() -> AppState {
+        let state = AppState::for_tests().await;
+        rebuild(&state).await.unwrap();
+        state
+    }
+
+    /// Applies the events published by `change`, as the background subscriber would.
+    async fn apply_events<F: std::future::Future>(state: &AppState, change: F) -> F::Output {
+        let mut events = state.db.events().subscribe();
+        let output = change.await;
+        while let Ok(event) = events.try_recv() {
+            apply(state, &event).await.unwrap();
+        }
+        output
+    }
+
+    fn labels(state: &AppState, query: &str) -> Vec<(QuickSearchKind, String)> {
+        state
+            .quick_search
+            .search(query, MAX_LIMIT)
+            .into_iter()
+            .map(|result| (result.kind, result.label))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_presentations_and_slides_follow_changes() {
+        let state = state().await;
+        let data = CreatePresentation {
+            title: "Quarterly Review".to_string(),
+            content: Some("# Revenue\n\n---\n\n# Hiring plan".to_string()),
+            theme: None,
+            if_not_exists: false,
+            on_conflict: None,
+        };
+        let deck = apply_events(&state, state.db.create_presentation(data)).await.unwrap();
+
+        assert_eq!(labels(&state, "quar"), [(QuickSearchKind::Presentation, "Quarterly Review".to_string())]);
+        assert_eq!(labels(&state, "REV"), [
+            (QuickSearchKind::Slide, "Revenue".to_string()),
+            (QuickSearchKind::Presentation, "Quarterly Review".to_string()),
+        ]);
+        let hiring = state.quick_search.search("plan", 5);
+        assert_eq!(hiring[0].slide_index, Some(1));
+        assert_eq!(hiring[0].target, format!("/editor/{}?slide=1", deck.id));
+
+        apply_events(&state, state.db.add_tag_to_presentation(&deck.id, "finance")).await.unwrap();
+        assert_eq!(labels(&state, "fin"), [(QuickSearchKind::Presentation, "Quarterly Review".to_string())]);
+
+        let rename = UpdatePresentation {
+            title: Some("Annual Review".to_string()),
+            content: Some("# Headcount".to_string()),
+            theme: None,
+            settings: None,
+        };
+        apply_events(&state, state.db.update_presentation(&deck.id, rename)).await.unwrap();
+        assert!(labels(&state, "quar").is_empty());
+        assert!(labels(&state, "hiring").is_empty());
+        assert_eq!(labels(&state, "ann"), [(QuickSearchKind::Presentation, "Annual Review".to_string())]);
+        assert_eq!(labels(&state, "head"), [(QuickSearchKind::Slide, "Headcount".to_string())]);
+
+        apply_events(&state, state.db.delete_presentation(&deck.id)).await.unwrap();
+        assert!(labels(&state, "ann").is_empty());
+        assert!(labels(&state, "head").is_empty());
+
+        apply_events(&state, state.db.restore_presentation(&deck.id)).await.unwrap();
+        assert_eq!(labels(&state, "ann").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_themes_and_media_follow_changes() {
+        let state = state().await;
+        let builtin = state.quick_search.len();
+
+        let data = CreateTheme {
+            name: "zephyr-glow".to_string(),
+            display_name: "Zephyr Glow".to_string(),
+            css_content: "[data-theme=\"zephyr-glow\"] {}".to_string(),
+            center_content: None,
+            fonts: Default::default(),
+        };
+        let theme = apply_events(&state, state.db.create_theme(data, NameConflict::Error)).await.unwrap();
+        assert_eq!(labels(&state, "zeph"), [(QuickSearchKind::Theme, "Zephyr Glow".to_string())]);
+
+        let rename = UpdateTheme {
+            display_name: Some("Quokka Dusk".to_string()),
+            css_content: None,
+            center_content: None,
+            fonts: None,
+        };
+        apply_events(&state, state.db.update_theme(&theme.id, rename)).await.unwrap();
+        // Still found by its name
+        assert_eq!(labels(&state, "zeph"), [(QuickSearchKind::Theme, "Quokka Dusk".to_string())]);
+        assert_eq!(labels(&state, "quok"), [(QuickSearchKind::Theme, "Quokka Dusk".to_string())]);
+
+        apply_events(&state, state.db.delete_theme(&theme.id, false)).await.unwrap();
+        assert!(labels(&state, "quok").is_empty());
+
+        let media = state.db.create_media(
+            "1-abc.png".to_string(),
+            "team-photo.png".to_string(),
+            "image/png".to_string(),
+            3,
+            "/api/uploads/1-abc.png".to_string(),
+            MediaMetadata::default(),
+            "hash".to_string(),
+        );
+        let media = apply_events(&state, media).await.unwrap();
+        let found = state.quick_search.search("photo", 5);
+        assert_eq!(found[0].kind, QuickSearchKind::Media);
+        assert_eq!(found[0].target, "/api/uploads/1-abc.png");
+
+        apply_events(&state, state.db.delete_media(&media.id)).await.unwrap();
+        assert!(labels(&state, "team").is_empty());
+        assert_eq!(state.quick_search.len(), builtin);
+    }
+
+    #[tokio::test]
+    async fn test_profile_change_and_missed_events_rebuild() {
+        let state = state().await;
+        for (indexed, (title, event)) in [
+            ("Written elsewhere", AppEvent::ProfileChanged { profile: "client-a".to_string() }),
+            ("Written meanwhile", AppEvent::EventsMissed { count: 3 }),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let data = CreatePresentation {
+                title: title.to_string(),
+                content: None,
+                theme: None,
+                if_not_exists: false,
+                on_conflict: None,
+            };
+            state.db.create_presentation(data).await.unwrap();
+            assert_eq!(labels(&state, "written").len(), indexed);
+
+            apply(&state, &event).await.unwrap();
+            assert_eq!(labels(&state, "written").len(), indexed + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_safe_mode_offers_builtin_themes_only() {
+        let state = AppState { safe_mode: true, ..state().await };
+        let data = CreateTheme {
+            name: "zephyr-glow".to_string(),
+            display_name: "Zephyr Glow".to_string(),
+            css_content: "[data-theme=\"zephyr-glow\"] {}".to_string(),
+            center_content: None,
+            fonts: Default::default(),
+        };
+        apply_events(&state, state.db.create_theme(data, NameConflict::Error)).await.unwrap();
+        assert!(labels(&state, "zeph").is_empty());
+
+        rebuild(&state).await.unwrap();
+        assert!(labels(&state, "zeph").is_empty());
+        assert!(labels(&state, "dark").contains(&(QuickSearchKind::Theme, "Dark Mode".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_multi_word_queries_use_full_text_search() {
+        let state = state().await;
+        let data = CreatePresentation {
+            title: "Roadmap".to_string(),
+            content: Some("# Plans\n\nshipping the mobile app".to_string()),
+            theme: None,
+            if_not_exists: false,
+            on_conflict: None,
+        };
+        apply_events(&state, state.db.create_presentation(data)).await.unwrap();
+
+        // Body text isn't in the index, but full-text search finds it
+        assert!(search(&state, "mobile", None).await.unwrap().is_empty());
+        let found = search(&state, "mobile app", None).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].label, "Roadmap");
+        assert!(search(&state, "  ", None).await.is_err());
+    }
+}
@@ -0,0 +1,247 @@
+// Related presentations. Each deck is reduced to its most distinguishing terms (tf-idf over
+// the workspace) and other decks are ranked by how much of that weight they share. The index
+// is cached and rebuilt when the presentations change: by the background refresh, or by the
+// first request that finds it stale.
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{Presentation, RelatedPresentation};
+use crate::{AppState, SharedState};
+
+pub const DEFAULT_LIMIT: usize = 5;
+pub const MAX_LIMIT: usize = 50;
+
+// Terms kept per deck; the rest of a deck's vocabulary doesn't take part in matching
+const TOP_TERMS: usize = 30;
+const MAX_MATCHED_TERMS: usize = 10;
+const MIN_TERM_LEN: usize = 3;
+// Title words say more about what a deck is about than body text
+const TITLE_WEIGHT: usize = 3;
+
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "but", "can",
+    "could", "did", "does", "each", "for", "from", "had", "has", "have", "her", "here", "his", "how",
+    "into", "its", "just", "like", "more", "most", "not", "now", "only", "other", "our", "out", "over",
+    "should", "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "those", "through", "too", "use", "using", "very", "was", "way", "were", "what", "when",
+    "where", "which", "while", "who", "why", "will", "with", "would", "you", "your",
+    // Markdown, Marp directives and links
+    "http", "https", "www", "com", "png", "jpg", "svg", "marp", "true", "false", "theme", "paginate",
+    "class", "style", "img", "src", "alt", "api", "uploads",
+];
+
+/// Distinguishing terms of every deck in the workspace.
+pub struct RelatedIndex {
+    docs: Vec<Doc>,
+    by_id: HashMap<String, usize>,
+}
+
+struct Doc {
+    id: String,
+    title: String,
+    archived: bool,
+    /// Top terms with unit-length tf-idf weights
+    terms: HashMap<String, f64>,
+}
+
+impl RelatedIndex {
+    pub fn build(presentations: &[Presentation]) -> Self {
+        let counts: Vec<HashMap<String, usize>> = presentations.iter().map(term_counts).collect();
+
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        for terms in &counts {
+            for term in terms.keys() {
+                *document_frequency.entry(term.as_str()).or_default() += 1;
+            }
+        }
+
+        // Smoothed so terms shared by every deck still count, just for less
+        let total = presentations.len() as f64;
+        let idf = |term: &str| (1.0 + total / document_frequency[term] as f64).ln();
+
+        let docs: Vec<Doc> = presentations
+            .iter()
+            .zip(&counts)
+            .map(|(presentation, terms)| {
+                let length = terms.values().sum::<usize>().max(1) as f64;
+                let mut weighted: Vec<(String, f64)> = terms
+                    .iter()
+                    .map(|(term, &count)| (term.clone(), count as f64 / length * idf(term)))
+                    .collect();
+                weighted.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                weighted.truncate(TOP_TERMS);
+
+                let norm = weighted.iter().map(|(_, w)| w * w).sum::<f64>().sqrt();
+                Doc {
+                    id: presentation.id.clone(),
+                    title: presentation.title.clone(),
+                    archived: presentation.archived,
+                    terms: weighted.into_iter().map(|(term, w)| (term, w / norm)).collect(),
+                }
+            })
+            .collect();
+
+        let by_id = docs.iter().enumerate().map(|(i, doc)| (doc.id.clone(), i)).collect();
+        Self { docs, by_id }
+    }
+
+    /// Up to `limit` unarchived decks sharing terms with `id`, best match first. None if the
+    /// deck isn't in the index.
+    pub fn related(&self, id: &str, limit: usize) -> Option<Vec<RelatedPresentation>> {
+        let target = &self.docs[*self.by_id.get(id)?];
+
+        let mut related: Vec<RelatedPresentation> = self
+            .docs
+            .iter()
+            .filter(|doc| doc.id != target.id && !doc.archived)
+            .filter_map(|doc| {
+                let mut shared: Vec<(&String, f64)> = target
+                    .terms
+                    .iter()
+                    .filter_map(|(term, w)| doc.terms.get(term).map(|other| (term, w * other)))
+                    .collect();
+                if shared.is_empty() {
+                    return None;
+                }
+                shared.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+                Some(RelatedPresentation {
+                    id: doc.id.clone(),
+                    title: doc.title.clone(),
+                    score: shared.iter().map(|(_, w)| w).sum(),
+                    matched_terms: shared
+                        .into_iter()
+                        .take(MAX_MATCHED_TERMS)
+                        .map(|(term, _)| term.clone())
+                        .collect(),
+                })
+            })
+            .collect();
+
+        related.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+        related.truncate(limit);
+        Some(related)
+    }
+}
+
+/// The index of the active profile, with the presentations fingerprint it was built from.
+#[derive(Default)]
+pub struct RelatedCache(RwLock<Option<(String, Arc<RelatedIndex>)>>);
+
+/// Decks related to presentation `id`, most related first.
+pub async fn find(state: &AppState, id: &str, limit: Option<usize>) -> AppResult<Vec<RelatedPresentation>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    index(state)
+        .await?
+        .related(id, limit)
+        .ok_or_else(|| AppError::NotFound(format!("Presentation {} not found", id)))
+}
+
+/// Rebuilds the cached index if the presentations changed since it was built. Run
+/// periodically by the background task so requests rarely have to.
+pub async fn refresh(state: &SharedState) -> AppResult<()> {
+    let state = state.read().await;
+    index(&state).await.map(|_| ())
+}
+
+async fn index(state: &AppState) -> AppResult<Arc<RelatedIndex>> {
+    // The profile is part of the key so switching profiles never serves the old workspace
+    let fingerprint = format!("{}:{}", state.profile, state.db.presentations_fingerprint().await?);
+    if let Some((cached, index)) = state.related.0.read().unwrap().as_ref() {
+        if *cached == fingerprint {
+            return Ok(index.clone());
+        }
+    }
+
+    let presentations = state.db.list_all_presentations().await?;
+    let index = Arc::new(tokio::task::spawn_blocking(move || RelatedIndex::build(&presentations))
+        .await
+        .map_err(|e| AppError::Internal(format!("Indexing task failed: {}", e)))?);
+    *state.related.0.write().unwrap() = Some((fingerprint, index.clone()));
+    Ok(index)
+}
+
+fn term_counts(presentation: &Presentation) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for term in terms(&presentation.title) {
+        *counts.entry(term).or_default() += TITLE_WEIGHT;
+    }
+    for term in terms(&presentation.content) {
+        *counts.entry(term).or_default() += 1;
+    }
+    counts
+}
+
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    let stop_words: HashSet<&str> = STOP_WORDS.iter().copied().collect();
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= MIN_TERM_LEN && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .filter(move |word| !stop_words.contains(word.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreatePresentation, UpdatePresentation};
+
+    async fn state() -> AppState {
+        AppState::for_tests().await
+    }
+
+    async fn create(state: &AppState, title: &str, content: &str) -> String {
+        let data = CreatePresentation {
+            title: title.to_string(),
+            content: Some(content.to_string()),
+            theme: None,
+            if_not_exists: false,
+            on_conflict: None,
+        };
+        state.db.create_presentation(data).await.unwrap().id
+    }
+
+    fn titles(related: &[RelatedPresentation]) -> Vec<&str> {
+        related.iter().map(|r| r.title.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_related_ranking() {
+        let state = state().await;
+        let rust = create(&state, "Async Rust", "# Futures\n\n- tokio runtime\n- async await\n- executors").await;
+        create(&state, "Async Tokio in Production", "# Runtime tuning\n\n- tokio workers\n- async tasks\n- tracing").await;
+        create(&state, "Rust for Beginners", "# Ownership\n\n- borrow checker\n- lifetimes\n- cargo").await;
+        let archived = create(&state, "Async Rust Deep Dive", "# Futures\n\n- tokio runtime\n- async await").await;
+        create(&state, "Gardening", "# Tomatoes\n\n- soil\n- watering\n- sunlight").await;
+        state.db.set_archived(&archived, true).await.unwrap();
+
+        let related = find(&state, &rust, None).await.unwrap();
+        assert_eq!(titles(&related), ["Async Tokio in Production", "Rust for Beginners"]);
+        assert!(related[0].score > related[1].score);
+        assert_eq!(related[0].matched_terms[..2], ["async", "tokio"]);
+        assert_eq!(related[1].matched_terms, ["rust"]);
+
+        assert_eq!(find(&state, &rust, Some(1)).await.unwrap().len(), 1);
+        assert!(matches!(find(&state, "missing", None).await, Err(AppError::NotFound(_))));
+
+        // Edits show up without waiting for the background refresh
+        state.db.set_archived(&archived, false).await.unwrap();
+        let gardening = create(&state, "Gardening with Rust", "# Tomatoes\n\n- borrow checker for plants").await;
+        let related = find(&state, &rust, Some(10)).await.unwrap();
+        assert_eq!(related[0].title, "Async Rust Deep Dive");
+        assert!(titles(&related).contains(&"Gardening with Rust"));
+
+        state
+            .db
+            .update_presentation(&gardening, UpdatePresentation {
+                title: Some("Gardening".to_string()),
+                content: Some("# Tomatoes\n\n- soil".to_string()),
+                theme: None,
+                settings: None,
+            })
+            .await
+            .unwrap();
+        let related = find(&state, &rust, Some(10)).await.unwrap();
+        assert!(!related.iter().any(|r| r.id == gardening));
+    }
+}
@@ -0,0 +1,183 @@
+// Find and replace across decks, for renames that touch many presentations. The search is
+// compiled to a `Regex` once (escaped unless the caller asked for a regular expression) and
+// run slide by slide, so matches can be reported per slide. Fenced code blocks are skipped
+// unless the caller opts in, since code samples usually mean exactly what they say.
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{ReplaceRequest, SlideMatches};
+use crate::slides;
+
+/// Snippets reported per slide; the match count covers the rest.
+pub const MAX_SNIPPETS: usize = 5;
+// Characters of the line kept on each side of a match in a snippet
+const SNIPPET_CONTEXT: usize = 40;
+
+pub struct Replacer {
+    pattern: Regex,
+    replacement: String,
+    /// Whether the replacement may refer to capture groups
+    expand: bool,
+    include_code: bool,
+}
+
+impl Replacer {
+    /// Compiles the request's search; an invalid regular expression is a BadRequest.
+    pub fn new(request: &ReplaceRequest) -> AppResult<Self> {
+        if request.search.is_empty() {
+            return Err(AppError::BadRequest("search must not be empty".to_string()));
+        }
+        let pattern = if request.regex {
+            Regex::new(&request.search).map_err(|e| AppError::BadRequest(format!("Invalid regex: {}", e)))?
+        } else {
+            Regex::new(&regex::escape(&request.search)).map_err(|e| AppError::Internal(e.to_string()))?
+        };
+
+        Ok(Self {
+            pattern,
+            replacement: request.replace.clone(),
+            expand: request.regex,
+            include_code: request.include_code,
+        })
+    }
+
+    /// `content` with every match replaced, and the matches in each slide that has any.
+    /// Empty matches are ignored, so a pattern like `x*` doesn't insert between every
+    /// character.
+    pub fn apply(&self, content: &str) -> (String, Vec<SlideMatches>) {
+        let mut deck = slides::split_slides(content);
+        let mut found = Vec::new();
+
+        for (index, slide) in deck.iter_mut().enumerate() {
+            let code = if self.include_code {
+                Vec::new()
+            } else {
+                slides::code_block_ranges(slide)
+            };
+
+            let mut replaced = String::with_capacity(slide.len());
+            let mut last = 0;
+            let mut matches = 0;
+            let mut snippets = Vec::new();
+            for captures in self.pattern.captures_iter(slide) {
+                let Some(m) = captures.get(0) else { continue };
+                if m.is_empty() || code.iter().any(|block| m.start() < block.end && block.start < m.end()) {
+                    continue;
+                }
+
+                replaced.push_str(&slide[last..m.start()]);
+                if self.expand {
+                    captures.expand(&self.replacement, &mut replaced);
+                } else {
+                    replaced.push_str(&self.replacement);
+                }
+                last = m.end();
+
+                matches += 1;
+                if snippets.len() < MAX_SNIPPETS {
+                    snippets.push(snippet(slide, m.range()));
+                }
+            }
+
+            if matches > 0 {
+                replaced.push_str(&slide[last..]);
+                *slide = replaced;
+                found.push(SlideMatches { index, matches, snippets });
+            }
+        }
+
+        (slides::join_slides(&deck), found)
+    }
+}
+
+/// The line around `range`, cut to `SNIPPET_CONTEXT` characters on either side.
+fn snippet(text: &str, range: Range<usize>) -> String {
+    let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[range.end..].find('\n').map_or(text.len(), |i| range.end + i);
+
+    let before = &text[line_start..range.start];
+    let before = match before.char_indices().rev().nth(SNIPPET_CONTEXT - 1) {
+        Some((i, _)) if i > 0 => format!("…{}", &before[i..]),
+        _ => before.to_string(),
+    };
+    let after = &text[range.end..line_end];
+    let after = match after.char_indices().nth(SNIPPET_CONTEXT) {
+        Some((i, _)) => format!("{}…", &after[..i]),
+        None => after.to_string(),
+    };
+
+    format!("{}{}{}", before.trim_start(), &text[range], after.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(search: &str, replace: &str, regex: bool, include_code: bool) -> ReplaceRequest {
+        ReplaceRequest {
+            search: search.to_string(),
+            replace: replace.to_string(),
+            ids: None,
+            regex,
+            dry_run: false,
+            include_code,
+        }
+    }
+
+    const DECK: &str = "# Acme Cloud\n\nAcme Cloud (a.k.a. acme) scales.\n\n---\n\n```sh\nacme-cli --cloud Acme Cloud\n```\n\n---\n\n# Pricing";
+
+    #[test]
+    fn test_literal_replace_skips_code() {
+        let replacer = Replacer::new(&request("Acme Cloud", "Nimbus $1", false, false)).unwrap();
+        let (content, found) = replacer.apply(DECK);
+
+        // Literal replacements don't expand `$1`, and the code block is untouched
+        assert!(content.starts_with("# Nimbus $1\n\nNimbus $1 (a.k.a. acme) scales."));
+        assert!(content.contains("acme-cli --cloud Acme Cloud"));
+        assert_eq!(found, vec![SlideMatches {
+            index: 0,
+            matches: 2,
+            snippets: vec!["# Acme Cloud".to_string(), "Acme Cloud (a.k.a. acme) scales.".to_string()],
+        }]);
+
+        let replacer = Replacer::new(&request("Acme Cloud", "Nimbus", false, true)).unwrap();
+        let (content, found) = replacer.apply(DECK);
+        assert!(content.contains("acme-cli --cloud Nimbus"));
+        assert_eq!(found.iter().map(|s| (s.index, s.matches)).collect::<Vec<_>>(), vec![(0, 2), (1, 1)]);
+
+        // Nothing matched, nothing changed
+        let (content, found) = replacer.apply("# Pricing");
+        assert_eq!((content.as_str(), found.len()), ("# Pricing", 0));
+    }
+
+    #[test]
+    fn test_regex_replace() {
+        let replacer = Replacer::new(&request(r"(?i)\bacme\b(?: cloud)?", "Nimbus", true, false)).unwrap();
+        let (content, found) = replacer.apply(DECK);
+        assert!(content.starts_with("# Nimbus\n\nNimbus (a.k.a. Nimbus) scales."));
+        assert_eq!(found[0].matches, 3);
+
+        let replacer = Replacer::new(&request(r"v(\d+)", "version $1", true, false)).unwrap();
+        assert_eq!(replacer.apply("v2 and v10").0, "version 2 and version 10");
+
+        // Empty matches are skipped
+        let replacer = Replacer::new(&request("x*", "y", true, false)).unwrap();
+        assert_eq!(replacer.apply("abc").0, "abc");
+
+        let err = Replacer::new(&request("(unclosed", "", true, false)).err().unwrap();
+        assert!(matches!(err, AppError::BadRequest(msg) if msg.starts_with("Invalid regex")));
+        // Without `regex` the same text is searched literally
+        assert!(Replacer::new(&request("(unclosed", "", false, false)).is_ok());
+    }
+
+    #[test]
+    fn test_snippets_are_cut_to_the_line() {
+        let line = format!("{}needle{}", "a".repeat(60), "b".repeat(60));
+        let text = format!("first\n{}\nlast", line);
+        let start = text.find("needle").unwrap();
+        let snippet = snippet(&text, start..start + 6);
+        assert_eq!(snippet, format!("…{}needle{}…", "a".repeat(40), "b".repeat(40)));
+    }
+}
@@ -0,0 +1,182 @@
+// Safe mode: a startup flag for recovering from a broken customization. While it is on, only
+// the themes and layout rules seeded with the app are served; custom ones (those with a
+// `user_id`) are left untouched in the database and come back on the next normal start.
+use crate::error::{AppError, AppResult};
+use crate::models::{LayoutRuleResponse, Theme};
+
+/// Command-line flag that starts the app in safe mode.
+pub const ARG: &str = "--safe-mode";
+/// Environment variable that starts the app in safe mode when set to `1`, `true` or `yes`.
+pub const ENV_VAR: &str = "SLIDES_SAFE_MODE";
+
+/// Whether safe mode was requested through the environment or the command line.
+pub fn requested() -> bool {
+    let from_env = std::env::var(ENV_VAR)
+        .map(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false);
+    from_env || std::env::args().skip(1).any(|arg| arg == ARG)
+}
+
+/// Whether a theme is still served in safe mode, that is, whether it shipped with the app.
+pub fn serves_theme(theme: &Theme) -> bool {
+    theme.user_id.is_none()
+}
+
+/// Built-in themes only, or with `include_disabled` every theme with the custom ones marked.
+pub fn themes(themes: Vec<Theme>, include_disabled: bool) -> Vec<Theme> {
+    themes
+        .into_iter()
+        .filter(|theme| include_disabled || serves_theme(theme))
+        .map(|theme| Theme {
+            disabled_by_safe_mode: !serves_theme(&theme),
+            ..theme
+        })
+        .collect()
+}
+
+/// A single theme as `themes` would list it: a custom theme is NotFound unless
+/// `include_disabled` is set.
+pub fn theme(theme: Theme, include_disabled: bool) -> AppResult<Theme> {
+    match self::themes(vec![theme], include_disabled).pop() {
+        Some(theme) => Ok(theme),
+        None => Err(AppError::NotFound("Theme is disabled in safe mode".to_string())),
+    }
+}
+
+/// Built-in layout rules only, or with `include_disabled` every rule with the custom ones marked.
+pub fn layout_rules(rules: Vec<LayoutRuleResponse>, include_disabled: bool) -> Vec<LayoutRuleResponse> {
+    rules
+        .into_iter()
+        .filter(|rule| include_disabled || rule.user_id.is_none())
+        .map(|rule| LayoutRuleResponse {
+            disabled_by_safe_mode: rule.user_id.is_some(),
+            ..rule
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::Database,
+        models::{CreateTheme, NameConflict},
+        AppState, SharedState,
+    };
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    fn state(db: Database) -> SharedState {
+        Arc::new(RwLock::new(AppState {
+            safe_mode: true,
+            ..AppState::for_tests_with(db)
+        }))
+    }
+
+    async fn send(router: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    fn names(list: &Value) -> Vec<(&str, bool)> {
+        list.as_array()
+            .unwrap()
+            .iter()
+            .filter(|item| !item["userId"].is_null())
+            .map(|item| (item["name"].as_str().unwrap(), item["disabledBySafeMode"].as_bool().unwrap()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_safe_mode_serves_defaults_only() {
+        let db = Database::new_with_url("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        let custom = db
+            .create_theme(
+                CreateTheme {
+                    name: "neon".to_string(),
+                    display_name: "Neon".to_string(),
+                    css_content: "[data-theme=\"neon\"] section { color: lime; }".to_string(),
+                    center_content: None,
+                    fonts: Default::default(),
+                },
+                NameConflict::Error,
+            )
+            .await
+            .unwrap();
+        db.create_layout_rule(
+            "wide".to_string(),
+            "Wide".to_string(),
+            None,
+            5,
+            "{}".to_string(),
+            "{}".to_string(),
+            String::new(),
+        )
+        .await
+        .unwrap();
+
+        let state = state(db);
+        let safe = crate::api::create_router(state.clone());
+        let (_, themes) = send(&safe, Method::GET, "/themes", None).await;
+        assert!(names(&themes).is_empty());
+        assert!(themes.as_array().unwrap().iter().all(|t| t["disabledBySafeMode"] == false));
+        let (_, themes) = send(&safe, Method::GET, "/themes?includeDisabledBySafeMode=true", None).await;
+        assert_eq!(names(&themes), vec![("neon", true)]);
+
+        // Nor is a custom theme served on its own
+        for uri in [format!("/themes/{}", custom.id), "/themes/neon".to_string(), "/themes/neon/export".to_string()] {
+            let (status, _) = send(&safe, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+        let (_, neon) = send(&safe, Method::GET, "/themes/neon?includeDisabledBySafeMode=true", None).await;
+        assert_eq!(neon["disabledBySafeMode"], true);
+        let (status, _) = send(&safe, Method::GET, "/themes/dark", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, rules) = send(&safe, Method::GET, "/layout-rules", None).await;
+        assert!(names(&rules).is_empty());
+        let (_, rules) = send(&safe, Method::GET, "/layout-rules?includeDisabledBySafeMode=true", None).await;
+        assert_eq!(names(&rules), vec![("wide", true)]);
+
+        let (_, health) = send(&safe, Method::GET, "/health", None).await;
+        assert_eq!(health["safeMode"], true);
+
+        // Edits made in safe mode are kept for the next normal start
+        let (status, _) = send(
+            &safe,
+            Method::PUT,
+            &format!("/themes/{}", custom.id),
+            Some(json!({ "displayName": "Neon Nights" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Restart without the flag
+        state.write().await.safe_mode = false;
+        let normal = crate::api::create_router(state);
+        let (_, themes) = send(&normal, Method::GET, "/themes", None).await;
+        let neon = themes.as_array().unwrap().iter().find(|t| t["name"] == "neon").unwrap();
+        assert_eq!(neon["displayName"], "Neon Nights");
+        assert_eq!(neon["disabledBySafeMode"], false);
+        let (_, rules) = send(&normal, Method::GET, "/layout-rules", None).await;
+        assert_eq!(names(&rules), vec![("wide", false)]);
+        let (_, health) = send(&normal, Method::GET, "/health", None).await;
+        assert_eq!(health["safeMode"], false);
+    }
+}
@@ -0,0 +1,487 @@
+// Slide-level editing of presentation markdown. Slides are separated by lines containing
+// only `---`; separators inside fenced code blocks belong to the code.
+use std::ops::Range;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{PresentationStats, SlideDetail, SlideItem};
+
+const SEPARATOR: &str = "---";
+pub const NOTES_START: &str = "<!-- notes -->";
+pub const NOTES_END: &str = "<!-- /notes -->";
+// Speaking pace assumed for reading-time estimates
+const WORDS_PER_MINUTE: f32 = 130.0;
+
+/// Splits presentation content into slides. Each slide keeps its text exactly as written
+/// (including surrounding blank lines), so `join_slides(split_slides(c))` round-trips.
+pub fn split_slides(content: &str) -> Vec<String> {
+    let mut slides = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in content.split('\n') {
+        let trimmed = line.trim();
+
+        match fence {
+            Some((marker, len)) => {
+                if is_closing_fence(trimmed, marker, len) {
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some(opening) = opening_fence(line) {
+                    fence = Some(opening);
+                } else if trimmed == SEPARATOR {
+                    slides.push(current.join("\n"));
+                    current.clear();
+                    continue;
+                }
+            }
+        }
+
+        current.push(line);
+    }
+
+    slides.push(current.join("\n"));
+    slides
+}
+
+pub fn slide_items(content: &str) -> Vec<SlideItem> {
+    split_slides(content)
+        .into_iter()
+        .enumerate()
+        .map(|(index, slide)| SlideItem {
+            index,
+            notes: speaker_notes(&slide),
+            content: slide.trim_matches('\n').to_string(),
+        })
+        .collect()
+}
+
+/// Slide `index` of the deck, or BadRequest naming the valid indices.
+pub fn get_slide(content: &str, index: usize) -> AppResult<SlideDetail> {
+    let mut items = slide_items(content);
+    let total = items.len();
+    check_index(index, total, total)?;
+    Ok(SlideDetail { slide: items.swap_remove(index), total })
+}
+
+/// Text of a slide's `<!-- notes -->` block, if it has a non-empty one.
+pub fn speaker_notes(slide: &str) -> Option<String> {
+    let start = slide.find(NOTES_START)? + NOTES_START.len();
+    let end = slide[start..].find(NOTES_END).map_or(slide.len(), |end| start + end);
+    let notes = slide[start..end].trim();
+    (!notes.is_empty()).then(|| notes.to_string())
+}
+
+/// Text of a slide's first markdown heading, if it has a non-empty one.
+pub fn heading(slide: &str) -> Option<String> {
+    slide
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .filter(|heading| !heading.is_empty())
+}
+
+pub fn join_slides(slides: &[String]) -> String {
+    slides.join(&format!("\n{}\n", SEPARATOR))
+}
+
+pub fn slide_count(content: &str) -> usize {
+    split_slides(content).len()
+}
+
+/// Summary figures stored alongside each presentation so listings don't have to parse content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeckStats {
+    pub slide_count: i64,
+    pub word_count: i64,
+    pub has_speaker_notes: bool,
+}
+
+/// Counts slides, words outside speaker notes (see `word_count`), and whether any slide has
+/// notes.
+pub fn deck_stats(content: &str) -> DeckStats {
+    let slides = split_slides(content);
+    let word_count = slides.iter().map(|slide| word_count(&strip_notes(slide))).sum::<usize>();
+
+    DeckStats {
+        slide_count: slides.len() as i64,
+        word_count: word_count as i64,
+        has_speaker_notes: slides.iter().any(|slide| speaker_notes(slide).is_some()),
+    }
+}
+
+/// Slide, word, image and code block counts for `content`, with the time it takes to read
+/// out. Speaker notes and other HTML comments (such as layout directives) aren't counted.
+pub fn presentation_stats(content: &str) -> PresentationStats {
+    let slides = split_slides(content);
+    let mut stats = PresentationStats {
+        slide_count: slides.len(),
+        word_count: 0,
+        estimated_reading_minutes: 0.0,
+        image_count: 0,
+        code_block_count: 0,
+    };
+
+    for slide in &slides {
+        let text = strip_comments(&strip_notes(slide));
+        let code = code_block_ranges(&text);
+
+        stats.word_count += word_count(&text);
+        stats.code_block_count += code.len();
+
+        let outside_code = |at: &usize| !code.iter().any(|block| block.contains(at));
+        let lower = text.to_ascii_lowercase();
+        stats.image_count += text.match_indices("![").map(|(at, _)| at).filter(outside_code).count();
+        stats.image_count += lower.match_indices("<img").map(|(at, _)| at).filter(outside_code).count();
+    }
+
+    stats.estimated_reading_minutes = stats.word_count as f32 / WORDS_PER_MINUTE;
+    stats
+}
+
+/// Words a reader sees in `text`: whitespace-separated tokens with at least one letter or
+/// digit, once comments, HTML tags, code fence lines and link targets are dropped. Markdown
+/// markers like `#` or `-` have no letters, so they don't count either.
+pub fn word_count(text: &str) -> usize {
+    strip_comments(text)
+        .lines()
+        .filter(|line| opening_fence(line).is_none())
+        .map(|line| {
+            strip_markup(line)
+                .split_whitespace()
+                .filter(|word| word.chars().any(char::is_alphanumeric))
+                .count()
+        })
+        .sum()
+}
+
+/// `line` without HTML tags and the `(target)` of links and images.
+fn strip_markup(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(at) = rest.find(['<', ']']) {
+        out.push_str(&rest[..at]);
+        let from = &rest[at..];
+        if from.starts_with("](") {
+            if let Some(end) = from.find(')') {
+                out.push(']');
+                rest = &from[end + 1..];
+                continue;
+            }
+        } else if from[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') {
+            if let Some(end) = from.find('>') {
+                // A tag separates the words on either side of it
+                out.push(' ');
+                rest = &from[end + 1..];
+                continue;
+            }
+        }
+        out.push_str(&from[..1]);
+        rest = &from[1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `text` without its `<!-- ... -->` comments. An unclosed comment runs to the end.
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 4..].find("-->").map_or("", |end| &rest[start + 4 + end + 3..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A slide with its `<!-- notes -->` block removed.
+pub fn strip_notes(slide: &str) -> String {
+    match slide.find(NOTES_START) {
+        Some(start) => {
+            let rest = &slide[start + NOTES_START.len()..];
+            let after = rest.find(NOTES_END).map_or("", |end| &rest[end + NOTES_END.len()..]);
+            format!("{}{}", &slide[..start], after)
+        }
+        None => slide.to_string(),
+    }
+}
+
+pub fn replace_slide(content: &str, index: usize, markdown: &str) -> AppResult<String> {
+    let mut slides = split_slides(content);
+    check_index(index, slides.len(), slides.len())?;
+
+    let last = slides.len() - 1;
+    slides[index] = frame(markdown, index == 0, index == last);
+    Ok(join_slides(&slides))
+}
+
+/// Inserts a slide before `index`; an index equal to the slide count appends.
+pub fn insert_slide(content: &str, index: usize, markdown: &str) -> AppResult<String> {
+    let mut slides = split_slides(content);
+    check_index(index, slides.len() + 1, slides.len())?;
+
+    // The slide that becomes the neighbour needs a blank line against the new separator
+    if index == 0 {
+        slides[0] = format!("\n{}", slides[0]);
+    }
+    if index == slides.len() {
+        let last = slides.len() - 1;
+        slides[last] = format!("{}\n", slides[last].trim_end_matches('\n'));
+    }
+
+    let framed = frame(markdown, index == 0, index == slides.len());
+    slides.insert(index, framed);
+    Ok(join_slides(&slides))
+}
+
+pub fn delete_slide(content: &str, index: usize) -> AppResult<String> {
+    let mut slides = split_slides(content);
+    check_index(index, slides.len(), slides.len())?;
+    if slides.len() == 1 {
+        return Err(AppError::BadRequest("Cannot delete the only slide".to_string()));
+    }
+
+    slides.remove(index);
+    if index == 0 {
+        slides[0] = slides[0].trim_start_matches('\n').to_string();
+    }
+    if index == slides.len() {
+        let last = slides.len() - 1;
+        slides[last] = slides[last].trim_end_matches('\n').to_string();
+    }
+    Ok(join_slides(&slides))
+}
+
+/// Concatenates decks in order. Blank slides at the start or end of a deck (an empty deck,
+/// a trailing separator) are dropped, so none appear where the decks meet.
+pub fn merge_decks<'a>(decks: impl IntoIterator<Item = &'a str>) -> String {
+    let mut merged = Vec::new();
+    for deck in decks {
+        let slides = split_slides(deck);
+        let start = slides.iter().position(|slide| !slide.trim().is_empty());
+        let end = slides.iter().rposition(|slide| !slide.trim().is_empty());
+        if let (Some(start), Some(end)) = (start, end) {
+            merged.extend(slides[start..=end].iter().cloned());
+        }
+    }
+
+    let last = merged.len().saturating_sub(1);
+    let framed: Vec<String> = merged
+        .iter()
+        .enumerate()
+        .map(|(i, slide)| frame(slide, i == 0, i == last))
+        .collect();
+    join_slides(&framed)
+}
+
+/// Rearranges slides so that slide `order[i]` ends up at position `i`. `order` must be a
+/// permutation of `0..slide_count`.
+pub fn reorder_slides(content: &str, order: &[usize]) -> AppResult<String> {
+    let slides = split_slides(content);
+    let count = slides.len();
+
+    if order.len() != count {
+        return Err(AppError::BadRequest(format!(
+            "Order lists {} indices but the presentation has {} slides",
+            order.len(),
+            count
+        )));
+    }
+
+    let mut seen = vec![false; count];
+    for &index in order {
+        check_index(index, count, count)?;
+        if std::mem::replace(&mut seen[index], true) {
+            return Err(AppError::BadRequest(format!(
+                "Slide index {} appears more than once; the order must list each of 0..{} exactly once",
+                index, count
+            )));
+        }
+    }
+
+    let last = count - 1;
+    let reordered: Vec<String> = order
+        .iter()
+        .enumerate()
+        .map(|(position, &index)| frame(&slides[index], position == 0, position == last))
+        .collect();
+    Ok(join_slides(&reordered))
+}
+
+/// Rejects indices at or past `limit`, reporting the presentation's slide `count`.
+pub fn check_index(index: usize, limit: usize, count: usize) -> AppResult<()> {
+    if index >= limit {
+        return Err(AppError::BadRequest(format!(
+            "Slide index {} is out of range: the presentation has {} slides (valid indices are 0 to {})",
+            index,
+            count,
+            limit.saturating_sub(1)
+        )));
+    }
+    Ok(())
+}
+
+/// Puts blank lines between the slide and its separators, like `add_slides` does.
+fn frame(markdown: &str, first: bool, last: bool) -> String {
+    let body = markdown.trim_matches('\n');
+    match (first, last) {
+        (true, true) => body.to_string(),
+        (true, false) => format!("{}\n", body),
+        (false, true) => format!("\n{}", body),
+        (false, false) => format!("\n{}\n", body),
+    }
+}
+
+/// Byte ranges of the fenced code blocks in `text`, fence lines included. A fence that is
+/// never closed runs to the end.
+pub fn code_block_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    // Marker, length and start of the open fence
+    let mut fence: Option<(char, usize, usize)> = None;
+    let mut offset = 0;
+
+    for line in text.split('\n') {
+        let end = offset + line.len();
+        match fence {
+            Some((marker, len, start)) => {
+                if is_closing_fence(line.trim(), marker, len) {
+                    ranges.push(start..end);
+                    fence = None;
+                }
+            }
+            None => {
+                if let Some((marker, len)) = opening_fence(line) {
+                    fence = Some((marker, len, offset));
+                }
+            }
+        }
+        offset = end + 1;
+    }
+
+    if let Some((_, _, start)) = fence {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+fn opening_fence(line: &str) -> Option<(char, usize)> {
+    // Fences may be indented by up to three spaces
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+
+    let rest = &line[indent..];
+    let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = rest.chars().take_while(|c| *c == marker).count();
+    (len >= 3).then_some((marker, len))
+}
+
+fn is_closing_fence(trimmed: &str, marker: char, len: usize) -> bool {
+    trimmed.chars().take_while(|c| *c == marker).count() >= len && trimmed.chars().all(|c| c == marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DECK: &str = "# One\n\n---\n\n# Two\n\n```yaml\nkey: value\n---\nother: 1\n```\n\n---\n\n# Three\n";
+
+    #[test]
+    fn test_split_ignores_separators_in_code_fences() {
+        let slides = split_slides(DECK);
+        assert_eq!(slides.len(), 3);
+        assert!(slides[1].contains("key: value\n---\nother: 1"));
+        assert_eq!(join_slides(&slides), DECK);
+
+        let tilde = "a\n~~~~\n---\n~~~\n---\n~~~~\nb";
+        assert_eq!(split_slides(tilde).len(), 1);
+    }
+
+    #[test]
+    fn test_slide_edits() {
+        let replaced = replace_slide(DECK, 2, "# 3").unwrap();
+        assert!(replaced.ends_with("---\n\n# 3"));
+        assert_eq!(slide_count(&replaced), 3);
+
+        let inserted = insert_slide(DECK, 0, "# Zero").unwrap();
+        assert!(inserted.starts_with("# Zero\n\n---\n\n# One"));
+        let appended = insert_slide("# A", 1, "# B").unwrap();
+        assert_eq!(appended, "# A\n\n---\n\n# B");
+
+        let deleted = delete_slide(DECK, 1).unwrap();
+        assert_eq!(deleted, "# One\n\n---\n\n# Three\n");
+        assert_eq!(delete_slide(&deleted, 0).unwrap(), "# Three\n");
+        assert!(delete_slide("# Only", 0).is_err());
+
+        let reordered = reorder_slides(DECK, &[2, 0, 1]).unwrap();
+        let slides = split_slides(&reordered);
+        assert_eq!(slides[0], "# Three\n");
+        assert!(slides[2].contains("key: value\n---\nother: 1"));
+        assert!(reorder_slides(DECK, &[0, 1]).is_err());
+        assert!(reorder_slides(DECK, &[0, 1, 1]).is_err());
+        assert!(reorder_slides(DECK, &[0, 1, 3]).is_err());
+
+        let items = slide_items("# A\n\n<!-- notes -->\nSay hi\n<!-- /notes -->\n\n---\n\n# B");
+        assert_eq!(items[0].notes.as_deref(), Some("Say hi"));
+        assert_eq!(items[1].content, "# B");
+        assert!(items[1].notes.is_none());
+
+        let err = replace_slide(DECK, 3, "x").unwrap_err().to_string();
+        assert!(err.contains("has 3 slides"), "{}", err);
+        let err = insert_slide(DECK, 4, "x").unwrap_err().to_string();
+        assert!(err.contains("has 3 slides"), "{}", err);
+        assert!(err.contains("0 to 3"), "{}", err);
+
+        let slide = get_slide("# A\n\n```yaml\n---\n```\n\n---\n\n# B", 0).unwrap();
+        assert_eq!((slide.slide.index, slide.total), (0, 2));
+        assert_eq!(slide.slide.content, "# A\n\n```yaml\n---\n```");
+        let err = get_slide(DECK, 3).unwrap_err().to_string();
+        assert!(err.contains("valid indices are 0 to 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_merge_decks() {
+        let merged = merge_decks(["# A\n\n---\n\n# B\n\n---\n", "", "\n---\n\n# C"]);
+        assert_eq!(merged, "# A\n\n---\n\n# B\n\n---\n\n# C");
+
+        let merged = merge_decks([DECK, "# Four"]);
+        assert_eq!(slide_count(&merged), 4);
+        assert!(merged.contains("key: value\n---\nother: 1"));
+        assert!(merged.ends_with("# Three\n\n---\n\n# Four"));
+
+        assert_eq!(merge_decks(["", "---"]), "");
+    }
+
+    #[test]
+    fn test_deck_stats() {
+        let stats = deck_stats(DECK);
+        assert_eq!(stats.slide_count, 3);
+        // One, Two, key:, value, other:, 1, Three
+        assert_eq!(stats.word_count, 7);
+        assert!(!stats.has_speaker_notes);
+
+        let stats = deck_stats("# Hello world\n\n<!-- notes -->\nNot counted\n<!-- /notes -->\n\n---\n\n- item");
+        assert_eq!(stats.slide_count, 2);
+        assert_eq!(stats.word_count, 3);
+        assert!(stats.has_speaker_notes);
+    }
+
+    #[test]
+    fn test_presentation_stats() {
+        let deck = "# Team\n\n<!-- columns -->\n![Photo](/api/uploads/team.png)\n<IMG src=\"logo.png\">\n\n\
+                    <!-- notes -->\nLots of words that are not counted\n<!-- /notes -->\n\n---\n\n\
+                    ```md\n![Not an image](x.png)\n```\n\n```sh\nrun it\n```";
+        let stats = presentation_stats(deck);
+        assert_eq!((stats.slide_count, stats.image_count, stats.code_block_count), (2, 2, 2));
+        // Team, ![Photo], ![Not, an, image], run, it
+        assert_eq!(stats.word_count, 7);
+        assert_eq!(stats.estimated_reading_minutes, 7.0 / 130.0);
+        assert_eq!(deck_stats(deck).word_count, 7);
+
+        assert_eq!(strip_comments("a <!-- b --> c <!-- d"), "a  c ");
+    }
+}
@@ -0,0 +1,301 @@
+// Proactive suggestions for the slide being edited. Cheap deterministic checks run first;
+// the AI provider is only asked about things that need judgment, under a short timeout so a
+// slow provider never holds up the editor.
+use serde_json::json;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::ai::{AIProvider, GenerateOptions};
+use crate::error::AppResult;
+use crate::models::{AiSuggestions, Suggestion, SuggestionType};
+use crate::slides;
+
+/// How long the AI provider gets before suggestions are returned without it.
+pub const AI_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_SUGGESTIONS: usize = 5;
+
+// A slide with more bullets or words than this reads better split in two
+const MAX_BULLETS: usize = 6;
+const MAX_WORDS: usize = 120;
+// Word overlap with a neighboring slide above which the two are treated as duplicates
+const DUPLICATE_SIMILARITY: f64 = 0.8;
+// Ordered lists at least this long are candidates for a flow diagram
+const MIN_STEPS: usize = 3;
+
+/// Ranked suggestions for slide `index` of `content`. `provider` (configured under
+/// `provider_name`) is consulted for wording improvements; without it, or if it doesn't
+/// answer within `ai_timeout`, only the deterministic suggestions are returned.
+pub async fn suggest(
+    presentation_id: &str,
+    content: &str,
+    index: usize,
+    provider_name: &str,
+    provider: Option<&dyn AIProvider>,
+    ai_timeout: Duration,
+) -> AppResult<AiSuggestions> {
+    let deck = slides::split_slides(content);
+    slides::check_index(index, deck.len(), deck.len())?;
+    let slide = slides::strip_notes(&deck[index]);
+    let slide_endpoint = format!("/api/presentations/{}/slides/{}", presentation_id, index);
+
+    let mut suggestions = Vec::new();
+
+    let bullets = list_items(&slide).len();
+    let words = slides::word_count(&slide);
+    if bullets > MAX_BULLETS || words > MAX_WORDS {
+        let message = if bullets > MAX_BULLETS {
+            format!("This slide has {} bullets. Want me to split it?", bullets)
+        } else {
+            format!("This slide has {} words. Want me to split it?", words)
+        };
+        suggestions.push(Suggestion {
+            suggestion_type: SuggestionType::SplitSlide,
+            message,
+            action_method: "POST".to_string(),
+            action_endpoint: "/api/ai/improve".to_string(),
+            action_payload: json!({
+                "slideContent": slide.trim(),
+                "provider": provider_name,
+                "instruction": "Split this slide into multiple slides separated by ---",
+            }),
+        });
+    }
+
+    for neighbor in [index.checked_sub(1), Some(index + 1)].into_iter().flatten() {
+        let Some(other) = deck.get(neighbor) else { continue };
+        if similarity(&slide, &slides::strip_notes(other)) >= DUPLICATE_SIMILARITY {
+            suggestions.push(Suggestion {
+                suggestion_type: SuggestionType::DuplicateSlide,
+                message: format!("This slide is nearly the same as slide {}. Delete it?", neighbor + 1),
+                action_method: "DELETE".to_string(),
+                action_endpoint: slide_endpoint.clone(),
+                action_payload: json!({}),
+            });
+            break;
+        }
+    }
+
+    if let Some(table) = numeric_table(&slide) {
+        suggestions.push(Suggestion {
+            suggestion_type: SuggestionType::TableToChart,
+            message: "This table holds numbers. Show it as a chart?".to_string(),
+            action_method: "POST".to_string(),
+            action_endpoint: "/api/ai/generate-diagram".to_string(),
+            action_payload: json!({
+                "description": format!("A chart of this data:\n{}", table),
+                "provider": provider_name,
+            }),
+        });
+    }
+
+    let steps = ordered_steps(&slide);
+    if steps.len() >= MIN_STEPS && !slide.contains("```mermaid") {
+        suggestions.push(Suggestion {
+            suggestion_type: SuggestionType::ListToDiagram,
+            message: format!("These {} steps could be a flow diagram.", steps.len()),
+            action_method: "POST".to_string(),
+            action_endpoint: "/api/ai/generate-diagram".to_string(),
+            action_payload: json!({
+                "description": format!("A flowchart of these steps: {}", steps.join(" -> ")),
+                "provider": provider_name,
+            }),
+        });
+    }
+
+    let mut ai_included = false;
+    if let Some(provider) = provider.filter(|_| words > 0) {
+        match tokio::time::timeout(ai_timeout, review_wording(provider, &slide)).await {
+            Ok(Ok(Some(advice))) => {
+                ai_included = true;
+                suggestions.push(Suggestion {
+                    suggestion_type: SuggestionType::Improve,
+                    message: advice.clone(),
+                    action_method: "POST".to_string(),
+                    action_endpoint: "/api/ai/improve".to_string(),
+                    action_payload: json!({
+                        "slideContent": slide.trim(),
+                        "provider": provider_name,
+                        "instruction": advice,
+                    }),
+                });
+            }
+            Ok(Ok(None)) => ai_included = true,
+            Ok(Err(e)) => tracing::warn!("AI suggestions failed, returning deterministic ones: {}", e),
+            Err(_) => tracing::warn!("AI suggestions timed out after {:?}", ai_timeout),
+        }
+    }
+
+    suggestions.sort_by_key(|s| std::cmp::Reverse(s.suggestion_type.rank()));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    Ok(AiSuggestions { suggestions, ai_included })
+}
+
+/// One sentence on how the slide's wording could be clearer, or None if it's fine.
+async fn review_wording(provider: &dyn AIProvider, slide: &str) -> AppResult<Option<String>> {
+    let prompt = format!(
+        "Review this presentation slide. If its wording or structure could clearly be improved, \
+        reply with one short sentence saying how. Otherwise reply NONE.\n\n{}",
+        slide.trim()
+    );
+    let reply = provider
+        .generate_content(&prompt, GenerateOptions {
+            system_prompt: Some("You are a presentation design expert. Be brief.".to_string()),
+            max_tokens: Some(100),
+            ..Default::default()
+        })
+        .await?;
+
+    let reply = reply.trim();
+    Ok((!reply.is_empty() && !reply.eq_ignore_ascii_case("none")).then(|| reply.to_string()))
+}
+
+/// Text of the slide's `-`, `*`, `+` and numbered list items.
+fn list_items(slide: &str) -> Vec<&str> {
+    slide
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            line.strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .or_else(|| line.strip_prefix("+ "))
+                .or_else(|| ordered_item(line))
+        })
+        .collect()
+}
+
+/// Items of the slide's numbered list, in order.
+fn ordered_steps(slide: &str) -> Vec<&str> {
+    slide.lines().filter_map(|line| ordered_item(line.trim_start())).map(str::trim).collect()
+}
+
+fn ordered_item(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") "))
+}
+
+/// Jaccard similarity of the two slides' lowercase word sets.
+fn similarity(a: &str, b: &str) -> f64 {
+    let words = |text: &str| -> HashSet<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+/// The slide's first markdown table, if at least two of its body rows contain a number.
+fn numeric_table(slide: &str) -> Option<String> {
+    let rows: Vec<&str> = slide
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with('|'))
+        .take_while(|line| line.starts_with('|'))
+        .collect();
+    if rows.len() < 3 {
+        return None;
+    }
+
+    let numeric_rows = rows[2..]
+        .iter()
+        .filter(|row| {
+            row.split('|')
+                .map(|cell| cell.trim().trim_end_matches('%').replace(',', ""))
+                .any(|cell| !cell.is_empty() && cell.parse::<f64>().is_ok())
+        })
+        .count();
+    (numeric_rows >= 2).then(|| rows.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{ConversationMessage, ModelInfo};
+    use async_trait::async_trait;
+
+    struct MockProvider {
+        delay: Duration,
+        reply: &'static str,
+    }
+
+    #[async_trait]
+    impl AIProvider for MockProvider {
+        fn default_model(&self) -> &str {
+            "mock"
+        }
+
+        async fn generate_content(&self, _prompt: &str, _options: GenerateOptions) -> AppResult<String> {
+            tokio::time::sleep(self.delay).await;
+            Ok(self.reply.to_string())
+        }
+
+        async fn generate_conversation(&self, _messages: &[ConversationMessage], options: GenerateOptions) -> AppResult<String> {
+            self.generate_content("", options).await
+        }
+
+        async fn list_models(&self) -> AppResult<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+    }
+
+    const DECK: &str = "# Results\n\n| Region | Sales |\n|---|---|\n| North | 120 |\n| South | 95 |\n\n\
+                        1. Collect\n2. Clean\n3. Report\n\n- a\n- b\n- c\n- d\n- e\n- f\n- g\n\n---\n\n# Thanks";
+
+    fn types(result: &AiSuggestions) -> Vec<SuggestionType> {
+        result.suggestions.iter().map(|s| s.suggestion_type).collect()
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_suggestions() {
+        let result = suggest("p1", DECK, 0, "anthropic", None, AI_TIMEOUT).await.unwrap();
+        assert_eq!(
+            types(&result),
+            vec![SuggestionType::SplitSlide, SuggestionType::TableToChart, SuggestionType::ListToDiagram]
+        );
+        assert!(result.suggestions[0].message.contains("10 bullets"));
+        assert!(!result.ai_included);
+
+        let duplicated = "# Agenda\n\n- Intro\n- Demo\n\n---\n\n# Agenda\n\n- Intro\n- Demo\n";
+        let result = suggest("p1", duplicated, 1, "anthropic", None, AI_TIMEOUT).await.unwrap();
+        assert_eq!(types(&result), vec![SuggestionType::DuplicateSlide]);
+        assert_eq!(result.suggestions[0].action_endpoint, "/api/presentations/p1/slides/1");
+
+        assert!(suggest("p1", DECK, 5, "anthropic", None, AI_TIMEOUT).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ai_suggestion_is_added_when_provider_answers() {
+        let provider = MockProvider {
+            delay: Duration::ZERO,
+            reply: "Lead with the key number.",
+        };
+        let result = suggest("p1", "# Thanks\n\nQuestions?", 0, "anthropic", Some(&provider), AI_TIMEOUT)
+            .await
+            .unwrap();
+        assert!(result.ai_included);
+        assert_eq!(types(&result), vec![SuggestionType::Improve]);
+        assert_eq!(result.suggestions[0].action_payload["instruction"], "Lead with the key number.");
+    }
+
+    #[tokio::test]
+    async fn test_slow_provider_degrades_to_deterministic() {
+        let provider = MockProvider {
+            delay: Duration::from_secs(30),
+            reply: "Too late",
+        };
+        let started = std::time::Instant::now();
+        let result = suggest("p1", DECK, 0, "anthropic", Some(&provider), Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!result.ai_included);
+        assert_eq!(result.suggestions.len(), 3);
+    }
+}
@@ -0,0 +1,337 @@
+// Checks on custom theme CSS before it's stored, since a broken stylesheet breaks every slide
+// rendered with the theme. This is a tokenizer, not a CSS parser: it tracks comments,
+// strings and blocks well enough to find unbalanced braces, rules that would escape the
+// theme's scope, and things that load or run code from elsewhere.
+use std::fmt;
+
+/// Something wrong with a stylesheet, on the line where the statement starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CssProblem {
+    /// 1-based; None for problems with the stylesheet as a whole
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for CssProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+// At-rules whose blocks hold rules rather than declarations
+const GROUPING_AT_RULES: [&str; 6] = ["@media", "@supports", "@layer", "@container", "@document", "@scope"];
+
+enum Block {
+    /// Top level, or the inside of a grouping at-rule
+    Rules { scoped: bool },
+    /// A style rule's declarations; `scoped` when every selector is inside the theme
+    Declarations { scoped: bool },
+    /// Blocks such as `@font-face` and `@keyframes`, which aren't checked further
+    Other,
+}
+
+/// Problems with `css` as the stylesheet of theme `theme`, in the order they appear. Empty
+/// means the CSS can be stored.
+pub fn validate(css: &str, theme: &str) -> Vec<CssProblem> {
+    let mut checker = Checker {
+        theme,
+        problems: Vec::new(),
+        blocks: vec![(0, Block::Rules { scoped: false })],
+        text: String::new(),
+        text_line: None,
+        found_scoped: false,
+    };
+
+    let mut line = 1;
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\n' => line += 1,
+                        '*' if chars.peek() == Some(&'/') => {
+                            chars.next();
+                            closed = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                if !closed {
+                    checker.problem(start, "comment is never closed");
+                }
+                // A comment separates tokens like whitespace does
+                checker.text.push(' ');
+            }
+            '"' | '\'' => {
+                checker.push(c, line);
+                while let Some(s) = chars.next() {
+                    checker.text.push(s);
+                    match s {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                line += usize::from(escaped == '\n');
+                                checker.text.push(escaped);
+                            }
+                        }
+                        '\n' => {
+                            checker.problem(line, "string is never closed");
+                            line += 1;
+                            break;
+                        }
+                        s if s == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' => checker.open(line),
+            '}' => checker.close(line),
+            ';' => checker.end_statement(),
+            '\n' => {
+                checker.text.push(c);
+                line += 1;
+            }
+            c => checker.push(c, line),
+        }
+    }
+
+    checker.finish()
+}
+
+struct Checker<'a> {
+    theme: &'a str,
+    problems: Vec<CssProblem>,
+    /// Open blocks and the lines they were opened on
+    blocks: Vec<(usize, Block)>,
+    /// The selector, at-rule or declaration read so far
+    text: String,
+    text_line: Option<usize>,
+    found_scoped: bool,
+}
+
+impl Checker<'_> {
+    fn problem(&mut self, line: usize, message: impl Into<String>) {
+        self.problems.push(CssProblem { line: Some(line), message: message.into() });
+    }
+
+    fn push(&mut self, c: char, line: usize) {
+        if self.text_line.is_none() && !c.is_whitespace() {
+            self.text_line = Some(line);
+        }
+        self.text.push(c);
+    }
+
+    /// The current statement, trimmed, and the line it starts on.
+    fn take(&mut self) -> Option<(String, usize)> {
+        let text = std::mem::take(&mut self.text);
+        let line = self.text_line.take()?;
+        self.check_urls(&text, line);
+        Some((text.trim().to_string(), line))
+    }
+
+    fn open(&mut self, line: usize) {
+        let (prelude, start) = self.take().unwrap_or_default();
+        let start = if prelude.is_empty() { line } else { start };
+        let block = match self.blocks.last().map(|(_, block)| block) {
+            Some(Block::Other) => Block::Other,
+            Some(Block::Rules { scoped }) | Some(Block::Declarations { scoped }) => {
+                let parent_scoped = *scoped;
+                if prelude.starts_with('@') {
+                    self.check_at_rule(&prelude, start);
+                    let name = at_rule_name(&prelude);
+                    if GROUPING_AT_RULES.contains(&name.as_str()) {
+                        Block::Rules { scoped: parent_scoped }
+                    } else {
+                        Block::Other
+                    }
+                } else {
+                    if prelude.is_empty() {
+                        self.problem(start, "rule without a selector");
+                    }
+                    let selectors: Vec<&str> = prelude.split(',').collect();
+                    let in_theme = selectors.iter().filter(|s| scopes(s, self.theme)).count();
+                    self.found_scoped |= in_theme > 0;
+                    Block::Declarations { scoped: parent_scoped || in_theme == selectors.len() }
+                }
+            }
+            None => Block::Other,
+        };
+        self.blocks.push((start, block));
+    }
+
+    fn close(&mut self, line: usize) {
+        self.end_statement();
+        // The top level is never closed
+        if self.blocks.len() > 1 {
+            self.blocks.pop();
+        } else {
+            self.problem(line, "'}' without a matching '{'");
+        }
+    }
+
+    /// Handles the declaration or at-rule statement ended by `;` or `}`.
+    fn end_statement(&mut self) {
+        let Some((statement, line)) = self.take() else { return };
+        if statement.is_empty() {
+            return;
+        }
+        match self.blocks.last().map(|(_, block)| block) {
+            Some(Block::Declarations { scoped }) => {
+                let scoped = *scoped;
+                if statement.starts_with('@') {
+                    self.check_at_rule(&statement, line);
+                } else if !scoped && is_position_fixed(&statement) {
+                    let message = format!(
+                        "position: fixed is only allowed in rules scoped to [data-theme=\"{}\"]",
+                        self.theme
+                    );
+                    self.problem(line, message);
+                }
+            }
+            Some(Block::Rules { .. }) => {
+                if statement.starts_with('@') {
+                    self.check_at_rule(&statement, line);
+                } else {
+                    self.problem(line, format!("expected a rule, found \"{}\"", excerpt(&statement)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_at_rule(&mut self, rule: &str, line: usize) {
+        if at_rule_name(rule) == "@import" {
+            self.problem(line, "@import is not allowed");
+        }
+    }
+
+    fn check_urls(&mut self, text: &str, line: usize) {
+        let lower = text.to_ascii_lowercase();
+        for (at, _) in lower.match_indices("url(") {
+            let target: String = lower[at + 4..]
+                .chars()
+                .take_while(|&c| c != ')')
+                .filter(|c| !c.is_whitespace() && *c != '"' && *c != '\'')
+                .collect();
+            if target.starts_with("javascript:") {
+                self.problem(line, "javascript: URLs are not allowed");
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<CssProblem> {
+        if let Some((statement, line)) = self.take() {
+            if !statement.is_empty() {
+                self.problem(line, format!("unexpected end of CSS after \"{}\"", excerpt(&statement)));
+            }
+        }
+        for (line, _) in self.blocks.drain(1..).collect::<Vec<_>>() {
+            self.problem(line, "'{' is never closed");
+        }
+        if !self.found_scoped {
+            self.problems.push(CssProblem {
+                line: None,
+                message: format!("no selector targets [data-theme=\"{}\"]", self.theme),
+            });
+        }
+        self.problems.sort_by_key(|problem| problem.line.unwrap_or(usize::MAX));
+        self.problems
+    }
+}
+
+/// `@media screen` -> `@media`, lowercased.
+fn at_rule_name(rule: &str) -> String {
+    rule.split(|c: char| c.is_whitespace() || c == '(' || c == '"' || c == '\'')
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+/// Whether `selector` only matches inside the theme, through its `data-theme` attribute.
+fn scopes(selector: &str, theme: &str) -> bool {
+    let selector: String = selector.chars().filter(|c| !c.is_whitespace()).collect();
+    [format!("[data-theme=\"{}\"]", theme), format!("[data-theme='{}']", theme), format!("[data-theme={}]", theme)]
+        .iter()
+        .any(|attribute| selector.contains(attribute.as_str()))
+}
+
+fn is_position_fixed(declaration: &str) -> bool {
+    let Some((property, value)) = declaration.split_once(':') else { return false };
+    let value = value.trim().to_ascii_lowercase();
+    let value = value.strip_suffix("!important").unwrap_or(&value).trim();
+    property.trim().eq_ignore_ascii_case("position") && value == "fixed"
+}
+
+fn excerpt(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("");
+    match line.char_indices().nth(40) {
+        Some((i, _)) => format!("{}…", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(css: &str) -> Vec<String> {
+        validate(css, "brand").iter().map(ToString::to_string).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn test_valid_theme_css() {
+        let css = r#"
+/* Brand colours } { */
+@charset "utf-8";
+.slide-content[data-theme="brand"], [data-theme="brand"] .slide-content {
+  --slide-bg: #fff;
+  background: url("/api/uploads/bg.png");
+  content: "}";
+}
+@media (max-width: 600px) {
+  [data-theme='brand'] h1 { font-size: 2em; position: fixed !important; }
+}
+@font-face { font-family: Brand; src: url(brand.woff2); }
+@keyframes pulse { from { opacity: 0 } to { opacity: 1 } }
+h1 { color: red }
+"#;
+        assert_eq!(problems(css), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_reports_each_problem_with_its_line() {
+        let css = "@import url(\"evil.css\");\n\
+                   [data-theme=\"brand\"] a {\n  background: url( 'JavaScript:alert(1)' );\n}\n\
+                   body { position : FIXED }\n\
+                   .x, [data-theme=\"brand\"] .y { position: fixed; }\n\
+                   }\n\
+                   @media print {\n  h1 { color: red }\n";
+        assert_eq!(problems(css), [
+            "line 1: @import is not allowed",
+            "line 3: javascript: URLs are not allowed",
+            "line 5: position: fixed is only allowed in rules scoped to [data-theme=\"brand\"]",
+            "line 6: position: fixed is only allowed in rules scoped to [data-theme=\"brand\"]",
+            "line 7: '}' without a matching '{'",
+            "line 8: '{' is never closed",
+        ]);
+    }
+
+    #[test]
+    fn test_requires_a_theme_selector() {
+        assert_eq!(problems(""), ["no selector targets [data-theme=\"brand\"]"]);
+        assert_eq!(problems("[data-theme=\"other\"] h1 { color: red }"), [
+            "no selector targets [data-theme=\"brand\"]"
+        ]);
+        assert_eq!(problems("[data-theme=\"brand\"] h1 { color: red }\ncolor: blue;\n/* open"), [
+            "line 2: expected a rule, found \"color: blue\"",
+            "line 3: comment is never closed",
+        ]);
+    }
+}
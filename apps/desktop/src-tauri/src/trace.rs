@@ -0,0 +1,117 @@
+// Trace ids tie one request together across frontend logs, backend logs and AI provider
+// requests. The id is taken from the caller's `traceparent` or `x-request-id` header (or
+// generated) by `middleware::RequestIdLayer`, attached to the request's tracing span as
+// `request_id` and kept in a task-local for the duration of the request.
+use axum::http::HeaderMap;
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header carrying our trace id on outgoing AI provider requests.
+pub const TRACE_HEADER: &str = "x-slides-trace-id";
+
+// Caller-supplied ids longer than this are replaced rather than logged
+const MAX_TRACE_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static TRACE_ID: String;
+}
+
+/// Trace id of the request being handled on this task, if any.
+pub fn current() -> Option<String> {
+    TRACE_ID.try_with(|id| id.clone()).ok()
+}
+
+pub fn new_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Runs `future` with `id` as the current trace id, inside a span that records it.
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    let span = tracing::info_span!("request", request_id = %id);
+    TRACE_ID.scope(id, future.instrument(span)).await
+}
+
+/// The trace-id field of a W3C `traceparent` header, falling back to `x-request-id`.
+pub fn from_headers(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    let traceparent = header("traceparent").and_then(|value| {
+        // version-traceid-parentid-flags
+        let trace_id = value.split('-').nth(1)?;
+        let valid = trace_id.len() == 32
+            && trace_id.chars().all(|c| c.is_ascii_hexdigit())
+            && trace_id.chars().any(|c| c != '0');
+        valid.then(|| trace_id.to_ascii_lowercase())
+    });
+
+    traceparent.or_else(|| {
+        header("x-request-id")
+            .filter(|id| !id.is_empty() && id.len() <= MAX_TRACE_ID_LEN)
+            .filter(|id| id.chars().all(|c| c.is_ascii_graphic()))
+            .map(str::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{middleware::RequestIdLayer, AppState};
+    use axum::{body::Body, extract::Request, http::StatusCode};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_trace_id_from_headers() {
+        let traceparent = "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01";
+        assert_eq!(
+            from_headers(&headers(&[("traceparent", traceparent), ("x-request-id", "ui-1")])).as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        // An all-zero trace id is invalid, so the request id is used instead
+        let zeros = "00-00000000000000000000000000000000-00f067aa0ba902b7-01";
+        assert_eq!(
+            from_headers(&headers(&[("traceparent", zeros), ("x-request-id", "ui-1")])).as_deref(),
+            Some("ui-1")
+        );
+        assert_eq!(from_headers(&headers(&[("x-request-id", "has space")])), None);
+        assert_eq!(from_headers(&HeaderMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_trace_id_flows_into_error_body() {
+        let state = Arc::new(RwLock::new(AppState::for_tests().await));
+        let router = crate::api::create_router(state).layer(RequestIdLayer);
+
+        let request = Request::builder()
+            .uri("/presentations/missing")
+            .header("x-request-id", "ui-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["x-request-id"], "ui-42");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["traceId"], "ui-42");
+
+        // Requests without an id get a fresh one, echoed back like a caller's
+        let request = Request::builder().uri("/presentations/missing").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let echoed = response.headers()["x-request-id"].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["traceId"], echoed);
+        assert_ne!(echoed, "ui-42");
+    }
+}
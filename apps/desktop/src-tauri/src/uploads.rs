@@ -0,0 +1,486 @@
+// Media uploads. Small files arrive in a single multipart request; large ones (screen
+// recordings) go through a chunked upload session whose chunks are written into a temp file
+// under `.partial/` in the uploads directory, then verified and moved into the library.
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::versioning;
+use crate::SharedState;
+
+/// Largest file accepted in a single request; bigger files go through a chunked upload.
+pub const MAX_SINGLE_UPLOAD_BYTES: usize = 50 * 1024 * 1024;
+/// Largest file accepted through a chunked upload.
+pub const MAX_UPLOAD_BYTES: i64 = 4 * 1024 * 1024 * 1024;
+/// Largest chunk accepted; also the request body limit for chunk uploads.
+pub const MAX_CHUNK_BYTES: i64 = 16 * 1024 * 1024;
+pub const DEFAULT_CHUNK_BYTES: i64 = 8 * 1024 * 1024;
+// Keeps tiny chunk sizes from turning one upload into millions of requests
+const MAX_CHUNKS: i64 = 10_000;
+/// How long a session survives without receiving a chunk.
+pub const SESSION_TTL: chrono::Duration = chrono::Duration::hours(24);
+
+const PARTIAL_DIR: &str = ".partial";
+
+/// Rejects single-request uploads over `MAX_SINGLE_UPLOAD_BYTES`.
+pub fn check_upload_size(len: usize) -> AppResult<()> {
+    if len > MAX_SINGLE_UPLOAD_BYTES {
+        return Err(upload_too_large());
+    }
+    Ok(())
+}
+
+pub fn upload_too_large() -> AppError {
+    AppError::TooLarge(format!("File exceeds {} MB limit", MAX_SINGLE_UPLOAD_BYTES / (1024 * 1024)))
+}
+
+/// Rejects files the media library doesn't accept.
+pub fn validate_media_type(content_type: &str) -> AppResult<()> {
+    if !content_type.starts_with("image/")
+        && !content_type.starts_with("video/")
+        && !content_type.starts_with("audio/") {
+        return Err(AppError::BadRequest("Only image, video, and audio files are allowed".to_string()));
+    }
+    Ok(())
+}
+
+/// A unique name for a new file in the uploads directory, keeping the original extension.
+pub fn media_filename(original_name: &str) -> String {
+    let ext = Path::new(original_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("bin");
+    format!("{}-{}.{}",
+        chrono::Utc::now().timestamp_millis(),
+        Uuid::new_v4().to_string().split('-').next().unwrap_or("x"),
+        ext
+    )
+}
+
+/// URL an uploaded file is served at.
+pub fn upload_url(filename: &str) -> String {
+    format!("{}/uploads/{}", versioning::PREFIX, filename)
+}
+
+/// Byte ranges of the upload URL prefixes (`/api/v1/uploads/`, or the deprecated
+/// `/api/uploads/`) in `text`, in order. Filenames start where each range ends.
+pub fn find_upload_prefixes(text: &str) -> Vec<(usize, usize)> {
+    let mut found: Vec<(usize, usize)> = [versioning::PREFIX, versioning::LEGACY_PREFIX]
+        .iter()
+        .flat_map(|prefix| {
+            let path = format!("{}/uploads/", prefix);
+            text.match_indices(&path).map(|(at, _)| (at, at + path.len())).collect::<Vec<_>>()
+        })
+        .collect();
+    found.sort_unstable();
+    found
+}
+
+/// Validates a new session's declared size, hash and chunk size, returning the chunk size.
+pub fn session_chunk_size(size: i64, sha256: &str, chunk_size: Option<i64>) -> AppResult<i64> {
+    if size <= 0 || size > MAX_UPLOAD_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "size must be between 1 and {} bytes",
+            MAX_UPLOAD_BYTES
+        )));
+    }
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::BadRequest("sha256 must be a hex SHA-256 digest".to_string()));
+    }
+
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_BYTES).min(size);
+    if chunk_size <= 0 || chunk_size > MAX_CHUNK_BYTES {
+        return Err(AppError::BadRequest(format!(
+            "chunkSize must be between 1 and {} bytes",
+            MAX_CHUNK_BYTES
+        )));
+    }
+    if (size + chunk_size - 1) / chunk_size > MAX_CHUNKS {
+        return Err(AppError::BadRequest(format!(
+            "chunkSize is too small; an upload can have at most {} chunks",
+            MAX_CHUNKS
+        )));
+    }
+    Ok(chunk_size)
+}
+
+/// Temp file holding the chunks of an upload session.
+pub fn partial_path(uploads_dir: &Path, id: &str) -> PathBuf {
+    uploads_dir.join(PARTIAL_DIR).join(id)
+}
+
+/// Path of an uploaded file named in a URL, refusing anything that would resolve outside
+/// `uploads_dir`.
+pub fn resolve_upload_path(uploads_dir: &Path, filename: &str) -> AppResult<PathBuf> {
+    let invalid = || AppError::Forbidden("Invalid file path".to_string());
+    if filename.is_empty() || filename.contains("..") || filename.contains(['/', '\\']) {
+        return Err(invalid());
+    }
+
+    let file_path = uploads_dir.join(filename);
+    if !file_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    // Symlinks could still point elsewhere
+    let resolved = file_path.canonicalize().map_err(|_| invalid())?;
+    let root = uploads_dir.canonicalize().map_err(|_| invalid())?;
+    if !resolved.starts_with(&root) {
+        return Err(invalid());
+    }
+    Ok(resolved)
+}
+
+/// Hex SHA-256 of an in-memory upload, computed on a blocking thread.
+pub async fn sha256_bytes(data: Bytes) -> AppResult<String> {
+    tokio::task::spawn_blocking(move || format!("{:x}", Sha256::digest(&data)))
+        .await
+        .map_err(|e| AppError::Internal(format!("Hashing task failed: {}", e)))
+}
+
+/// Hex SHA-256 of a file, read on a blocking thread.
+pub async fn sha256_file(path: PathBuf) -> AppResult<String> {
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok::<_, std::io::Error>(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Hashing task failed: {}", e)))?
+    .map_err(|e| AppError::Internal(format!("Failed to hash upload: {}", e)))
+}
+
+/// Most bytes sent for an open-ended range (`bytes=X-`); the client asks for the rest as
+/// playback continues.
+pub const MAX_OPEN_RANGE_BYTES: u64 = 8 * 1024 * 1024;
+
+/// An inclusive byte range of a served file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn byte_count(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// How to answer a request carrying a `Range` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// Serve the whole file: other units, several ranges or a malformed header
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header against a file of `total` bytes.
+pub fn parse_range(header: &str, total: u64) -> RangeRequest {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // Suffix range: the last `end` bytes
+        let Ok(suffix) = end.parse::<u64>() else { return RangeRequest::Full };
+        if suffix == 0 || total == 0 {
+            return RangeRequest::Unsatisfiable;
+        }
+        ByteRange { start: total.saturating_sub(suffix), end: total - 1 }
+    } else {
+        let Ok(start) = start.parse::<u64>() else { return RangeRequest::Full };
+        if start >= total {
+            return RangeRequest::Unsatisfiable;
+        }
+        let end = if end.is_empty() {
+            (start + MAX_OPEN_RANGE_BYTES - 1).min(total - 1)
+        } else {
+            match end.parse::<u64>() {
+                Ok(end) if end >= start => end.min(total - 1),
+                _ => return RangeRequest::Full,
+            }
+        };
+        ByteRange { start, end }
+    };
+    RangeRequest::Partial(range)
+}
+
+/// Whether an `If-None-Match` header matches `etag`, comparing weakly as RFC 9110 requires.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = strip(etag);
+    if_none_match.split(',').any(|tag| tag.trim() == "*" || strip(tag) == etag)
+}
+
+/// Deletes expired upload sessions and their temp files. Run periodically by the maintenance task.
+pub async fn reap_expired_sessions(state: &SharedState) -> AppResult<usize> {
+    let state = state.read().await;
+    let ids = state.db.delete_expired_upload_sessions().await?;
+    for id in &ids {
+        let _ = tokio::fs::remove_file(partial_path(&state.uploads_dir, id)).await;
+    }
+    Ok(ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{Method, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    async fn state() -> SharedState {
+        let uploads_dir = std::env::temp_dir().join(format!("slides-uploads-{}", Uuid::new_v4()));
+        Arc::new(RwLock::new(AppState {
+            uploads_dir,
+            ..AppState::for_tests().await
+        }))
+    }
+
+    async fn send(router: &Router, method: Method, uri: &str, body: Body) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(body)
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    async fn start(router: &Router, data: &[u8], sha256: String) -> String {
+        let (status, session) = send(
+            router,
+            Method::POST,
+            "/media/uploads",
+            Body::from(
+                json!({
+                    "filename": "recording.mp4",
+                    "mimeType": "video/mp4",
+                    "size": data.len(),
+                    "sha256": sha256,
+                    "chunkSize": 4,
+                })
+                .to_string(),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(session["chunkCount"], 3);
+        session["id"].as_str().unwrap().to_string()
+    }
+
+    async fn put_chunk(router: &Router, id: &str, index: usize, data: &[u8]) -> (StatusCode, Value) {
+        let chunk = &data[index * 4..data.len().min(index * 4 + 4)];
+        let uri = format!("/media/uploads/{}/chunks/{}", id, index);
+        send(router, Method::PUT, &uri, Body::from(chunk.to_vec())).await
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = |start, end| RangeRequest::Partial(ByteRange { start, end });
+        assert_eq!(parse_range("bytes=0-99", 1000), range(0, 99));
+        assert_eq!(parse_range("bytes=900-2000", 1000), range(900, 999));
+        assert_eq!(parse_range("bytes=-100", 1000), range(900, 999));
+        assert_eq!(parse_range("bytes=-5000", 1000), range(0, 999));
+        assert_eq!(parse_range("bytes=10-", 1000), range(10, 999));
+        assert_eq!(parse_range("bytes=0-", 100 * 1024 * 1024), range(0, MAX_OPEN_RANGE_BYTES - 1));
+
+        assert_eq!(parse_range("bytes=1000-", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=9-1", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=a-b", 1000), RangeRequest::Full);
+
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"x\", W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abd\"", "\"abc\""));
+    }
+
+    fn digest(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_resumes_after_missing_chunk() {
+        let state = state().await;
+        let router = crate::api::create_router(state.clone());
+        let data = b"\x00\x00\x00\x18ftypmp42";
+        let id = start(&router, data, digest(data)).await;
+
+        // Chunks may arrive out of order; a wrong-length chunk is rejected
+        put_chunk(&router, &id, 2, data).await;
+        put_chunk(&router, &id, 0, data).await;
+        let uri = format!("/media/uploads/{}/chunks/1", id);
+        let (status, _) = send(&router, Method::PUT, &uri, Body::from("45")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let complete = format!("/media/uploads/{}/complete", id);
+        let (status, body) = send(&router, Method::POST, &complete, Body::empty()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("1"));
+
+        // Resume: the session reports what is missing
+        let (_, session) = send(&router, Method::GET, &format!("/media/uploads/{}", id), Body::empty()).await;
+        assert_eq!(session["receivedChunks"], json!([0, 2]));
+        put_chunk(&router, &id, 1, data).await;
+
+        let (status, media) = send(&router, Method::POST, &complete, Body::empty()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(media["originalName"], "recording.mp4");
+        assert_eq!(media["size"], 12);
+
+        let uploads_dir = state.read().await.uploads_dir.clone();
+        let stored = std::fs::read(uploads_dir.join(media["filename"].as_str().unwrap())).unwrap();
+        assert_eq!(stored, data);
+        assert!(!partial_path(&uploads_dir, &id).exists());
+        let (status, _) = send(&router, Method::GET, &format!("/media/uploads/{}", id), Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let _ = std::fs::remove_dir_all(uploads_dir);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload_rejects_hash_mismatch() {
+        let state = state().await;
+        let router = crate::api::create_router(state.clone());
+        let data = b"\x00\x00\x00\x18ftypmp42";
+        let id = start(&router, data, digest(b"something else")).await;
+
+        for index in 0..3 {
+            let (status, _) = put_chunk(&router, &id, index, data).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let complete = format!("/media/uploads/{}/complete", id);
+        let (status, body) = send(&router, Method::POST, &complete, Body::empty()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("SHA-256"));
+
+        let uploads_dir = state.read().await.uploads_dir.clone();
+        assert!(!partial_path(&uploads_dir, &id).exists());
+        assert!(state.read().await.db.list_media().await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(uploads_dir);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_upload_returns_existing_media() {
+        let state = state().await;
+        let router = crate::api::create_router(state.clone());
+        let data = b"\x00\x00\x00\x18ftypmp42";
+
+        let mut uploads = Vec::new();
+        for _ in 0..2 {
+            let id = start(&router, data, digest(data)).await;
+            for index in 0..3 {
+                put_chunk(&router, &id, index, data).await;
+            }
+            let complete = format!("/media/uploads/{}/complete", id);
+            let (status, media) = send(&router, Method::POST, &complete, Body::empty()).await;
+            assert_eq!(status, StatusCode::OK);
+            uploads.push(media);
+        }
+
+        assert_eq!(uploads[0]["deduplicated"], false);
+        assert_eq!(uploads[1]["deduplicated"], true);
+        assert_eq!(uploads[1]["id"], uploads[0]["id"]);
+        assert_eq!(uploads[1]["contentHash"], digest(data));
+
+        let uploads_dir = state.read().await.uploads_dir.clone();
+        let files = std::fs::read_dir(&uploads_dir).unwrap().filter(|e| e.as_ref().unwrap().path().is_file()).count();
+        assert_eq!(files, 1);
+        assert_eq!(state.read().await.db.list_media().await.unwrap().len(), 1);
+        let _ = std::fs::remove_dir_all(uploads_dir);
+    }
+
+    #[tokio::test]
+    async fn test_serve_upload_ranges_and_etag() {
+        let state = state().await;
+        let router = crate::api::create_router(state.clone());
+        let data = b"\x00\x00\x00\x18ftypmp42";
+        let id = start(&router, data, digest(data)).await;
+        for index in 0..3 {
+            put_chunk(&router, &id, index, data).await;
+        }
+        let complete = format!("/media/uploads/{}/complete", id);
+        let (_, media) = send(&router, Method::POST, &complete, Body::empty()).await;
+        let uri = format!("/uploads/{}", media["filename"].as_str().unwrap());
+
+        let get = |headers: &[(&'static str, &str)]| {
+            let mut request = Request::builder().uri(&uri);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let router = router.clone();
+            let request = request.body(Body::empty()).unwrap();
+            async move {
+                let response = router.oneshot(request).await.unwrap();
+                let status = response.status();
+                let headers = response.headers().clone();
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                (status, headers, bytes)
+            }
+        };
+
+        let (status, headers, bytes) = get(&[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["accept-ranges"], "bytes");
+        assert_eq!(headers["etag"], format!("\"{}\"", digest(data)));
+        assert_eq!(&bytes[..], data);
+
+        let (status, headers, bytes) = get(&[("range", "bytes=4-7")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers["content-range"], "bytes 4-7/12");
+        assert_eq!(&bytes[..], b"ftyp");
+
+        let (status, headers, _) = get(&[("range", "bytes=12-")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers["content-range"], "bytes */12");
+
+        let etag = format!("\"{}\"", digest(data));
+        let (status, _, bytes) = get(&[("if-none-match", &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(bytes.is_empty());
+
+        let _ = std::fs::remove_dir_all(state.read().await.uploads_dir.clone());
+    }
+
+    #[tokio::test]
+    async fn test_serve_upload_rejects_path_traversal() {
+        let state = state().await;
+        let router = crate::api::create_router(state.clone());
+        let uploads_dir = state.read().await.uploads_dir.clone();
+        std::fs::create_dir_all(&uploads_dir).unwrap();
+        std::fs::write(uploads_dir.join("..secret"), b"x").unwrap();
+
+        for uri in ["/uploads/..%2Fsecret", "/uploads/..%5Csecret", "/uploads/..secret"] {
+            let (status, _) = send(&router, Method::GET, uri, Body::empty()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+        }
+        let (status, _) = send(&router, Method::GET, "/uploads/missing.png", Body::empty()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let _ = std::fs::remove_dir_all(uploads_dir);
+    }
+}
@@ -0,0 +1,133 @@
+// API versioning. The routes from `api::create_router` are mounted at `/api/v1` and, for
+// clients written before versioning, at `/api`, where responses are marked deprecated and
+// point at their v1 equivalent. Clients can pin a version with `X-Slides-Api-Version`.
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+
+use crate::error::AppError;
+
+/// Version of the HTTP API served under `/api/v{API_VERSION}`.
+pub const API_VERSION: u32 = 1;
+pub const PREFIX: &str = "/api/v1";
+/// Unversioned prefix kept for older clients.
+pub const LEGACY_PREFIX: &str = "/api";
+/// Request header a client sends to state the API version it was written against.
+pub const VERSION_HEADER: &str = "x-slides-api-version";
+
+/// Mounts `api` under the versioned prefix and the deprecated alias.
+pub fn mount(api: Router) -> Router {
+    Router::new()
+        .nest(PREFIX, api.clone().layer(middleware::from_fn(check_version)))
+        .nest(
+            LEGACY_PREFIX,
+            api.layer(middleware::from_fn(check_version))
+                .layer(middleware::from_fn(mark_deprecated)),
+        )
+}
+
+/// Turns away clients asking for a version this server doesn't speak.
+async fn check_version(request: Request, next: Next) -> Response {
+    let Some(requested) = request.headers().get(VERSION_HEADER) else {
+        return next.run(request).await;
+    };
+
+    let requested = requested.to_str().unwrap_or("").trim();
+    if requested.trim_start_matches(['v', 'V']).parse() == Ok(API_VERSION) {
+        return next.run(request).await;
+    }
+    AppError::BadRequest(format!(
+        "API version {:?} is not supported. This server speaks version {}: send {}: {} and use {}/... \
+         (see GET {}/version)",
+        requested, API_VERSION, VERSION_HEADER, API_VERSION, PREFIX, PREFIX
+    ))
+    .into_response()
+}
+
+/// Adds `Deprecation` and a `Link` to the v1 route on responses served through `/api`.
+async fn mark_deprecated(request: Request, next: Next) -> Response {
+    // Inside the nested router the path no longer carries the prefix
+    let successor = match request.uri().query() {
+        Some(query) => format!("{}{}?{}", PREFIX, request.uri().path(), query),
+        None => format!("{}{}", PREFIX, request.uri().path()),
+    };
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.insert(HeaderName::from_static("link"), link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppState;
+    use axum::{body::Body, http::StatusCode};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use tower::ServiceExt;
+
+    async fn app() -> Router {
+        let state = Arc::new(RwLock::new(AppState::for_tests().await));
+        mount(crate::api::create_router(state))
+    }
+
+    async fn get(app: &Router, uri: &str, version: Option<&str>) -> (StatusCode, Option<String>, Value) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(version) = version {
+            request = request.header(VERSION_HEADER, version);
+        }
+        let response = app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let link = response.headers().get("link").map(|v| v.to_str().unwrap().to_string());
+        assert_eq!(response.headers().contains_key("deprecation"), link.is_some());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, link, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_legacy_prefix_matches_v1_and_is_deprecated() {
+        let app = app().await;
+        for path in ["/presentations?perPage=5", "/themes", "/version", "/presentations/missing"] {
+            let (v1_status, v1_link, mut v1_body) = get(&app, &format!("/api/v1{}", path), None).await;
+            let (status, link, mut body) = get(&app, &format!("/api{}", path), None).await;
+            // Error bodies carry a per-request trace id
+            for body in [&mut v1_body, &mut body] {
+                if let Some(body) = body.as_object_mut() {
+                    body.remove("traceId");
+                }
+            }
+            assert_eq!(status, v1_status, "{}", path);
+            assert_eq!(body, v1_body, "{}", path);
+            assert_eq!(v1_link, None);
+            assert_eq!(link.unwrap(), format!("</api/v1{}>; rel=\"successor-version\"", path));
+        }
+
+        let (_, _, health) = get(&app, "/api/v1/health", None).await;
+        assert_eq!(health["apiVersion"], API_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_version_header_is_checked() {
+        let app = app().await;
+        let (status, _, _) = get(&app, "/api/v1/themes", Some("1")).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = get(&app, "/api/themes", Some("v1")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, link, body) = get(&app, "/api/themes", Some("2")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(link.is_some());
+        assert!(body["error"].as_str().unwrap().contains("/api/v1"));
+        let (status, _, _) = get(&app, "/api/v1/themes", Some("latest")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,645 @@
+// Watchlists: RSS/Atom feeds attached to a presentation to keep "industry update" decks
+// fresh. A refresh fetches every source of the deck, turns items the deck hasn't seen yet into
+// candidate slides and stages them in its Inbox section (a slide marked
+// `<!-- section: Inbox -->`) rather than the deck body, so nothing is presented until someone
+// moves it. Items are remembered by a hash of their guid (or link, or title), so items that
+// stay in a feed across refreshes are only imported once.
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::ai::{AIProvider, GenerateOptions};
+use crate::error::{AppError, AppResult};
+use crate::models::{WatchlistRefreshResult, WatchlistSourceResult};
+use crate::slides;
+use crate::SharedState;
+
+pub const INBOX_MARKER: &str = "<!-- section: Inbox -->";
+const SECTION_PREFIX: &str = "<!-- section:";
+
+/// Feeds larger than this are rejected instead of parsed.
+pub const MAX_FEED_BYTES: usize = 2 * 1024 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long the AI provider gets to summarize one item before the excerpt is used instead.
+const SUMMARY_TIMEOUT: Duration = Duration::from_secs(20);
+// Feeds list newest first; older items beyond this are left for nobody to import
+const MAX_ITEMS_PER_SOURCE: usize = 10;
+const EXCERPT_CHARS: usize = 280;
+
+/// An entry of a fetched feed, with markup still in `summary`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeedItem {
+    pub title: String,
+    pub link: Option<String>,
+    pub guid: Option<String>,
+    pub summary: String,
+}
+
+impl FeedItem {
+    /// Identity used to recognize items already imported into a deck.
+    pub fn key(&self) -> String {
+        let id = self.guid.as_deref().or(self.link.as_deref()).unwrap_or(&self.title);
+        format!("{:x}", Sha256::digest(id.trim().as_bytes()))
+    }
+}
+
+/// Only web feeds can be watched; anything else is turned away when the source is saved.
+pub fn validate_source_url(url: &str) -> AppResult<()> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        _ => Err(AppError::BadRequest(format!("Watchlist sources must be http(s) URLs, got {:?}", url))),
+    }
+}
+
+/// Fetches every source of presentation `id` and stages the items it hasn't imported yet in
+/// its Inbox section. `provider` writes a two-bullet summary of each item; without one, or if
+/// it fails, the slide gets an excerpt of the item instead. A source that can't be fetched
+/// or parsed is reported in its result and doesn't stop the others.
+pub async fn refresh(
+    state: &SharedState,
+    id: &str,
+    provider: Option<&dyn AIProvider>,
+) -> AppResult<WatchlistRefreshResult> {
+    let (sources, client) = {
+        let state = state.read().await;
+        (state.db.list_watchlist_sources(id).await?, state.http_client.clone())
+    };
+
+    let mut staged = Vec::new();
+    let mut claimed = Vec::new();
+    let mut results = Vec::with_capacity(sources.len());
+
+    for source in &sources {
+        let items = match fetch_feed(&client, &source.url).await {
+            Ok(items) => items,
+            Err(e) => {
+                tracing::warn!("Watchlist source {} failed: {}", source.url, e);
+                results.push(WatchlistSourceResult {
+                    source_id: source.id.clone(),
+                    url: source.url.clone(),
+                    imported: 0,
+                    error: Some(e.to_string()),
+                });
+                continue;
+            }
+        };
+
+        let items: Vec<FeedItem> = items.into_iter().take(MAX_ITEMS_PER_SOURCE).collect();
+        let keys: Vec<String> = items.iter().map(FeedItem::key).collect();
+        let mut new_keys: HashSet<String> = {
+            let state = state.read().await;
+            let new_keys = state.db.claim_watchlist_items(id, &keys).await?;
+            state.db.mark_watchlist_source_refreshed(&source.id).await?;
+            new_keys.into_iter().collect()
+        };
+
+        let mut imported = 0;
+        for (item, key) in items.iter().zip(keys) {
+            // A feed listing the same item twice still yields one slide
+            if new_keys.remove(&key) {
+                staged.push(candidate_slide(item, provider).await);
+                claimed.push(key);
+                imported += 1;
+            }
+        }
+
+        results.push(WatchlistSourceResult {
+            source_id: source.id.clone(),
+            url: source.url.clone(),
+            imported,
+            error: None,
+        });
+    }
+
+    let state = state.read().await;
+    let presentation = if staged.is_empty() {
+        state.db.get_presentation(id).await?
+    } else {
+        match state.db.stage_inbox_slides(id, &staged).await {
+            Ok(presentation) => presentation,
+            Err(e) => {
+                // Let the next refresh try these items again
+                state.db.release_watchlist_items(id, &claimed).await?;
+                return Err(e);
+            }
+        }
+    };
+
+    Ok(WatchlistRefreshResult { presentation, imported: staged.len(), sources: results })
+}
+
+/// Appends `markdown` slides to the end of the deck's Inbox section, which runs from the
+/// marked slide up to the next `<!-- section: ... -->` slide. Decks without an Inbox get one
+/// at the end.
+pub fn stage_in_inbox(content: &str, markdown: &[String]) -> AppResult<String> {
+    let mut content = content.to_string();
+    let mut slides = slides::split_slides(&content);
+
+    let inbox = match slides.iter().position(|slide| slide.contains(INBOX_MARKER)) {
+        Some(inbox) => inbox,
+        None => {
+            content = slides::insert_slide(&content, slides.len(), &format!("{}\n\n# Inbox", INBOX_MARKER))?;
+            slides = slides::split_slides(&content);
+            slides.len() - 1
+        }
+    };
+
+    let end = slides
+        .iter()
+        .enumerate()
+        .skip(inbox + 1)
+        .find(|(_, slide)| slide.lines().any(|line| line.trim_start().starts_with(SECTION_PREFIX)))
+        .map_or(slides.len(), |(index, _)| index);
+
+    for (offset, slide) in markdown.iter().enumerate() {
+        content = slides::insert_slide(&content, end + offset, slide)?;
+    }
+    Ok(content)
+}
+
+/// Items of an RSS 2.0, RSS 1.0 (RDF) or Atom document, in document order.
+pub fn parse_feed(xml: &[u8]) -> AppResult<Vec<FeedItem>> {
+    let text = std::str::from_utf8(xml).map_err(|_| malformed("the feed is not UTF-8"))?;
+    let mut reader = Reader::from_str(text);
+
+    let mut items = Vec::new();
+    let mut root_seen = false;
+    let mut depth = 0;
+    // Depth of the open <item>/<entry>, and the field of it being read
+    let mut item: Option<(usize, FeedItem)> = None;
+    let mut field: Option<(Field, String)> = None;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| malformed(&format!("{} (at byte {})", e, reader.error_position())))?;
+        let opens = matches!(event, Event::Start(_));
+
+        match event {
+            Event::Start(element) | Event::Empty(element) if !root_seen => {
+                let name = local_name(&element);
+                if !matches!(name.as_str(), "rss" | "feed" | "RDF") {
+                    return Err(malformed(&format!("expected an RSS or Atom document, found <{}>", name)));
+                }
+                root_seen = true;
+                // An empty root is a feed without items
+                if opens {
+                    depth += 1;
+                }
+            }
+            Event::Start(element) => {
+                depth += 1;
+                let name = local_name(&element);
+                match &mut item {
+                    None if name == "item" || name == "entry" => item = Some((depth, FeedItem::default())),
+                    Some((item_depth, current)) if depth == *item_depth + 1 => {
+                        if let Some(kind) = Field::from_name(&name) {
+                            if kind == Field::Link {
+                                // Atom links carry the URL in href
+                                set_link(current, &element);
+                            }
+                            field = Some((kind, String::new()));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(element) => {
+                if let Some((item_depth, current)) = &mut item {
+                    if depth == *item_depth && local_name(&element) == "link" {
+                        set_link(current, &element);
+                    }
+                }
+            }
+            Event::Text(text) => {
+                if let Some((_, value)) = &mut field {
+                    value.push_str(&text.xml10_content());
+                }
+            }
+            Event::CData(cdata) => {
+                if let Some((_, value)) = &mut field {
+                    value.push_str(&cdata.into_inner());
+                }
+            }
+            Event::GeneralRef(reference) => {
+                if let Some((_, value)) = &mut field {
+                    let resolved = reference.resolve_char_ref().map_err(|e| malformed(&e.to_string()))?;
+                    match resolved {
+                        Some(c) => value.push(c),
+                        None => {
+                            let name = reference.into_inner();
+                            // Undeclared (HTML) entities are kept as written
+                            match resolve_predefined_entity(&name) {
+                                Some(entity) => value.push_str(entity),
+                                None => value.push_str(&format!("&{};", name)),
+                            }
+                        }
+                    }
+                }
+            }
+            Event::End(_) => {
+                if let Some((item_depth, current)) = &mut item {
+                    if depth == *item_depth + 1 {
+                        if let Some((kind, value)) = field.take() {
+                            kind.assign(current, value.trim());
+                        }
+                    } else if depth == *item_depth {
+                        let (_, finished) = item.take().unwrap_or_default();
+                        items.push(finished);
+                    }
+                }
+                depth -= 1;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if !root_seen {
+        return Err(malformed("the document is empty"));
+    }
+    if depth > 0 {
+        return Err(malformed("the document ends inside an element"));
+    }
+    Ok(items)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Link,
+    Guid,
+    Summary,
+    Content,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(Field::Title),
+            "link" => Some(Field::Link),
+            "guid" | "id" => Some(Field::Guid),
+            "description" | "summary" => Some(Field::Summary),
+            "content" | "encoded" => Some(Field::Content),
+            _ => None,
+        }
+    }
+
+    fn assign(self, item: &mut FeedItem, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        match self {
+            Field::Title => item.title = value.to_string(),
+            Field::Link if item.link.is_none() => item.link = Some(value.to_string()),
+            Field::Guid => item.guid = Some(value.to_string()),
+            Field::Summary => item.summary = value.to_string(),
+            // Full content only stands in for a missing summary
+            Field::Content if item.summary.is_empty() => item.summary = value.to_string(),
+            _ => {}
+        }
+    }
+}
+
+fn local_name(element: &BytesStart) -> String {
+    element.local_name().as_ref().to_string()
+}
+
+/// Takes an Atom link's href, skipping links that aren't to the item itself (self, enclosure).
+fn set_link(item: &mut FeedItem, element: &BytesStart) {
+    let attribute = |key: &str| {
+        element
+            .try_get_attribute(key)
+            .ok()
+            .flatten()
+            .map(|attribute| attribute.value.trim().to_string())
+    };
+    let alternate = attribute("rel").is_none_or(|rel| rel == "alternate");
+    if let Some(href) = attribute("href").filter(|href| alternate && !href.is_empty()) {
+        item.link.get_or_insert(href);
+    }
+}
+
+fn malformed(reason: &str) -> AppError {
+    AppError::BadRequest(format!("Malformed feed: {}", reason))
+}
+
+async fn fetch_feed(client: &reqwest::Client, url: &str) -> AppResult<Vec<FeedItem>> {
+    let failed = |e: String| AppError::BadRequest(format!("Failed to fetch {}: {}", url, e));
+    let too_large = || AppError::TooLarge(format!("{} is larger than {} bytes", url, MAX_FEED_BYTES));
+
+    let mut response = client
+        .get(url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(failed(response.status().to_string()));
+    }
+    if response.content_length().is_some_and(|len| len > MAX_FEED_BYTES as u64) {
+        return Err(too_large());
+    }
+
+    // The declared length can be missing or wrong, so the cap is enforced while reading
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| failed(e.to_string()))? {
+        if body.len() + chunk.len() > MAX_FEED_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    parse_feed(&body)
+}
+
+/// Slide for a feed item: its title, a summary, and a link back to the source.
+async fn candidate_slide(item: &FeedItem, provider: Option<&dyn AIProvider>) -> String {
+    let title = plain_text(&item.title);
+    let title = if title.is_empty() { "Untitled item".to_string() } else { title };
+    let excerpt = excerpt(&plain_text(&item.summary));
+
+    let summary = match provider {
+        Some(provider) => match tokio::time::timeout(SUMMARY_TIMEOUT, summarize(provider, &title, &excerpt)).await {
+            Ok(Ok(Some(bullets))) => bullets,
+            Ok(Ok(None)) => excerpt,
+            Ok(Err(e)) => {
+                tracing::warn!("Summarizing \"{}\" failed, using an excerpt: {}", title, e);
+                excerpt
+            }
+            Err(_) => {
+                tracing::warn!("Summarizing \"{}\" timed out after {:?}", title, SUMMARY_TIMEOUT);
+                excerpt
+            }
+        },
+        None => excerpt,
+    };
+
+    let mut slide = format!("## {}", title);
+    if !summary.is_empty() {
+        slide.push_str(&format!("\n\n{}", summary));
+    }
+    if let Some(link) = &item.link {
+        let label = url::Url::parse(link)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| link.clone());
+        slide.push_str(&format!("\n\nSource: [{}]({})", label, link));
+    }
+    slide
+}
+
+/// Two markdown bullets summarizing the item, or None if the reply doesn't contain them.
+async fn summarize(provider: &dyn AIProvider, title: &str, excerpt: &str) -> AppResult<Option<String>> {
+    let prompt = format!("Title: {}\n\n{}", title, excerpt);
+    let reply = provider
+        .generate_content(&prompt, GenerateOptions {
+            system_prompt: Some(
+                "You summarize news items for presentation slides. Reply with exactly two markdown \
+                bullet points starting with '- ', one short sentence each. No heading, no other text."
+                    .to_string(),
+            ),
+            max_tokens: Some(200),
+            ..Default::default()
+        })
+        .await?;
+
+    let bullets: Vec<&str> = reply
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("- ") || line.starts_with("* "))
+        .take(2)
+        .collect();
+    Ok((bullets.len() == 2).then(|| bullets.iter().map(|b| format!("- {}", b[2..].trim())).collect::<Vec<_>>().join("\n")))
+}
+
+/// Feed text with HTML tags and the common entities removed, on one line.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The start of `text`, cut at a word boundary.
+fn excerpt(text: &str) -> String {
+    if text.chars().count() <= EXCERPT_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(EXCERPT_CHARS).collect();
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CreatePresentation, CreateWatchlistSource};
+    use crate::AppState;
+    use axum::{routing::get, Router};
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::RwLock;
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+  <channel>
+    <title>Industry News</title>
+    <link>https://news.example.com/</link>
+    <item>
+      <title>Chips &amp; Margins</title>
+      <link>https://news.example.com/chips</link>
+      <guid isPermaLink="false">news-1</guid>
+      <description><![CDATA[<p>Foundries raised <b>prices</b> again.</p>]]></description>
+    </item>
+    <item>
+      <title>Cloud spend slows</title>
+      <link>https://news.example.com/cloud</link>
+      <content:encoded>&lt;p&gt;Budgets are flat this quarter.&lt;/p&gt;</content:encoded>
+    </item>
+    <item>
+      <title>Chips &amp; Margins (updated)</title>
+      <guid isPermaLink="false">news-1</guid>
+    </item>
+  </channel>
+</rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Release notes</title>
+  <link rel="self" href="https://releases.example.com/feed.atom"/>
+  <entry>
+    <title type="html">Version 2.0 &#8211; out now</title>
+    <link rel="self" href="https://releases.example.com/entries/2.0.atom"/>
+    <link rel="alternate" href="https://releases.example.com/2.0"/>
+    <id>tag:releases.example.com,2024:2.0</id>
+    <content type="xhtml"><div>Long <em>release</em> notes</div></content>
+    <summary>Faster builds.</summary>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_parse_rss_and_atom() {
+        let items = parse_feed(RSS.as_bytes()).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].title, "Chips & Margins");
+        assert_eq!(items[0].link.as_deref(), Some("https://news.example.com/chips"));
+        assert_eq!(items[0].guid.as_deref(), Some("news-1"));
+        assert_eq!(plain_text(&items[0].summary), "Foundries raised prices again.");
+        assert_eq!(plain_text(&items[1].summary), "Budgets are flat this quarter.");
+        // Same guid, same item, even when the title changes
+        assert_eq!(items[0].key(), items[2].key());
+        assert_ne!(items[0].key(), items[1].key());
+
+        let entries = parse_feed(ATOM.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, "Version 2.0 – out now");
+        assert_eq!(entries[0].link.as_deref(), Some("https://releases.example.com/2.0"));
+        assert_eq!(entries[0].guid.as_deref(), Some("tag:releases.example.com,2024:2.0"));
+        assert_eq!(entries[0].summary, "Faster builds.");
+
+        assert!(parse_feed(b"<rss version=\"2.0\"/>").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_feeds() {
+        let malformed = [
+            &b"<rss><channel><item><title>Broken</item></channel></rss>"[..],
+            b"<rss><channel><item><title>Cut off</title>",
+            b"<html><body>Not a feed</body></html>",
+            b"",
+            b"<rss><channel><item><title>\xff\xfe</title></item></channel></rss>",
+        ];
+        for xml in malformed {
+            let result = parse_feed(xml);
+            assert!(
+                matches!(&result, Err(AppError::BadRequest(msg)) if msg.starts_with("Malformed feed")),
+                "{:?} -> {:?}",
+                String::from_utf8_lossy(xml),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_stage_in_inbox() {
+        let staged = stage_in_inbox("# Deck\n", &["## One".to_string(), "## Two".to_string()]).unwrap();
+        assert_eq!(staged, format!("# Deck\n\n---\n\n{}\n\n# Inbox\n\n---\n\n## One\n\n---\n\n## Two", INBOX_MARKER));
+
+        // Later items go to the end of the Inbox, ahead of the next section
+        let deck = format!(
+            "# Deck\n\n---\n\n{}\n\n# Inbox\n\n---\n\n## One\n\n---\n\n<!-- section: Archive -->\n\n# Archive\n",
+            INBOX_MARKER
+        );
+        let staged = stage_in_inbox(&deck, &["## Two".to_string()]).unwrap();
+        let titles: Vec<String> = slides::split_slides(&staged)
+            .iter()
+            .map(|slide| slide.trim().lines().last().unwrap().to_string())
+            .collect();
+        assert_eq!(titles, ["# Deck", "# Inbox", "## One", "## Two", "# Archive"]);
+    }
+
+    async fn state() -> SharedState {
+        Arc::new(RwLock::new(AppState::for_tests().await))
+    }
+
+    /// Serves `feed` at /feed.xml, a broken document at /broken.xml and an oversized one at
+    /// /huge.xml, returning the base URL.
+    async fn serve(feed: Arc<Mutex<String>>) -> String {
+        let app = Router::new()
+            .route("/feed.xml", get(move || async move { feed.lock().unwrap().clone() }))
+            .route("/broken.xml", get(|| async { "<rss><channel><item><title>Oops</channel></rss>" }))
+            .route("/huge.xml", get(|| async { "x".repeat(MAX_FEED_BYTES + 1) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_stages_new_items_once() {
+        let feed = Arc::new(Mutex::new(RSS.to_string()));
+        let base = serve(feed.clone()).await;
+        let state = state().await;
+
+        let id = {
+            let state = state.read().await;
+            let presentation = state
+                .db
+                .create_presentation(CreatePresentation {
+                    title: "Industry update".to_string(),
+                    content: Some("# Industry update\n".to_string()),
+                    theme: None,
+                    if_not_exists: false,
+                    on_conflict: None,
+                })
+                .await
+                .unwrap();
+            for path in ["feed.xml", "broken.xml", "huge.xml"] {
+                let source = CreateWatchlistSource { url: format!("{}/{}", base, path), title: None };
+                state.db.create_watchlist_source(&presentation.id, source).await.unwrap();
+            }
+            let duplicate = CreateWatchlistSource { url: format!("{}/feed.xml", base), title: None };
+            let result = state.db.create_watchlist_source(&presentation.id, duplicate).await;
+            assert!(matches!(result, Err(AppError::Conflict(_))));
+            presentation.id
+        };
+
+        let result = refresh(&state, &id, None).await.unwrap();
+        assert_eq!(result.imported, 2);
+        let by_path = |path: &str| result.sources.iter().find(|s| s.url.ends_with(path)).unwrap().clone();
+        assert_eq!(by_path("feed.xml").imported, 2);
+        assert!(by_path("broken.xml").error.unwrap().contains("Malformed feed"));
+        assert!(by_path("huge.xml").error.unwrap().starts_with("Too large"));
+
+        let content = result.presentation.content;
+        let slides = slides::split_slides(&content);
+        assert_eq!(slides.len(), 4);
+        assert!(slides[0].contains("# Industry update"));
+        assert!(slides[1].contains(INBOX_MARKER));
+        assert_eq!(
+            slides[2].trim(),
+            "## Chips & Margins\n\nFoundries raised prices again.\n\nSource: [news.example.com](https://news.example.com/chips)"
+        );
+        assert!(slides[3].contains("## Cloud spend slows"));
+
+        // Nothing new: nothing staged, and the deck is left alone
+        let again = refresh(&state, &id, None).await.unwrap();
+        assert_eq!(again.imported, 0);
+        assert_eq!(again.presentation.content, content);
+
+        let updated = RSS.replace(
+            "<item>",
+            "<item>\n      <title>Fresh item</title>\n      <guid>news-2</guid>\n    </item>\n    <item>",
+        );
+        *feed.lock().unwrap() = updated;
+        let result = refresh(&state, &id, None).await.unwrap();
+        assert_eq!(result.imported, 1);
+        let slides = slides::split_slides(&result.presentation.content);
+        assert_eq!(slides.len(), 5);
+        assert_eq!(slides[4].trim(), "## Fresh item");
+
+        let sources = state.read().await.db.list_watchlist_sources(&id).await.unwrap();
+        let refreshed: Vec<bool> = sources.iter().map(|s| s.last_refreshed_at.is_some()).collect();
+        assert_eq!(refreshed, [true, false, false]);
+    }
+}
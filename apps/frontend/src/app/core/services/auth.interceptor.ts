@@ -1,16 +1,24 @@
 import { HttpInterceptorFn } from '@angular/common/http';
 import { inject } from '@angular/core';
+import { from, switchMap } from 'rxjs';
 import { AuthService } from './auth.service';
 
-const DESKTOP_BACKEND_URL = 'http://127.0.0.1:3332';
+let desktopBackendUrl: Promise<string> | undefined;
+
+/** The desktop backend's base URL. Its port is configurable, so ask the app once. */
+function getDesktopBackendUrl(): Promise<string> {
+  desktopBackendUrl ??= import('@tauri-apps/api/core').then(({ invoke }) => invoke<string>('backend_url'));
+  return desktopBackendUrl;
+}
 
 export const authInterceptor: HttpInterceptorFn = (req, next) => {
   const auth = inject(AuthService);
 
   // In desktop mode, prepend the backend URL for API requests
   if (auth.isDesktopApp && req.url.startsWith('/api')) {
-    req = req.clone({ url: `${DESKTOP_BACKEND_URL}${req.url}` });
-    return next(req);
+    return from(getDesktopBackendUrl()).pipe(
+      switchMap((backendUrl) => next(req.clone({ url: `${backendUrl}${req.url}` })))
+    );
   }
 
   const token = auth.getToken();
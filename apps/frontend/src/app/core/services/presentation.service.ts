@@ -1,14 +1,32 @@
 import { Injectable } from '@angular/core';
 import { HttpClient } from '@angular/common/http';
-import { Observable } from 'rxjs';
-import type { PresentationDto, CreatePresentationDto, UpdatePresentationDto } from '@slides/shared-types';
+import { EMPTY, Observable, expand, reduce } from 'rxjs';
+import type {
+  PresentationDto,
+  CreatePresentationDto,
+  UpdatePresentationDto,
+  PaginatedResult,
+} from '@slides/shared-types';
+
+// The largest page the backend serves
+const PAGE_SIZE = 100;
 
 @Injectable({ providedIn: 'root' })
 export class PresentationService {
   constructor(private http: HttpClient) {}
 
+  /** Every presentation, fetched page by page. */
   list(): Observable<PresentationDto[]> {
-    return this.http.get<PresentationDto[]>('/api/presentations');
+    return this.listPage(1).pipe(
+      expand((result) => (result.page < result.totalPages ? this.listPage(result.page + 1) : EMPTY)),
+      reduce((all, result) => all.concat(result.items), [] as PresentationDto[])
+    );
+  }
+
+  listPage(page: number, perPage = PAGE_SIZE): Observable<PaginatedResult<PresentationDto>> {
+    return this.http.get<PaginatedResult<PresentationDto>>('/api/presentations', {
+      params: { page, perPage },
+    });
   }
 
   get(id: string): Observable<PresentationDto> {
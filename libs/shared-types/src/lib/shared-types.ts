@@ -21,6 +21,13 @@ export interface PresentationDto {
   updatedAt: string;
 }
 
+export interface PaginatedResult<T> {
+  items: T[];
+  total: number;
+  page: number;
+  totalPages: number;
+}
+
 export interface CreatePresentationDto {
   title: string;
   content: string;